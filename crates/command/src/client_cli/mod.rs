@@ -0,0 +1,533 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use kari_move::base::build::compile_for_publish;
+use mona_client::types::{
+    normalize_address, EventFilter, EventInfo, ExecutionStatus, ObjectInfo, Owner,
+};
+use mona_client::{Keystore, RpcClient, TransactionData, TransactionKind, TransactionResponse};
+use mona_config::KariConfig;
+use move_package::BuildConfig;
+
+use crate::output::print_json;
+
+#[derive(Args)]
+pub struct GasArgs {
+    /// Gas coin to pay with, picked by the node by default
+    #[clap(long)]
+    gas: Option<String>,
+    /// Gas budget, `gas.budget` of the config by default
+    #[clap(long)]
+    gas_budget: Option<u64>,
+}
+
+#[derive(Subcommand)]
+pub enum TxCommand {
+    /// Call a Move function
+    Call {
+        #[clap(long)]
+        package: String,
+        #[clap(long)]
+        module: String,
+        #[clap(long)]
+        function: String,
+        /// Type arguments, e.g. 0x2::kari::KARI
+        #[clap(long, num_args = 1..)]
+        type_args: Vec<String>,
+        /// Arguments: object IDs or literals
+        #[clap(long, num_args = 1..)]
+        args: Vec<String>,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+    /// Publish the Move package at the given path (current directory by default)
+    Publish {
+        package_path: Option<PathBuf>,
+        #[clap(flatten)]
+        build_config: BuildConfig,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+    /// Transfer an object to an address
+    Transfer {
+        #[clap(long)]
+        object_id: String,
+        #[clap(long)]
+        to: String,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+    /// Split coins of the given amounts off a coin
+    SplitCoin {
+        #[clap(long)]
+        coin: String,
+        #[clap(long, required = true, num_args = 1..)]
+        amounts: Vec<u64>,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+    /// Merge coins into a primary coin
+    MergeCoin {
+        #[clap(long)]
+        primary: String,
+        #[clap(long, required = true, num_args = 1..)]
+        coins: Vec<String>,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+    /// Pay amounts to recipients out of the given coins
+    Pay {
+        #[clap(long, required = true, num_args = 1..)]
+        coins: Vec<String>,
+        #[clap(long, required = true, num_args = 1..)]
+        recipients: Vec<String>,
+        #[clap(long, required = true, num_args = 1..)]
+        amounts: Vec<u64>,
+        #[clap(flatten)]
+        gas: GasArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ClientCommand {
+    #[clap(flatten)]
+    Tx(TxCommand),
+    /// Execute a transaction without committing its effects
+    DryRun {
+        #[clap(subcommand)]
+        tx: TxCommand,
+    },
+    /// Show an object
+    Object { object_id: String },
+    /// List the objects owned by an address (the active address by default)
+    Objects { address: Option<String> },
+    /// List the gas coins of an address (the active address by default)
+    Gas { address: Option<String> },
+    /// Show the coin balances of an address (the active address by default)
+    Balance {
+        address: Option<String>,
+        /// Only this coin type, e.g. 0x2::kari::KARI
+        #[clap(long)]
+        coin_type: Option<String>,
+    },
+    /// Show an executed transaction
+    TxBlock { digest: String },
+    /// Query events, newest last
+    Events {
+        #[clap(long)]
+        sender: Option<String>,
+        #[clap(long)]
+        tx_digest: Option<String>,
+        #[clap(long)]
+        event_type: Option<String>,
+        #[clap(long, default_value_t = 50)]
+        limit: usize,
+    },
+}
+
+// Handle client commands against the node of the active environment
+pub fn handle_client_command(
+    command: ClientCommand,
+    config: &KariConfig,
+    json: bool,
+) -> Result<()> {
+    let client = RpcClient::http(config.rpc_url()?);
+    let keystore = Keystore::open(config.keystore_path()).context("Failed to open keystore")?;
+    run_client_command(command, &client, &keystore, config, json)
+}
+
+/// Run `command` against `client`, signing with `keystore`.
+pub fn run_client_command(
+    command: ClientCommand,
+    client: &RpcClient,
+    keystore: &Keystore,
+    config: &KariConfig,
+    json: bool,
+) -> Result<()> {
+    let signer = || {
+        keystore
+            .signer(config.active_address.as_deref())
+            .context("No address to use")
+    };
+    let address_or_active = |address: Option<String>| match address {
+        Some(address) => Ok(normalize_address(&address)?),
+        None => signer(),
+    };
+
+    match command {
+        ClientCommand::Tx(tx) => {
+            let tx = build_transaction(tx, signer()?, config)?;
+            let signature = keystore.sign(&tx).context("Failed to sign transaction")?;
+            let response = client
+                .execute_transaction(&tx, &signature)
+                .context("Failed to execute transaction")?;
+            print_response(&response, json)
+        }
+
+        ClientCommand::DryRun { tx } => {
+            let tx = build_transaction(tx, signer()?, config)?;
+            let response = client
+                .dry_run_transaction(&tx)
+                .context("Failed to dry run transaction")?;
+            print_response(&response, json)
+        }
+
+        ClientCommand::Object { object_id } => {
+            let object = client
+                .get_object(&object_id)
+                .context("Failed to get object")?
+                .with_context(|| format!("Object {} not found", object_id))?;
+            if json {
+                return print_json(&object);
+            }
+            print_object(&object);
+            Ok(())
+        }
+
+        ClientCommand::Objects { address } => {
+            let address = address_or_active(address)?;
+            let objects = client
+                .get_owned_objects(&address)
+                .context("Failed to get objects")?;
+            if json {
+                return print_json(&objects);
+            }
+            if objects.is_empty() {
+                println!("No objects owned by {}", address);
+                return Ok(());
+            }
+            println!("{}", "OBJECTS:".bright_yellow().bold());
+            for object in objects {
+                println!(
+                    "  {}  v{}  {}",
+                    object.object_id.green().bold(),
+                    object.version,
+                    object.type_
+                );
+            }
+            Ok(())
+        }
+
+        ClientCommand::Gas { address } => {
+            let address = address_or_active(address)?;
+            let coins = client
+                .get_gas_coins(&address)
+                .context("Failed to get gas coins")?;
+            if json {
+                let coins: Vec<_> = coins
+                    .iter()
+                    .map(|coin| {
+                        serde_json::json!({
+                            "object_id": coin.object_id,
+                            "balance": coin.coin_balance(),
+                        })
+                    })
+                    .collect();
+                return print_json(&coins);
+            }
+            if coins.is_empty() {
+                println!("No gas coins owned by {}", address);
+                return Ok(());
+            }
+            println!("{}", "GAS COINS:".bright_yellow().bold());
+            for coin in coins {
+                println!(
+                    "  {}  {}",
+                    coin.object_id.green().bold(),
+                    coin.coin_balance().unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+
+        ClientCommand::Balance { address, coin_type } => {
+            let address = address_or_active(address)?;
+            let balances = client
+                .get_balance(&address, coin_type.as_deref())
+                .context("Failed to get balance")?;
+            if json {
+                return print_json(&balances);
+            }
+            if balances.is_empty() {
+                println!("No coins owned by {}", address);
+                return Ok(());
+            }
+            println!("{}", "BALANCES:".bright_yellow().bold());
+            for balance in balances {
+                println!(
+                    "  {}: {} ({} coins)",
+                    balance.coin_type.blue(),
+                    balance.total_balance.to_string().green().bold(),
+                    balance.coin_object_count
+                );
+            }
+            Ok(())
+        }
+
+        ClientCommand::TxBlock { digest } => {
+            let response = client
+                .get_transaction_block(&digest)
+                .context("Failed to get transaction")?
+                .with_context(|| format!("Transaction {} not found", digest))?;
+            print_response(&response, json)
+        }
+
+        ClientCommand::Events {
+            sender,
+            tx_digest,
+            event_type,
+            limit,
+        } => {
+            let filter = EventFilter {
+                sender: sender.as_deref().map(normalize_address).transpose()?,
+                tx_digest,
+                event_type,
+            };
+            let events = client
+                .query_events(&filter, limit)
+                .context("Failed to query events")?;
+            if json {
+                return print_json(&events);
+            }
+            if events.is_empty() {
+                println!("No events");
+                return Ok(());
+            }
+            for event in &events {
+                print_event(event);
+            }
+            Ok(())
+        }
+    }
+}
+
+// The transaction described by `tx`, sent by `sender`
+fn build_transaction(
+    tx: TxCommand,
+    sender: String,
+    config: &KariConfig,
+) -> Result<TransactionData> {
+    let (kind, gas) = match tx {
+        TxCommand::Call {
+            package,
+            module,
+            function,
+            type_args,
+            args,
+            gas,
+        } => (
+            TransactionKind::MoveCall {
+                package: normalize_address(&package)?,
+                module,
+                function,
+                type_args,
+                args,
+            },
+            gas,
+        ),
+        TxCommand::Publish {
+            package_path,
+            build_config,
+            gas,
+        } => {
+            let package = compile_for_publish(package_path, build_config)
+                .context("Failed to build package")?;
+            (
+                TransactionKind::Publish {
+                    modules: package.modules,
+                    dependencies: package
+                        .dependencies
+                        .iter()
+                        .map(|address| address.to_hex_literal())
+                        .collect(),
+                },
+                gas,
+            )
+        }
+        TxCommand::Transfer { object_id, to, gas } => (
+            TransactionKind::TransferObject {
+                object_id: normalize_address(&object_id)?,
+                recipient: normalize_address(&to)?,
+            },
+            gas,
+        ),
+        TxCommand::SplitCoin { coin, amounts, gas } => (
+            TransactionKind::SplitCoin {
+                coin: normalize_address(&coin)?,
+                amounts,
+            },
+            gas,
+        ),
+        TxCommand::MergeCoin {
+            primary,
+            coins,
+            gas,
+        } => (
+            TransactionKind::MergeCoins {
+                primary: normalize_address(&primary)?,
+                coins: normalize_all(&coins)?,
+            },
+            gas,
+        ),
+        TxCommand::Pay {
+            coins,
+            recipients,
+            amounts,
+            gas,
+        } => {
+            if recipients.len() != amounts.len() {
+                bail!("Expected one amount per recipient");
+            }
+            (
+                TransactionKind::Pay {
+                    coins: normalize_all(&coins)?,
+                    recipients: normalize_all(&recipients)?,
+                    amounts,
+                },
+                gas,
+            )
+        }
+    };
+
+    Ok(TransactionData {
+        sender,
+        kind,
+        gas_payment: gas.gas.as_deref().map(normalize_address).transpose()?,
+        gas_budget: gas.gas_budget.unwrap_or(config.gas.budget),
+        gas_price: config.gas.price,
+    })
+}
+
+fn normalize_all(addresses: &[String]) -> Result<Vec<String>> {
+    Ok(addresses
+        .iter()
+        .map(|address| normalize_address(address))
+        .collect::<Result<_, _>>()?)
+}
+
+fn print_response(response: &TransactionResponse, json: bool) -> Result<()> {
+    if json {
+        return print_json(response);
+    }
+    match &response.status {
+        ExecutionStatus::Success => println!("{}", "✓ Transaction succeeded".green().bold()),
+        ExecutionStatus::Failure { error } => {
+            println!("{} {}", "✗ Transaction failed:".red().bold(), error)
+        }
+    }
+    println!("{}: {}", "Digest".blue(), response.digest);
+    println!("{}: {}", "Gas used".blue(), response.gas_used);
+    for (label, ids) in [
+        ("Created", &response.created),
+        ("Mutated", &response.mutated),
+        ("Deleted", &response.deleted),
+    ] {
+        if !ids.is_empty() {
+            println!("{}:", label.blue());
+            for id in ids {
+                println!("  {}", id);
+            }
+        }
+    }
+    for event in &response.events {
+        print_event(event);
+    }
+    Ok(())
+}
+
+fn print_object(object: &ObjectInfo) {
+    println!(
+        "{}: {}",
+        "Object ID".blue(),
+        object.object_id.green().bold()
+    );
+    println!("{}: {}", "Version".blue(), object.version);
+    println!("{}: {}", "Digest".blue(), object.digest);
+    println!("{}: {}", "Type".blue(), object.type_);
+    let owner = match &object.owner {
+        Owner::AddressOwner(address) => address.clone(),
+        Owner::Shared => "shared".to_string(),
+        Owner::Immutable => "immutable".to_string(),
+    };
+    println!("{}: {}", "Owner".blue(), owner);
+    println!(
+        "{}: {}",
+        "Fields".blue(),
+        serde_json::to_string_pretty(&object.fields).unwrap_or_default()
+    );
+}
+
+fn print_event(event: &EventInfo) {
+    println!(
+        "{} {} #{}: {}",
+        "Event".blue(),
+        event.type_.bold(),
+        event.sequence,
+        event.data
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mona_client::LocalNode;
+
+    #[test]
+    fn test_client_commands_against_local_node() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = Keystore::open(dir.path().join("kari.keystore")).unwrap();
+        let sender = keystore.generate();
+        let node = LocalNode::new();
+        let gas = node.fund(&sender, 100_000_000).unwrap();
+        let client = RpcClient::new(node.clone());
+        let config = KariConfig::default();
+        let run = |command| run_client_command(command, &client, &keystore, &config, true);
+
+        run(ClientCommand::Tx(TxCommand::SplitCoin {
+            coin: gas.clone(),
+            amounts: vec![500],
+            gas: GasArgs {
+                gas: None,
+                gas_budget: None,
+            },
+        }))
+        .unwrap();
+        let coins = client.get_gas_coins(&sender).unwrap();
+        assert_eq!(coins.len(), 2);
+        let coin = coins[1].object_id.clone();
+
+        // A dry run leaves the coin where it is
+        let transfer = || TxCommand::Transfer {
+            object_id: coin.clone(),
+            to: "0xb0b".to_string(),
+            gas: GasArgs {
+                gas: Some(gas.clone()),
+                gas_budget: Some(2_000_000),
+            },
+        };
+        run(ClientCommand::DryRun { tx: transfer() }).unwrap();
+        assert_eq!(client.get_owned_objects(&sender).unwrap().len(), 2);
+        run(ClientCommand::Tx(transfer())).unwrap();
+        assert_eq!(client.get_owned_objects(&sender).unwrap().len(), 1);
+        assert_eq!(
+            client.get_balance("0xb0b", None).unwrap()[0].total_balance,
+            500
+        );
+
+        run(ClientCommand::Balance {
+            address: None,
+            coin_type: None,
+        })
+        .unwrap();
+        assert!(run(ClientCommand::Object {
+            object_id: "0x1234".to_string()
+        })
+        .is_err());
+        assert!(run(ClientCommand::TxBlock {
+            digest: "missing".to_string()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use mona_config::KariConfig;
+use serde_json::json;
+
+use crate::output::print_json;
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the active configuration
+    Show,
+    /// Print the path of the configuration file
+    Path,
+    /// Set a value (active_env, active_address, storage_path, keystore_path,
+    /// gas.budget, gas.price, move.storage_dir, move.gas_limit,
+    /// update.index_url, update.public_key, envs.<name>.rpc_url)
+    Set { key: String, value: String },
+    /// List the configured environments
+    Envs,
+    /// Make <env> the active environment
+    Switch { env: String },
+}
+
+// Handle config commands
+pub fn handle_config_command(command: ConfigCommand, mut config: KariConfig, json: bool) -> Result<()> {
+    match command {
+        ConfigCommand::Show if json => print_json(&json!({
+            "path": config.path(),
+            "active_env": config.active_env,
+            "rpc_url": config.rpc_url().ok(),
+            "active_address": config.active_address,
+            "storage_path": config.storage_path(),
+            "keystore_path": config.keystore_path(),
+            "gas": config.gas,
+            "update": config.update,
+        }))?,
+        ConfigCommand::Show => {
+            println!("{} {}", "CONFIG:".bright_yellow().bold(), config.path().display());
+            println!("  Active env:      {}", config.active_env.green().bold());
+            match config.rpc_url() {
+                Ok(rpc_url) => println!("  RPC endpoint:    {}", rpc_url),
+                Err(e) => println!("  RPC endpoint:    {}", e.to_string().red()),
+            }
+            println!(
+                "  Active address:  {}",
+                config.active_address.as_deref().unwrap_or("(none)")
+            );
+            println!("  Storage path:    {}", config.storage_path().display());
+            println!("  Keystore path:   {}", config.keystore_path().display());
+            println!("  Gas budget:      {}", config.gas.budget);
+            println!("  Gas price:       {}", config.gas.price);
+            println!("  Release index:   {}", config.update.index_url);
+        }
+        ConfigCommand::Path if json => print_json(&json!({ "path": config.path() }))?,
+        ConfigCommand::Path => println!("{}", config.path().display()),
+        ConfigCommand::Set { key, value } => {
+            config.set(&key, &value).context("Failed to set value")?;
+            config.save().context("Failed to save config")?;
+            if json {
+                print_json(&json!({ "key": key, "value": value }))?;
+            } else {
+                println!("Set {} = {}", key, value);
+            }
+        }
+        ConfigCommand::Envs if json => print_json(&json!({
+            "active_env": config.active_env,
+            "envs": config.envs,
+        }))?,
+        ConfigCommand::Envs => {
+            println!("{}", "ENVIRONMENTS:".bright_yellow().bold());
+            for (name, env) in &config.envs {
+                let marker = if *name == config.active_env { "*" } else { " " };
+                println!("{} {}  {}", marker, name.green().bold(), env.rpc_url);
+            }
+        }
+        ConfigCommand::Switch { env } => {
+            config.switch_env(&env).context("Failed to switch environment")?;
+            config.save().context("Failed to save config")?;
+            if json {
+                print_json(&json!({ "active_env": env }))?;
+            } else {
+                println!("Active environment: {}", env);
+            }
+        }
+    }
+    Ok(())
+}
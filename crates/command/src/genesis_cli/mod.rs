@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use mona_config::KariConfig;
+use mona_genesis::config::TEMPLATE;
+use mona_genesis::{Genesis, GenesisConfig, GenesisError};
+use serde::Serialize;
+
+#[derive(Subcommand)]
+pub enum GenesisCommand {
+    /// Write a genesis config to start from
+    Init {
+        #[clap(default_value = "genesis.toml")]
+        path: PathBuf,
+        /// Overwrite an existing file
+        #[clap(long)]
+        force: bool,
+    },
+    /// Build the genesis blob from a genesis config
+    Build {
+        #[clap(default_value = "genesis.toml")]
+        genesis_config: PathBuf,
+        /// Where to write the blob
+        #[clap(long, short, default_value = "genesis.blob")]
+        output: PathBuf,
+    },
+    /// Check a genesis blob and print its digest
+    Verify {
+        #[clap(default_value = "genesis.blob")]
+        genesis: PathBuf,
+        /// Genesis config the blob must have been built from
+        #[clap(long, value_name = "GENESIS_CONFIG")]
+        from: Option<PathBuf>,
+        /// Digest the blob must have
+        #[clap(long)]
+        digest: Option<String>,
+    },
+}
+
+/// Summary of a genesis blob, printed by `build` and `verify`.
+#[derive(Serialize)]
+pub struct GenesisSummary {
+    pub path: PathBuf,
+    pub chain_id: String,
+    /// Hex encoded SHA3-256 digest of the blob.
+    pub digest: String,
+    pub packages: Vec<String>,
+    pub objects: usize,
+    pub validators: Vec<String>,
+    /// KARI minted by the genesis (stakes and coins), in MIST.
+    pub total_supply: u128,
+}
+
+impl GenesisSummary {
+    fn new(path: &Path, genesis: &Genesis) -> Result<Self> {
+        Ok(GenesisSummary {
+            path: path.to_path_buf(),
+            chain_id: genesis.chain_id.clone(),
+            digest: genesis.digest(),
+            packages: genesis
+                .packages
+                .iter()
+                .map(|package| package.id.to_hex_literal())
+                .collect(),
+            objects: genesis.objects.len(),
+            validators: genesis
+                .validators
+                .iter()
+                .map(|validator| validator.name.clone())
+                .collect(),
+            total_supply: genesis.total_supply()?,
+        })
+    }
+
+    fn print(&self, json: bool) -> Result<()> {
+        if json {
+            return crate::output::print_json(self);
+        }
+        println!(
+            "{} {}",
+            "GENESIS:".bright_yellow().bold(),
+            self.path.display()
+        );
+        println!("  Chain ID:    {}", self.chain_id);
+        println!("  Digest:      {}", self.digest.green().bold());
+        println!("  Packages:    {}", self.packages.join(", "));
+        println!("  Objects:     {}", self.objects);
+        println!("  Validators:  {}", self.validators.join(", "));
+        println!("  Supply:      {} MIST", self.total_supply);
+        Ok(())
+    }
+}
+
+// Handle genesis ceremony commands
+pub fn handle_genesis_command(
+    command: GenesisCommand,
+    _config: &KariConfig,
+    json: bool,
+) -> Result<()> {
+    match command {
+        GenesisCommand::Init { path, force } => {
+            if path.exists() && !force {
+                bail!(
+                    "{} already exists, use --force to overwrite it",
+                    path.display()
+                );
+            }
+            fs::write(&path, TEMPLATE)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            if json {
+                return crate::output::print_json(&serde_json::json!({ "path": path }));
+            }
+            println!(
+                "Wrote genesis config {}",
+                path.display().to_string().green()
+            );
+            Ok(())
+        }
+
+        GenesisCommand::Build {
+            genesis_config,
+            output,
+        } => {
+            let genesis_config = GenesisConfig::load(&genesis_config)?;
+            let genesis = mona_genesis::build(&genesis_config)?;
+            fs::write(&output, genesis.to_bytes())
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            GenesisSummary::new(&output, &genesis)?.print(json)
+        }
+
+        GenesisCommand::Verify {
+            genesis: path,
+            from,
+            digest,
+        } => {
+            let blob =
+                fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let genesis = Genesis::from_bytes(&blob)?;
+            genesis.verify()?;
+            if let Some(from) = from {
+                genesis.verify_config(&GenesisConfig::load(&from)?)?;
+            }
+            if let Some(expected) = digest {
+                let actual = genesis.digest();
+                let expected = expected.trim_start_matches("0x").to_lowercase();
+                if expected != actual {
+                    return Err(GenesisError::Digest { expected, actual }.into());
+                }
+            }
+            GenesisSummary::new(&path, &genesis)?.print(json)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_build_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = KariConfig::default();
+        let toml = dir.path().join("genesis.toml");
+        let blob = dir.path().join("genesis.blob");
+
+        let init = GenesisCommand::Init {
+            path: toml.clone(),
+            force: false,
+        };
+        handle_genesis_command(init, &config, true).unwrap();
+        let init = GenesisCommand::Init {
+            path: toml.clone(),
+            force: false,
+        };
+        assert!(handle_genesis_command(init, &config, true).is_err());
+
+        let build = GenesisCommand::Build {
+            genesis_config: toml.clone(),
+            output: blob.clone(),
+        };
+        handle_genesis_command(build, &config, true).unwrap();
+        let digest = Genesis::from_bytes(&fs::read(&blob).unwrap())
+            .unwrap()
+            .digest();
+
+        let verify = |digest: &str| GenesisCommand::Verify {
+            genesis: blob.clone(),
+            from: Some(toml.clone()),
+            digest: Some(digest.to_string()),
+        };
+        handle_genesis_command(verify(&digest), &config, true).unwrap();
+        assert!(handle_genesis_command(verify("00"), &config, true).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use mona_client::types::normalize_address;
+use mona_client::Keystore;
+use mona_config::KariConfig;
+use serde_json::json;
+
+use crate::output::print_json;
+
+#[derive(Subcommand)]
+pub enum KeytoolCommand {
+    /// Generate a new key and add it to the keystore
+    Generate,
+    /// Add a hex encoded secp256k1 private key to the keystore
+    Import { private_key: String },
+    /// List the addresses of the keystore
+    List,
+}
+
+// Handle keytool commands
+pub fn handle_keytool_command(
+    command: KeytoolCommand,
+    config: &KariConfig,
+    json: bool,
+) -> Result<()> {
+    let mut keystore = Keystore::open(config.keystore_path()).context("Failed to open keystore")?;
+    run_keytool_command(
+        command,
+        &mut keystore,
+        config.active_address.as_deref(),
+        json,
+    )
+}
+
+/// Run `command` on `keystore`; `active_address` is marked in the listing.
+pub fn run_keytool_command(
+    command: KeytoolCommand,
+    keystore: &mut Keystore,
+    active_address: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let address = match command {
+        KeytoolCommand::Generate => keystore.generate(),
+        KeytoolCommand::Import { private_key } => keystore
+            .import(&private_key)
+            .context("Invalid private key")?,
+        KeytoolCommand::List if json => {
+            let addresses: Vec<&str> = keystore.addresses().collect();
+            return print_json(&json!({ "addresses": addresses }));
+        }
+        KeytoolCommand::List => {
+            let active_address = active_address.and_then(|address| normalize_address(address).ok());
+            println!("{}", "ADDRESSES:".bright_yellow().bold());
+            for address in keystore.addresses() {
+                let marker = if Some(address) == active_address.as_deref() {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}", marker, address.green().bold());
+            }
+            return Ok(());
+        }
+    };
+    keystore.save().context("Failed to save keystore")?;
+    if json {
+        print_json(&json!({ "address": address }))?;
+    } else {
+        println!("Added key {}", address.green().bold());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kari.keystore");
+        let mut keystore = Keystore::open(&path).unwrap();
+        run_keytool_command(KeytoolCommand::Generate, &mut keystore, None, true).unwrap();
+        let import = KeytoolCommand::Import {
+            private_key: "01".repeat(32),
+        };
+        run_keytool_command(import, &mut keystore, None, true).unwrap();
+        let import = KeytoolCommand::Import {
+            private_key: "zz".to_string(),
+        };
+        assert!(run_keytool_command(import, &mut keystore, None, true).is_err());
+
+        // The keys were saved
+        let keystore = Keystore::open(&path).unwrap();
+        assert_eq!(keystore.addresses().count(), 2);
+    }
+}
@@ -1,2 +1,8 @@
+pub mod client_cli;
+pub mod config_cli;
+pub mod genesis_cli;
+pub mod keytool_cli;
 pub mod move_cli;
+pub mod output;
 pub mod public_cli;
+pub mod update_cli;
@@ -1,149 +1,109 @@
-use colored::Colorize;
-use kari_move::{
-    base::{
-        build::Build,
-        coverage::{Coverage, CoverageSummaryOptions},
-        disassemble::Disassemble,
-        docgen::Docgen,
-        errmap::Errmap,
-        info::Info,
-        new::New,
-        prove::Prove,
-        test::Test,
-    },
-    run_cli, Command, Move,
-};
-use move_core_types::errmap::ErrorMapping;
-use move_package::BuildConfig;
-use move_vm_test_utils::gas_schedule::zero_cost_schedule;
-use std::{path::PathBuf, process::exit};
-
-struct CommandInfo {
-    name: &'static str,
-    description: &'static str,
-}
+use std::path::{Path, PathBuf};
 
-const COMMANDS: &[CommandInfo] = &[
-    CommandInfo { name: "build", description: "Build the package" },
-    CommandInfo { name: "coverage", description: "Inspect test coverage for this package. A previous test run with the `--coverage` flag must have" },
-    CommandInfo { name: "disassemble", description: "Disassemble Move bytecode" },
-    CommandInfo { name: "docgen", description: "Generate documentation" },
-    CommandInfo { name: "errmap", description: "Generate error map" },
-    CommandInfo { name: "info", description: "Print address information" },
-    CommandInfo { name: "new", description: "Create a new Move package with name `name` at `path`. If `path` is not provided the package will" },
-    CommandInfo { name: "prove", description: "Prove a Move module" },
-    CommandInfo { name: "test", description: "Run Move unit tests" },
-];
-
-fn display_help(show_error: bool) {
-    if show_error {
-        println!("\n{}", "ERROR: Invalid command".red().bold());
-    }
+use anyhow::Result;
+use kari_move::sandbox::cli::SandboxCommand;
+use kari_move::{run_cli, Command, MoveCLI, DEFAULT_STORAGE_DIR};
+use mona_config::KariConfig;
+use move_core_types::{account_address::AccountAddress, errmap::ErrorMapping};
+use move_stdlib::natives::{all_natives, nursery_natives, GasParameters, NurseryGasParameters};
+use move_vm_test_utils::gas_schedule::zero_cost_schedule;
 
-    println!("{}", "USAGE:".bright_yellow().bold());
-    println!("kari move <command> [options]\n");
+// Run `kari move ...`, parsed with the Move CLI's own clap definitions so
+// every flag of the base, sandbox and experimental commands is available
+pub fn handle_move_command(cli: MoveCLI, config: &KariConfig, json: bool) -> Result<()> {
+    let MoveCLI {
+        mut move_args,
+        mut cmd,
+    } = cli;
+    move_args.json = json;
+    apply_config(&mut cmd, config);
 
-    println!("{}", "COMMANDS:".bright_yellow().bold());
+    let error_mapping: ErrorMapping =
+        bcs::from_bytes(move_stdlib::error_descriptions()).unwrap_or_default();
+    let cost_table = zero_cost_schedule();
+    let addr = AccountAddress::from_hex_literal("0x1").unwrap();
+    let natives = all_natives(addr, GasParameters::zeros())
+        .into_iter()
+        .chain(nursery_natives(addr, NurseryGasParameters::zeros()))
+        .collect();
 
-    let max_name_len = COMMANDS.iter().map(|cmd| cmd.name.len()).max().unwrap_or(0);
+    run_cli(natives, &cost_table, &error_mapping, move_args, cmd)
+}
 
-    for cmd in COMMANDS {
-        println!(
-            "  {}{}  {}",
-            cmd.name.green().bold(),
-            " ".repeat(max_name_len - cmd.name.len() + 2),
-            cmd.description.bright_white()
-        );
+// Fill in the `move` defaults of the config where the command line gives no
+// value. Unset defaults leave the Move CLI's own: the unit test runner's gas
+// limit, unmetered scripts and a `storage` directory.
+fn apply_config(cmd: &mut Command, config: &KariConfig) {
+    let gas_limit = config.r#move.gas_limit;
+    let storage = config.move_storage_dir();
+    let use_storage = |storage_dir: &mut PathBuf| {
+        if let Some(storage) = &storage {
+            if storage_dir == Path::new(DEFAULT_STORAGE_DIR) {
+                storage_dir.clone_from(storage);
+            }
+        }
+    };
+    match cmd {
+        Command::Test(test) => {
+            test.gas_limit = test.gas_limit.or(gas_limit);
+        }
+        Command::Sandbox { storage_dir, cmd } => {
+            use_storage(storage_dir);
+            if let SandboxCommand::Run { gas_budget, .. } = cmd {
+                *gas_budget = gas_budget.or(gas_limit);
+            }
+        }
+        Command::Experimental { storage_dir, .. } => use_storage(storage_dir),
+        _ => {}
     }
-    println!();
-
-    exit(1);
 }
 
-pub fn handle_move_command() {
-    let args: Vec<String> = std::env::args().collect();
-    let cost_table = zero_cost_schedule();
-    let error_mapping = ErrorMapping::default();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
 
-    // Check for minimum arguments
-    if args.len() <= 2 {
-        display_help(false);
-        return;
+    fn parse(args: &str) -> Command {
+        MoveCLI::parse_from(std::iter::once("move").chain(args.split_whitespace())).cmd
     }
 
-    let move_args = Move {
-        package_path: None,
-        verbose: false,
-        build_config: BuildConfig::default(),
-    };
+    #[test]
+    fn test_apply_config() {
+        let mut config = KariConfig::default();
 
-    let cmd = match args.get(2).map(|s| s.as_str()) {
-        Some("build") => Command::Build(Build {}),
-        Some("coverage") => Command::Coverage(Coverage {
-            options: CoverageSummaryOptions::Summary {
-                functions: false,
-                output_csv: false,
-            },
-        }),
-        Some("disassemble") => Command::Disassemble(Disassemble {
-            interactive: false,
-            package_name: None,
-            module_or_script_name: String::new(),
-        }),
-        Some("docgen") => Command::Docgen(Docgen {
-            section_level_start: Some(0),
-            exclude_private_fun: false,
-            exclude_specs: false,
-            independent_specs: false,
-            exclude_impl: false,
-            toc_depth: Some(3),
-            no_collapsed_sections: false,
-            output_directory: None,
-            compile_relative_to_output_dir: false,
-            references_file: None,
-            template: Vec::new(),
-            include_dep_diagrams: false,
-            include_call_diagrams: false,
-        }),
-        Some("errmap") => Command::Errmap(Errmap {
-            error_prefix: None,
-            output_file: PathBuf::new(),
-        }),
-        Some("info") => Command::Info(Info {}),
+        // Without `move` defaults the Move CLI's are kept
+        let mut cmd = parse("test");
+        apply_config(&mut cmd, &config);
+        assert!(matches!(cmd, Command::Test(test) if test.gas_limit.is_none()));
 
-        Some("new") => match args.get(3).map(String::from) {
-            Some(name) if !name.is_empty() => Command::New(New { name }),
-            _ => {
-                eprintln!("Error: Project name is required. Usage: kari move new <project_name>");
-                std::process::exit(1);
-            }
-        },
+        config.set("move.gas_limit", "500").unwrap();
+        config.set("move.storage_dir", "/tmp/sandbox").unwrap();
+        let mut cmd = parse("test");
+        apply_config(&mut cmd, &config);
+        assert!(matches!(cmd, Command::Test(test) if test.gas_limit == Some(500)));
+        let mut cmd = parse("test --gas_limit 7");
+        apply_config(&mut cmd, &config);
+        assert!(matches!(cmd, Command::Test(test) if test.gas_limit == Some(7)));
 
-        Some("test") => Command::Test(Test {
-            gas_limit: None,
-            filter: None,
-            list: false,
-            num_threads: 8,
-            report_statistics: false,
-            report_storage_on_error: false,
-            ignore_compile_warnings: false,
-            check_stackless_vm: false,
-            verbose_mode: false,
-            compute_coverage: false,
-        }),
-        Some("prove") => Command::Prove(Prove {
-            target_filter: None,
-            for_test: false,
-            options: None,
-        }),
-        _ => {
-            display_help(true);
-            return;
-        }
-    };
+        let mut cmd = parse("sandbox run script.mv");
+        apply_config(&mut cmd, &config);
+        let Command::Sandbox { storage_dir, cmd } = cmd else {
+            panic!("expected sandbox command");
+        };
+        assert_eq!(storage_dir, PathBuf::from("/tmp/sandbox"));
+        assert!(matches!(
+            cmd,
+            SandboxCommand::Run {
+                gas_budget: Some(500),
+                ..
+            }
+        ));
 
-    if let Err(e) = run_cli(Vec::new(), &cost_table, &error_mapping, move_args, cmd) {
-        println!("\n{}: {}", "ERROR".red().bold(), e);
-        exit(1);
+        let mut cmd = parse("sandbox --storage-dir other view x");
+        apply_config(&mut cmd, &config);
+        let Command::Sandbox { storage_dir, .. } = cmd else {
+            panic!("expected sandbox command");
+        };
+        assert_eq!(storage_dir, PathBuf::from("other"));
     }
 }
@@ -0,0 +1,38 @@
+//! Machine-readable output of `kari --json`.
+//!
+//! With `--json`, a command prints exactly one JSON document on stdout and
+//! nothing else; progress bars and prose are suppressed. The document of each
+//! command is described on its output type. A failing command prints
+//!
+//! ```json
+//! { "error": { "message": "Failed to get file", "causes": ["File not found: x"] } }
+//! ```
+//!
+//! and exits with status 1. Sizes are in bytes and timestamps are RFC 3339
+//! strings in UTC. Existing fields keep their name and meaning; new fields
+//! may be added.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::time::SystemTime;
+
+/// Print `value` as a single JSON document.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// The JSON document printed for a failed command.
+pub fn error_json(error: &anyhow::Error) -> serde_json::Value {
+    let causes: Vec<String> = error.chain().skip(1).map(|cause| cause.to_string()).collect();
+    serde_json::json!({
+        "error": {
+            "message": error.to_string(),
+            "causes": causes,
+        }
+    })
+}
+
+pub fn timestamp(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()
+}
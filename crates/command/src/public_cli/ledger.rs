@@ -0,0 +1,215 @@
+//! Metadata registry calls executed on-chain.
+//!
+//! [`ChainLedger`] turns a [`MetadataCall`] into a `MoveCall` to the entry
+//! function of `0x2::metadata`, signs it with the keystore and executes it on
+//! the node. The events the transaction emits are appended to the
+//! [`LocalLedger`] of the store, which is the log the indexer reads.
+
+use mona_client::system::CLOCK_OBJECT_ID;
+use mona_client::types::{normalize_type, EventInfo, ExecutionStatus};
+use mona_client::{Keystore, RpcClient, TransactionData, TransactionKind};
+use mona_config::GasConfig;
+use mona_storage::metadata::{
+    normalize_address, LocalLedger, MetadataCall, MetadataEvent, MetadataLedger,
+};
+use mona_storage::StorageError;
+use serde_json::Value;
+
+const METADATA_PACKAGE: &str = "0x2";
+const METADATA_MODULE: &str = "metadata";
+
+pub struct ChainLedger<'a> {
+    client: &'a RpcClient,
+    keystore: &'a Keystore,
+    gas: GasConfig,
+    cache: LocalLedger,
+}
+
+impl<'a> ChainLedger<'a> {
+    pub fn new(
+        client: &'a RpcClient,
+        keystore: &'a Keystore,
+        gas: GasConfig,
+        cache: LocalLedger,
+    ) -> Self {
+        ChainLedger {
+            client,
+            keystore,
+            gas,
+            cache,
+        }
+    }
+}
+
+impl MetadataLedger for ChainLedger<'_> {
+    fn submit(
+        &mut self,
+        sender: &str,
+        call: MetadataCall,
+    ) -> Result<Vec<MetadataEvent>, StorageError> {
+        let tx = TransactionData {
+            sender: normalize_address(sender)?,
+            kind: move_call(call),
+            gas_payment: None,
+            gas_budget: self.gas.budget,
+            gas_price: self.gas.price,
+        };
+        let failed = |e: mona_client::ClientError| StorageError::Transaction(e.to_string());
+        let signature = self.keystore.sign(&tx).map_err(failed)?;
+        let response = self
+            .client
+            .execute_transaction(&tx, &signature)
+            .map_err(failed)?;
+        if let ExecutionStatus::Failure { error } = response.status {
+            return Err(StorageError::Transaction(error));
+        }
+
+        let events: Vec<MetadataEvent> =
+            response.events.iter().filter_map(metadata_event).collect();
+        self.cache.append(&events)?;
+        Ok(events)
+    }
+
+    fn events_since(&self, from: u64) -> Result<Vec<(u64, MetadataEvent)>, StorageError> {
+        self.cache.events_since(from)
+    }
+}
+
+// The call of the entry function of `0x2::metadata` matching `call`
+fn move_call(call: MetadataCall) -> TransactionKind {
+    // `vector<u8>` arguments are passed as hex literals
+    let bytes = |hash: String| format!("0x{}", hash);
+    let (function, args) = match call {
+        MetadataCall::Register { hash } => {
+            ("register", vec![bytes(hash), CLOCK_OBJECT_ID.to_string()])
+        }
+        MetadataCall::UpdateVersion { id, hash } => (
+            "update_version",
+            vec![id, bytes(hash), CLOCK_OBJECT_ID.to_string()],
+        ),
+        MetadataCall::Transfer { id, recipient } => ("transfer", vec![id, recipient]),
+        MetadataCall::SetPermissions { id, grantee, flags } => {
+            ("set_permissions", vec![id, grantee, flags.to_string()])
+        }
+        MetadataCall::SetPublicRead { id, public_read } => {
+            ("set_public_read", vec![id, public_read.to_string()])
+        }
+    };
+    TransactionKind::MoveCall {
+        package: METADATA_PACKAGE.to_string(),
+        module: METADATA_MODULE.to_string(),
+        function: function.to_string(),
+        type_args: Vec::new(),
+        args,
+    }
+}
+
+// The registry event of an event emitted on-chain, if it is one. Byte vectors
+// such as the hashes may be rendered as arrays of bytes or as hex strings.
+fn metadata_event(event: &EventInfo) -> Option<MetadataEvent> {
+    let prefix = normalize_type(&format!("{}::{}::", METADATA_PACKAGE, METADATA_MODULE)).ok()?;
+    let type_ = normalize_type(&event.type_).ok()?;
+    let name = type_.strip_prefix(&prefix)?;
+
+    let mut data = event.data.clone();
+    let fields = data.as_object_mut()?;
+    for key in ["hash", "previous_hash"] {
+        let hash = match fields.get(key) {
+            Some(Value::Array(bytes)) => bytes
+                .iter()
+                .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .map(hex::encode)?,
+            Some(Value::String(hash)) => hash.trim_start_matches("0x").to_lowercase(),
+            _ => continue,
+        };
+        fields.insert(key.to_string(), Value::String(hash));
+    }
+    fields.insert("type".to_string(), Value::String(name.to_string()));
+    serde_json::from_value(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mona_client::{LocalNode, RpcTransport};
+    use mona_storage::metadata::MetadataIndexer;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    // `LocalNode` does not run Move code and only records the call, so emit
+    // the event `metadata::register` would
+    struct MetadataNode(LocalNode);
+
+    impl RpcTransport for MetadataNode {
+        fn request(&self, method: &str, params: Value) -> Result<Value, mona_client::ClientError> {
+            let mut result = self.0.request(method, params)?;
+            if method != "kari_executeTransactionBlock" {
+                return Ok(result);
+            }
+            for event in result["events"].as_array_mut().into_iter().flatten() {
+                let Some(module) = event["type"]
+                    .as_str()
+                    .and_then(|type_| type_.strip_suffix("register"))
+                    .map(str::to_string)
+                else {
+                    continue;
+                };
+                let hash = event["data"]["args"][0].as_str().unwrap();
+                let hash = hex::decode(hash.trim_start_matches("0x")).unwrap();
+                event["type"] = json!(format!("{}MetadataRegistered", module));
+                event["data"] = json!({
+                    "id": format!("0x{:0>64}", event["sequence"]),
+                    "owner": event["sender"],
+                    "hash": hash,
+                    "timestamp": event["timestamp_ms"],
+                });
+            }
+            Ok(result)
+        }
+    }
+
+    #[test]
+    fn test_register_on_chain() {
+        let dir = tempdir().unwrap();
+        let mut keystore = Keystore::open(dir.path().join("kari.keystore")).unwrap();
+        let sender = keystore.generate();
+        let node = LocalNode::new();
+        node.fund(&sender, 100_000_000).unwrap();
+        let client = RpcClient::new(MetadataNode(node.clone()));
+        let gas = GasConfig {
+            budget: 10_000_000,
+            price: 1,
+        };
+        let cache = LocalLedger::open(dir.path().join("metadata")).unwrap();
+        let mut ledger = ChainLedger::new(&client, &keystore, gas, cache);
+
+        let hash = "ab".repeat(32);
+        let events = ledger
+            .submit(&sender, MetadataCall::Register { hash: hash.clone() })
+            .unwrap();
+        assert!(matches!(
+            &events[..],
+            [MetadataEvent::MetadataRegistered { owner, hash: registered, .. }]
+                if *owner == sender && *registered == hash
+        ));
+        // The node recorded the call to `0x2::metadata::register`
+        let call = node.events(&Default::default(), 10).pop().unwrap();
+        let package = normalize_address("0x2").unwrap();
+        assert_eq!(call.type_, format!("{}::metadata::register", package));
+        assert_eq!(
+            call.data["args"],
+            json!([format!("0x{}", hash), CLOCK_OBJECT_ID])
+        );
+
+        // The events are cached for the indexer
+        let indexer = MetadataIndexer::open(dir.path().join("index")).unwrap();
+        assert_eq!(indexer.sync(&ledger).unwrap(), 1);
+        assert_eq!(indexer.find_by_owner(&sender).unwrap()[0].hash, hash);
+
+        // Only keys of the keystore can sign
+        assert!(ledger
+            .submit("0xb0b", MetadataCall::Register { hash })
+            .is_err());
+    }
+}
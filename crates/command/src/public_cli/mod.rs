@@ -1,149 +1,682 @@
-use std::path::Path;
-use std::process::exit;
+use std::path::{Path, PathBuf};
 
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Subcommand;
 use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use mona_client::{Keystore, RpcClient};
+use mona_config::{GasConfig, KariConfig};
 use mona_storage::file_storage::FileStorage;
+use mona_storage::metadata::{
+    hash_file, normalize_address, LocalLedger, MetadataCall, MetadataEvent, MetadataIndexer,
+    MetadataLedger, MetadataRecord,
+};
+use mona_storage::quota::{parse_size, QuotaManager, StoragePolicy};
+use mona_storage::upload::UploadSession;
+use mona_storage::versions::{FileVersion, RetentionPolicy, VersionStore};
+use serde::Serialize;
+use uuid::Uuid;
 
-struct CommandInfo {
-    name: &'static str,
-    description: &'static str,
-}
+use crate::output::{print_json, timestamp};
+
+mod ledger;
 
-const COMMANDS: &[CommandInfo] = &[
-    CommandInfo {
-        name: "upload",
-        description: "Upload a file to storage",
+use ledger::ChainLedger;
+
+#[derive(Subcommand)]
+pub enum PublicCommand {
+    /// Upload a file to storage
+    Upload {
+        file: PathBuf,
+        /// Store the file as the next version of this name
+        #[clap(long, value_parser = parse_name)]
+        name: Option<String>,
+        /// Register the file's metadata on-chain with `0x2::metadata::register`,
+        /// signed with the owner's key
+        #[clap(long)]
+        register: bool,
+        /// Owner of the file, the active address of the config by default
+        #[clap(long)]
+        owner: Option<String>,
+    },
+    /// Resume an interrupted upload, or list pending uploads
+    Resume { upload_id: Option<String> },
+    /// Get a file by ID, or a version of a named file (latest by default)
+    Get {
+        #[clap(value_name = "ID | NAME[@VERSION]")]
+        file: String,
+    },
+    /// List the versions of a named file
+    History { name: String },
+    /// Compare the metadata of two versions
+    Diff {
+        name: String,
+        #[clap(value_parser = parse_version)]
+        v1: u64,
+        #[clap(value_parser = parse_version)]
+        v2: u64,
+    },
+    /// Set the retention policy of a named file and prune
+    Retention {
+        name: String,
+        /// Keep at most this many versions
+        #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+        keep: Option<u64>,
+        /// Drop versions older than this many days
+        #[clap(long)]
+        max_age_days: Option<u64>,
+    },
+    /// Remove versions not allowed by the retention policy
+    Prune { name: String },
+    /// Pin a file so that it is never evicted
+    Pin { file_id: Uuid },
+    /// Allow a file to be evicted again
+    Unpin { file_id: Uuid },
+    /// Show or set quotas ('none' to clear a limit)
+    Quota {
+        /// Limit on the total size of the store, e.g. 10G
+        #[clap(long, value_parser = parse_limit)]
+        total: Option<SizeLimit>,
+        /// Limit on the size stored per owner
+        #[clap(long, value_parser = parse_limit)]
+        per_owner: Option<SizeLimit>,
     },
-    CommandInfo {
-        name: "get <id>",
-        description: "Get a file from storage by ID Image/File",
+    /// Report storage usage
+    Stats,
+    /// Find registered metadata by owner or content hash, in the local index
+    Lookup {
+        #[clap(long, conflicts_with = "hash", required_unless_present = "hash")]
+        owner: Option<String>,
+        /// Hex encoded SHA-256 of the content
+        #[clap(long)]
+        hash: Option<String>,
     },
-];
+}
+
+/// A quota limit given on the command line; `none` removes the limit.
+#[derive(Clone, Copy, Debug)]
+pub struct SizeLimit(Option<u64>);
+
+fn parse_limit(value: &str) -> Result<SizeLimit, String> {
+    if value == "none" {
+        return Ok(SizeLimit(None));
+    }
+    parse_size(value)
+        .map(|bytes| SizeLimit(Some(bytes)))
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
+
+fn parse_version(value: &str) -> Result<u64, String> {
+    value
+        .trim_start_matches('v')
+        .parse()
+        .map_err(|_| format!("invalid version '{}'", value))
+}
 
-fn display_help(show_error: bool) {
-    if show_error {
-        println!("\n{}", "ERROR: Invalid command".red().bold());
+fn parse_name(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.contains('@') {
+        return Err("names must be non-empty and may not contain '@'".to_string());
     }
+    Ok(value.to_string())
+}
 
-    // Usage section
-    println!("{}", "USAGE:".bright_yellow().bold());
-    println!("kari public <command> [options]\n");
+/// `upload` and `resume`: the stored file.
+#[derive(Serialize)]
+struct UploadOutput {
+    file_id: Uuid,
+    filename: String,
+    path: PathBuf,
+    size: u64,
+    content_type: String,
+    uploaded_at: String,
+    /// The version created with `--name`, otherwise `null`.
+    version: Option<VersionRef>,
+    /// The metadata record registered with `--register`, otherwise `null`.
+    record: Option<MetadataRecord>,
+}
 
-    // Commands section
-    println!("{}", "COMMANDS:".bright_yellow().bold());
+#[derive(Serialize)]
+struct VersionRef {
+    name: String,
+    version: u64,
+}
 
-    let max_name_len = COMMANDS.iter().map(|cmd| cmd.name.len()).max().unwrap_or(0);
+/// `resume` without an upload ID: `{"pending": [...]}`.
+#[derive(Serialize)]
+struct PendingUpload {
+    upload_id: Uuid,
+    source: PathBuf,
+    filename: String,
+    bytes_written: u64,
+    total_size: u64,
+    started_at: String,
+}
+
+/// `get`: the downloaded file.
+#[derive(Serialize)]
+struct DownloadOutput {
+    file_id: Uuid,
+    filename: String,
+    saved_as: PathBuf,
+    size: u64,
+    content_type: String,
+}
 
-    for cmd in COMMANDS {
-        println!(
-            "  {}{}  {}",
-            cmd.name.green().bold(),
-            " ".repeat(max_name_len - cmd.name.len() + 2),
-            cmd.description.bright_white()
-        );
+/// A version of a named file, as listed by `history`, `retention` and `prune`.
+#[derive(Serialize)]
+struct VersionOutput {
+    version: u64,
+    file_id: Uuid,
+    filename: String,
+    size: u64,
+    content_type: String,
+    hash: String,
+    created_at: String,
+}
+
+impl From<&FileVersion> for VersionOutput {
+    fn from(version: &FileVersion) -> Self {
+        VersionOutput {
+            version: version.version,
+            file_id: version.file_id,
+            filename: version.filename.clone(),
+            size: version.size,
+            content_type: version.content_type.clone(),
+            hash: version.hash.clone(),
+            created_at: timestamp(version.created_at),
+        }
     }
-    println!();
+}
 
-    exit(1);
+// `retention` and `prune`: the versions that were removed
+fn pruned_output(name: &str, pruned: &[FileVersion]) -> serde_json::Value {
+    let pruned: Vec<VersionOutput> = pruned.iter().map(VersionOutput::from).collect();
+    serde_json::json!({ "name": name, "pruned": pruned })
 }
 
 // Handle public commands
-pub fn handle_public_command() -> Option<String> {
-    // Collect command line arguments
-    let args: Vec<String> = std::env::args().collect();
-
-    // Check if any arguments were provided
-    if args.len() > 2 {
-        // Collect command line arguments
-        let command = &args[2];
-        // Use string comparison in the match statement
-        match command.as_str() {
-            "upload" => {
-                if args.len() != 4 {
-                    // Changed from 3 to 4 since command is at index 2
-                    return Some("Usage: kari public upload <file_path>".to_string());
-                }
+pub fn handle_public_command(command: PublicCommand, config: &KariConfig, json: bool) -> Result<()> {
+    let root = config.storage_path();
+    FileStorage::init_storage(&root).context("Failed to initialize storage")?;
 
-                // Initialize storage first
-                if let Err(e) = FileStorage::init_storage() {
-                    return Some(format!("Failed to initialize storage: {}", e));
-                }
+    match command {
+        PublicCommand::Upload {
+            file,
+            name,
+            register,
+            owner,
+        } => {
+            // Fall back to the active address of the configuration
+            let owner = match owner {
+                Some(owner) => Some(normalize_address(&owner)?),
+                None => match &config.active_address {
+                    Some(address) => Some(
+                        normalize_address(address).context("Invalid active_address in config")?,
+                    ),
+                    None => None,
+                },
+            };
+            // Registering signs with the owner's key, so check there is one
+            // before uploading
+            let registrar = if register {
+                let keystore =
+                    Keystore::open(config.keystore_path()).context("Failed to open keystore")?;
+                let owner = keystore
+                    .signer(owner.as_deref())
+                    .context("No key to register the metadata with")?;
+                Some((keystore, owner))
+            } else {
+                None
+            };
+            let owner = match &registrar {
+                Some((_, signer)) => Some(signer.clone()),
+                None => owner,
+            };
 
-                // Get file path from correct argument index (3 instead of 2)
-                let file_path = Path::new(&args[3]);
+            if !file.exists() {
+                bail!("File '{}' not found", file.display());
+            }
 
-                if !file_path.exists() {
-                    return Some(format!("Error: File '{}' not found", file_path.display()));
-                }
+            let filename = file
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unnamed")
+                .to_string();
 
-                let filename = file_path
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unnamed")
-                    .to_string();
+            let session = UploadSession::begin(&root, &file, filename, owner.clone())
+                .context("Upload failed")?;
+            let storage = run_upload(session, json)?;
 
-                match FileStorage::upload(file_path, filename) {
-                    Ok(storage) => {
+            let version = match name {
+                Some(name) => {
+                    let version = add_version(&root, &name, &storage)?;
+                    if !json {
                         println!(
-                            "\n{}\n\nFile ID: {}\nLocation: {}\nSize: {} bytes\nType: {}\n\n{}\n    kari public get {}\n",
-                            "✓ File uploaded successfully!".green().bold(),
-                            storage.id.to_string().yellow().bold(),
-                            storage.path.display(),
-                            storage.metadata.size,
-                            storage.metadata.content_type,
-                            "To download this file, use:".bright_blue(),
-                            storage.id
+                            "{} {}@{}\n",
+                            "✓ Stored as".green().bold(),
+                            name,
+                            version.version
                         );
-                        None
                     }
-                    Err(e) => {
-                        println!("Debug: Upload error = {:?}", e);
-                        Some(format!("{}: {}", "Upload failed".red().bold(), e))
+                    Some(VersionRef {
+                        name,
+                        version: version.version,
+                    })
+                }
+                None => None,
+            };
+            let record = match registrar {
+                Some((keystore, owner)) => {
+                    let client = RpcClient::http(config.rpc_url()?);
+                    let hash = hash_file(&storage.path).context("Failed to hash file")?;
+                    let record =
+                        register_metadata(&root, &client, &keystore, &config.gas, &owner, hash)?;
+                    if !json {
+                        println!("{}", "✓ Metadata registered on-chain!".green().bold());
+                        print_record(&record);
                     }
+                    Some(record)
                 }
+                None => None,
+            };
+
+            if json {
+                print_json(&upload_output(&storage, version, record))?;
             }
+            Ok(())
+        }
 
-            "get" => {
-                if args.len() != 4 {
-                    return Some("Usage: kari public get <file_id>".to_string());
+        PublicCommand::Resume { upload_id } => {
+            let Some(upload_id) = upload_id else {
+                let sessions =
+                    UploadSession::pending(&root).context("Failed to list pending uploads")?;
+                if json {
+                    let pending: Vec<PendingUpload> = sessions
+                        .into_iter()
+                        .map(|session| PendingUpload {
+                            upload_id: session.upload_id,
+                            source: session.source,
+                            filename: session.filename,
+                            bytes_written: session.bytes_written,
+                            total_size: session.total_size,
+                            started_at: timestamp(session.started_at),
+                        })
+                        .collect();
+                    return print_json(&serde_json::json!({ "pending": pending }));
+                }
+                if sessions.is_empty() {
+                    println!("No pending uploads");
+                    return Ok(());
                 }
+                println!("{}", "PENDING UPLOADS:".bright_yellow().bold());
+                for session in sessions {
+                    println!(
+                        "  {}  {} ({}/{} bytes)",
+                        session.upload_id.to_string().green().bold(),
+                        session.source.display(),
+                        session.bytes_written,
+                        session.total_size
+                    );
+                }
+                return Ok(());
+            };
+
+            let session = UploadSession::resume(&root, &upload_id).context("Resume failed")?;
+            let storage = run_upload(session, json)?;
+            if json {
+                print_json(&upload_output(&storage, None, None))?;
+            }
+            Ok(())
+        }
+
+        PublicCommand::Get { file } => {
+            // Anything that is not a file ID is looked up as a named file
+            let file_id = if Uuid::parse_str(&file).is_ok() {
+                file
+            } else {
+                VersionStore::open(&root)
+                    .and_then(|store| store.resolve(&file))
+                    .context("Failed to get file")?
+                    .file_id
+                    .to_string()
+            };
+
+            let storage = FileStorage::get_by_id(&root, &file_id).context("Failed to get file")?;
 
-                let file_id = &args[3];
+            // Keep recently downloaded files out of the eviction queue
+            if let Err(e) = QuotaManager::open(&root).and_then(|mut q| q.touch(&storage.id)) {
+                eprintln!("Warning: failed to update usage index: {}", e);
+            }
+
+            // Copy file to current directory
+            let target_path = std::env::current_dir()
+                .context("Failed to get current directory")?
+                .join(&storage.metadata.filename);
+            std::fs::copy(&storage.path, &target_path).context("Failed to save file")?;
+            if json {
+                return print_json(&DownloadOutput {
+                    file_id: storage.id,
+                    filename: storage.metadata.filename,
+                    saved_as: target_path,
+                    size: storage.metadata.size,
+                    content_type: storage.metadata.content_type,
+                });
+            }
+            println!(
+                "File downloaded successfully!\nID: {}\nSaved as: {}\nSize: {} bytes\nType: {}",
+                storage.id,
+                target_path.display(),
+                storage.metadata.size,
+                storage.metadata.content_type
+            );
+            Ok(())
+        }
+
+        PublicCommand::History { name } => {
+            let chain = VersionStore::open(&root)
+                .and_then(|store| store.history(&name))
+                .context("Failed to read history")?;
+            if json {
+                let versions: Vec<VersionOutput> =
+                    chain.versions.iter().map(VersionOutput::from).collect();
+                return print_json(&serde_json::json!({
+                    "name": chain.name,
+                    "retention": chain.retention,
+                    "versions": versions,
+                }));
+            }
+            println!("{} {}", "HISTORY:".bright_yellow().bold(), chain.name);
+            for version in chain.versions.iter().rev() {
+                println!(
+                    "  {}  {}  {} bytes  {}  {}",
+                    format!("v{}", version.version).green().bold(),
+                    version.file_id,
+                    version.size,
+                    &version.hash[..version.hash.len().min(16)],
+                    chrono::DateTime::<chrono::Local>::from(version.created_at)
+                        .format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+            Ok(())
+        }
+
+        PublicCommand::Diff { name, v1, v2 } => {
+            let changes = VersionStore::open(&root)
+                .and_then(|store| store.diff(&name, v1, v2))
+                .context("Failed to diff versions")?;
+            if json {
+                return print_json(&serde_json::json!({
+                    "name": name,
+                    "v1": v1,
+                    "v2": v2,
+                    "changes": changes,
+                }));
+            }
+            if changes.is_empty() {
+                println!("v{} and v{} are identical", v1, v2);
+            }
+            for change in changes {
+                println!("{}:", change.field.bright_white().bold());
+                println!("  {} {}", "-".red(), change.old.red());
+                println!("  {} {}", "+".green(), change.new.green());
+            }
+            Ok(())
+        }
 
-                if let Err(e) = FileStorage::init_storage() {
-                    return Some(format!("Failed to initialize storage: {}", e));
+        PublicCommand::Retention {
+            name,
+            keep,
+            max_age_days,
+        } => {
+            let retention = RetentionPolicy {
+                keep_last: keep.map(|keep| keep as usize),
+                max_age_days,
+            };
+            let pruned = VersionStore::open(&root)
+                .and_then(|store| store.set_retention(&name, retention))
+                .context("Failed to apply retention policy")?;
+            if json {
+                return print_json(&pruned_output(&name, &pruned));
+            }
+            print_pruned(&pruned);
+            Ok(())
+        }
+
+        PublicCommand::Prune { name } => {
+            let pruned = VersionStore::open(&root)
+                .and_then(|store| store.prune(&name))
+                .context("Failed to apply retention policy")?;
+            if json {
+                return print_json(&pruned_output(&name, &pruned));
+            }
+            print_pruned(&pruned);
+            Ok(())
+        }
+
+        PublicCommand::Pin { file_id } => set_pinned(&root, file_id, true, json),
+        PublicCommand::Unpin { file_id } => set_pinned(&root, file_id, false, json),
+
+        PublicCommand::Quota { total, per_owner } => {
+            let mut quotas = QuotaManager::open(&root).context("Failed to open usage index")?;
+            if total.is_some() || per_owner.is_some() {
+                let mut policy = quotas.policy().clone();
+                if let Some(SizeLimit(limit)) = total {
+                    policy.max_total_bytes = limit;
                 }
+                if let Some(SizeLimit(limit)) = per_owner {
+                    policy.max_bytes_per_owner = limit;
+                }
+                quotas.set_policy(policy).context("Failed to save quotas")?;
+            }
+            // `{"max_total_bytes": .., "max_bytes_per_owner": ..}`, `null` when unlimited
+            if json {
+                return print_json(quotas.policy());
+            }
+            print_policy(quotas.policy());
+            Ok(())
+        }
 
-                match FileStorage::get_by_id(file_id) {
-                    Ok(storage) => {
-                        // Get current directory for saving the file
-                        let current_dir =
-                            std::env::current_dir().expect("Failed to get current directory");
-                        let target_path = current_dir.join(&storage.metadata.filename);
-
-                        // Copy file to current directory
-                        match std::fs::copy(&storage.path, &target_path) {
-                            Ok(_) => Some(format!(
-                                "File downloaded successfully!\nID: {}\nSaved as: {}\nSize: {} bytes\nType: {}",
-                                storage.id,
-                                target_path.display(),
-                                storage.metadata.size,
-                                storage.metadata.content_type
-                            )),
-                            Err(e) => Some(format!("Failed to save file: {}", e))
-                        }
-                    }
-                    Err(e) => Some(format!("Failed to get file: {}", e)),
+        PublicCommand::Stats => {
+            let quotas = QuotaManager::open(&root).context("Failed to open usage index")?;
+            let stats = quotas.stats();
+            if json {
+                return print_json(&stats);
+            }
+            println!("{}", "STORAGE:".bright_yellow().bold());
+            println!("  Files:        {}", stats.file_count);
+            println!("  Used:         {}", format_bytes(stats.total_bytes));
+            println!(
+                "  Pinned:       {} ({} files)",
+                format_bytes(stats.pinned_bytes),
+                stats.pinned_count
+            );
+            if !stats.bytes_per_owner.is_empty() {
+                println!("{}", "OWNERS:".bright_yellow().bold());
+                for (owner, bytes) in &stats.bytes_per_owner {
+                    println!("  {}  {}", owner, format_bytes(*bytes));
                 }
             }
+            print_policy(&stats.policy);
+            Ok(())
+        }
 
-            _ => {
-                display_help(true);
-                None
+        PublicCommand::Lookup { owner, hash } => {
+            let (_, indexer) = open_metadata_index(&root).context("Failed to open metadata index")?;
+            let records = match (owner, hash) {
+                (Some(owner), _) => indexer.find_by_owner(&owner),
+                (None, Some(hash)) => indexer.find_by_hash(&hash),
+                (None, None) => unreachable!("clap requires --owner or --hash"),
+            }
+            .context("Lookup failed")?;
+            if json {
+                return print_json(&serde_json::json!({ "records": records }));
+            }
+            if records.is_empty() {
+                println!("No metadata records found");
             }
+            for record in &records {
+                print_record(record);
+            }
+            Ok(())
         }
+    }
+}
+
+fn upload_output(
+    storage: &FileStorage,
+    version: Option<VersionRef>,
+    record: Option<MetadataRecord>,
+) -> UploadOutput {
+    UploadOutput {
+        file_id: storage.id,
+        filename: storage.metadata.filename.clone(),
+        path: storage.path.clone(),
+        size: storage.metadata.size,
+        content_type: storage.metadata.content_type.clone(),
+        uploaded_at: timestamp(storage.metadata.uploaded_at),
+        version,
+        record,
+    }
+}
+
+// Stream an upload session into storage, with a progress bar unless the
+// output is JSON
+fn run_upload(session: UploadSession, json: bool) -> Result<FileStorage> {
+    let upload_id = session.upload_id;
+    let bar = if json {
+        ProgressBar::hidden()
     } else {
-        display_help(false);
-        return None;
+        ProgressBar::new(session.total_size)
+    };
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+
+    match session.run(|written, _| bar.set_position(written)) {
+        Ok(storage) => {
+            bar.finish_and_clear();
+            if !json {
+                println!(
+                    "\n{}\n\nFile ID: {}\nLocation: {}\nSize: {} bytes\nType: {}\n\n{}\n    kari public get {}\n",
+                    "✓ File uploaded successfully!".green().bold(),
+                    storage.id.to_string().yellow().bold(),
+                    storage.path.display(),
+                    storage.metadata.size,
+                    storage.metadata.content_type,
+                    "To download this file, use:".bright_blue(),
+                    storage.id
+                );
+            }
+            Ok(storage)
+        }
+        Err(e) => {
+            bar.abandon();
+            Err(anyhow!(
+                "Upload failed: {}\nTo continue this upload, use:\n    kari public resume {}",
+                e,
+                upload_id
+            ))
+        }
+    }
+}
+
+fn set_pinned(root: &Path, file_id: Uuid, pinned: bool, json: bool) -> Result<()> {
+    QuotaManager::open(root)
+        .and_then(|mut q| q.set_pinned(&file_id, pinned))
+        .with_context(|| format!("Failed to {} file", if pinned { "pin" } else { "unpin" }))?;
+    if json {
+        return print_json(&serde_json::json!({ "file_id": file_id, "pinned": pinned }));
+    }
+    println!("{} {}", if pinned { "Pinned" } else { "Unpinned" }, file_id);
+    Ok(())
+}
+
+fn print_pruned(pruned: &[FileVersion]) {
+    println!("{}", "✓ Retention policy applied".green().bold());
+    for version in pruned {
+        println!("  pruned v{} ({})", version.version, version.file_id);
+    }
+}
+
+fn print_policy(policy: &StoragePolicy) {
+    let limit = |limit: Option<u64>| limit.map_or("unlimited".to_string(), format_bytes);
+    println!("{}", "QUOTAS:".bright_yellow().bold());
+    println!("  Total:        {}", limit(policy.max_total_bytes));
+    println!("  Per owner:    {}", limit(policy.max_bytes_per_owner));
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
-}
\ No newline at end of file
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Record an uploaded file as the next version of `name`
+fn add_version(root: &Path, name: &str, storage: &FileStorage) -> Result<FileVersion> {
+    let hash = hash_file(&storage.path).context("Failed to hash file")?;
+    VersionStore::open(root)
+        .and_then(|store| store.add_version(name, storage.id, &storage.metadata, hash))
+        .context("Failed to record version")
+}
+
+// Open the local metadata ledger, which caches the registry events, and bring
+// the index up to date with it
+fn open_metadata_index(
+    root: &Path,
+) -> Result<(LocalLedger, MetadataIndexer), mona_storage::StorageError> {
+    let ledger = LocalLedger::open_in(root)?;
+    let indexer = MetadataIndexer::open_in(root)?;
+    indexer.sync(&ledger)?;
+    Ok((ledger, indexer))
+}
+
+// Register the metadata of a file with content `hash` on-chain, signed by
+// `owner`, and index the emitted events
+fn register_metadata(
+    root: &Path,
+    client: &RpcClient,
+    keystore: &Keystore,
+    gas: &GasConfig,
+    owner: &str,
+    hash: String,
+) -> Result<MetadataRecord> {
+    let (cache, indexer) = open_metadata_index(root).context("Failed to open metadata index")?;
+    let mut ledger = ChainLedger::new(client, keystore, gas.clone(), cache);
+    let events = ledger
+        .submit(owner, MetadataCall::Register { hash })
+        .context("Registration failed")?;
+    indexer
+        .sync(&ledger)
+        .context("Failed to index metadata events")?;
+
+    events
+        .iter()
+        .find(|event| matches!(event, MetadataEvent::MetadataRegistered { .. }))
+        .and_then(|event| indexer.get(event.record_id()).ok().flatten())
+        .ok_or_else(|| anyhow!("Registration did not emit a MetadataRegistered event"))
+}
+
+fn print_record(record: &MetadataRecord) {
+    println!(
+        "\nRecord ID: {}\nOwner: {}\nHash: {}\nVersion: {}\nTimestamp: {}\nPublic read: {}\n",
+        record.id.yellow().bold(),
+        record.owner,
+        record.hash,
+        record.version,
+        record.timestamp,
+        record.permissions.public_read
+    );
+}
@@ -0,0 +1,573 @@
+//! `kari update`: install the latest release of the Kari tools.
+//!
+//! Releases are listed in a JSON index, `update.index_url` of the config:
+//!
+//! ```json
+//! {
+//!   "version": "0.0.6",
+//!   "notes": "Bug fixes",
+//!   "assets": {
+//!     "x86_64-linux": {
+//!       "url": "kari-0.0.6-x86_64-linux.tar.gz",
+//!       "sha256": "<hex>",
+//!       "signature": "kari-0.0.6-x86_64-linux.tar.gz.minisig"
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Assets are keyed by `<arch>-<os>` and relative URLs are resolved against
+//! the index. An archive is only installed if its SHA-256 matches and it has a
+//! valid minisign signature by the release key, whose trusted comment names
+//! the version of the index (`minisign -S -t "version:0.0.6" -m <archive>`),
+//! so that an old archive cannot be passed off as a newer release. The release
+//! key is built into release binaries from `KARI_RELEASE_PUBLIC_KEY`, and
+//! `update.public_key` overrides it. The new binary is renamed
+//! over the running one; the previous binary is kept next to it as
+//! `<name>.old` and put back if the new one fails to start, or on
+//! `kari update --rollback`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use minisign_verify::{PublicKey, Signature};
+use mona_config::KariConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::output::print_json;
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Minisign public key that releases are signed with, set by release builds.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("KARI_RELEASE_PUBLIC_KEY");
+
+#[derive(Args)]
+pub struct UpdateArgs {
+    /// Only report whether an update is available
+    #[clap(long)]
+    check: bool,
+    /// Install the latest release even if it is not newer
+    #[clap(long)]
+    force: bool,
+    /// Restore the binary replaced by the last update
+    #[clap(long, conflicts_with_all = ["check", "force", "index"])]
+    rollback: bool,
+    /// Release index to use instead of `update.index_url`
+    #[clap(long, value_name = "URL")]
+    index: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReleaseIndex {
+    pub version: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Archives by platform, see [`platform`].
+    pub assets: BTreeMap<String, ReleaseAsset>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReleaseAsset {
+    /// `.tar.gz` archive holding the `kari` binary.
+    pub url: String,
+    /// Hex encoded SHA-256 of the archive.
+    pub sha256: String,
+    /// Minisign signature of the archive, with `version:<version>` in its
+    /// trusted comment.
+    pub signature: String,
+}
+
+/// `--check`, and the outcome of an update.
+#[derive(Serialize, Debug)]
+pub struct UpdateReport {
+    pub current_version: String,
+    pub latest_version: String,
+    pub update_available: bool,
+    pub notes: Option<String>,
+    /// Whether the latest release was installed.
+    pub installed: bool,
+    /// The replaced binary, when installed.
+    pub backup: Option<PathBuf>,
+}
+
+/// Platform key of release assets, `<arch>-<os>`.
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Where the binary replaced by an update is kept.
+pub fn backup_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+// `1.2.3`, `v1.2.3` or `1.2.3-rc.1` as numeric components; pre-release tags are ignored
+fn parse_version(version: &str) -> Result<Vec<u64>> {
+    let version = version.trim().trim_start_matches('v');
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    core.split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("Invalid version '{}'", version))
+}
+
+fn is_newer(latest: &str, current: &str) -> Result<bool> {
+    Ok(parse_version(latest)? > parse_version(current)?)
+}
+
+// Move `new` to `exe`, which may be the running executable
+fn move_into_place(new: &Path, exe: &Path) -> std::io::Result<()> {
+    // A running executable can be renamed on Windows, but not replaced
+    #[cfg(windows)]
+    if exe.exists() {
+        let aside = exe.with_extension("replaced");
+        let _ = fs::remove_file(&aside);
+        fs::rename(exe, &aside)?;
+    }
+    fs::rename(new, exe)
+}
+
+// The `kari` binary inside a `.tar.gz` release archive
+fn extract_binary(archive: &[u8]) -> Result<Vec<u8>> {
+    let name = format!("kari{}", std::env::consts::EXE_SUFFIX);
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries().context("Invalid release archive")? {
+        let mut entry = entry.context("Invalid release archive")?;
+        if entry
+            .path()?
+            .file_name()
+            .is_some_and(|file| file == name.as_str())
+        {
+            let mut binary = Vec::new();
+            entry.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    bail!("Release archive does not contain '{}'", name)
+}
+
+pub struct Updater {
+    index_url: Url,
+    public_key: Option<String>,
+    exe: PathBuf,
+    current_version: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Updater {
+    /// Updater of the binary `exe`, currently at `current_version`.
+    pub fn new(
+        index_url: &str,
+        public_key: Option<String>,
+        exe: PathBuf,
+        current_version: &str,
+    ) -> Result<Self> {
+        Ok(Updater {
+            index_url: Url::parse(index_url)
+                .with_context(|| format!("Invalid release index URL '{}'", index_url))?,
+            public_key,
+            exe,
+            current_version: current_version.to_string(),
+            client: reqwest::blocking::Client::new(),
+        })
+    }
+
+    fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let url = self
+            .index_url
+            .join(url)
+            .with_context(|| format!("Invalid URL '{}'", url))?;
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to download {}", url))?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    pub fn fetch_index(&self) -> Result<ReleaseIndex> {
+        let index = self.download(self.index_url.as_str())?;
+        serde_json::from_slice(&index).context("Invalid release index")
+    }
+
+    pub fn check(&self, index: &ReleaseIndex) -> Result<UpdateReport> {
+        Ok(UpdateReport {
+            current_version: self.current_version.clone(),
+            latest_version: index.version.clone(),
+            update_available: is_newer(&index.version, &self.current_version)?,
+            notes: index.notes.clone(),
+            installed: false,
+            backup: None,
+        })
+    }
+
+    // Check the archive against the checksum and signature of `asset`, which must be signed
+    // as the release `version`
+    fn verify(&self, asset: &ReleaseAsset, version: &str, archive: &[u8]) -> Result<()> {
+        let checksum = hex::encode(Sha256::digest(archive));
+        if !checksum.eq_ignore_ascii_case(asset.sha256.trim()) {
+            bail!(
+                "Checksum mismatch: expected {}, got {}",
+                asset.sha256.trim(),
+                checksum
+            );
+        }
+
+        let public_key = self
+            .public_key
+            .as_deref()
+            .context("No release key to verify the update with, set update.public_key")?;
+        let public_key = if public_key.trim().contains('\n') {
+            PublicKey::decode(public_key.trim())
+        } else {
+            PublicKey::from_base64(public_key.trim())
+        }
+        .map_err(|e| anyhow!("Invalid update.public_key: {}", e))?;
+
+        let signature = String::from_utf8(self.download(&asset.signature)?)
+            .context("Invalid release signature")?;
+        let signature = Signature::decode(&signature)
+            .map_err(|e| anyhow!("Invalid release signature: {}", e))?;
+        public_key
+            .verify(archive, &signature, false)
+            .map_err(|e| anyhow!("Release signature verification failed: {}", e))?;
+
+        // The trusted comment is covered by the signature, unlike the index
+        let signed_version = signature
+            .trusted_comment()
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("version:"));
+        if signed_version != Some(version.trim()) {
+            bail!(
+                "The release signature is for version {}, not {}",
+                signed_version.unwrap_or("(none)"),
+                version.trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// Download, verify and install the release for this platform, returning
+    /// the path of the replaced binary.
+    pub fn install(&self, index: &ReleaseIndex) -> Result<PathBuf> {
+        let platform = platform();
+        let asset = index
+            .assets
+            .get(&platform)
+            .with_context(|| format!("Release {} has no build for {}", index.version, platform))?;
+        let archive = self.download(&asset.url)?;
+        self.verify(asset, &index.version, &archive)?;
+        let binary = extract_binary(&archive)?;
+
+        // Stage next to the binary so that the final rename is atomic
+        let dir = self.exe.parent().unwrap_or(Path::new("."));
+        let mut staged = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to write to {}", dir.display()))?;
+        staged.write_all(&binary)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(staged.path(), fs::Permissions::from_mode(0o755))?;
+        }
+        let staged = staged.into_temp_path();
+
+        let backup = backup_path(&self.exe);
+        fs::copy(&self.exe, &backup)
+            .with_context(|| format!("Failed to back up {}", self.exe.display()))?;
+        move_into_place(&staged, &self.exe)
+            .with_context(|| format!("Failed to replace {}", self.exe.display()))?;
+
+        let started = Command::new(&self.exe)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !started {
+            self.rollback()?;
+            bail!(
+                "The new binary failed to start, restored kari {}",
+                self.current_version
+            );
+        }
+        Ok(backup)
+    }
+
+    /// Restore the binary replaced by the last update.
+    pub fn rollback(&self) -> Result<()> {
+        let backup = backup_path(&self.exe);
+        if !backup.exists() {
+            bail!("No previous version to roll back to");
+        }
+        move_into_place(&backup, &self.exe)
+            .with_context(|| format!("Failed to restore {}", self.exe.display()))
+    }
+}
+
+// Handle `kari update`
+pub fn handle_update_command(args: UpdateArgs, config: &KariConfig, json: bool) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate the kari binary")?;
+    let index_url = args.index.as_deref().unwrap_or(&config.update.index_url);
+    let public_key = config
+        .update
+        .public_key
+        .clone()
+        .or(RELEASE_PUBLIC_KEY.map(str::to_string));
+    let updater = Updater::new(index_url, public_key, exe.clone(), CURRENT_VERSION)?;
+
+    if args.rollback {
+        updater.rollback()?;
+        if json {
+            return print_json(&serde_json::json!({ "restored": exe }));
+        }
+        println!(
+            "{} {}",
+            "✓ Restored previous version of".green().bold(),
+            exe.display()
+        );
+        return Ok(());
+    }
+
+    let index = updater.fetch_index()?;
+    let mut report = updater.check(&index)?;
+    if !args.check && (report.update_available || args.force) {
+        if !json {
+            println!("Installing kari {}...", report.latest_version);
+        }
+        report.backup = Some(updater.install(&index)?);
+        report.installed = true;
+    }
+
+    if json {
+        return print_json(&report);
+    }
+    if report.installed {
+        println!(
+            "{} {} -> {}",
+            "✓ Updated kari".green().bold(),
+            report.current_version,
+            report.latest_version
+        );
+        println!("Run 'kari update --rollback' to restore the previous version");
+    } else if report.update_available {
+        println!(
+            "kari {} is available (installed: {}), run 'kari update' to install it",
+            report.latest_version.green().bold(),
+            report.current_version
+        );
+    } else {
+        println!("kari {} is up to date", report.current_version);
+    }
+    if let Some(notes) = report.notes.as_deref().filter(|_| report.update_available) {
+        println!("\n{}\n{}", "RELEASE NOTES:".bright_yellow().bold(), notes);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use blake2::Blake2b512;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::collections::HashMap;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    const KEY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    // Serve `files` over HTTP, returning the base URL
+    fn serve(files: HashMap<String, Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match files.get(path.trim_start_matches('/')) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        base
+    }
+
+    fn public_key(key: &SigningKey) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend_from_slice(&KEY_ID);
+        bytes.extend_from_slice(key.verifying_key().as_bytes());
+        STANDARD.encode(bytes)
+    }
+
+    // Minisign signature of `data`, prehashed, as release `version`
+    fn minisign(key: &SigningKey, data: &[u8], version: &str) -> Vec<u8> {
+        let signature = key.sign(&Blake2b512::digest(data)).to_bytes();
+        let trusted_comment = format!("timestamp:0\tfile:kari.tar.gz\tversion:{}", version);
+        let mut global = signature.to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+        let global = key.sign(&global).to_bytes();
+
+        let mut line = b"ED".to_vec();
+        line.extend_from_slice(&KEY_ID);
+        line.extend_from_slice(&signature);
+        format!(
+            "untrusted comment: signature\n{}\ntrusted comment: {}\n{}\n",
+            STANDARD.encode(line),
+            trusted_comment,
+            STANDARD.encode(global)
+        )
+        .into_bytes()
+    }
+
+    fn archive(script: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "kari-9.9.9/kari", script.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    // Serve release 9.9.9 of `script`; `tamper` edits the index before it is published
+    fn release(
+        script: &str,
+        signer: &SigningKey,
+        tamper: impl FnOnce(&mut serde_json::Value),
+    ) -> String {
+        let archive = archive(script);
+        let mut index = serde_json::json!({
+            "version": "9.9.9",
+            "notes": "Faster",
+            "assets": {
+                platform(): {
+                    "url": "kari.tar.gz",
+                    "sha256": hex::encode(Sha256::digest(&archive)),
+                    "signature": "kari.tar.gz.minisig",
+                }
+            }
+        });
+        tamper(&mut index);
+        let files = HashMap::from([
+            (
+                "release-index.json".to_string(),
+                index.to_string().into_bytes(),
+            ),
+            (
+                "kari.tar.gz.minisig".to_string(),
+                minisign(signer, &archive, "9.9.9"),
+            ),
+            ("kari.tar.gz".to_string(), archive),
+        ]);
+        format!("{}release-index.json", serve(files))
+    }
+
+    fn installed(dir: &Path) -> PathBuf {
+        let exe = dir.join("kari");
+        fs::write(&exe, "#!/bin/sh\necho kari 0.0.5\n").unwrap();
+        exe
+    }
+
+    #[test]
+    fn test_install_and_rollback() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let url = release("#!/bin/sh\necho kari 9.9.9\n", &key, |_| {});
+        let dir = tempfile::tempdir().unwrap();
+        let exe = installed(dir.path());
+        let updater = Updater::new(&url, Some(public_key(&key)), exe.clone(), "0.0.5").unwrap();
+
+        let index = updater.fetch_index().unwrap();
+        let report = updater.check(&index).unwrap();
+        assert!(report.update_available);
+        assert_eq!(report.notes.as_deref(), Some("Faster"));
+
+        let backup = updater.install(&index).unwrap();
+        assert!(fs::read_to_string(&exe).unwrap().contains("9.9.9"));
+        assert!(fs::read_to_string(&backup).unwrap().contains("0.0.5"));
+
+        updater.rollback().unwrap();
+        assert!(fs::read_to_string(&exe).unwrap().contains("0.0.5"));
+        assert!(!backup.exists());
+        assert!(updater.rollback().is_err());
+    }
+
+    #[test]
+    fn test_rejects_unverified_release() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let dir = tempfile::tempdir().unwrap();
+        let exe = installed(dir.path());
+        let script = "#!/bin/sh\necho kari 9.9.9\n";
+
+        let cases = [
+            (
+                release(script, &key, |index| {
+                    index["assets"][platform()]["sha256"] = "00".into()
+                }),
+                Some(public_key(&key)),
+            ),
+            // Signed as 9.9.9, so it cannot be installed as another version
+            (
+                release(script, &key, |index| index["version"] = "10.0.0".into()),
+                Some(public_key(&key)),
+            ),
+            (release(script, &other, |_| {}), Some(public_key(&key))),
+            (release(script, &key, |_| {}), None),
+        ];
+        for (url, public_key) in cases {
+            let updater = Updater::new(&url, public_key, exe.clone(), "0.0.5").unwrap();
+            let index = updater.fetch_index().unwrap();
+            assert!(updater.install(&index).is_err());
+            assert!(fs::read_to_string(&exe).unwrap().contains("0.0.5"));
+            assert!(!backup_path(&exe).exists());
+        }
+    }
+
+    #[test]
+    fn test_restores_binary_that_fails_to_start() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let url = release("#!/bin/sh\nexit 1\n", &key, |_| {});
+        let dir = tempfile::tempdir().unwrap();
+        let exe = installed(dir.path());
+        let updater = Updater::new(&url, Some(public_key(&key)), exe.clone(), "0.0.5").unwrap();
+
+        let index = updater.fetch_index().unwrap();
+        assert!(updater.install(&index).is_err());
+        assert!(fs::read_to_string(&exe).unwrap().contains("0.0.5"));
+    }
+
+    #[test]
+    fn test_version_order() {
+        assert!(is_newer("0.0.6", "0.0.5").unwrap());
+        assert!(is_newer("v0.10.0", "0.9.9").unwrap());
+        assert!(!is_newer("0.0.5-rc.1", "0.0.5").unwrap());
+        assert!(is_newer("latest", "0.0.5").is_err());
+    }
+}
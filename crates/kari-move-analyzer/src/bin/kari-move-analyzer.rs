@@ -7,8 +7,11 @@ use clap::Parser;
 use crossbeam::channel::{bounded, select};
 use lsp_server::{Connection, Message, Notification, Request, Response};
 use lsp_types::{
-    notification::Notification as _, request::Request as _, CompletionOptions, Diagnostic,
-    HoverProviderCapability, OneOf, SaveOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    notification::Notification as _, request::Request as _, CallHierarchyServerCapability,
+    CodeActionProviderCapability, CodeLensOptions, CompletionOptions, Diagnostic,
+    ExecuteCommandOptions, HoverProviderCapability, OneOf, RenameOptions, SaveOptions,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensServerCapabilities,
+    SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
     TextDocumentSyncOptions, TypeDefinitionProviderCapability, WorkDoneProgressOptions,
 };
 use std::{
@@ -19,9 +22,20 @@ use std::{
 };
 
 use kari_move_analyzer::{
+    call_hierarchy::{
+        on_incoming_calls_request, on_outgoing_calls_request, on_prepare_call_hierarchy_request,
+    },
+    code_actions::on_code_action_request,
+    code_lens::on_code_lens_request,
     completion::on_completion_request,
     context::Context,
+    formatting::{on_formatting_request, on_range_formatting_request},
+    inlay_hints::{on_inlay_hint_request, INLAY_HINT_METHOD},
+    rename::{on_prepare_rename_request, on_rename_request},
+    semantic_tokens::{self, on_semantic_tokens_request},
+    signature_help::on_signature_help_request,
     symbols,
+    test_runner::{on_execute_command_request, run_tests_in_process, TestRun, RUN_TEST_COMMAND},
     vfs::{on_text_document_sync_notification, VirtualFileSystem},
 };
 use move_symbol_pool::Symbol;
@@ -29,12 +43,21 @@ use url::Url;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
-struct Options {}
+struct Options {
+    /// Run the tests of a `TestRun`, given as JSON, instead of serving (used by the test code
+    /// lenses)
+    #[clap(long = "run-tests", value_name = "TEST_RUN", hide = true)]
+    run_tests: Option<String>,
+}
 
 fn main() {
-    // For now, move-analyzer only responds to options built-in to clap,
-    // such as `--help` or `--version`.
-    Options::parse();
+    // Besides the options built-in to clap, such as `--help` or `--version`, move-analyzer only
+    // has the option it runs tests with.
+    let options = Options::parse();
+    if let Some(run) = options.run_tests {
+        let run: TestRun = serde_json::from_str(&run).expect("could not deserialize test run");
+        std::process::exit(run_tests_in_process(&run));
+    }
 
     // stdio is used to communicate Language Server Protocol requests and responses.
     // stderr is used for logging (and, when Visual Studio Code is used to communicate with this
@@ -61,19 +84,15 @@ fn main() {
         .initialize_start()
         .expect("could not start connection initialization");
 
-    let capabilities = serde_json::to_value(lsp_types::ServerCapabilities {
+    let mut capabilities = serde_json::to_value(lsp_types::ServerCapabilities {
         // The server receives notifications from the client as users open, close,
         // and modify documents.
         text_document_sync: Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
                 open_close: Some(true),
-                // TODO: We request that the language server client send us the entire text of any
-                // files that are modified. We ought to use the "incremental" sync kind, which would
-                // have clients only send us what has changed and where, thereby requiring far less
-                // data be sent "over the wire." However, to do so, our language server would need
-                // to be capable of applying deltas to its view of the client's open files. See the
-                // 'move_analyzer::vfs' module for details.
-                change: Some(TextDocumentSyncKind::Full),
+                // Clients only send us what has changed and where, which the virtual file system
+                // applies to its view of the client's open files.
+                change: Some(TextDocumentSyncKind::Incremental),
                 will_save: None,
                 will_save_wait_until: None,
                 save: Some(
@@ -101,15 +120,56 @@ fn main() {
                 work_done_progress: None,
             },
         }),
+        // Signature help is requested when an argument list is opened or an argument is started.
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            retrigger_characters: None,
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+        }),
         definition_provider: Some(OneOf::Left(symbols::DEFS_AND_REFS_SUPPORT)),
         type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(
             symbols::DEFS_AND_REFS_SUPPORT,
         )),
         references_provider: Some(OneOf::Left(symbols::DEFS_AND_REFS_SUPPORT)),
         document_symbol_provider: Some(OneOf::Left(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+                legend: semantic_tokens::legend(),
+                range: None,
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+            },
+        )),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        // Tests are run by lenses over them, with a command that runs them in the background.
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![RUN_TEST_COMMAND.to_string()],
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+        }),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+        })),
         ..Default::default()
     })
     .expect("could not serialize server capabilities");
+    // inlay hints are not part of the server capabilities known to `lsp_types`
+    capabilities["inlayHintProvider"] = serde_json::Value::Bool(true);
 
     let (diag_sender, diag_receiver) = bounded::<Result<BTreeMap<Symbol, Vec<Diagnostic>>>>(0);
     let mut symbolicator_runner = symbols::SymbolicatorRunner::idle();
@@ -241,6 +301,46 @@ fn on_request(context: &Context, request: &Request) {
         lsp_types::request::DocumentSymbolRequest::METHOD => {
             symbols::on_document_symbol_request(context, request, &context.symbols.lock().unwrap());
         }
+        lsp_types::request::WorkspaceSymbol::METHOD => {
+            symbols::on_workspace_symbol_request(
+                context,
+                request,
+                &context.symbols.lock().unwrap(),
+            );
+        }
+        lsp_types::request::CallHierarchyPrepare::METHOD => {
+            on_prepare_call_hierarchy_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::CallHierarchyIncomingCalls::METHOD => {
+            on_incoming_calls_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::CallHierarchyOutgoingCalls::METHOD => {
+            on_outgoing_calls_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::SemanticTokensFullRequest::METHOD => {
+            on_semantic_tokens_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::CodeActionRequest::METHOD => {
+            on_code_action_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::CodeLensRequest::METHOD => on_code_lens_request(context, request),
+        lsp_types::request::ExecuteCommand::METHOD => on_execute_command_request(context, request),
+        lsp_types::request::SignatureHelpRequest::METHOD => {
+            on_signature_help_request(context, request, &context.symbols.lock().unwrap());
+        }
+        INLAY_HINT_METHOD => {
+            on_inlay_hint_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::PrepareRenameRequest::METHOD => {
+            on_prepare_rename_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::Rename::METHOD => {
+            on_rename_request(context, request, &context.symbols.lock().unwrap());
+        }
+        lsp_types::request::Formatting::METHOD => on_formatting_request(context, request),
+        lsp_types::request::RangeFormatting::METHOD => {
+            on_range_formatting_request(context, request)
+        }
         _ => eprintln!("handle request '{}' from client", request.method),
     }
 }
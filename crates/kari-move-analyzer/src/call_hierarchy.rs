@@ -0,0 +1,296 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the call hierarchy of functions. Calls are the uses of functions that
+//! the symbolicator records for module calls: the callers of a function are the functions whose
+//! bodies contain its uses, and its callees are the functions used in its body. As the symbols
+//! cover the dependencies of the package, so do the calls (e.g., calls into the framework).
+
+use crate::{
+    context::Context,
+    symbols::{addr_to_ide_string, DefLoc, FunctionDef, ModuleDefs, Symbols},
+};
+use lsp_server::Request;
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    Position, Range, SymbolKind, Url,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// All functions (of the package and its dependencies), by the location of their names
+fn functions(symbols: &Symbols) -> BTreeMap<DefLoc, (&ModuleDefs, &FunctionDef)> {
+    let mut functions = BTreeMap::new();
+    for mod_def in symbols.file_mods.values().flatten() {
+        for fun_def in mod_def.functions.values() {
+            let def_loc = DefLoc {
+                fhash: mod_def.fhash,
+                start: fun_def.start,
+            };
+            functions.insert(def_loc, (mod_def, fun_def));
+        }
+    }
+    functions
+}
+
+/// Checks if `position` is within the function after its name, i.e., in its signature or body
+fn in_function(fun_def: &FunctionDef, position: Position) -> bool {
+    fun_def.start < position && position <= fun_def.end
+}
+
+fn name_range(start: Position, name: &str) -> Range {
+    Range {
+        start,
+        end: Position::new(start.line, start.character + name.len() as u32),
+    }
+}
+
+/// The call hierarchy item of a function
+fn item(
+    symbols: &Symbols,
+    mod_def: &ModuleDefs,
+    fun_def: &FunctionDef,
+) -> Option<CallHierarchyItem> {
+    let uri = Url::from_file_path(symbols.file_path(&mod_def.fhash)?).ok()?;
+    Some(CallHierarchyItem {
+        name: fun_def.name.to_string(),
+        kind: SymbolKind::Function,
+        tags: None,
+        detail: Some(format!(
+            "{}::{}",
+            addr_to_ide_string(&mod_def.name.address),
+            mod_def.name.module
+        )),
+        uri,
+        range: Range {
+            start: fun_def.start,
+            end: fun_def.end,
+        },
+        selection_range: name_range(fun_def.start, fun_def.name.as_str()),
+        data: None,
+    })
+}
+
+/// The location of the function of a call hierarchy item
+fn item_def_loc(symbols: &Symbols, item: &CallHierarchyItem) -> Option<DefLoc> {
+    let path = item.uri.to_file_path().ok()?;
+    let path = dunce::canonicalize(&path).unwrap_or(path);
+    symbols
+        .file_mods
+        .get(&path)?
+        .iter()
+        .find(|m| {
+            m.functions
+                .values()
+                .any(|f| f.start == item.selection_range.start)
+        })
+        .map(|m| DefLoc {
+            fhash: m.fhash,
+            start: item.selection_range.start,
+        })
+}
+
+/// Returns the call hierarchy item of the function defined or called at `position` in the file
+/// at `path`
+pub fn prepare_call_hierarchy(
+    symbols: &Symbols,
+    path: &Path,
+    position: Position,
+) -> Option<CallHierarchyItem> {
+    let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let use_def = symbols.use_def_at(&path, position)?;
+    let (mod_def, fun_def) = functions(symbols).get(&use_def.def_loc).copied()?;
+    item(symbols, mod_def, fun_def)
+}
+
+/// Returns the functions calling the function of `item`, with the ranges of their calls
+pub fn incoming_calls(
+    symbols: &Symbols,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyIncomingCall> {
+    let Some(def_loc) = item_def_loc(symbols, item) else {
+        return vec![];
+    };
+    let functions = functions(symbols);
+
+    let mut calls: BTreeMap<DefLoc, Vec<Range>> = BTreeMap::new();
+    for use_loc in symbols.references.get(&def_loc).into_iter().flatten() {
+        // the function whose body contains the call
+        let caller = functions.iter().find(|(loc, (_, fun_def))| {
+            loc.fhash == use_loc.fhash && in_function(fun_def, use_loc.start)
+        });
+        if let Some((caller_loc, _)) = caller {
+            calls.entry(*caller_loc).or_default().push(Range {
+                start: use_loc.start,
+                end: Position::new(use_loc.start.line, use_loc.col_end),
+            });
+        }
+    }
+    calls
+        .into_iter()
+        .filter_map(|(caller_loc, from_ranges)| {
+            let (mod_def, fun_def) = functions[&caller_loc];
+            Some(CallHierarchyIncomingCall {
+                from: self::item(symbols, mod_def, fun_def)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+/// Returns the functions called by the function of `item`, with the ranges of their calls
+pub fn outgoing_calls(
+    symbols: &Symbols,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let Some(def_loc) = item_def_loc(symbols, item) else {
+        return vec![];
+    };
+    let functions = functions(symbols);
+    let (_, caller) = functions[&def_loc];
+    let Some(use_defs) = symbols
+        .file_path(&def_loc.fhash)
+        .and_then(|path| symbols.file_use_defs.get(&path))
+    else {
+        return vec![];
+    };
+
+    let mut calls: BTreeMap<DefLoc, Vec<Range>> = BTreeMap::new();
+    for (line, uses) in use_defs.range(caller.start.line..=caller.end.line) {
+        for u in uses {
+            let start = Position::new(*line, u.col_start);
+            if in_function(caller, start) && functions.contains_key(&u.def_loc) {
+                calls.entry(u.def_loc).or_default().push(Range {
+                    start,
+                    end: Position::new(*line, u.col_end),
+                });
+            }
+        }
+    }
+    calls
+        .into_iter()
+        .filter_map(|(callee_loc, from_ranges)| {
+            let (mod_def, fun_def) = functions[&callee_loc];
+            Some(CallHierarchyOutgoingCall {
+                to: self::item(symbols, mod_def, fun_def)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+fn send_response<R: Serialize>(context: &Context, request: &Request, result: R) {
+    let response = lsp_server::Response::new_ok(request.id.clone(), result);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send call hierarchy response: {:?}", err);
+    }
+}
+
+/// Handles call hierarchy preparation request of the language server
+pub fn on_prepare_call_hierarchy_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<CallHierarchyPrepareParams>(request.params.clone())
+        .expect("could not deserialize call hierarchy preparation request");
+
+    let fpath = parameters
+        .text_document_position_params
+        .text_document
+        .uri
+        .to_file_path()
+        .unwrap();
+    let position = parameters.text_document_position_params.position;
+    let items = prepare_call_hierarchy(symbols, &fpath, position).map(|item| vec![item]);
+    send_response(context, request, items);
+}
+
+/// Handles incoming calls request of the language server
+pub fn on_incoming_calls_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters =
+        serde_json::from_value::<CallHierarchyIncomingCallsParams>(request.params.clone())
+            .expect("could not deserialize incoming calls request");
+
+    send_response(context, request, incoming_calls(symbols, &parameters.item));
+}
+
+/// Handles outgoing calls request of the language server
+pub fn on_outgoing_calls_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters =
+        serde_json::from_value::<CallHierarchyOutgoingCallsParams>(request.params.clone())
+            .expect("could not deserialize outgoing calls request");
+
+    send_response(context, request, outgoing_calls(symbols, &parameters.item));
+}
+
+#[test]
+fn call_hierarchy_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let m1 = path.join("sources/M1.move");
+    let m2 = path.join("sources/M2.move");
+
+    // `M2::some_other_struct(7)` in `other_mod_struct_import`
+    let item = prepare_call_hierarchy(&symbols, &m1, Position::new(31, 14)).unwrap();
+    assert_eq!(item.name, "some_other_struct");
+    assert_eq!(item.detail.as_deref(), Some("Symbols::M2"));
+    assert_eq!(
+        item.uri,
+        Url::from_file_path(dunce::canonicalize(&m2).unwrap()).unwrap()
+    );
+    assert_eq!(
+        item.selection_range,
+        name_range(Position::new(6, 15), "some_other_struct")
+    );
+    assert_eq!(item.range.end, Position::new(8, 5));
+    // the same item at the definition
+    assert_eq!(
+        prepare_call_hierarchy(&symbols, &m2, Position::new(6, 20)),
+        Some(item.clone())
+    );
+
+    let incoming = incoming_calls(&symbols, &item);
+    let callers: Vec<_> = incoming
+        .iter()
+        .map(|call| (call.from.name.as_str(), call.from_ranges.len()))
+        .collect();
+    assert_eq!(
+        callers,
+        vec![
+            ("other_mod_struct", 1),
+            ("other_mod_struct_import", 1),
+            ("struct_var", 2),
+        ]
+    );
+    assert_eq!(
+        incoming[1].from_ranges[0],
+        name_range(Position::new(31, 12), "some_other_struct")
+    );
+
+    // `fun struct_var(p: bool): SomeOtherStruct {`
+    let item = prepare_call_hierarchy(&symbols, &m1, Position::new(126, 8)).unwrap();
+    let outgoing = outgoing_calls(&symbols, &item);
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!(outgoing[0].to.name, "some_other_struct");
+    assert_eq!(
+        outgoing[0].from_ranges,
+        vec![
+            name_range(Position::new(127, 22), "some_other_struct"),
+            name_range(Position::new(131, 16), "some_other_struct"),
+        ]
+    );
+
+    // functions without calls
+    let item = prepare_call_hierarchy(&symbols, &m1, Position::new(14, 8)).unwrap();
+    assert!(incoming_calls(&symbols, &item).is_empty());
+    assert!(outgoing_calls(&symbols, &item).is_empty());
+}
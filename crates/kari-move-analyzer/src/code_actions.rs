@@ -0,0 +1,657 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements code actions: quick fixes for the diagnostics of the compiler (removing
+//! unused `use` declarations, prefixing unused variables with `_`, importing unbound modules
+//! found in the package or its dependencies and adding missing abilities to structs), and the
+//! generation of a test for the function at the cursor.
+//!
+//! Diagnostics are recognized by the category that `diagnostics::lsp_diagnostics` attaches to
+//! them, or by their message for diagnostics that clients send back without it.
+
+use crate::{
+    context::Context,
+    symbols::{addr_to_ide_string, Symbols},
+    syntax::{block_end, is_test_fun, tokens, use_decls, Token, UseDecl},
+    utils::{offset_to_position, position_to_offset},
+};
+use lsp_server::Request;
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, Diagnostic, Position, Range,
+    TextEdit, Url, WorkspaceEdit,
+};
+use move_compiler::{parser::lexer::Tok, shared::Identifier};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+const ABILITIES: &[&str] = &["copy", "drop", "store", "key"];
+/// Indentation of the code generated within a module
+const INDENT: &str = "    ";
+
+/// Checks if `diagnostic` has the category `category` or, failing that, a message starting with
+/// `message_prefix`
+fn is_diagnostic(diagnostic: &Diagnostic, category: &str, message_prefix: &str) -> bool {
+    match diagnostic.data.as_ref().and_then(|data| data.as_str()) {
+        Some(data) => data == category,
+        None => diagnostic.message.starts_with(message_prefix),
+    }
+}
+
+/// Index of the token starting at `position`
+fn token_at(buffer: &str, tokens: &[Token], position: Position) -> Option<usize> {
+    let offset = position_to_offset(buffer, position)?;
+    tokens.binary_search_by_key(&offset, |t| t.start).ok()
+}
+
+/// The indentation of the line containing the byte offset `offset`
+fn indentation(buffer: &str, offset: usize) -> String {
+    let line_start = buffer[..offset].rfind('\n').map_or(0, |i| i + 1);
+    buffer[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// Range of the byte offsets `start..end`, extended to whole lines if nothing else is on them
+fn line_range(buffer: &str, start: usize, end: usize) -> Range {
+    let line_start = buffer[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = buffer[end..]
+        .find('\n')
+        .map_or(buffer.len(), |i| end + i + 1);
+    let (start, end) =
+        if buffer[line_start..start].trim().is_empty() && buffer[end..line_end].trim().is_empty() {
+            (line_start, line_end)
+        } else {
+            (start, end)
+        };
+    Range {
+        start: offset_to_position(buffer, start),
+        end: offset_to_position(buffer, end),
+    }
+}
+
+fn edit(buffer: &str, start: usize, end: usize, new_text: String) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: offset_to_position(buffer, start),
+            end: offset_to_position(buffer, end),
+        },
+        new_text,
+    }
+}
+
+/// Removes the unused alias or member of a `use` declaration, or the whole declaration if it is
+/// the only one it introduces
+fn remove_unused_use(
+    buffer: &str,
+    tokens: &[Token],
+    decls: &[UseDecl],
+    diagnostic: &Diagnostic,
+) -> Option<(String, Vec<TextEdit>)> {
+    let idx = token_at(buffer, tokens, diagnostic.range.start)?;
+    let decl = decls.iter().find(|d| d.tokens.contains(&idx))?;
+    let name = &tokens[idx].content;
+
+    if decl.aliases.len() + decl.members.len() == 1 {
+        let start = tokens[decl.tokens.start].start;
+        let end = tokens[decl.tokens.end - 1].end();
+        let edit = TextEdit {
+            range: line_range(buffer, start, end),
+            new_text: String::new(),
+        };
+        return Some((format!("Remove unused `use` of `{}`", name), vec![edit]));
+    }
+
+    // the member, with its alias if any (e.g., `Self as M`)
+    let mut start = idx;
+    if start >= 2 && tokens[start - 1].tok == Tok::As {
+        start -= 2;
+    }
+    let mut end = idx + 1;
+    if tokens.get(end).map(|t| t.tok) == Some(Tok::As) {
+        end += 2;
+    }
+    let edit = if tokens.get(end).map(|t| t.tok) == Some(Tok::Comma) {
+        self::edit(
+            buffer,
+            tokens[start].start,
+            tokens[end + 1].start,
+            String::new(),
+        )
+    } else if tokens[start - 1].tok == Tok::Comma {
+        self::edit(
+            buffer,
+            tokens[start - 1].start,
+            tokens[end - 1].end(),
+            String::new(),
+        )
+    } else {
+        return None;
+    };
+    Some((format!("Remove unused `use` of `{}`", name), vec![edit]))
+}
+
+/// Prefixes an unused variable with `_`, expanding the shorthand of a field in a struct pattern
+/// (e.g., `f` in `let S { f } = s;`)
+fn prefix_unused_variable(
+    buffer: &str,
+    tokens: &[Token],
+    diagnostic: &Diagnostic,
+) -> Option<(String, Vec<TextEdit>)> {
+    let idx = token_at(buffer, tokens, diagnostic.range.start)?;
+    let token = &tokens[idx];
+    if token.tok != Tok::Identifier || token.content.starts_with('_') {
+        return None;
+    }
+
+    // find the bracket enclosing the variable
+    let mut depth = 0;
+    let mut opener = None;
+    for i in (0..idx).rev() {
+        match tokens[i].tok {
+            Tok::RParen | Tok::RBrace => depth += 1,
+            Tok::LParen | Tok::LBrace if depth > 0 => depth -= 1,
+            Tok::LParen | Tok::LBrace => {
+                opener = Some(i);
+                break;
+            }
+            _ => (),
+        }
+    }
+    let is_shorthand = opener.is_some_and(|i| {
+        tokens[i].tok == Tok::LBrace
+            && i > 0
+            && matches!(tokens[i - 1].tok, Tok::Identifier | Tok::Greater)
+            && matches!(tokens[idx - 1].tok, Tok::LBrace | Tok::Comma)
+            && tokens.get(idx + 1).map(|t| t.tok) != Some(Tok::Colon)
+    });
+    let new_text = if is_shorthand {
+        format!("{}: _", token.content)
+    } else {
+        "_".to_string()
+    };
+    let edit = edit(buffer, token.start, token.start, new_text);
+    Some((
+        format!("Prefix `{}` with an underscore", token.content),
+        vec![edit],
+    ))
+}
+
+/// Imports a module that is used without being declared, for each module of that name in the
+/// package or its dependencies
+fn import_module(
+    buffer: &str,
+    tokens: &[Token],
+    decls: &[UseDecl],
+    symbols: &Symbols,
+    diagnostic: &Diagnostic,
+) -> Vec<(String, Vec<TextEdit>)> {
+    let Some(idx) = token_at(buffer, tokens, diagnostic.range.start) else {
+        return vec![];
+    };
+    let name = &tokens[idx].content;
+    if tokens[idx].tok != Tok::Identifier {
+        return vec![];
+    }
+
+    // after the last preceding `use` declaration visible at the use of the module, or at the
+    // start of the enclosing module
+    let (line, indent) = match decls
+        .iter()
+        .filter(|d| d.scope.contains(&idx) && d.tokens.end <= idx)
+        .last()
+    {
+        Some(decl) => {
+            let semicolon = &tokens[decl.tokens.end - 1];
+            (
+                offset_to_position(buffer, semicolon.start).line + 1,
+                indentation(buffer, tokens[decl.tokens.start].start),
+            )
+        }
+        None => {
+            let Some(module) = tokens[..idx].iter().rposition(|t| t.tok == Tok::Module) else {
+                return vec![];
+            };
+            let Some(open) = tokens[module..idx]
+                .iter()
+                .position(|t| t.tok == Tok::LBrace)
+            else {
+                return vec![];
+            };
+            (
+                offset_to_position(buffer, tokens[module + open].start).line + 1,
+                format!("{}{}", indentation(buffer, tokens[module].start), INDENT),
+            )
+        }
+    };
+
+    let modules: BTreeSet<String> = symbols
+        .file_mods
+        .values()
+        .flatten()
+        .filter(|m| m.name.module.value().as_str() == name)
+        .map(|m| format!("{}::{}", addr_to_ide_string(&m.name.address), name))
+        .collect();
+    modules
+        .into_iter()
+        .map(|module| {
+            let edit = TextEdit {
+                range: Range {
+                    start: Position::new(line, 0),
+                    end: Position::new(line, 0),
+                },
+                new_text: format!("{}use {};\n", indent, module),
+            };
+            (format!("Import `{}`", module), vec![edit])
+        })
+        .collect()
+}
+
+/// Adds the abilities that a diagnostic reports as missing to the structs of this file it reports
+/// them missing on, e.g., for "The type 'M::S' does not have the ability 'drop'"
+fn add_missing_abilities(
+    buffer: &str,
+    tokens: &[Token],
+    diagnostic: &Diagnostic,
+) -> Vec<(String, Vec<TextEdit>)> {
+    let mut messages = vec![diagnostic.message.as_str()];
+    if let Some(related) = &diagnostic.related_information {
+        messages.extend(related.iter().map(|r| r.message.as_str()));
+    }
+    if !messages.iter().any(|m| m.contains("abilit")) {
+        return vec![];
+    }
+    // the quoted parts of the messages
+    let quoted: BTreeSet<&str> = messages
+        .iter()
+        .flat_map(|m| m.split('\'').skip(1).step_by(2))
+        .collect();
+    let abilities: Vec<&str> = quoted
+        .iter()
+        .copied()
+        .filter(|q| ABILITIES.contains(q))
+        .collect();
+
+    let mut fixes = vec![];
+    for ty in quoted.iter().filter(|q| q.contains("::")) {
+        // strip type arguments, e.g., `M::S<u64>`
+        let ty = ty.split('<').next().unwrap();
+        let mut path = ty.rsplit("::");
+        let (Some(struct_name), Some(module_name)) = (path.next(), path.next()) else {
+            continue;
+        };
+        let Some(name_idx) = struct_name_token(tokens, module_name, struct_name) else {
+            continue;
+        };
+
+        // skip type parameters
+        let mut idx = name_idx + 1;
+        if tokens.get(idx).map(|t| t.tok) == Some(Tok::Less) {
+            let mut depth = 0;
+            while idx < tokens.len() {
+                match tokens[idx].tok {
+                    Tok::Less => depth += 1,
+                    Tok::Greater => depth -= 1,
+                    Tok::GreaterGreater => depth -= 2,
+                    _ => (),
+                }
+                idx += 1;
+                if depth <= 0 {
+                    break;
+                }
+            }
+        }
+        let has = tokens.get(idx).is_some_and(|t| t.content == "has");
+        let existing: Vec<&str> = if has {
+            tokens[idx + 1..]
+                .iter()
+                .take_while(|t| !matches!(t.tok, Tok::LBrace | Tok::Semicolon))
+                .filter(|t| t.tok != Tok::Comma)
+                .map(|t| t.content.as_str())
+                .collect()
+        } else {
+            vec![]
+        };
+        let missing: Vec<&str> = abilities
+            .iter()
+            .copied()
+            .filter(|a| !existing.contains(a))
+            .collect();
+        if missing.is_empty() {
+            continue;
+        }
+
+        let edit = if has {
+            // the last ability, each but the first preceded by a comma
+            let last = &tokens[idx + (existing.len() * 2).saturating_sub(1)];
+            edit(
+                buffer,
+                last.end(),
+                last.end(),
+                format!(", {}", missing.join(", ")),
+            )
+        } else {
+            let last = &tokens[idx - 1];
+            edit(
+                buffer,
+                last.end(),
+                last.end(),
+                format!(" has {}", missing.join(", ")),
+            )
+        };
+        let title = format!(
+            "Add {} to `{}`",
+            missing
+                .iter()
+                .map(|a| format!("`{}`", a))
+                .collect::<Vec<_>>()
+                .join(", "),
+            struct_name
+        );
+        fixes.push((title, vec![edit]));
+    }
+    fixes
+}
+
+/// Index of the name of the struct `struct_name` declared in module `module_name`
+fn struct_name_token(tokens: &[Token], module_name: &str, struct_name: &str) -> Option<usize> {
+    let mut module = None;
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.tok {
+            // the module name is the last identifier before the `{` of the module
+            Tok::Module => {
+                module = tokens[idx..]
+                    .iter()
+                    .take_while(|t| t.tok != Tok::LBrace)
+                    .filter(|t| t.tok == Tok::Identifier)
+                    .last()
+                    .map(|t| t.content.as_str())
+            }
+            Tok::Struct
+                if module == Some(module_name)
+                    && tokens
+                        .get(idx + 1)
+                        .is_some_and(|t| t.content == struct_name) =>
+            {
+                return Some(idx + 1);
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Generates a test for the function whose name is at `position`, at the end of its module
+fn generate_test(
+    buffer: &str,
+    tokens: &[Token],
+    position: Position,
+) -> Option<(String, Vec<TextEdit>)> {
+    let offset = position_to_offset(buffer, position)?;
+    let idx = tokens
+        .iter()
+        .position(|t| t.start <= offset && offset <= t.end())?;
+    if tokens[idx].tok != Tok::Identifier || idx == 0 || tokens[idx - 1].tok != Tok::Fun {
+        return None;
+    }
+    let name = &tokens[idx].content;
+    let test_name = format!("test_{}", name);
+    if tokens.iter().any(|t| t.content == test_name) || is_test_fun(tokens, idx - 1) {
+        return None;
+    }
+
+    let module = tokens[..idx].iter().rposition(|t| t.tok == Tok::Module)?;
+    let open = module + tokens[module..].iter().position(|t| t.tok == Tok::LBrace)?;
+    let close = tokens.get(block_end(tokens, open + 1))?;
+    let indent = format!("{}{}", indentation(buffer, tokens[module].start), INDENT);
+    let line_start = buffer[..close.start].rfind('\n').map_or(0, |i| i + 1);
+    let new_text = format!(
+        "\n{indent}#[test]\n{indent}fun {test_name}() {{\n{indent}{INDENT}// TODO: test `{name}`\n{indent}}}\n",
+    );
+    let edit = edit(buffer, line_start, line_start, new_text);
+    Some((format!("Generate test for `{}`", name), vec![edit]))
+}
+
+/// Returns the code actions for `range` of `buffer`, the contents of the file at `path`, given the
+/// diagnostics in that range
+pub fn code_actions(
+    buffer: &str,
+    symbols: &Symbols,
+    path: &Path,
+    range: Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeAction> {
+    let Ok(uri) = Url::from_file_path(path) else {
+        return vec![];
+    };
+    let tokens = tokens(buffer);
+    let decls = use_decls(&tokens);
+
+    let action = |(title, edits): (String, Vec<TextEdit>),
+                  kind: CodeActionKind,
+                  diagnostic: Option<&Diagnostic>| CodeAction {
+        title,
+        kind: Some(kind),
+        diagnostics: diagnostic.map(|d| vec![d.clone()]),
+        edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+        command: None,
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    };
+
+    let mut actions = vec![];
+    for diagnostic in diagnostics {
+        let mut fixes = vec![];
+        if is_diagnostic(diagnostic, "unused alias", "Unused 'use'") {
+            fixes.extend(remove_unused_use(buffer, &tokens, &decls, diagnostic));
+        }
+        if is_diagnostic(diagnostic, "unused variable", "Unused local variable") {
+            fixes.extend(prefix_unused_variable(buffer, &tokens, diagnostic));
+        }
+        if is_diagnostic(diagnostic, "unbound module", "Unbound module") {
+            fixes.extend(import_module(buffer, &tokens, &decls, symbols, diagnostic));
+        }
+        fixes.extend(add_missing_abilities(buffer, &tokens, diagnostic));
+        actions.extend(
+            fixes
+                .into_iter()
+                .map(|fix| action(fix, CodeActionKind::QUICKFIX, Some(diagnostic))),
+        );
+    }
+    if let Some(fix) = generate_test(buffer, &tokens, range.start) {
+        actions.push(action(fix, CodeActionKind::REFACTOR, None));
+    }
+    actions
+}
+
+/// Handles code action request of the language server
+pub fn on_code_action_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<CodeActionParams>(request.params.clone())
+        .expect("could not deserialize code action request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let actions: Vec<CodeActionOrCommand> = context
+        .files
+        .get(&fpath)
+        .map(|buffer| {
+            code_actions(
+                buffer,
+                symbols,
+                &fpath,
+                parameters.range,
+                &parameters.context.diagnostics,
+            )
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(CodeActionOrCommand::CodeAction)
+        .collect();
+
+    let response = lsp_server::Response::new_ok(request.id.clone(), actions);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send code action response: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+fn diagnostic(line: u32, start: u32, end: u32, category: &str, message: &str) -> Diagnostic {
+    let mut diagnostic = Diagnostic::new_simple(
+        Range {
+            start: Position::new(line, start),
+            end: Position::new(line, end),
+        },
+        message.to_string(),
+    );
+    diagnostic.data = Some(serde_json::Value::from(category));
+    diagnostic
+}
+
+#[test]
+fn code_actions_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let fpath = dunce::canonicalize(path.join("sources/M1.move")).unwrap();
+    let buffer = r#"module Symbols::M1 {
+    use Symbols::M2;
+    use Symbols::M6::{Self, DocumentedStruct};
+
+    struct S { f: u64 }
+
+    fun f(x: u64): u64 {
+        let y = 1;
+        M3::foo();
+        x
+    }
+}
+"#;
+    let actions = |range: Range, diagnostics: Vec<Diagnostic>| {
+        code_actions(buffer, &symbols, &fpath, range, &diagnostics)
+            .into_iter()
+            .map(|action| {
+                let edits = action.edit.unwrap().changes.unwrap();
+                let edit = edits.values().next().unwrap()[0].clone();
+                (
+                    action.title,
+                    edit.range.start.line,
+                    edit.range.start.character,
+                    edit.range.end.line,
+                    edit.range.end.character,
+                    edit.new_text,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let range = Range::default();
+    let fix = |action: (&str, u32, u32, u32, u32, &str)| {
+        (
+            action.0.to_string(),
+            action.1,
+            action.2,
+            action.3,
+            action.4,
+            action.5.to_string(),
+        )
+    };
+
+    assert_eq!(
+        actions(
+            range,
+            vec![
+                diagnostic(1, 17, 19, "unused alias", "Unused 'use' of alias 'M2'"),
+                diagnostic(2, 28, 44, "unused alias", "Unused 'use' of alias"),
+            ]
+        ),
+        vec![
+            fix(("Remove unused `use` of `M2`", 1, 0, 2, 0, "")),
+            fix((
+                "Remove unused `use` of `DocumentedStruct`",
+                2,
+                26,
+                2,
+                44,
+                ""
+            )),
+        ]
+    );
+    assert_eq!(
+        actions(
+            range,
+            vec![diagnostic(
+                7,
+                12,
+                13,
+                "unused variable",
+                "Unused local variable 'y'"
+            )]
+        ),
+        vec![fix(("Prefix `y` with an underscore", 7, 12, 7, 12, "_"))]
+    );
+    assert_eq!(
+        actions(
+            range,
+            vec![diagnostic(
+                8,
+                8,
+                10,
+                "unbound module",
+                "Unbound module alias 'M3'"
+            )]
+        ),
+        vec![fix((
+            "Import `Symbols::M3`",
+            3,
+            0,
+            3,
+            0,
+            "    use Symbols::M3;\n"
+        ))]
+    );
+
+    let mut missing_drop = diagnostic(
+        8,
+        8,
+        17,
+        "ability constraint not satisfied",
+        "Cannot ignore values without the 'drop' ability. The value must be used",
+    );
+    missing_drop.related_information = Some(vec![lsp_types::DiagnosticRelatedInformation {
+        location: lsp_types::Location::new(Url::from_file_path(&fpath).unwrap(), range),
+        message: "The type 'Symbols::M1::S' does not have the ability 'drop'".to_string(),
+    }]);
+    assert_eq!(
+        actions(range, vec![missing_drop]),
+        vec![fix(("Add `drop` to `S`", 4, 12, 4, 12, " has drop"))]
+    );
+
+    assert_eq!(
+        actions(
+            Range {
+                start: Position::new(6, 8),
+                end: Position::new(6, 8),
+            },
+            vec![]
+        ),
+        vec![fix((
+            "Generate test for `f`",
+            11,
+            0,
+            11,
+            0,
+            "\n    #[test]\n    fun test_f() {\n        // TODO: test `f`\n    }\n"
+        ))]
+    );
+}
@@ -0,0 +1,173 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements code lenses running unit tests: "Run test" and "Run with coverage" over
+//! each `#[test]` function, and "Run tests" and "Run tests with coverage" over each module with
+//! tests. The lenses execute the command of `test_runner` with the package of the file and the
+//! names of the module and test to run.
+
+use crate::{
+    context::Context,
+    symbols::SymbolicatorRunner,
+    syntax::{block_end, is_test_fun, tokens, Token},
+    test_runner::{TestRun, RUN_TEST_COMMAND},
+};
+use lsp_server::Request;
+use lsp_types::{CodeLens, CodeLensParams, Command, Range};
+use move_compiler::parser::lexer::Tok;
+use std::path::Path;
+
+/// The modules of a file that have tests, as the indices of the module name and of the names of
+/// its test functions
+fn module_tests(tokens: &[Token]) -> Vec<(usize, Vec<usize>)> {
+    let mut modules = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.tok != Tok::Module {
+            continue;
+        }
+        let Some(open) = tokens[idx..].iter().position(|t| t.tok == Tok::LBrace) else {
+            continue;
+        };
+        let open = idx + open;
+        // the module name is the last identifier before the `{` (not so for `spec module {`)
+        if tokens[open - 1].tok != Tok::Identifier {
+            continue;
+        }
+        let tests: Vec<usize> = (open + 1..block_end(tokens, open + 1))
+            .filter(|fun| {
+                tokens[*fun].tok == Tok::Fun
+                    && is_test_fun(tokens, *fun)
+                    && tokens
+                        .get(fun + 1)
+                        .is_some_and(|t| t.tok == Tok::Identifier)
+            })
+            .map(|fun| fun + 1)
+            .collect();
+        if !tests.is_empty() {
+            modules.push((open - 1, tests));
+        }
+    }
+    modules
+}
+
+fn lens(range: Range, title: &str, run: TestRun) -> CodeLens {
+    CodeLens {
+        range,
+        command: Some(Command {
+            title: title.to_string(),
+            command: RUN_TEST_COMMAND.to_string(),
+            arguments: Some(vec![serde_json::to_value(run).unwrap()]),
+        }),
+        data: None,
+    }
+}
+
+/// Returns the code lenses running the tests of `buffer`, the contents of the file at `path`
+pub fn code_lenses(buffer: &str, path: &Path) -> Vec<CodeLens> {
+    let Some(package) = path.parent().and_then(SymbolicatorRunner::root_dir) else {
+        return vec![];
+    };
+    let tokens = tokens(buffer);
+    let mut lenses = vec![];
+    // tests are selected by the names of their modules, which spares resolving their addresses
+    for (module, tests) in module_tests(&tokens) {
+        let run = |test: Option<&String>, coverage: bool| TestRun {
+            package: package.clone(),
+            module: tokens[module].content.clone(),
+            test: test.cloned(),
+            coverage,
+        };
+
+        let range = tokens[module].range(buffer);
+        lenses.push(lens(range, "Run tests", run(None, false)));
+        lenses.push(lens(range, "Run tests with coverage", run(None, true)));
+        for test in tests {
+            let name = Some(&tokens[test].content);
+            let range = tokens[test].range(buffer);
+            lenses.push(lens(range, "Run test", run(name, false)));
+            lenses.push(lens(range, "Run with coverage", run(name, true)));
+        }
+    }
+    lenses
+}
+
+/// Handles code lens request of the language server
+pub fn on_code_lens_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<CodeLensParams>(request.params.clone())
+        .expect("could not deserialize code lens request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let lenses = context
+        .files
+        .get(&fpath)
+        .map(|buffer| code_lenses(buffer, &fpath))
+        .unwrap_or_default();
+
+    let response = lsp_server::Response::new_ok(request.id.clone(), lenses);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send code lens response: {:?}", err);
+    }
+}
+
+#[test]
+fn code_lenses_test() {
+    use lsp_types::Position;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols/sources/M1.move");
+
+    let buffer = r#"module Symbols::M1 {
+    fun f(): u64 { 42 }
+
+    #[test]
+    fun test_f() { assert!(f() == 42, 0); }
+
+    #[test(account = @0x1)]
+    #[expected_failure]
+    public entry fun test_abort(account: signer) { abort 0 }
+
+    #[test_only]
+    fun helper() {}
+}
+
+module Symbols::M2 {
+    fun g() {}
+}
+"#;
+    let lenses: Vec<_> = code_lenses(buffer, &path)
+        .into_iter()
+        .map(|lens| {
+            let command = lens.command.unwrap();
+            assert_eq!(command.command, RUN_TEST_COMMAND);
+            let run: TestRun =
+                serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+            assert!(run.package.ends_with("tests/symbols"));
+            assert_eq!(run.module, "M1");
+            (lens.range.start, command.title, run.test, run.coverage)
+        })
+        .collect();
+    let lens = |line, character, title: &str, test: Option<&str>, coverage| {
+        (
+            Position::new(line, character),
+            title.to_string(),
+            test.map(str::to_string),
+            coverage,
+        )
+    };
+    assert_eq!(
+        lenses,
+        vec![
+            lens(0, 16, "Run tests", None, false),
+            lens(0, 16, "Run tests with coverage", None, true),
+            lens(4, 8, "Run test", Some("test_f"), false),
+            lens(4, 8, "Run with coverage", Some("test_f"), true),
+            lens(8, 21, "Run test", Some("test_abort"), false),
+            lens(8, 21, "Run with coverage", Some("test_abort"), true),
+        ]
+    );
+}
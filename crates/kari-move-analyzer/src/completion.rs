@@ -2,16 +2,57 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{context::Context, symbols::Symbols};
+use crate::{
+    context::Context,
+    symbols::{addr_to_ide_string, IdentType, ModuleDefs, Symbols},
+    syntax::{tokens, use_decls, Token},
+    utils::position_to_offset,
+};
 use lsp_server::Request;
 use lsp_types::{CompletionItem, CompletionItemKind, CompletionParams, Position};
 use move_command_line_common::files::FileHash;
-use move_compiler::parser::{
-    keywords::{BUILTINS, CONTEXTUAL_KEYWORDS, KEYWORDS, PRIMITIVE_TYPES},
-    lexer::{Lexer, Tok},
+use move_compiler::{
+    naming::ast::{TypeName_, Type_},
+    parser::{
+        keywords::{BUILTINS, CONTEXTUAL_KEYWORDS, KEYWORDS, PRIMITIVE_TYPES},
+        lexer::{Lexer, Tok},
+    },
+    shared::Identifier,
 };
+use move_ir_types::location::*;
 use move_symbol_pool::Symbol;
-use std::{collections::HashSet, path::PathBuf};
+use std::{collections::HashSet, path::Path};
+
+/// Keywords of the specification language, which are only offered within spec blocks.
+const SPEC_KEYWORDS: &[&str] = &[
+    "aborts_if",
+    "aborts_with",
+    "apply",
+    "assert",
+    "assume",
+    "axiom",
+    "choose",
+    "decreases",
+    "emits",
+    "ensures",
+    "except",
+    "exists",
+    "forall",
+    "global",
+    "include",
+    "internal",
+    "invariant",
+    "local",
+    "modifies",
+    "pragma",
+    "requires",
+    "schema",
+    "succeeds_if",
+    "to",
+    "update",
+    "where",
+    "with",
+];
 
 /// Constructs an `lsp_types::CompletionItem` with the given `label` and `kind`.
 fn completion_item(label: &str, kind: CompletionItemKind) -> CompletionItem {
@@ -24,15 +65,14 @@ fn completion_item(label: &str, kind: CompletionItemKind) -> CompletionItem {
 
 /// Return a list of completion items corresponding to each one of Move's keywords.
 ///
-/// Currently, this does not filter keywords out based on whether they are valid at the completion
-/// request's cursor position, but in the future it ought to. For example, this function returns
-/// all specification language keywords, but in the future it should be modified to only do so
-/// within a spec block.
-fn keywords() -> Vec<CompletionItem> {
+/// Specification language keywords are only included within a spec block, but otherwise keywords
+/// are not filtered based on whether they are valid at the completion request's cursor position.
+fn keywords(in_spec: bool) -> Vec<CompletionItem> {
     KEYWORDS
         .iter()
         .chain(CONTEXTUAL_KEYWORDS.iter())
         .chain(PRIMITIVE_TYPES.iter())
+        .filter(|label| in_spec || !SPEC_KEYWORDS.contains(label))
         .map(|label| {
             let kind = if label == &"copy" || label == &"move" {
                 CompletionItemKind::Operator
@@ -69,7 +109,7 @@ fn builtins() -> Vec<CompletionItem> {
 /// server did not initialize with a response indicating it's capable of providing completions. In
 /// the future, the server should be modified to return semantically valid completion items, not
 /// simple textual suggestions.
-fn identifiers(buffer: &str, symbols: &Symbols, path: &PathBuf) -> Vec<CompletionItem> {
+fn identifiers(buffer: &str, symbols: &Symbols, path: &Path) -> Vec<CompletionItem> {
     let mut lexer = Lexer::new(buffer, FileHash::new(buffer));
     if lexer.advance().is_err() {
         return vec![];
@@ -142,6 +182,291 @@ fn get_cursor_token(buffer: &str, position: &Position) -> Option<Tok> {
     }
 }
 
+/// Returns true if `offset` is within a spec block, either a spec declaration (e.g.,
+/// `spec foo { ... }`) or a spec block within a function body (`spec { ... }`).
+fn in_spec_block(tokens: &[Token], offset: usize) -> bool {
+    // whether each of the blocks enclosing the current token is a spec block
+    let mut blocks = vec![];
+    let mut spec = false;
+    for token in tokens.iter().take_while(|t| t.start < offset) {
+        match token.tok {
+            Tok::Spec => spec = true,
+            Tok::LBrace => {
+                blocks.push(spec || blocks.last() == Some(&true));
+                spec = false;
+            }
+            Tok::RBrace => {
+                blocks.pop();
+            }
+            Tok::Semicolon => spec = false,
+            _ => (),
+        }
+    }
+    blocks.last() == Some(&true)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits a path such as `0x1::vector` into its names, or returns `None` if one of them is not a
+/// name (or an address).
+fn path_names(path: &str) -> Option<Vec<String>> {
+    path.split("::")
+        .map(|name| {
+            let name = name.trim();
+            (!name.is_empty() && name.chars().all(is_name_char)).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Returns the names of the path preceding the identifier being completed if it is a member of a
+/// `use` declaration, e.g., `["0x1", "vector"]` for `use 0x1::vector::{Self, le`.
+fn use_path(buffer: &str, tokens: &[Token], offset: usize) -> Option<Vec<String>> {
+    let before: Vec<_> = tokens.iter().take_while(|t| t.start < offset).collect();
+    let use_idx = before.iter().rposition(|t| t.tok == Tok::Use)?;
+    if before[use_idx..].iter().any(|t| t.tok == Tok::Semicolon) {
+        return None;
+    }
+    let decl = buffer.get(before[use_idx].end()..offset)?;
+    let path = match decl.split_once('{') {
+        Some((path, _)) => path.trim_end().strip_suffix("::")?,
+        None => decl.trim_end_matches(is_name_char).strip_suffix("::")?,
+    };
+    path_names(path)
+}
+
+/// Returns the names of the path preceding the identifier being completed at `character` on
+/// `line` if it is preceded by `::`, e.g., `["0x1", "vector"]` for `0x1::vector::le`.
+fn path_before(line: &str, character: usize) -> Option<Vec<String>> {
+    let before: String = line.chars().take(character).collect();
+    let mut rest = before.trim_end_matches(is_name_char).strip_suffix("::")?;
+    let mut names = vec![];
+    loop {
+        let name_start = rest.trim_end_matches(is_name_char);
+        let name = &rest[name_start.len()..];
+        if name.is_empty() {
+            break;
+        }
+        names.insert(0, name.to_string());
+        match name_start.strip_suffix("::") {
+            Some(r) => rest = r,
+            None => break,
+        }
+    }
+    (!names.is_empty()).then_some(names)
+}
+
+/// Returns the name preceding the `.` before the identifier being completed at `character` on
+/// `line`, along with the column it starts at.
+fn receiver_before(line: &str, character: usize) -> Option<(String, u32)> {
+    let before: String = line.chars().take(character).collect();
+    let rest = before.trim_end_matches(is_name_char).strip_suffix('.')?;
+    let name_start = rest.trim_end_matches(is_name_char);
+    let name = &rest[name_start.len()..];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((name.to_string(), name_start.chars().count() as u32))
+}
+
+/// Return the completion items for the functions, structs and constants of a module.
+fn module_members(mod_defs: &ModuleDefs) -> Vec<CompletionItem> {
+    let functions = mod_defs
+        .functions
+        .iter()
+        .map(|(name, fun_def)| CompletionItem {
+            detail: Some(fun_def.ident_type.to_string()),
+            ..completion_item(name.as_str(), CompletionItemKind::Function)
+        });
+    let structs = mod_defs
+        .structs
+        .keys()
+        .map(|name| completion_item(name.as_str(), CompletionItemKind::Struct));
+    let constants = mod_defs
+        .constants
+        .keys()
+        .map(|name| completion_item(name.as_str(), CompletionItemKind::Constant));
+    functions.chain(structs).chain(constants).collect()
+}
+
+/// Return the completion items following `names::`: the members of the module named by `names`
+/// (an address and a module name, or a module alias or name), or the modules at the address
+/// `names`.
+fn path_completions(
+    tokens: &[Token],
+    offset: usize,
+    symbols: &Symbols,
+    names: &[String],
+) -> Vec<CompletionItem> {
+    let mods = symbols.file_mods.values().flatten();
+    let members_of = |address: Option<&str>, module: &str| {
+        symbols
+            .file_mods
+            .values()
+            .flatten()
+            .filter(|m| {
+                m.name.module.value().as_str() == module
+                    && address.is_none_or(|a| addr_to_ide_string(&m.name.address) == a)
+            })
+            .flat_map(module_members)
+            .collect::<Vec<_>>()
+    };
+    match names {
+        [address, module] => members_of(Some(address.as_str()), module),
+        [name] => {
+            let cursor = tokens.iter().take_while(|t| t.start < offset).count();
+            let alias = use_decls(tokens).into_iter().find_map(|decl| {
+                let in_scope = decl.scope.start <= cursor && cursor <= decl.scope.end;
+                let aliased = decl
+                    .aliases
+                    .iter()
+                    .any(|alias| decl.alias_name(tokens, alias) == name.as_str());
+                (in_scope && aliased).then(|| {
+                    (
+                        tokens[decl.address()].content.clone(),
+                        tokens[decl.module].content.clone(),
+                    )
+                })
+            });
+            let mut items = match alias {
+                Some((address, module)) => members_of(Some(address.as_str()), &module),
+                None => members_of(None, name),
+            };
+            items.extend(
+                mods.filter(|m| addr_to_ide_string(&m.name.address) == *name)
+                    .map(|m| {
+                        let module = m.name.module.value();
+                        CompletionItem {
+                            detail: Some(format!("{}::{}", name, module)),
+                            ..completion_item(module.as_str(), CompletionItemKind::Module)
+                        }
+                    }),
+            );
+            items
+        }
+        _ => vec![],
+    }
+}
+
+/// Return the completion items for the fields of the struct type of `receiver`, which starts at
+/// `col` on the line of `position`.
+fn field_completions(
+    buffer: &str,
+    symbols: &Symbols,
+    path: &Path,
+    position: &Position,
+    receiver: &str,
+    col: u32,
+) -> Vec<CompletionItem> {
+    let start = Position {
+        line: position.line,
+        character: col,
+    };
+    match symbols.use_def_by_name(path, buffer, start, receiver) {
+        Some(u) => struct_fields(symbols, &u.use_type),
+        None => vec![],
+    }
+}
+
+/// Return the completion items for the fields of `ident_type` if it is a struct type (or a
+/// reference to one).
+fn struct_fields(symbols: &Symbols, ident_type: &IdentType) -> Vec<CompletionItem> {
+    let IdentType::RegularType(t) = ident_type else {
+        return vec![];
+    };
+    let mut t = t;
+    while let sp!(_, Type_::Ref(_, inner)) = t {
+        t = inner.as_ref();
+    }
+    let sp!(
+        _,
+        Type_::Apply(
+            _,
+            sp!(_, TypeName_::ModuleType(sp!(_, module_ident), struct_name)),
+            _
+        )
+    ) = t
+    else {
+        return vec![];
+    };
+    symbols
+        .file_mods
+        .values()
+        .flatten()
+        .filter(|m| m.name == *module_ident)
+        .filter_map(|m| m.structs.get(&struct_name.value()))
+        .flat_map(|struct_def| &struct_def.field_defs)
+        .map(|field| completion_item(field.name.as_str(), CompletionItemKind::Field))
+        .collect()
+}
+
+/// Return the completion items that depend on what precedes the cursor: members of a module after
+/// `module::`, modules after `address::` and struct fields after `value.`. Returns no items if the
+/// cursor follows none of these.
+fn context_completions(
+    buffer: &str,
+    symbols: &Symbols,
+    path: &Path,
+    position: &Position,
+) -> Vec<CompletionItem> {
+    let Some(line) = buffer.lines().nth(position.line as usize) else {
+        return vec![];
+    };
+    let character = position.character as usize;
+    let tokens = tokens(buffer);
+    let offset = position_to_offset(buffer, *position).unwrap_or(buffer.len());
+
+    if let Some(names) = use_path(buffer, &tokens, offset) {
+        let mut items = path_completions(&tokens, offset, symbols, &names);
+        if names.len() == 2 {
+            items.push(completion_item("Self", CompletionItemKind::Module));
+        }
+        return items;
+    }
+    if let Some(names) = path_before(line, character) {
+        return path_completions(&tokens, offset, symbols, &names);
+    }
+    if let Some((receiver, col)) = receiver_before(line, character) {
+        return field_completions(buffer, symbols, path, position, &receiver, col);
+    }
+    vec![]
+}
+
+/// Return the completion items at `position` in `buffer`, the contents of the file at `path`.
+pub fn completion_items(
+    buffer: &str,
+    symbols: &Symbols,
+    path: &Path,
+    position: &Position,
+) -> Vec<CompletionItem> {
+    let mut items = context_completions(buffer, symbols, path, position);
+    if !items.is_empty() {
+        let mut labels = HashSet::new();
+        items.retain(|item| labels.insert(item.label.clone()));
+        return items;
+    }
+
+    match get_cursor_token(buffer, position) {
+        Some(Tok::Colon) => {
+            items.extend_from_slice(&primitive_types());
+        }
+        Some(Tok::Period) | Some(Tok::ColonColon) => {
+            // `.` or `::` must be followed by identifiers, which are added to the completion items
+            // below (unless the symbols provide the members or fields to offer).
+        }
+        _ => {
+            // If the user's cursor is positioned anywhere other than following a `.`, `:`, or `::`,
+            // offer them Move's keywords, operators, and builtins as completion items.
+            let offset = position_to_offset(buffer, *position).unwrap_or(buffer.len());
+            items.extend_from_slice(&keywords(in_spec_block(&tokens(buffer), offset)));
+            items.extend_from_slice(&builtins());
+        }
+    }
+    items.extend_from_slice(&identifiers(buffer, symbols, path));
+    items
+}
+
 /// Sends the given connection a response to a completion request.
 ///
 /// The completions returned depend upon where the user's cursor is positioned.
@@ -165,30 +490,17 @@ pub fn on_completion_request(context: &Context, request: &Request, symbols: &Sym
     }
 
     // The completion items we provide depend upon where the user's cursor is positioned.
-    let cursor =
-        buffer.and_then(|buf| get_cursor_token(buf, &parameters.text_document_position.position));
-
-    let mut items = vec![];
-    match cursor {
-        Some(Tok::Colon) => {
-            items.extend_from_slice(&primitive_types());
-        }
-        Some(Tok::Period) | Some(Tok::ColonColon) => {
-            // `.` or `::` must be followed by identifiers, which are added to the completion items
-            // below.
-        }
-        _ => {
-            // If the user's cursor is positioned anywhere other than following a `.`, `:`, or `::`,
-            // offer them Move's keywords, operators, and builtins as completion items.
-            items.extend_from_slice(&keywords());
-            items.extend_from_slice(&builtins());
-        }
-    }
-
-    if let Some(buffer) = &buffer {
-        let identifiers = identifiers(buffer, symbols, &path);
-        items.extend_from_slice(&identifiers);
-    }
+    let items = buffer.map_or_else(
+        || [keywords(false), builtins()].concat(),
+        |buffer| {
+            completion_items(
+                buffer,
+                symbols,
+                &path,
+                &parameters.text_document_position.position,
+            )
+        },
+    );
 
     let result = serde_json::to_value(items).expect("could not serialize completion response");
     eprintln!("about to send completion response");
@@ -201,3 +513,41 @@ pub fn on_completion_request(context: &Context, request: &Request, symbols: &Sym
         eprintln!("could not send completion response: {:?}", err);
     }
 }
+
+#[test]
+fn context_completions_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let fpath = dunce::canonicalize(path.join("sources/M1.move")).unwrap();
+    let buffer = std::fs::read_to_string(&fpath).unwrap();
+    let items = |line, character| {
+        completion_items(&buffer, &symbols, &fpath, &Position { line, character })
+            .into_iter()
+            .map(|item| (item.label, item.kind.unwrap()))
+            .collect::<Vec<_>>()
+    };
+    let item = |label: &str, kind| (label.to_string(), kind);
+
+    // members of an aliased module: `M2::some_other_struct(7)`
+    let members = items(31, 12);
+    assert!(members.contains(&item("some_other_struct", CompletionItemKind::Function)));
+    assert!(members.contains(&item("multi_arg", CompletionItemKind::Function)));
+    assert!(members.contains(&item("SomeOtherStruct", CompletionItemKind::Struct)));
+    assert!(!members.contains(&item("let", CompletionItemKind::Keyword)));
+
+    // modules at an address: `Symbols::M2::some_other_struct(SOME_CONST)`
+    assert!(items(25, 17).contains(&item("M2", CompletionItemKind::Module)));
+
+    // fields of a struct reference: `val.some_field`
+    assert_eq!(
+        items(36, 12),
+        vec![item("some_field", CompletionItemKind::Field)]
+    );
+}
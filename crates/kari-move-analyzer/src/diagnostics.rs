@@ -24,7 +24,7 @@ pub fn lsp_diagnostics(
     file_name_mapping: &BTreeMap<FileHash, Symbol>,
 ) -> BTreeMap<Symbol, Vec<Diagnostic>> {
     let mut lsp_diagnostics = BTreeMap::new();
-    for (s, _, (loc, msg), labels, _) in diagnostics {
+    for (s, category, (loc, msg), labels, _) in diagnostics {
         let fpath = file_name_mapping.get(&loc.file_hash()).unwrap();
         if let Some(start) = get_loc(&loc.file_hash(), loc.start(), files, file_id_mapping) {
             if let Some(end) = get_loc(&loc.file_hash(), loc.end(), files, file_id_mapping) {
@@ -57,18 +57,21 @@ pub fn lsp_diagnostics(
                             .collect(),
                     )
                 };
+                let mut diagnostic = Diagnostic::new(
+                    range,
+                    Some(severity(*s)),
+                    None,
+                    None,
+                    msg.to_string(),
+                    related_info_opt,
+                    None,
+                );
+                // the category of the diagnostic (e.g., "unused alias") is kept for code actions
+                diagnostic.data = Some(serde_json::Value::from(*category));
                 lsp_diagnostics
                     .entry(*fpath)
                     .or_insert_with(Vec::new)
-                    .push(Diagnostic::new(
-                        range,
-                        Some(severity(*s)),
-                        None,
-                        None,
-                        msg.to_string(),
-                        related_info_opt,
-                        None,
-                    ));
+                    .push(diagnostic);
             }
         }
     }
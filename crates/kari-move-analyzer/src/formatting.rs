@@ -0,0 +1,142 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements document and range formatting with the formatter of `kari move fmt`,
+//! configured by the package the document belongs to. Range formatting formats whole lines.
+
+use crate::{
+    context::Context,
+    symbols::SymbolicatorRunner,
+    utils::{offset_to_position, position_to_offset},
+};
+use kari_move_fmt::FmtConfig;
+use lsp_server::{ErrorCode, Request, Response};
+use lsp_types::{
+    DocumentFormattingParams, DocumentRangeFormattingParams, Position, Range, TextEdit,
+};
+use std::path::Path;
+
+/// Loads the formatter configuration of the package containing the file at `path`
+fn config(path: &Path) -> Result<FmtConfig, String> {
+    match path.parent().and_then(SymbolicatorRunner::root_dir) {
+        Some(root) => FmtConfig::load(&root).map_err(|e| e.to_string()),
+        None => Ok(FmtConfig::default()),
+    }
+}
+
+/// Returns the edits formatting `buffer`, the contents of the file at `path`, or only the lines
+/// of `range` if given
+pub fn formatting_edits(
+    buffer: &str,
+    path: &Path,
+    range: Option<Range>,
+) -> Result<Vec<TextEdit>, String> {
+    let config = config(path)?;
+    let (start, end) = match range {
+        Some(range) => {
+            // a range ending at the start of a line does not include that line
+            let end_line = if range.end.character == 0 && range.end.line > range.start.line {
+                range.end.line - 1
+            } else {
+                range.end.line
+            };
+            (range.start.line, end_line)
+        }
+        None => (0, u32::MAX),
+    };
+
+    let formatted = kari_move_fmt::format_range(buffer, &config, start as usize..=end as usize)
+        .map_err(|e| e.to_string())?;
+    let start_offset = position_to_offset(buffer, Position::new(start, 0)).unwrap_or(buffer.len());
+    let end_offset = end
+        .checked_add(1)
+        .and_then(|line| position_to_offset(buffer, Position::new(line, 0)))
+        .unwrap_or(buffer.len());
+    if buffer[start_offset..end_offset] == formatted {
+        return Ok(vec![]);
+    }
+    Ok(vec![TextEdit {
+        range: Range {
+            start: offset_to_position(buffer, start_offset),
+            end: offset_to_position(buffer, end_offset),
+        },
+        new_text: formatted,
+    }])
+}
+
+fn send_response(context: &Context, request: &Request, result: Result<Vec<TextEdit>, String>) {
+    let response = match result {
+        Ok(edits) => Response::new_ok(request.id.clone(), edits),
+        Err(message) => {
+            Response::new_err(request.id.clone(), ErrorCode::InvalidParams as i32, message)
+        }
+    };
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send formatting response: {:?}", err);
+    }
+}
+
+/// Handles document formatting request of the language server
+pub fn on_formatting_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<DocumentFormattingParams>(request.params.clone())
+        .expect("could not deserialize formatting request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let result = match context.files.get(&fpath) {
+        Some(buffer) => formatting_edits(buffer, &fpath, None),
+        None => Ok(vec![]),
+    };
+    send_response(context, request, result);
+}
+
+/// Handles range formatting request of the language server
+pub fn on_range_formatting_request(context: &Context, request: &Request) {
+    let parameters =
+        serde_json::from_value::<DocumentRangeFormattingParams>(request.params.clone())
+            .expect("could not deserialize range formatting request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let result = match context.files.get(&fpath) {
+        Some(buffer) => formatting_edits(buffer, &fpath, Some(parameters.range)),
+        None => Ok(vec![]),
+    };
+    send_response(context, request, result);
+}
+
+#[test]
+fn formatting_test() {
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols/sources/M1.move");
+
+    let buffer = "module Symbols::M1 {\nfun f(): u64 {\nlet a=1;\n  a\n}\n}\n";
+    let edits = formatting_edits(buffer, &path, None).unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].range.start, Position::new(0, 0));
+    assert_eq!(edits[0].range.end, Position::new(6, 0));
+    assert_eq!(
+        edits[0].new_text,
+        "module Symbols::M1 {\n    fun f(): u64 {\n        let a = 1;\n        a\n    }\n}\n"
+    );
+    assert!(formatting_edits(&edits[0].new_text, &path, None)
+        .unwrap()
+        .is_empty());
+
+    // the range ends at the start of the line following `let a=1;`
+    let range = Range {
+        start: Position::new(2, 3),
+        end: Position::new(3, 0),
+    };
+    let edits = formatting_edits(buffer, &path, Some(range)).unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].range.start, Position::new(2, 0));
+    assert_eq!(edits[0].range.end, Position::new(3, 0));
+    assert_eq!(edits[0].new_text, "        let a = 1;\n");
+
+    assert!(formatting_edits("module M { /* open", &path, None).is_err());
+}
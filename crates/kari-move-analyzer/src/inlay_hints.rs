@@ -0,0 +1,229 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements inlay hints: the types of locals bound by `let` without a type
+//! annotation, and the names of the parameters that the arguments of function calls are passed
+//! to. The types are those the symbolicator recorded for each identifier.
+//!
+//! The version of `lsp_types` used by the language server predates inlay hints, so the request
+//! and its result are defined here.
+
+use crate::{
+    context::Context,
+    symbols::{type_to_ide_string, IdentType, Symbols},
+    syntax::{call_args, tokens, Token},
+    utils::{offset_to_position, position_to_offset},
+};
+use lsp_server::Request;
+use lsp_types::{Position, Range, TextDocumentIdentifier};
+use move_compiler::parser::lexer::Tok;
+use serde::{Deserialize, Serialize, Serializer};
+use std::path::Path;
+
+/// Method of the inlay hint request
+pub const INLAY_HINT_METHOD: &str = "textDocument/inlayHint";
+
+/// Parameters of an inlay hint request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The range hints are requested for
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlayHintKind {
+    Type = 1,
+    Parameter = 2,
+}
+
+impl Serialize for InlayHintKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    pub position: Position,
+    pub label: String,
+    pub kind: InlayHintKind,
+    pub padding_left: bool,
+    pub padding_right: bool,
+}
+
+/// Checks if the identifier at index `idx` is bound by a `let` without a type annotation (and not
+/// as the shorthand of a field, e.g., `f` in `let S { f } = s;`)
+fn is_unannotated_let_binding(tokens: &[Token], idx: usize) -> bool {
+    let mut in_struct_pattern = false;
+    let mut depth = 0;
+    let mut let_idx = None;
+    for i in (0..idx).rev() {
+        match tokens[i].tok {
+            Tok::Let => {
+                let_idx = Some(i);
+                break;
+            }
+            Tok::Semicolon | Tok::Equal | Tok::Fun | Tok::Spec => return false,
+            Tok::RBrace => depth += 1,
+            Tok::LBrace if depth == 0 => in_struct_pattern = true,
+            Tok::LBrace => depth -= 1,
+            _ => (),
+        }
+    }
+    let Some(let_idx) = let_idx else {
+        return false;
+    };
+    if in_struct_pattern && tokens[idx - 1].tok != Tok::Colon {
+        return false;
+    }
+
+    // a type annotation of the whole pattern, e.g., `let (a, b): (u64, u64) = ...;`
+    let mut depth = 0;
+    for token in &tokens[let_idx + 1..] {
+        match token.tok {
+            Tok::LParen | Tok::LBrace => depth += 1,
+            Tok::RParen | Tok::RBrace => depth -= 1,
+            Tok::Colon if depth == 0 => return false,
+            Tok::Equal | Tok::Semicolon => break,
+            _ => (),
+        }
+    }
+    true
+}
+
+/// Returns the inlay hints in `range` of `buffer`, the contents of the file at `path`
+pub fn inlay_hints(buffer: &str, symbols: &Symbols, path: &Path, range: Range) -> Vec<InlayHint> {
+    let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(use_defs) = symbols.file_use_defs.get(&path) else {
+        return vec![];
+    };
+    let tokens = tokens(buffer);
+
+    let mut hints = vec![];
+    for (line, uses) in use_defs.range(range.start.line..=range.end.line) {
+        for u in uses {
+            let start = Position {
+                line: *line,
+                character: u.col_start,
+            };
+            // skip uses that the (possibly outdated) symbols place elsewhere than an identifier
+            // of the same length in the buffer
+            let Some(idx) = position_to_offset(buffer, start)
+                .and_then(|offset| tokens.binary_search_by_key(&offset, |t| t.start).ok())
+            else {
+                continue;
+            };
+            let token = &tokens[idx];
+            if token.tok != Tok::Identifier
+                || token.content.chars().count() != (u.col_end - u.col_start) as usize
+            {
+                continue;
+            }
+
+            match &u.use_type {
+                IdentType::RegularType(t)
+                    if u.def_loc.start == start && is_unannotated_let_binding(&tokens, idx) =>
+                {
+                    hints.push(InlayHint {
+                        position: Position {
+                            line: *line,
+                            character: u.col_end,
+                        },
+                        label: format!(": {}", type_to_ide_string(t)),
+                        kind: InlayHintKind::Type,
+                        padding_left: false,
+                        padding_right: false,
+                    });
+                }
+                IdentType::FunctionType(_, name, _, arg_names, _, _, _)
+                    if token.content == name.as_str() =>
+                {
+                    let Some(args) = call_args(&tokens, idx) else {
+                        continue;
+                    };
+                    for (arg, param) in args.iter().zip(arg_names) {
+                        let first = &tokens[arg.start];
+                        // an argument that is just a name matching the parameter needs no hint
+                        if arg.len() == 1 && first.content == param.as_str() {
+                            continue;
+                        }
+                        hints.push(InlayHint {
+                            position: offset_to_position(buffer, first.start),
+                            label: format!("{}:", param),
+                            kind: InlayHintKind::Parameter,
+                            padding_left: false,
+                            padding_right: true,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    hints
+}
+
+/// Handles inlay hint request of the language server
+pub fn on_inlay_hint_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<InlayHintParams>(request.params.clone())
+        .expect("could not deserialize inlay hint request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let hints = context
+        .files
+        .get(&fpath)
+        .map(|buffer| inlay_hints(buffer, symbols, &fpath, parameters.range))
+        .unwrap_or_default();
+
+    let response = lsp_server::Response::new_ok(request.id.clone(), hints);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send inlay hint response: {:?}", err);
+    }
+}
+
+#[test]
+fn inlay_hints_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let fpath = dunce::canonicalize(path.join("sources/M1.move")).unwrap();
+    let buffer = std::fs::read_to_string(&fpath).unwrap();
+
+    // `let tmp = M2::some_other_struct(7);`
+    let range = Range {
+        start: Position::new(127, 0),
+        end: Position::new(127, 0),
+    };
+    assert_eq!(
+        inlay_hints(&buffer, &symbols, &fpath, range),
+        vec![
+            InlayHint {
+                position: Position::new(127, 15),
+                label: ": Symbols::M2::SomeOtherStruct".to_string(),
+                kind: InlayHintKind::Type,
+                padding_left: false,
+                padding_right: false,
+            },
+            InlayHint {
+                position: Position::new(127, 40),
+                label: "v:".to_string(),
+                kind: InlayHintKind::Parameter,
+                padding_left: false,
+                padding_right: true,
+            },
+        ]
+    );
+}
@@ -5,9 +5,19 @@
 #[macro_use(sp)]
 extern crate move_ir_types;
 
+pub mod call_hierarchy;
+pub mod code_actions;
+pub mod code_lens;
 pub mod completion;
 pub mod context;
 pub mod diagnostics;
+pub mod formatting;
+pub mod inlay_hints;
+pub mod rename;
+pub mod semantic_tokens;
+pub mod signature_help;
 pub mod symbols;
+pub mod syntax;
+pub mod test_runner;
 pub mod utils;
 pub mod vfs;
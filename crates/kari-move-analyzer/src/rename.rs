@@ -0,0 +1,574 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements renaming of identifiers across the source files of a package.
+//!
+//! Locals, functions, structs, fields and constants are renamed at their definition and at all
+//! references to it found by the symbolicator. Members named in `use` declarations and module
+//! aliases are not part of the typed AST the symbolicator works on, so they are found by lexing
+//! the source files of the package instead. Definitions outside of the package (i.e., in its
+//! dependencies) cannot be renamed.
+
+use crate::{
+    context::Context,
+    symbols::{DefLoc, SymbolicatorRunner, Symbols},
+    syntax::{alias_uses, tokens, use_decls},
+    utils::position_to_offset,
+    vfs::VirtualFileSystem,
+};
+use lsp_server::{ErrorCode, Request, Response};
+use lsp_types::{
+    Position, PrepareRenameResponse, Range, RenameParams, TextDocumentPositionParams, TextEdit,
+    WorkspaceEdit,
+};
+use move_compiler::{
+    parser::{keywords::KEYWORDS, lexer::Tok},
+    shared::Identifier,
+};
+use move_symbol_pool::Symbol;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Kind of a definition that can be renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DefKind {
+    /// Local variables, parameters and type parameters
+    Local,
+    Function,
+    Struct,
+    Field,
+    Constant,
+}
+
+/// The identifier a rename applies to
+#[derive(Debug, Clone)]
+enum Target {
+    /// An identifier whose definition and uses are known to the symbolicator
+    Def {
+        def_loc: DefLoc,
+        kind: DefKind,
+        /// Module defining a function or a struct, which can be named in `use` declarations
+        module: Option<Symbol>,
+        name: String,
+        range: Range,
+    },
+    /// A module alias introduced by a `use` declaration
+    ModuleAlias {
+        path: PathBuf,
+        /// Index of the declaration in the `use` declarations of the file
+        decl: usize,
+        /// Index of the alias in the aliases of the declaration
+        alias: usize,
+        name: String,
+        range: Range,
+    },
+}
+
+/// Edits by file, keyed by the range they replace
+type Edits = BTreeMap<PathBuf, BTreeMap<(Position, Position), String>>;
+
+/// Contents of source files, taken from the virtual file system if a file is open in the editor
+/// and read from disk otherwise
+struct Sources<'a> {
+    files: &'a VirtualFileSystem,
+    texts: HashMap<PathBuf, Option<String>>,
+}
+
+impl<'a> Sources<'a> {
+    fn new(files: &'a VirtualFileSystem) -> Self {
+        Self {
+            files,
+            texts: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, path: &Path) -> Option<&str> {
+        let files = self.files;
+        self.texts
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                files
+                    .get(&path.to_path_buf())
+                    .map(str::to_string)
+                    .or_else(|| fs::read_to_string(path).ok())
+            })
+            .as_deref()
+    }
+}
+
+/// Returns the text of `text` in `range`, a range within a single line
+fn text_at(text: &str, range: Range) -> Option<&str> {
+    text.get(position_to_offset(text, range.start)?..position_to_offset(text, range.end)?)
+}
+
+/// Returns the identifier starting at `position` in `text`
+fn identifier_at(text: &str, position: Position) -> Option<String> {
+    let start = position_to_offset(text, position)?;
+    let name: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Checks if both paths are in the same package
+fn same_package(path1: &Path, path2: &Path) -> bool {
+    let root = SymbolicatorRunner::root_dir(path1);
+    root.is_some() && root == SymbolicatorRunner::root_dir(path2)
+}
+
+/// Finds the kind of the definition at `def_loc` and, for functions and structs, the name of the
+/// defining module
+fn def_kind(symbols: &Symbols, def_loc: &DefLoc) -> (DefKind, Option<Symbol>) {
+    let mod_defs = symbols
+        .file_mods
+        .values()
+        .flatten()
+        .filter(|mod_defs| mod_defs.fhash == def_loc.fhash);
+    for mod_defs in mod_defs {
+        let module = Some(mod_defs.name.module.value());
+        if mod_defs.constants.values().any(|c| *c == def_loc.start) {
+            return (DefKind::Constant, None);
+        }
+        for struct_def in mod_defs.structs.values() {
+            if struct_def.name_start == def_loc.start {
+                return (DefKind::Struct, module);
+            }
+            if struct_def
+                .field_defs
+                .iter()
+                .any(|f| f.start == def_loc.start)
+            {
+                return (DefKind::Field, None);
+            }
+        }
+        if mod_defs
+            .functions
+            .values()
+            .any(|f| f.start == def_loc.start)
+        {
+            return (DefKind::Function, module);
+        }
+    }
+    (DefKind::Local, None)
+}
+
+/// Finds what a rename at `position` in the file at `path` applies to
+fn target(
+    sources: &mut Sources,
+    symbols: &Symbols,
+    path: &Path,
+    position: Position,
+) -> Option<Target> {
+    let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(u) = symbols.use_def_at(&path, position) {
+        let def_path = symbols.file_path(&u.def_loc.fhash)?;
+        if !same_package(&def_path, &path) {
+            return None;
+        }
+        let name = identifier_at(sources.get(&def_path)?, u.def_loc.start)?;
+        let range = Range {
+            start: Position {
+                line: position.line,
+                character: u.col_start,
+            },
+            end: Position {
+                line: position.line,
+                character: u.col_end,
+            },
+        };
+        // uses of aliased members (e.g., `N` imported by `use 0x1::M::{S as N}`) are not renamed
+        if text_at(sources.get(&path)?, range)? != name {
+            return None;
+        }
+        let (kind, module) = def_kind(symbols, &u.def_loc);
+        return Some(Target::Def {
+            def_loc: u.def_loc,
+            kind,
+            module,
+            name,
+            range,
+        });
+    }
+
+    let text = sources.get(&path)?;
+    let offset = position_to_offset(text, position)?;
+    let tokens = tokens(text);
+    let cursor = tokens
+        .iter()
+        .position(|t| t.tok == Tok::Identifier && t.start <= offset && offset <= t.end())?;
+    let decls = use_decls(&tokens);
+    for (decl_idx, decl) in decls.iter().enumerate() {
+        for (alias_idx, alias) in decl.aliases.iter().enumerate() {
+            let name = decl.alias_name(&tokens, alias);
+            let declared_at = if alias.explicit {
+                alias.token
+            } else {
+                decl.module
+            };
+            if cursor == declared_at || alias_uses(&tokens, &decls, decl, name).contains(&cursor) {
+                return Some(Target::ModuleAlias {
+                    path,
+                    decl: decl_idx,
+                    alias: alias_idx,
+                    name: name.to_string(),
+                    range: tokens[cursor].range(text),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Checks that `new_name` is a valid name for a definition of the given kind
+fn check_name(new_name: &str, kind: Option<DefKind>) -> Result<(), String> {
+    let mut chars = new_name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&new_name);
+    if !valid {
+        return Err(format!("'{}' is not a valid identifier", new_name));
+    }
+    let first = new_name.chars().next().unwrap();
+    match kind {
+        Some(DefKind::Struct) | Some(DefKind::Constant) if !first.is_ascii_uppercase() => Err(
+            format!("'{}' must start with an uppercase letter", new_name),
+        ),
+        Some(DefKind::Local) if first.is_ascii_uppercase() => Err(format!(
+            "'{}' must start with a lowercase letter or an underscore",
+            new_name
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn add_edit(edits: &mut Edits, path: &Path, range: Range, new_text: String) {
+    edits
+        .entry(path.to_path_buf())
+        .or_default()
+        .insert((range.start, range.end), new_text);
+}
+
+/// Adds the edits renaming a definition and its references
+#[allow(clippy::too_many_arguments)]
+fn def_edits(
+    sources: &mut Sources,
+    symbols: &Symbols,
+    def_loc: &DefLoc,
+    kind: DefKind,
+    module: Option<Symbol>,
+    name: &str,
+    new_name: &str,
+    edits: &mut Edits,
+) -> Option<()> {
+    for use_loc in symbols.references.get(def_loc)? {
+        let Some(path) = symbols.file_path(&use_loc.fhash) else {
+            continue;
+        };
+        let Some(text) = sources.get(&path) else {
+            continue;
+        };
+        let range = Range {
+            start: use_loc.start,
+            end: Position {
+                line: use_loc.start.line,
+                character: use_loc.col_end,
+            },
+        };
+        if text_at(text, range) != Some(name) {
+            continue;
+        }
+        // in field shorthands (e.g., `S { f }`) the same identifier refers to both a field and a
+        // local, so the shorthand is expanded to keep referring to the one not being renamed
+        let shorthand = matches!(kind, DefKind::Field | DefKind::Local)
+            && symbols
+                .references
+                .iter()
+                .any(|(d, uses)| d != def_loc && uses.contains(use_loc));
+        let new_text = match kind {
+            DefKind::Field if shorthand => format!("{}: {}", new_name, name),
+            DefKind::Local if shorthand => format!("{}: {}", name, new_name),
+            _ => new_name.to_string(),
+        };
+        add_edit(edits, &path, range, new_text);
+    }
+
+    // imports of the definition in `use` declarations
+    let (Some(module), Some(def_path)) = (module, symbols.file_path(&def_loc.fhash)) else {
+        return Some(());
+    };
+    for path in symbols.file_mods.keys() {
+        if !same_package(path, &def_path) {
+            continue;
+        }
+        let Some(text) = sources.get(path) else {
+            continue;
+        };
+        let tokens = tokens(text);
+        for decl in use_decls(&tokens) {
+            if tokens[decl.module].content != module.as_str() {
+                continue;
+            }
+            for member in &decl.members {
+                if tokens[*member].content == name {
+                    add_edit(
+                        edits,
+                        path,
+                        tokens[*member].range(text),
+                        new_name.to_string(),
+                    );
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+/// Adds the edits renaming a module alias at its declaration and its uses
+fn alias_edits(
+    sources: &mut Sources,
+    path: &Path,
+    decl_idx: usize,
+    alias_idx: usize,
+    new_name: &str,
+    edits: &mut Edits,
+) -> Option<()> {
+    let text = sources.get(path)?;
+    let tokens = tokens(text);
+    let decls = use_decls(&tokens);
+    let decl = decls.get(decl_idx)?;
+    let alias = decl.aliases.get(alias_idx)?;
+    let name = decl.alias_name(&tokens, alias);
+    let declared_at = tokens[alias.token].range(text);
+    if alias.explicit {
+        add_edit(edits, path, declared_at, new_name.to_string());
+    } else {
+        // an implicit alias becomes an explicit one: `use 0x1::M;` to `use 0x1::M as N;`
+        let end = Range {
+            start: declared_at.end,
+            end: declared_at.end,
+        };
+        add_edit(edits, path, end, format!(" as {}", new_name));
+    }
+    for idx in alias_uses(&tokens, &decls, decl, name) {
+        add_edit(edits, path, tokens[idx].range(text), new_name.to_string());
+    }
+    Some(())
+}
+
+/// Returns the range and the name of the identifier at `position` in the file at `path` if it can
+/// be renamed
+pub fn prepare_rename(
+    files: &VirtualFileSystem,
+    symbols: &Symbols,
+    path: &Path,
+    position: Position,
+) -> Option<(Range, String)> {
+    match target(&mut Sources::new(files), symbols, path, position)? {
+        Target::Def { range, name, .. } | Target::ModuleAlias { range, name, .. } => {
+            Some((range, name))
+        }
+    }
+}
+
+/// Computes the edits, by file, renaming the identifier at `position` in the file at `path` to
+/// `new_name`
+pub fn rename(
+    files: &VirtualFileSystem,
+    symbols: &Symbols,
+    path: &Path,
+    position: Position,
+    new_name: &str,
+) -> Result<BTreeMap<PathBuf, Vec<TextEdit>>, String> {
+    let mut sources = Sources::new(files);
+    let mut edits = Edits::new();
+    match target(&mut sources, symbols, path, position) {
+        Some(Target::Def {
+            def_loc,
+            kind,
+            module,
+            name,
+            ..
+        }) => {
+            check_name(new_name, Some(kind))?;
+            def_edits(
+                &mut sources,
+                symbols,
+                &def_loc,
+                kind,
+                module,
+                &name,
+                new_name,
+                &mut edits,
+            );
+        }
+        Some(Target::ModuleAlias {
+            path, decl, alias, ..
+        }) => {
+            check_name(new_name, None)?;
+            alias_edits(&mut sources, &path, decl, alias, new_name, &mut edits);
+        }
+        None => return Err("The element at the cursor cannot be renamed".to_string()),
+    }
+    Ok(edits
+        .into_iter()
+        .map(|(path, file_edits)| {
+            let file_edits = file_edits
+                .into_iter()
+                .map(|((start, end), new_text)| TextEdit {
+                    range: Range { start, end },
+                    new_text,
+                })
+                .collect();
+            (path, file_edits)
+        })
+        .collect())
+}
+
+fn send_response(context: &Context, response: Response) {
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send rename response: {:?}", err);
+    }
+}
+
+/// Handles prepare-rename request of the language server
+pub fn on_prepare_rename_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<TextDocumentPositionParams>(request.params.clone())
+        .expect("could not deserialize prepare-rename request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let result = prepare_rename(&context.files, symbols, &fpath, parameters.position).map(
+        |(range, placeholder)| PrepareRenameResponse::RangeWithPlaceholder { range, placeholder },
+    );
+    let response = Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap());
+    send_response(context, response);
+}
+
+/// Handles rename request of the language server
+pub fn on_rename_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<RenameParams>(request.params.clone())
+        .expect("could not deserialize rename request");
+
+    let fpath = parameters
+        .text_document_position
+        .text_document
+        .uri
+        .to_file_path()
+        .unwrap();
+    let position = parameters.text_document_position.position;
+    let response = match rename(
+        &context.files,
+        symbols,
+        &fpath,
+        position,
+        &parameters.new_name,
+    ) {
+        Ok(edits) => {
+            let changes = edits
+                .into_iter()
+                .map(|(path, file_edits)| (Url::from_file_path(path).unwrap(), file_edits))
+                .collect();
+            let result = WorkspaceEdit::new(changes);
+            Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap())
+        }
+        Err(message) => {
+            Response::new_err(request.id.clone(), ErrorCode::InvalidParams as i32, message)
+        }
+    };
+    send_response(context, response);
+}
+
+#[cfg(test)]
+fn edit_starts(edits: &BTreeMap<PathBuf, Vec<TextEdit>>, path: &Path) -> Vec<(u32, u32, String)> {
+    edits
+        .get(path)
+        .unwrap()
+        .iter()
+        .map(|e| {
+            (
+                e.range.start.line,
+                e.range.start.character,
+                e.new_text.clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn rename_test() {
+    use crate::symbols::Symbolicator;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+    let files = VirtualFileSystem::default();
+
+    let m1 = dunce::canonicalize(path.join("sources/M1.move")).unwrap();
+    let m2 = dunce::canonicalize(path.join("sources/M2.move")).unwrap();
+    let new = |name: &str| name.to_string();
+
+    // function, renamed from its definition
+    let def = Position {
+        line: 6,
+        character: 15,
+    };
+    let (_, name) = prepare_rename(&files, &symbols, &m2, def).unwrap();
+    assert_eq!(name, "some_other_struct");
+    let edits = rename(&files, &symbols, &m2, def, "make").unwrap();
+    assert_eq!(edit_starts(&edits, &m2), vec![(6, 15, new("make"))]);
+    assert_eq!(
+        edit_starts(&edits, &m1),
+        vec![
+            (25, 21, new("make")),
+            (31, 12, new("make")),
+            (127, 22, new("make")),
+            (131, 16, new("make")),
+        ]
+    );
+
+    // struct, renamed from a use in another module, including its `use` declaration
+    let use_pos = Position {
+        line: 30,
+        character: 35,
+    };
+    let edits = rename(&files, &symbols, &m1, use_pos, "Other").unwrap();
+    let m1_edits = edit_starts(&edits, &m1);
+    assert!(m1_edits.contains(&(28, 28, new("Other"))));
+    assert!(m1_edits.contains(&(30, 35, new("Other"))));
+    assert!(edit_starts(&edits, &m2).contains(&(2, 11, new("Other"))));
+    assert!(rename(&files, &symbols, &m1, use_pos, "other").is_err());
+    assert!(rename(&files, &symbols, &m1, use_pos, "struct").is_err());
+
+    // implicit module alias
+    let alias_use = Position {
+        line: 31,
+        character: 8,
+    };
+    let (range, name) = prepare_rename(&files, &symbols, &m1, alias_use).unwrap();
+    assert_eq!(name, "M2");
+    assert_eq!(range.end.character, 10);
+    let edits = rename(&files, &symbols, &m1, alias_use, "N").unwrap();
+    assert_eq!(
+        edit_starts(&edits, &m1),
+        vec![
+            (28, 26, new(" as N")),
+            (31, 8, new("N")),
+            (40, 8, new("N")),
+            (127, 18, new("N")),
+            (131, 12, new("N")),
+        ]
+    );
+    assert_eq!(edits.len(), 1);
+}
@@ -0,0 +1,304 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements semantic tokens, which classify the identifiers of a file for
+//! highlighting. Identifiers are classified by the definitions the symbolicator matched them to,
+//! while abilities and addresses, which the symbolicator does not record, are recognized from the
+//! tokens of the file.
+
+use crate::{
+    context::Context,
+    symbols::{DefLoc, IdentType, Symbols},
+    syntax::{tokens, Token},
+    utils::{offset_to_position, position_to_offset},
+};
+use lsp_server::Request;
+use lsp_types::{
+    Position, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensResult,
+};
+use move_compiler::{naming::ast::Type_, parser::lexer::Tok};
+use std::{collections::BTreeMap, path::Path};
+
+/// Kinds of semantic tokens, in the order of their types in the legend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Addresses and modules
+    Namespace,
+    Struct,
+    Ability,
+    Function,
+    /// Locals and constants
+    Variable,
+    TypeParameter,
+    Field,
+}
+
+/// Modifier of constants
+const READONLY: u32 = 1 << 0;
+/// Modifier of entry functions
+const ENTRY: u32 = 1 << 1;
+
+const ABILITIES: &[&str] = &["copy", "drop", "store", "key"];
+
+/// The semantic token types and modifiers, whose indices the tokens are encoded with
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::NAMESPACE,
+            SemanticTokenType::STRUCT,
+            SemanticTokenType::INTERFACE,
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::TYPE_PARAMETER,
+            SemanticTokenType::PROPERTY,
+        ],
+        token_modifiers: vec![
+            SemanticTokenModifier::READONLY,
+            SemanticTokenModifier::new("entry"),
+        ],
+    }
+}
+
+/// Kinds of the module-level definitions of all modules, by the location of their names
+fn definitions(symbols: &Symbols) -> BTreeMap<DefLoc, (Kind, u32)> {
+    let mut defs = BTreeMap::new();
+    for mod_def in symbols.file_mods.values().flatten() {
+        let def_loc = |start| DefLoc {
+            fhash: mod_def.fhash,
+            start,
+        };
+        defs.insert(def_loc(mod_def.start), (Kind::Namespace, 0));
+        for struct_def in mod_def.structs.values() {
+            defs.insert(def_loc(struct_def.name_start), (Kind::Struct, 0));
+            for field_def in &struct_def.field_defs {
+                defs.insert(def_loc(field_def.start), (Kind::Field, 0));
+            }
+        }
+        for start in mod_def.constants.values() {
+            defs.insert(def_loc(*start), (Kind::Variable, READONLY));
+        }
+        for fun_def in mod_def.functions.values() {
+            let modifiers = if fun_def.entry { ENTRY } else { 0 };
+            defs.insert(def_loc(fun_def.start), (Kind::Function, modifiers));
+        }
+    }
+    defs
+}
+
+/// Checks if the token at index `idx` is an ability, either in the abilities of a struct (e.g.,
+/// `has copy, drop`) or in the constraints of a type parameter (e.g., `T: copy + drop`)
+fn is_ability(tokens: &[Token], idx: usize) -> bool {
+    if !ABILITIES.contains(&tokens[idx].content.as_str())
+        || tokens
+            .get(idx + 1)
+            .is_some_and(|t| t.tok == Tok::Identifier)
+    {
+        return false;
+    }
+    let preceding = tokens[..idx].iter().rev().find(|t| {
+        !(t.tok == Tok::Comma || t.tok == Tok::Plus || ABILITIES.contains(&t.content.as_str()))
+    });
+    preceding.is_some_and(|t| t.tok == Tok::Colon || t.content == "has")
+}
+
+/// Checks if the token at index `idx` is an address, e.g., `0x1` in `0x1::M` or `std` in `@std`
+/// and `use std::vector`
+fn is_address(tokens: &[Token], idx: usize) -> bool {
+    let token = &tokens[idx];
+    if token.tok != Tok::Identifier && token.tok != Tok::NumValue {
+        return false;
+    }
+    let prev = idx.checked_sub(1).map(|i| tokens[i].tok);
+    let next = |n: usize| tokens.get(idx + n).map(|t| t.tok);
+    if prev == Some(Tok::AtSign) {
+        return true;
+    }
+    if next(1) != Some(Tok::ColonColon) || prev == Some(Tok::ColonColon) {
+        return false;
+    }
+    // the first of three path components (e.g., `std::vector::empty`), the first of two in
+    // declarations (e.g., `module std::vector`) or a numerical address
+    token.tok == Tok::NumValue
+        || next(3) == Some(Tok::ColonColon)
+        || matches!(prev, Some(Tok::Module | Tok::Use | Tok::Friend))
+}
+
+/// Returns the semantic tokens of `buffer`, the contents of the file at `path`
+pub fn semantic_tokens(buffer: &str, symbols: &Symbols, path: &Path) -> Vec<SemanticToken> {
+    let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let tokens = tokens(buffer);
+    // the kind and modifiers of each classified identifier, by its start and length
+    let mut classified: BTreeMap<(Position, u32), (Kind, u32)> = BTreeMap::new();
+
+    if let Some(use_defs) = symbols.file_use_defs.get(&path) {
+        let defs = definitions(symbols);
+        for (line, uses) in use_defs.range(..) {
+            for u in uses {
+                let start = Position {
+                    line: *line,
+                    character: u.col_start,
+                };
+                let length = u.col_end - u.col_start;
+                // skip uses that the (possibly outdated) symbols place elsewhere than an
+                // identifier of the same length in the buffer
+                let Some(token) = position_to_offset(buffer, start)
+                    .and_then(|offset| tokens.binary_search_by_key(&offset, |t| t.start).ok())
+                    .map(|idx| &tokens[idx])
+                else {
+                    continue;
+                };
+                if token.tok != Tok::Identifier || token.content.chars().count() != length as usize
+                {
+                    continue;
+                }
+
+                let kind = defs
+                    .get(&u.def_loc)
+                    .copied()
+                    .unwrap_or_else(|| match &u.use_type {
+                        IdentType::RegularType(sp!(_, Type_::Param(tp)))
+                            if tp.user_specified_name.value.as_str() == token.content =>
+                        {
+                            (Kind::TypeParameter, 0)
+                        }
+                        IdentType::FunctionType(..) => (Kind::Function, 0),
+                        IdentType::RegularType(_) => (Kind::Variable, 0),
+                    });
+                classified.insert((start, length), kind);
+            }
+        }
+    }
+
+    for idx in 0..tokens.len() {
+        let kind = if is_ability(&tokens, idx) {
+            Kind::Ability
+        } else if is_address(&tokens, idx) {
+            Kind::Namespace
+        } else {
+            continue;
+        };
+        let token = &tokens[idx];
+        let start = offset_to_position(buffer, token.start);
+        classified
+            .entry((start, token.content.chars().count() as u32))
+            .or_insert((kind, 0));
+    }
+
+    let mut data = vec![];
+    let mut prev = Position::new(0, 0);
+    for ((start, length), (kind, modifiers)) in classified {
+        let delta_line = start.line - prev.line;
+        data.push(SemanticToken {
+            delta_line,
+            delta_start: if delta_line == 0 {
+                start.character - prev.character
+            } else {
+                start.character
+            },
+            length,
+            token_type: kind as u32,
+            token_modifiers_bitset: modifiers,
+        });
+        prev = start;
+    }
+    data
+}
+
+/// Handles semantic tokens request of the language server
+pub fn on_semantic_tokens_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<SemanticTokensParams>(request.params.clone())
+        .expect("could not deserialize semantic tokens request");
+
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let data = context
+        .files
+        .get(&fpath)
+        .map(|buffer| semantic_tokens(buffer, symbols, &fpath))
+        .unwrap_or_default();
+    let result = SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    });
+
+    let response = lsp_server::Response::new_ok(request.id.clone(), result);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send semantic tokens response: {:?}", err);
+    }
+}
+
+#[test]
+fn semantic_tokens_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    // decodes the tokens of a file to (line, character, length, kind, modifiers)
+    let classify = |file: &str| {
+        let fpath = dunce::canonicalize(path.join(file)).unwrap();
+        let buffer = std::fs::read_to_string(&fpath).unwrap();
+        let mut prev = Position::new(0, 0);
+        semantic_tokens(&buffer, &symbols, &fpath)
+            .into_iter()
+            .map(|t| {
+                let character = if t.delta_line == 0 {
+                    prev.character + t.delta_start
+                } else {
+                    t.delta_start
+                };
+                prev = Position::new(prev.line + t.delta_line, character);
+                (
+                    prev.line,
+                    prev.character,
+                    t.length,
+                    t.token_type,
+                    t.token_modifiers_bitset,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let m1 = classify("sources/M1.move");
+    let expected = [
+        // `module Symbols::M1 {`
+        (0, 7, 7, Kind::Namespace as u32, 0),
+        // `struct SomeStruct has key, drop, store {`
+        (2, 11, 10, Kind::Struct as u32, 0),
+        (2, 26, 3, Kind::Ability as u32, 0),
+        (2, 31, 4, Kind::Ability as u32, 0),
+        (2, 37, 5, Kind::Ability as u32, 0),
+        // `const SOME_CONST: u64 = 42;`
+        (6, 10, 10, Kind::Variable as u32, READONLY),
+        // `fun unpack(s: SomeStruct): u64 {`
+        (9, 8, 6, Kind::Function as u32, 0),
+        (9, 15, 1, Kind::Variable as u32, 0),
+        (9, 18, 10, Kind::Struct as u32, 0),
+        // `let SomeStruct { some_field: value } = s;`
+        (10, 25, 10, Kind::Field as u32, 0),
+        (10, 37, 5, Kind::Variable as u32, 0),
+    ];
+    for token in expected {
+        assert!(m1.contains(&token), "{:?} not in {:?}", token, m1);
+    }
+
+    let m3 = classify("sources/M3.move");
+    // `fun type_param_arg<T: copy + drop>(param: T): T {`
+    for token in [
+        (6, 23, 1, Kind::TypeParameter as u32, 0),
+        (6, 26, 4, Kind::Ability as u32, 0),
+        (6, 33, 4, Kind::Ability as u32, 0),
+        (6, 39, 5, Kind::Variable as u32, 0),
+        (6, 46, 1, Kind::TypeParameter as u32, 0),
+    ] {
+        assert!(m3.contains(&token), "{:?} not in {:?}", token, m3);
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements signature help: while the arguments of a function call are being typed,
+//! the signature and documentation of the function are shown with the parameter at the cursor
+//! highlighted.
+
+use crate::{
+    context::Context,
+    symbols::{type_to_ide_string, IdentType, Symbols},
+    syntax::{call_name, tokens},
+    utils::{offset_to_position, position_to_offset},
+};
+use lsp_server::Request;
+use lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, Position, SignatureHelp,
+    SignatureHelpParams, SignatureInformation,
+};
+use move_compiler::parser::lexer::Tok;
+use std::path::Path;
+
+/// Returns the signature help at `position` in `buffer`, the contents of the file at `path`, if
+/// the position is within the arguments of a call of a known function.
+pub fn signature_help(
+    buffer: &str,
+    symbols: &Symbols,
+    path: &Path,
+    position: Position,
+) -> Option<SignatureHelp> {
+    let offset = position_to_offset(buffer, position)?;
+    let tokens = tokens(buffer);
+    let before = tokens.iter().take_while(|t| t.start < offset).count();
+
+    // find the unclosed `(` preceding the cursor, counting the arguments before the cursor
+    let mut depth = 0;
+    let mut active_parameter = 0;
+    let mut open = None;
+    for idx in (0..before).rev() {
+        match tokens[idx].tok {
+            Tok::RParen | Tok::RBracket | Tok::RBrace => depth += 1,
+            Tok::LParen | Tok::LBracket | Tok::LBrace if depth > 0 => depth -= 1,
+            Tok::LParen => {
+                open = Some(idx);
+                break;
+            }
+            Tok::LBracket | Tok::LBrace | Tok::Semicolon => return None,
+            Tok::Comma if depth == 0 => active_parameter += 1,
+            _ => (),
+        }
+    }
+    let name = &tokens[call_name(&tokens, open?)?];
+    let start = offset_to_position(buffer, name.start);
+    let u = symbols.use_def_by_name(path, buffer, start, &name.content)?;
+    let IdentType::FunctionType(_, _, _, arg_names, arg_types, _, _) = &u.use_type else {
+        return None;
+    };
+
+    let parameters = arg_names
+        .iter()
+        .zip(arg_types)
+        .map(|(name, t)| ParameterInformation {
+            label: ParameterLabel::Simple(format!("{}: {}", name, type_to_ide_string(t))),
+            documentation: None,
+        })
+        .collect();
+    let documentation = if u.doc_string.is_empty() {
+        None
+    } else {
+        Some(Documentation::String(u.doc_string.clone()))
+    };
+    let signature = SignatureInformation {
+        label: u.use_type.to_string(),
+        documentation,
+        parameters: Some(parameters),
+        active_parameter: None,
+    };
+    Some(SignatureHelp {
+        signatures: vec![signature],
+        active_signature: Some(0),
+        active_parameter: Some(active_parameter),
+    })
+}
+
+/// Handles signature help request of the language server
+pub fn on_signature_help_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<SignatureHelpParams>(request.params.clone())
+        .expect("could not deserialize signature help request");
+
+    let fpath = parameters
+        .text_document_position_params
+        .text_document
+        .uri
+        .to_file_path()
+        .unwrap();
+    let position = parameters.text_document_position_params.position;
+    let result = context
+        .files
+        .get(&fpath)
+        .and_then(|buffer| signature_help(buffer, symbols, &fpath, position));
+
+    let response = lsp_server::Response::new_ok(request.id.clone(), result);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send signature help response: {:?}", err);
+    }
+}
+
+#[test]
+fn signature_help_test() {
+    use crate::symbols::Symbolicator;
+    use std::path::PathBuf;
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let fpath = dunce::canonicalize(path.join("sources/M1.move")).unwrap();
+    let buffer = std::fs::read_to_string(&fpath).unwrap();
+
+    // `M2::multi_arg(SOME_CONST, SOME_CONST)`
+    let help = |character| {
+        signature_help(
+            &buffer,
+            &symbols,
+            &fpath,
+            Position {
+                line: 40,
+                character,
+            },
+        )
+    };
+    let first = help(22).unwrap();
+    assert_eq!(first.active_parameter, Some(0));
+    assert_eq!(
+        first.signatures[0].label,
+        "fun Symbols::M2::multi_arg(p1: u64, p2: u64): u64"
+    );
+    assert_eq!(help(34).unwrap().active_parameter, Some(1));
+    assert!(help(8).is_none());
+}
@@ -60,16 +60,19 @@ use lsp_server::{Request, RequestId};
 use lsp_types::{
     request::GotoTypeDefinitionParams, Diagnostic, DocumentSymbol, DocumentSymbolParams,
     GotoDefinitionParams, Hover, HoverContents, HoverParams, LanguageString, Location,
-    MarkedString, Position, Range, ReferenceParams, SymbolKind,
+    MarkedString, Position, Range, ReferenceParams, SymbolInformation, SymbolKind,
+    WorkspaceSymbolParams,
 };
 
 use std::{
     cmp,
     collections::{BTreeMap, BTreeSet, HashMap},
     fmt,
+    ops::RangeBounds,
     path::{Path, PathBuf},
     sync::{Arc, Condvar, Mutex},
     thread,
+    time::Duration,
 };
 use tempfile::tempdir;
 use url::Url;
@@ -96,25 +99,28 @@ pub const DEFS_AND_REFS_SUPPORT: bool = true;
 // Building Move code requires a larger stack size on Windows (16M has been chosen somewhat
 // arbitrarily)
 pub const STACK_SIZE_BYTES: usize = 16 * 1024 * 1024;
+// How long symbolication waits for further requests (e.g., when several files are saved at once)
+// before it starts, so that it runs once for all of them
+pub const SYMBOLICATION_DEBOUNCE: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Copy)]
 /// Location of a definition's identifier
-struct DefLoc {
+pub(crate) struct DefLoc {
     /// File where the definition of the identifier starts
-    fhash: FileHash,
+    pub(crate) fhash: FileHash,
     /// Location where the definition of the identifier starts
-    start: Position,
+    pub(crate) start: Position,
 }
 
 /// Location of a use's identifier
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Copy)]
-struct UseLoc {
+pub(crate) struct UseLoc {
     /// File where this use identifier starts
-    fhash: FileHash,
+    pub(crate) fhash: FileHash,
     /// Location where this use identifier starts
-    start: Position,
+    pub(crate) start: Position,
     /// Column (on the same line as start)  where this use identifier ends
-    col_end: u32,
+    pub(crate) col_end: u32,
 }
 
 /// Information about a type of an identifier. The reason we need an additional enum is that there
@@ -140,59 +146,62 @@ pub enum IdentType {
 pub struct UseDef {
     /// Column where the (use) identifier location starts on a given line (use this field for
     /// sorting uses on the line)
-    col_start: u32,
+    pub(crate) col_start: u32,
     /// Column where the (use) identifier location ends on a given line
-    col_end: u32,
+    pub(crate) col_end: u32,
     /// Type of the (use) identifier
-    use_type: IdentType,
+    pub(crate) use_type: IdentType,
     /// Location of the definition
-    def_loc: DefLoc,
+    pub(crate) def_loc: DefLoc,
     /// Location of the type definition
-    type_def_loc: Option<DefLoc>,
+    pub(crate) type_def_loc: Option<DefLoc>,
     /// Doc string for the relevant identifier/function
-    doc_string: String,
+    pub(crate) doc_string: String,
 }
 
 /// Definition of a struct field
 #[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
-struct FieldDef {
-    name: Symbol,
-    start: Position,
+pub(crate) struct FieldDef {
+    pub(crate) name: Symbol,
+    pub(crate) start: Position,
 }
 
 /// Definition of a struct
 #[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
-struct StructDef {
-    name_start: Position,
-    field_defs: Vec<FieldDef>,
+pub(crate) struct StructDef {
+    pub(crate) name_start: Position,
+    pub(crate) field_defs: Vec<FieldDef>,
 }
 
 #[derive(Derivative, Debug, Clone, PartialEq, Eq)]
 #[derivative(PartialOrd, Ord)]
 pub struct FunctionDef {
-    name: Symbol,
-    start: Position,
-    attrs: Vec<String>,
+    pub(crate) name: Symbol,
+    pub(crate) start: Position,
+    /// Location where the function body ends
+    pub(crate) end: Position,
+    pub(crate) attrs: Vec<String>,
+    pub(crate) entry: bool,
     #[derivative(PartialOrd = "ignore")]
     #[derivative(Ord = "ignore")]
-    ident_type: IdentType,
+    pub(crate) ident_type: IdentType,
 }
 
 /// Module-level definitions
 #[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
 pub struct ModuleDefs {
     /// File where this module is located
-    fhash: FileHash,
+    pub(crate) fhash: FileHash,
     /// Location where this module is located
-    start: Position,
+    pub(crate) start: Position,
     /// Module name
-    name: ModuleIdent_,
+    pub(crate) name: ModuleIdent_,
     /// Struct definitions
-    structs: BTreeMap<Symbol, StructDef>,
+    pub(crate) structs: BTreeMap<Symbol, StructDef>,
     /// Const definitions
-    constants: BTreeMap<Symbol, Position>,
+    pub(crate) constants: BTreeMap<Symbol, Position>,
     /// Function definitions
-    functions: BTreeMap<Symbol, FunctionDef>,
+    pub(crate) functions: BTreeMap<Symbol, FunctionDef>,
 }
 
 /// Data used during symbolication
@@ -214,7 +223,7 @@ pub struct Symbolicator {
 /// Maps a line number to a list of use-def pairs on a given line (use-def set is sorted by
 /// col_start)
 #[derive(Debug, Clone, Eq, PartialEq)]
-struct UseDefMap(BTreeMap<u32, BTreeSet<UseDef>>);
+pub(crate) struct UseDefMap(BTreeMap<u32, BTreeSet<UseDef>>);
 
 /// Maps a function name to its usage definition
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -223,13 +232,13 @@ pub struct FunctionIdentTypeMap(BTreeMap<String, IdentType>);
 /// Result of the symbolication process
 pub struct Symbols {
     /// A map from def locations to all the references (uses)
-    references: BTreeMap<DefLoc, BTreeSet<UseLoc>>,
+    pub(crate) references: BTreeMap<DefLoc, BTreeSet<UseLoc>>,
     /// A mapping from uses to definitions in a file
-    file_use_defs: BTreeMap<PathBuf, UseDefMap>,
+    pub(crate) file_use_defs: BTreeMap<PathBuf, UseDefMap>,
     /// A mapping from file hashes to file names
-    file_name_mapping: BTreeMap<FileHash, Symbol>,
+    pub(crate) file_name_mapping: BTreeMap<FileHash, Symbol>,
     /// A mapping from filePath to ModuleDefs
-    file_mods: BTreeMap<PathBuf, BTreeSet<ModuleDefs>>,
+    pub(crate) file_mods: BTreeMap<PathBuf, BTreeSet<ModuleDefs>>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -309,7 +318,7 @@ fn arg_list_to_ide_string(names: &[Symbol], types: &[Type]) -> String {
         .join(", ")
 }
 
-fn type_to_ide_string(sp!(_, t): &Type) -> String {
+pub(crate) fn type_to_ide_string(sp!(_, t): &Type) -> String {
     match t {
         Type_::Unit => "()".to_string(),
         Type_::Ref(m, r) => format!("&{} {}", if *m { "mut" } else { "" }, type_to_ide_string(r)),
@@ -348,7 +357,7 @@ fn type_to_ide_string(sp!(_, t): &Type) -> String {
     }
 }
 
-fn addr_to_ide_string(addr: &Address) -> String {
+pub(crate) fn addr_to_ide_string(addr: &Address) -> String {
     match addr {
         Address::Numerical(None, sp!(_, bytes)) => format!("{}", bytes),
         Address::Numerical(Some(name), _) => format!("{}", name),
@@ -413,6 +422,8 @@ impl SymbolicatorRunner {
                             }
                         }
                     };
+                    let starting_path_opt =
+                        starting_path_opt.and_then(|path| Self::debounce(&thread_mtx_cvar, path));
                     if let Some(starting_path) = starting_path_opt {
                         let root_dir = Self::root_dir(&starting_path);
                         if root_dir.is_none() && !missing_manifests.contains(&starting_path) {
@@ -442,7 +453,7 @@ impl SymbolicatorRunner {
                                     //
                                     // TODO: we may consider "unloading" symbolication information when
                                     // files/directories are being closed but as with other performance
-                                    // optimizations (e.g. incrementalizatino of the vfs), let's wait
+                                    // optimizations (e.g. incremental symbolication), let's wait
                                     // until we know we actually need it
                                     let mut old_symbols = symbols.lock().unwrap();
                                     (*old_symbols).merge(new_symbols);
@@ -467,6 +478,31 @@ impl SymbolicatorRunner {
         runner
     }
 
+    /// Waits until no new symbolication request has arrived for `SYMBOLICATION_DEBOUNCE`,
+    /// returning the starting path of the latest request (or `None` if the runner is to quit)
+    fn debounce(
+        mtx_cvar: &(Mutex<RunnerState>, Condvar),
+        mut starting_path: PathBuf,
+    ) -> Option<PathBuf> {
+        let (mtx, cvar) = mtx_cvar;
+        let mut symbolicate = mtx.lock().unwrap();
+        loop {
+            let (guard, timeout) = cvar
+                .wait_timeout(symbolicate, SYMBOLICATION_DEBOUNCE)
+                .unwrap();
+            symbolicate = guard;
+            match symbolicate.clone() {
+                RunnerState::Quit => return None,
+                RunnerState::Run(root_dir) => {
+                    *symbolicate = RunnerState::Wait;
+                    starting_path = root_dir;
+                }
+                RunnerState::Wait if timeout.timed_out() => return Some(starting_path),
+                RunnerState::Wait => (),
+            }
+        }
+    }
+
     pub fn run(&self, starting_path: PathBuf) {
         eprintln!("scheduling run for {:?}", starting_path);
         let (mtx, cvar) = &*self.mtx_cvar;
@@ -563,7 +599,15 @@ impl UseDefMap {
         self.0.entry(key).or_insert_with(BTreeSet::new).insert(val);
     }
 
-    fn get(&self, key: u32) -> Option<BTreeSet<UseDef>> {
+    /// Use-def pairs on the lines in `lines`
+    pub(crate) fn range(
+        &self,
+        lines: impl RangeBounds<u32>,
+    ) -> impl DoubleEndedIterator<Item = (&u32, &BTreeSet<UseDef>)> {
+        self.0.range(lines)
+    }
+
+    pub(crate) fn get(&self, key: u32) -> Option<BTreeSet<UseDef>> {
         self.0.get(&key).cloned()
     }
 
@@ -606,6 +650,53 @@ impl Symbols {
     pub fn file_mods(&self) -> &BTreeMap<PathBuf, BTreeSet<ModuleDefs>> {
         &self.file_mods
     }
+
+    /// The use-def pair of the identifier at `position` in the file at `path`, if any
+    pub(crate) fn use_def_at(&self, path: &Path, position: Position) -> Option<UseDef> {
+        let uses = self.file_use_defs.get(path)?.get(position.line)?;
+        uses.into_iter()
+            .find(|u| position.character >= u.col_start && position.character <= u.col_end)
+    }
+
+    /// The use-def pair of the identifier `name` starting at `start` in `buffer`, the current
+    /// contents of the file at `path`. As the symbols may not reflect the latest edits of the
+    /// buffer, this falls back to the closest preceding use of the same name.
+    pub(crate) fn use_def_by_name(
+        &self,
+        path: &Path,
+        buffer: &str,
+        start: Position,
+        name: &str,
+    ) -> Option<UseDef> {
+        let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let lines: Vec<&str> = buffer.lines().collect();
+        for (line, uses) in self.file_use_defs.get(&path)?.range(..=start.line).rev() {
+            let Some(text) = lines.get(*line as usize) else {
+                continue;
+            };
+            for u in uses.iter().rev() {
+                if *line == start.line && u.col_start > start.character {
+                    continue;
+                }
+                let use_name: String = text
+                    .chars()
+                    .skip(u.col_start as usize)
+                    .take((u.col_end - u.col_start) as usize)
+                    .collect();
+                if use_name == name {
+                    return Some(u.clone());
+                }
+            }
+        }
+        None
+    }
+
+    /// Path of the file with hash `fhash`, canonicalized like the keys of `file_use_defs`
+    pub(crate) fn file_path(&self, fhash: &FileHash) -> Option<PathBuf> {
+        self.file_name_mapping.get(fhash).map(|path| {
+            dunce::canonicalize(path.as_str()).unwrap_or_else(|_| PathBuf::from(path.as_str()))
+        })
+    }
 }
 
 impl Symbolicator {
@@ -862,6 +953,8 @@ impl Symbolicator {
                     continue;
                 }
             };
+            let end = get_loc(&pos.file_hash(), fun.body.loc.end(), files, file_id_mapping)
+                .unwrap_or(name_start);
             let ident_type = IdentType::FunctionType(
                 mod_ident.value,
                 *name,
@@ -891,12 +984,14 @@ impl Symbolicator {
                 FunctionDef {
                     name: *name,
                     start: name_start,
+                    end,
                     attrs: fun
                         .attributes
                         .clone()
                         .iter()
                         .map(|(_loc, name, _attr)| name.to_string())
                         .collect(),
+                    entry: fun.entry.is_some(),
                     ident_type,
                 },
             );
@@ -2388,6 +2483,99 @@ fn handle_struct_fields(struct_def: StructDef, fields: &mut Vec<DocumentSymbol>)
     }
 }
 
+/// Matches `name` against the fuzzy search `query` (case-insensitively), returning the rank of
+/// the match (lower is better): a prefix match ranks before a substring match, which ranks before a
+/// match of the query characters in order
+fn fuzzy_match(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    let query = query.to_lowercase();
+    if name.starts_with(&query) {
+        return Some(0);
+    }
+    if name.contains(&query) {
+        return Some(1);
+    }
+    let mut chars = name.chars();
+    query.chars().all(|q| chars.any(|c| c == q)).then_some(2)
+}
+
+/// Returns the module-level definitions of all modules (of the package and its dependencies)
+/// whose names match `query`, best matches first
+#[allow(deprecated)]
+pub fn workspace_symbols(symbols: &Symbols, query: &str) -> Vec<SymbolInformation> {
+    let mut matches = vec![];
+    for (path, mods) in &symbols.file_mods {
+        let Ok(uri) = Url::from_file_path(path) else {
+            continue;
+        };
+        for mod_def in mods {
+            let address = addr_to_ide_string(&mod_def.name.address);
+            let module = format!("{}::{}", address, mod_def.name.module);
+            let mut defs = vec![(
+                mod_def.name.module.value(),
+                SymbolKind::Module,
+                mod_def.start,
+                address,
+            )];
+            defs.extend(mod_def.structs.iter().map(|(name, struct_def)| {
+                (
+                    *name,
+                    SymbolKind::Struct,
+                    struct_def.name_start,
+                    module.clone(),
+                )
+            }));
+            defs.extend(
+                mod_def
+                    .constants
+                    .iter()
+                    .map(|(name, start)| (*name, SymbolKind::Constant, *start, module.clone())),
+            );
+            defs.extend(mod_def.functions.iter().map(|(name, fun_def)| {
+                (*name, SymbolKind::Function, fun_def.start, module.clone())
+            }));
+
+            for (name, kind, start, container) in defs {
+                let Some(rank) = fuzzy_match(name.as_str(), query) else {
+                    continue;
+                };
+                let symbol = SymbolInformation {
+                    name: name.to_string(),
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: Range { start, end: start },
+                    },
+                    container_name: Some(container),
+                };
+                matches.push((rank, symbol));
+            }
+        }
+    }
+    matches.sort_by(|(rank1, s1), (rank2, s2)| {
+        (rank1, s1.name.len(), &s1.name).cmp(&(rank2, s2.name.len(), &s2.name))
+    });
+    matches.into_iter().map(|(_, symbol)| symbol).collect()
+}
+
+/// Handles workspace symbol request of the language server
+pub fn on_workspace_symbol_request(context: &Context, request: &Request, symbols: &Symbols) {
+    let parameters = serde_json::from_value::<WorkspaceSymbolParams>(request.params.clone())
+        .expect("could not deserialize workspace symbol request");
+
+    let result = workspace_symbols(symbols, &parameters.query);
+    let response = lsp_server::Response::new_ok(request.id.clone(), result);
+    if let Err(err) = context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response))
+    {
+        eprintln!("could not send workspace symbol response: {:?}", err);
+    }
+}
+
 #[cfg(test)]
 fn assert_use_def_with_doc_string(
     mod_symbols: &UseDefMap,
@@ -3677,3 +3865,31 @@ fn symbols_test() {
         None,
     );
 }
+
+#[test]
+fn workspace_symbols_test() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/symbols");
+
+    let (symbols_opt, _) = Symbolicator::get_symbols(path.as_path()).unwrap();
+    let symbols = symbols_opt.unwrap();
+
+    let found = workspace_symbols(&symbols, "somestruct");
+    let names: Vec<_> = found.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names[0], "SomeStruct");
+    assert_eq!(found[0].kind, SymbolKind::Struct);
+    assert_eq!(found[0].container_name.as_deref(), Some("Symbols::M1"));
+    assert_eq!(found[0].location.range.start, Position::new(2, 11));
+    assert!(names.contains(&"SomeOtherStruct"));
+
+    // fuzzy matches, including modules of dependencies
+    let names: Vec<_> = workspace_symbols(&symbols, "sot")
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert!(names.contains(&"SomeOtherStruct".to_string()));
+    assert!(workspace_symbols(&symbols, "vector")
+        .iter()
+        .any(|s| s.kind == SymbolKind::Module && s.container_name.as_deref() == Some("std")));
+    assert!(workspace_symbols(&symbols, "zzzz").is_empty());
+}
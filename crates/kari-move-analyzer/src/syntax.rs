@@ -0,0 +1,344 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides a token-level view of Move source files, for the language server features
+//! that need details of the source which the typed AST used by the symbolicator does not retain:
+//! `use` declarations, the module aliases they introduce and the blocks they are visible in.
+
+use crate::utils::offset_to_position;
+use lsp_types::Range;
+use move_command_line_common::files::FileHash;
+use move_compiler::parser::lexer::{Lexer, Tok};
+use std::ops::Range as Span;
+
+/// A token of a source file
+pub(crate) struct Token {
+    pub(crate) tok: Tok,
+    pub(crate) content: String,
+    /// Byte offset where the token starts
+    pub(crate) start: usize,
+}
+
+/// A `use` declaration (e.g., `use 0x1::M::{Self as N, f};`), in terms of indices into the tokens
+/// of its file
+pub(crate) struct UseDecl {
+    /// Tokens of the declaration, from `use` to `;`
+    pub(crate) tokens: Span<usize>,
+    /// Tokens the declaration is visible in, i.e., the block it is declared in
+    pub(crate) scope: Span<usize>,
+    /// Name of the module
+    pub(crate) module: usize,
+    /// Aliases introduced for the module
+    pub(crate) aliases: Vec<Alias>,
+    /// Names of the imported members
+    pub(crate) members: Vec<usize>,
+}
+
+/// A module alias introduced by a `use` declaration
+pub(crate) struct Alias {
+    /// The name following `as` if the alias is explicit, otherwise the module name or `Self` that
+    /// the alias is implicitly named after
+    pub(crate) token: usize,
+    pub(crate) explicit: bool,
+}
+
+impl Token {
+    pub(crate) fn end(&self) -> usize {
+        self.start + self.content.len()
+    }
+
+    pub(crate) fn range(&self, text: &str) -> Range {
+        Range {
+            start: offset_to_position(text, self.start),
+            end: offset_to_position(text, self.end()),
+        }
+    }
+}
+
+impl UseDecl {
+    /// The address of the module
+    pub(crate) fn address(&self) -> usize {
+        self.module - 2
+    }
+
+    pub(crate) fn alias_name<'t>(&self, tokens: &'t [Token], alias: &Alias) -> &'t str {
+        if alias.explicit {
+            &tokens[alias.token].content
+        } else {
+            &tokens[self.module].content
+        }
+    }
+}
+
+/// Lexes `buffer`, stopping at the first token that cannot be lexed
+pub(crate) fn tokens(buffer: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut lexer = Lexer::new(buffer, FileHash::new(buffer));
+    if lexer.advance().is_err() {
+        return tokens;
+    }
+    while lexer.peek() != Tok::EOF {
+        tokens.push(Token {
+            tok: lexer.peek(),
+            content: lexer.content().to_string(),
+            start: lexer.start_loc(),
+        });
+        if lexer.advance().is_err() {
+            break;
+        }
+    }
+    tokens
+}
+
+/// Finds the `use` declarations among `tokens`
+pub(crate) fn use_decls(tokens: &[Token]) -> Vec<UseDecl> {
+    let mut decls = vec![];
+    // starts of the blocks enclosing the current token
+    let mut blocks = vec![];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match tokens[idx].tok {
+            Tok::LBrace => blocks.push(idx + 1),
+            Tok::RBrace => {
+                blocks.pop();
+            }
+            Tok::Use => {
+                if let Some(mut decl) = use_decl(tokens, idx) {
+                    let start = blocks.last().copied().unwrap_or(0);
+                    decl.scope = start..block_end(tokens, start);
+                    idx = decl.tokens.end;
+                    decls.push(decl);
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    decls
+}
+
+/// Index of the token closing the block starting at `start` (or of the end of the file for the
+/// top-level "block")
+pub(crate) fn block_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    for (idx, token) in tokens.iter().enumerate().skip(start) {
+        match token.tok {
+            Tok::LBrace => depth += 1,
+            Tok::RBrace if depth == 0 => return idx,
+            Tok::RBrace => depth -= 1,
+            _ => (),
+        }
+    }
+    tokens.len()
+}
+
+/// Parses the `use` declaration whose `use` keyword is at index `start`
+pub(crate) fn use_decl(tokens: &[Token], start: usize) -> Option<UseDecl> {
+    let tok_at = |idx: usize| tokens.get(idx).map(|t| t.tok);
+    let mut idx = start + 1;
+    if !matches!(tok_at(idx), Some(Tok::Identifier | Tok::NumValue))
+        || tok_at(idx + 1) != Some(Tok::ColonColon)
+        || tok_at(idx + 2) != Some(Tok::Identifier)
+    {
+        return None;
+    }
+    let module = idx + 2;
+    idx += 3;
+
+    let mut aliases = vec![];
+    let mut members = vec![];
+    match tok_at(idx) {
+        Some(Tok::Semicolon) => aliases.push(Alias {
+            token: module,
+            explicit: false,
+        }),
+        Some(Tok::As) if tok_at(idx + 1) == Some(Tok::Identifier) => {
+            aliases.push(Alias {
+                token: idx + 1,
+                explicit: true,
+            });
+            idx += 2;
+        }
+        Some(Tok::ColonColon) if tok_at(idx + 1) == Some(Tok::LBrace) => {
+            idx += 2;
+            while tok_at(idx) != Some(Tok::RBrace) {
+                idx = use_member(tokens, idx, &mut aliases, &mut members)?;
+                match tok_at(idx) {
+                    Some(Tok::Comma) => idx += 1,
+                    Some(Tok::RBrace) => (),
+                    _ => return None,
+                }
+            }
+            idx += 1;
+        }
+        Some(Tok::ColonColon) => idx = use_member(tokens, idx + 1, &mut aliases, &mut members)?,
+        _ => return None,
+    }
+    if tok_at(idx) != Some(Tok::Semicolon) {
+        return None;
+    }
+    Some(UseDecl {
+        tokens: start..idx + 1,
+        scope: 0..0,
+        module,
+        aliases,
+        members,
+    })
+}
+
+/// Parses a member of a `use` declaration (`Self` or a member name, possibly followed by an
+/// alias) starting at index `start`, returning the index of the token following it
+pub(crate) fn use_member(
+    tokens: &[Token],
+    start: usize,
+    aliases: &mut Vec<Alias>,
+    members: &mut Vec<usize>,
+) -> Option<usize> {
+    let tok_at = |idx: usize| tokens.get(idx).map(|t| t.tok);
+    if tok_at(start) != Some(Tok::Identifier) {
+        return None;
+    }
+    let mut end = start + 1;
+    let mut member_alias = None;
+    if tok_at(end) == Some(Tok::As) {
+        if tok_at(end + 1) != Some(Tok::Identifier) {
+            return None;
+        }
+        member_alias = Some(end + 1);
+        end += 2;
+    }
+    if tokens[start].content == "Self" {
+        aliases.push(match member_alias {
+            Some(token) => Alias {
+                token,
+                explicit: true,
+            },
+            None => Alias {
+                token: start,
+                explicit: false,
+            },
+        });
+    } else {
+        members.push(start);
+    }
+    Some(end)
+}
+
+/// Indices of the tokens using the module alias `name` introduced by `decl`, i.e., identifiers
+/// `name` in the scope of the declaration followed by `::` (and not preceded by it, as the module
+/// name of a fully qualified access is not an alias)
+pub(crate) fn alias_uses(
+    tokens: &[Token],
+    decls: &[UseDecl],
+    decl: &UseDecl,
+    name: &str,
+) -> Vec<usize> {
+    decl.scope
+        .clone()
+        .filter(|idx| {
+            let token = &tokens[*idx];
+            token.tok == Tok::Identifier
+                && token.content == name
+                && tokens.get(idx + 1).map(|t| t.tok) == Some(Tok::ColonColon)
+                && (*idx == 0 || tokens[idx - 1].tok != Tok::ColonColon)
+                && !decls.iter().any(|d| d.tokens.contains(idx))
+        })
+        .collect()
+}
+
+/// Index of the name of the function called with the arguments opened by the `(` at index
+/// `open`, skipping over type arguments (e.g., `foo` in `foo<u64>(`)
+pub(crate) fn call_name(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut idx = open.checked_sub(1)?;
+    if matches!(tokens[idx].tok, Tok::Greater | Tok::GreaterGreater) {
+        let mut depth = 0;
+        loop {
+            match tokens[idx].tok {
+                Tok::Greater => depth += 1,
+                Tok::GreaterGreater => depth += 2,
+                Tok::Less => depth -= 1,
+                _ => (),
+            }
+            if depth == 0 {
+                break;
+            }
+            idx = idx.checked_sub(1)?;
+        }
+        idx = idx.checked_sub(1)?;
+    }
+    let is_call = tokens[idx].tok == Tok::Identifier
+        && (idx == 0 || !matches!(tokens[idx - 1].tok, Tok::Fun | Tok::Spec));
+    is_call.then_some(idx)
+}
+
+/// Tokens of each of the arguments of a call of the function named at index `name`, or `None`
+/// if the name is not followed by a complete argument list
+pub(crate) fn call_args(tokens: &[Token], name: usize) -> Option<Vec<Span<usize>>> {
+    let mut open = name + 1;
+    if tokens.get(open)?.tok == Tok::Less {
+        let mut depth = 0;
+        while open < tokens.len() {
+            match tokens[open].tok {
+                Tok::Less => depth += 1,
+                Tok::Greater => depth -= 1,
+                Tok::GreaterGreater => depth -= 2,
+                _ => (),
+            }
+            open += 1;
+            if depth <= 0 {
+                break;
+            }
+        }
+    }
+    if tokens.get(open)?.tok != Tok::LParen {
+        return None;
+    }
+
+    let mut args = vec![];
+    let mut arg_start = open + 1;
+    let mut depth = 0;
+    for (idx, token) in tokens.iter().enumerate().skip(open + 1) {
+        match token.tok {
+            Tok::LParen | Tok::LBracket | Tok::LBrace => depth += 1,
+            Tok::RParen if depth == 0 => {
+                if idx > arg_start {
+                    args.push(arg_start..idx);
+                }
+                return Some(args);
+            }
+            Tok::RParen | Tok::RBracket | Tok::RBrace => depth -= 1,
+            Tok::Comma if depth == 0 => {
+                args.push(arg_start..idx);
+                arg_start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Checks if the function declared by the `fun` at index `fun` has a `test` attribute (e.g.,
+/// `#[test]` or `#[test(account = @0x1)]`), among the attributes preceding its modifiers
+pub(crate) fn is_test_fun(tokens: &[Token], fun: usize) -> bool {
+    let mut idx = fun;
+    // skip modifiers, e.g., `public(friend) entry`
+    while idx > 0
+        && (matches!(
+            tokens[idx - 1].tok,
+            Tok::Public | Tok::Friend | Tok::LParen | Tok::RParen | Tok::Native
+        ) || tokens[idx - 1].content == "entry")
+    {
+        idx -= 1;
+    }
+    while idx > 0 && tokens[idx - 1].tok == Tok::RBracket {
+        let Some(open) = tokens[..idx].iter().rposition(|t| t.tok == Tok::NumSign) else {
+            return false;
+        };
+        if tokens[open..idx].iter().any(|t| t.content == "test") {
+            return true;
+        }
+        idx = open;
+    }
+    false
+}
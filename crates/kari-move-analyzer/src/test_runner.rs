@@ -0,0 +1,394 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the command that the test code lenses execute. Tests run with the unit
+//! test runner of `kari move test`, in a process of their own (the binary of the language server,
+//! started with `--run-tests`) since the VM reads whether to trace execution for coverage from
+//! the environment once per process. That process writes the outcome of each test as a line of
+//! JSON once it has run, which is sent to the client as a notification, and once the run is over
+//! a notification with its report follows and failing tests are marked with diagnostics. Runs with
+//! coverage also mark the source their tests do not cover with hints.
+//!
+//! Runs of a package share its trace and coverage map files, so they are made one at a time. The
+//! package is symbolicated first, for the locations of its tests, and tests only run if it
+//! compiles.
+
+use crate::{
+    context::Context,
+    symbols::{Symbolicator, Symbols, STACK_SIZE_BYTES},
+};
+use anyhow::{bail, Result};
+use kari_move::{
+    base::{
+        coverage::{source_coverage, ModuleCoverage},
+        test::{run_unit_tests_with_outcomes, UnitTestResult},
+    },
+    output::{TestOutcome, TestReport, TestStatus},
+};
+use lsp_server::{ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::Notification as _, Diagnostic, DiagnosticSeverity, ExecuteCommandParams,
+    MessageType, Position, PublishDiagnosticsParams, Range, ShowMessageParams, Url,
+};
+use move_core_types::{account_address::AccountAddress, language_storage::ModuleId};
+use move_stdlib::natives::{all_natives, nursery_natives, GasParameters, NurseryGasParameters};
+use move_unit_test::UnitTestingConfig;
+use move_vm_test_utils::gas_schedule::zero_cost_schedule;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+/// The command running tests, with a `TestRun` as its argument
+pub const RUN_TEST_COMMAND: &str = "kari-move-analyzer.runTest";
+/// The notification of the result of a test, with a `TestOutcome`
+pub const TEST_RESULT_NOTIFICATION: &str = "kari-move-analyzer/testResult";
+/// The notification of the end of a test run, with its `TestReport`
+pub const TEST_RUN_FINISHED_NOTIFICATION: &str = "kari-move-analyzer/testRunFinished";
+
+/// The option of the language server binary running the tests of a `TestRun`, given as JSON
+pub const RUN_TESTS_OPTION: &str = "--run-tests";
+
+/// Held by the test run in progress
+static TEST_RUNS: Mutex<()> = Mutex::new(());
+
+/// A run of the tests of a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestRun {
+    /// Root directory of the package
+    pub package: PathBuf,
+    /// Name of the module whose tests to run
+    pub module: String,
+    /// Name of the test to run, all tests of the module if none
+    pub test: Option<String>,
+    /// Whether to collect coverage, as with `kari move test --coverage`
+    pub coverage: bool,
+}
+
+impl TestRun {
+    /// Whether the run includes the test `name` of module `module_id`
+    fn selects(&self, module_id: &ModuleId, name: &str) -> bool {
+        module_id.name().as_str() == self.module
+            && self.test.as_ref().map_or(true, |test| test == name)
+    }
+}
+
+/// Reads the outcomes written by a test run process, one per line, reporting each with
+/// `on_result` as soon as its line is complete
+fn read_outcomes(
+    output: impl Read,
+    mut on_result: impl FnMut(TestOutcome),
+) -> Result<Vec<TestOutcome>> {
+    let mut outcomes = vec![];
+    for line in BufReader::new(output).lines() {
+        let outcome: TestOutcome = serde_json::from_str(&line?)?;
+        on_result(outcome.clone());
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+/// The diagnostics of the files of `symbols`, with those of `diagnostics` (e.g., compiler
+/// warnings) followed by errors at the names of the tests that failed in `report`
+fn test_diagnostics(
+    symbols: &Symbols,
+    mut diagnostics: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    report: &TestReport,
+) -> BTreeMap<PathBuf, Vec<Diagnostic>> {
+    for (path, mods) in &symbols.file_mods {
+        for mod_def in mods {
+            for fun_def in mod_def.functions.values() {
+                // test names are fully qualified, `<addr>::<module>::<fun>`
+                let suffix = format!("::{}::{}", mod_def.name.module, fun_def.name);
+                let Some(test) = report.tests.iter().find(|t| t.name.ends_with(&suffix)) else {
+                    continue;
+                };
+                if test.status == TestStatus::Pass {
+                    continue;
+                }
+                let message = match (&test.failure, test.status) {
+                    (Some(failure), _) => failure.clone(),
+                    (None, TestStatus::Timeout) => "Test timed out".to_string(),
+                    (None, _) => "Test failed".to_string(),
+                };
+                let start = fun_def.start;
+                let range = Range {
+                    start,
+                    end: Position::new(start.line, start.character + fun_def.name.len() as u32),
+                };
+                let mut diagnostic =
+                    Diagnostic::new_simple(range, format!("{}: {}", test.name, message));
+                diagnostic.severity = Some(DiagnosticSeverity::Error);
+                diagnostic.source = Some("kari move test".to_string());
+                diagnostics
+                    .entry(path.clone())
+                    .or_default()
+                    .push(diagnostic);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Runs the tests of `run`, reporting the result of each test with `on_result`, and returns the
+/// report of the run with the diagnostics of the files of the package
+pub fn run_tests(
+    run: &TestRun,
+    on_result: impl FnMut(TestOutcome),
+) -> Result<(TestReport, BTreeMap<PathBuf, Vec<Diagnostic>>)> {
+    let _run = TEST_RUNS.lock().unwrap_or_else(PoisonError::into_inner);
+    let (symbols, diagnostics) = Symbolicator::get_symbols(&run.package)?;
+    let compiles = !diagnostics
+        .values()
+        .flatten()
+        .any(|d| d.severity == Some(DiagnosticSeverity::Error));
+    let (Some(symbols), true) = (symbols, compiles) else {
+        bail!("the package does not compile");
+    };
+    let diagnostics = diagnostics
+        .into_iter()
+        .map(|(path, diags)| {
+            let path = PathBuf::from(path.as_str());
+            (dunce::canonicalize(&path).unwrap_or(path), diags)
+        })
+        .collect();
+
+    let mut child = Command::new(env::current_exe()?)
+        .arg(RUN_TESTS_OPTION)
+        .arg(serde_json::to_string(run)?)
+        .env_remove("MOVE_VM_TRACE")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let outcomes = read_outcomes(child.stdout.take().unwrap(), on_result)?;
+    let status = child.wait()?;
+    let success = match status.code() {
+        Some(0) => true,
+        Some(1) => false,
+        _ => bail!("the test runner exited with {}", status),
+    };
+
+    let report = TestReport::new(outcomes);
+    let mut diagnostics = test_diagnostics(&symbols, diagnostics, &report);
+    // the runner only saves the coverage of runs where all tests pass
+    if run.coverage && success {
+        match source_coverage(&run.package, move_package::BuildConfig::default()) {
+            Ok(coverage) => add_coverage_hints(&mut diagnostics, &coverage),
+            Err(err) => eprintln!("could not compute coverage: {:#}", err),
+        }
+    }
+    Ok((report, diagnostics))
+}
+
+/// Runs the tests of `run` in this process, writing the outcome of each test to stdout as a line
+/// of JSON and the compiler's output to stderr, and returns the exit code of the process: 0 if
+/// all tests pass, 1 if some fail and 2 if the tests could not be run
+pub fn run_tests_in_process(run: &TestRun) -> i32 {
+    let unit_test_config = UnitTestingConfig {
+        // warnings are reported by the diagnostics of the language server
+        ignore_compile_warnings: true,
+        ..UnitTestingConfig::default_with_bound(None)
+    };
+    let addr = AccountAddress::from_hex_literal("0x1").unwrap();
+    let natives = all_natives(addr, GasParameters::zeros())
+        .into_iter()
+        .chain(nursery_natives(addr, NurseryGasParameters::zeros()))
+        .collect();
+    let result = run_unit_tests_with_outcomes(
+        &run.package,
+        move_package::BuildConfig::default(),
+        unit_test_config,
+        natives,
+        Some(zero_cost_schedule()),
+        run.coverage,
+        |module_id, name| run.selects(module_id, name),
+        |outcome| {
+            let mut stdout = io::stdout().lock();
+            let _ = serde_json::to_writer(&mut stdout, &outcome);
+            let _ = writeln!(stdout);
+            let _ = stdout.flush();
+        },
+        &mut io::stderr(),
+    );
+    match result {
+        Ok(UnitTestResult::Success) => 0,
+        Ok(UnitTestResult::Failure) => 1,
+        Err(err) => {
+            eprintln!("could not run tests: {:#}", err);
+            2
+        }
+    }
+}
+
+/// Adds hints at the source that `coverage` reports as not covered by tests
+fn add_coverage_hints(
+    diagnostics: &mut BTreeMap<PathBuf, Vec<Diagnostic>>,
+    coverage: &[ModuleCoverage],
+) {
+    for module in coverage {
+        let path =
+            dunce::canonicalize(&module.source_path).unwrap_or_else(|_| module.source_path.clone());
+        let hints = diagnostics.entry(path).or_default();
+        for (line, characters) in &module.uncovered {
+            let range = Range {
+                start: Position::new(*line as u32, characters.start as u32),
+                end: Position::new(*line as u32, characters.end as u32),
+            };
+            let mut hint = Diagnostic::new_simple(range, "Not covered by tests".to_string());
+            hint.severity = Some(DiagnosticSeverity::Hint);
+            hint.source = Some("kari move test".to_string());
+            hints.push(hint);
+        }
+    }
+}
+
+/// Handles execute command request of the language server, running tests in the background
+pub fn on_execute_command_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<ExecuteCommandParams>(request.params.clone())
+        .expect("could not deserialize execute command request");
+
+    let run = match parameters.arguments.first() {
+        Some(argument) if parameters.command == RUN_TEST_COMMAND => {
+            serde_json::from_value::<TestRun>(argument.clone()).ok()
+        }
+        _ => None,
+    };
+    let response = match &run {
+        Some(_) => Response::new_ok(request.id.clone(), serde_json::Value::Null),
+        None => Response::new_err(
+            request.id.clone(),
+            ErrorCode::InvalidParams as i32,
+            format!("unknown command or arguments: {}", parameters.command),
+        ),
+    };
+    if let Err(err) = context.connection.sender.send(Message::Response(response)) {
+        eprintln!("could not send execute command response: {:?}", err);
+    }
+    let Some(run) = run else {
+        return;
+    };
+
+    let sender = context.connection.sender.clone();
+    thread::Builder::new()
+        .stack_size(STACK_SIZE_BYTES)
+        .spawn(move || {
+            let notify = |method: &str, params: serde_json::Value| {
+                let notification = Notification::new(method.to_string(), params);
+                if let Err(err) = sender.send(Message::Notification(notification)) {
+                    eprintln!("could not send test run notification: {:?}", err);
+                }
+            };
+            match run_tests(&run, |outcome| {
+                notify(
+                    TEST_RESULT_NOTIFICATION,
+                    serde_json::to_value(outcome).unwrap(),
+                )
+            }) {
+                Ok((report, diagnostics)) => {
+                    notify(
+                        TEST_RUN_FINISHED_NOTIFICATION,
+                        serde_json::to_value(report).unwrap(),
+                    );
+                    for (path, diagnostics) in diagnostics {
+                        let Ok(uri) = Url::from_file_path(&path) else {
+                            continue;
+                        };
+                        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+                        notify(
+                            lsp_types::notification::PublishDiagnostics::METHOD,
+                            serde_json::to_value(params).unwrap(),
+                        );
+                    }
+                }
+                Err(err) => {
+                    let params = ShowMessageParams {
+                        typ: MessageType::Error,
+                        message: format!("could not run tests: {:#}", err),
+                    };
+                    notify(
+                        lsp_types::notification::ShowMessage::METHOD,
+                        serde_json::to_value(params).unwrap(),
+                    );
+                }
+            }
+        })
+        .unwrap();
+}
+
+#[test]
+fn read_outcomes_test() {
+    let outcome = |name: &str, status| TestOutcome {
+        name: name.to_string(),
+        status,
+        gas_used: Some(3),
+        duration_ms: Some(1.5),
+        failure: (status != TestStatus::Pass).then(|| "aborted with code 1".to_string()),
+    };
+    let written = [
+        outcome("0x1::M::test_a", TestStatus::Pass),
+        outcome("0x1::M::test_b", TestStatus::Fail),
+    ];
+    let mut output = vec![];
+    for outcome in &written {
+        serde_json::to_writer(&mut output, outcome).unwrap();
+        writeln!(output).unwrap();
+    }
+    let mut results = vec![];
+    let outcomes = read_outcomes(output.as_slice(), |outcome| results.push(outcome)).unwrap();
+    assert_eq!(outcomes, written);
+    assert_eq!(results, written);
+
+    assert!(read_outcomes(&b"[ PASS    ] 0x1::M::test_a\n"[..], |_| ()).is_err());
+}
+
+#[test]
+fn test_run_selection_test() {
+    use move_core_types::identifier::Identifier;
+
+    let module = |name| ModuleId::new(AccountAddress::ONE, Identifier::new(name).unwrap());
+    let mut run = TestRun {
+        package: PathBuf::from("."),
+        module: "M".to_string(),
+        test: Some("test_f".to_string()),
+        coverage: false,
+    };
+    assert!(run.selects(&module("M"), "test_f"));
+    // names are matched exactly, not as substrings
+    assert!(!run.selects(&module("M"), "test_foo"));
+    assert!(!run.selects(&module("M2"), "test_f"));
+
+    run.test = None;
+    assert!(run.selects(&module("M"), "test_foo"));
+    assert!(!run.selects(&module("N"), "test_f"));
+}
+
+#[test]
+fn run_tests_in_process_compile_error_test() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("Move.toml"),
+        "[package]\nname = \"Broken\"\nversion = \"0.0.1\"\n\n[addresses]\nBroken = \"0xCAFE\"\n",
+    )
+    .unwrap();
+    std::fs::create_dir(dir.path().join("sources")).unwrap();
+    std::fs::write(
+        dir.path().join("sources/M.move"),
+        "module Broken::M {\n    #[test]\n    fun test_f() { let x: u64 = true; }\n}\n",
+    )
+    .unwrap();
+
+    // compilation errors are reported with the exit code, not by exiting the process
+    let run = TestRun {
+        package: dir.path().to_path_buf(),
+        module: "M".to_string(),
+        test: None,
+        coverage: false,
+    };
+    assert_eq!(run_tests_in_process(&run), 2);
+}
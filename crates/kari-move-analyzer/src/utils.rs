@@ -29,3 +29,67 @@ pub fn get_loc(
         Err(_) => None,
     }
 }
+
+/// Converts a character index into `line`, counted in UTF-16 code units as in the positions of the
+/// Language Server Protocol, to a byte offset, or returns `None` if it is past the end of the
+/// line. An index inside a surrogate pair maps to the character that follows it.
+pub fn line_offset(line: &str, character: u32) -> Option<usize> {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character as usize {
+            return Some(offset);
+        }
+        units += c.len_utf16();
+    }
+    (units >= character as usize).then_some(line.len())
+}
+
+/// Converts a line/character position (both 0-based, with characters counted in UTF-16 code units)
+/// to a byte offset into `text`, or returns `None` if the position is past the end of its line.
+pub fn position_to_offset(text: &str, position: Position) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    line_offset(line, position.character).map(|offset| line_start + offset)
+}
+
+/// Converts a byte offset into `text` to a line/character position, the inverse of
+/// `position_to_offset`.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+#[test]
+fn utf16_positions_test() {
+    // `é` is one UTF-16 code unit (two bytes) and `𝔸` two (four bytes)
+    let text = "let é𝔸 = 1;\nx";
+    let pair = text.find('𝔸').unwrap();
+    let equals = text.find('=').unwrap();
+    assert_eq!(position_to_offset(text, Position::new(0, 5)), Some(pair));
+    assert_eq!(position_to_offset(text, Position::new(0, 8)), Some(equals));
+    // inside the surrogate pair of `𝔸`
+    assert_eq!(
+        position_to_offset(text, Position::new(0, 6)),
+        Some(pair + 4)
+    );
+    assert_eq!(
+        position_to_offset(text, Position::new(0, 12)),
+        Some(text.find('\n').unwrap())
+    );
+    assert_eq!(position_to_offset(text, Position::new(0, 13)), None);
+    assert_eq!(
+        position_to_offset(text, Position::new(1, 1)),
+        Some(text.len())
+    );
+
+    assert_eq!(offset_to_position(text, pair), Position::new(0, 5));
+    assert_eq!(offset_to_position(text, equals), Position::new(0, 8));
+    assert_eq!(offset_to_position(text, text.len()), Position::new(1, 1));
+}
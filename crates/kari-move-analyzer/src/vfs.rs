@@ -9,36 +9,102 @@
 //! To manage these buffers, this module provides a "virtual file system" -- in reality, it is
 //! basically just a mapping from file identifier (this could be the file's path were it to be
 //! saved) to its textual contents.
+//!
+//! Clients send the changes made to a buffer as edits of ranges of it ("incremental" sync), with
+//! positions counting characters in UTF-16 code units as required by the Language Server Protocol.
+//! Each buffer keeps the offsets at which its lines start so that positions can be mapped to byte
+//! offsets without scanning the buffer from its beginning.
 
-use crate::symbols;
+use crate::{symbols, utils::line_offset};
 use lsp_server::Notification;
 use lsp_types::{
     notification::Notification as _, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
-    DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, Position, Range,
+    TextDocumentContentChangeEvent,
 };
 use std::path::PathBuf;
 
 /// A mapping from identifiers (file names, potentially, but not necessarily) to their contents.
 #[derive(Debug, Default)]
 pub struct VirtualFileSystem {
-    files: std::collections::HashMap<PathBuf, String>,
+    files: std::collections::HashMap<PathBuf, Buffer>,
+}
+
+/// Contents of a buffer along with the byte offsets its lines start at.
+#[derive(Debug)]
+struct Buffer {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Buffer {
+    fn new(text: &str) -> Self {
+        let mut buffer = Buffer {
+            text: text.to_string(),
+            line_starts: vec![0],
+        };
+        buffer.index_lines(0);
+        buffer
+    }
+
+    /// Recomputes the starts of the lines following the given one.
+    fn index_lines(&mut self, line: usize) {
+        self.line_starts.truncate(line + 1);
+        let start = self.line_starts[line];
+        self.line_starts.extend(
+            self.text[start..]
+                .match_indices('\n')
+                .map(|(offset, _)| start + offset + 1),
+        );
+    }
+
+    /// Converts a position to a byte offset into the buffer. Positions past the end of a line are
+    /// clamped to the end of that line, and positions past the last line to the end of the buffer.
+    fn offset(&self, position: Position) -> usize {
+        let line_start = match self.line_starts.get(position.line as usize) {
+            Some(line_start) => *line_start,
+            None => return self.text.len(),
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map_or(self.text.len(), |next_start| next_start - 1);
+        line_offset(&self.text[line_start..line_end], position.character)
+            .map_or(line_end, |offset| line_start + offset)
+    }
+
+    /// Replaces the text in the given range.
+    fn replace(&mut self, range: Range, text: &str) {
+        let start = self.offset(range.start);
+        let end = self.offset(range.end).max(start);
+        self.text.replace_range(start..end, text);
+        let line = (range.start.line as usize).min(self.line_starts.len() - 1);
+        self.index_lines(line);
+    }
 }
 
 impl VirtualFileSystem {
     /// Returns a reference to the buffer corresponding to the given identifier, or `None` if it
     /// is not present in the system.
     pub fn get(&self, identifier: &PathBuf) -> Option<&str> {
-        self.files.get(identifier).map(|s| s.as_str())
+        self.files.get(identifier).map(|b| b.text.as_str())
     }
 
     /// Inserts or overwrites the buffer corresponding to the given identifier.
-    ///
-    /// TODO: A far more efficient "virtual file system" would update its buffers with changes sent
-    /// from the client, instead of completely replacing them each time. The rust-analyzer has a
-    /// 'vfs' module that is capable of doing just that, but it is not published on crates.io. If
-    /// we could help get it published, we could use it here.
     pub fn update(&mut self, identifier: PathBuf, content: &str) {
-        self.files.insert(identifier, content.to_string());
+        self.files.insert(identifier, Buffer::new(content));
+    }
+
+    /// Applies a change sent by the client, either an edit of a range or the entire new contents,
+    /// to the buffer corresponding to the given identifier.
+    pub fn apply_change(&mut self, identifier: &PathBuf, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => match self.files.get_mut(identifier) {
+                Some(buffer) => buffer.replace(range, &change.text),
+                None => eprintln!("change to a buffer that is not open: {:?}", identifier),
+            },
+            None => self.update(identifier.clone(), &change.text),
+        }
     }
 
     /// Removes the buffer and its identifier from the system.
@@ -69,10 +135,10 @@ pub fn on_text_document_sync_notification(
             let parameters =
                 serde_json::from_value::<DidChangeTextDocumentParams>(notification.params.clone())
                     .expect("could not deserialize notification");
-            files.update(
-                parameters.text_document.uri.to_file_path().unwrap(),
-                &parameters.content_changes.last().unwrap().text,
-            );
+            let identifier = parameters.text_document.uri.to_file_path().unwrap();
+            for change in &parameters.content_changes {
+                files.apply_change(&identifier, change);
+            }
         }
         lsp_types::notification::DidSaveTextDocument::METHOD => {
             let parameters =
@@ -94,3 +160,53 @@ pub fn on_text_document_sync_notification(
     }
     eprintln!("text document notification handled");
 }
+
+#[test]
+fn incremental_changes_test() {
+    let path = PathBuf::from("M.move");
+    let change = |start: (u32, u32), end: (u32, u32), text: &str| TextDocumentContentChangeEvent {
+        range: Some(Range {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+        }),
+        range_length: None,
+        text: text.to_string(),
+    };
+
+    let mut vfs = VirtualFileSystem::default();
+    vfs.update(path.clone(), "module M {\n    // \u{1F600} é\n}\n");
+
+    // the emoji takes two UTF-16 code units (and four bytes)
+    vfs.apply_change(&path, &change((1, 10), (1, 11), "e"));
+    assert_eq!(vfs.get(&path), Some("module M {\n    // \u{1F600} e\n}\n"));
+
+    // edits spanning lines update the line index
+    vfs.apply_change(&path, &change((1, 4), (1, 11), "fun f() {}\n    e"));
+    assert_eq!(
+        vfs.get(&path),
+        Some("module M {\n    fun f() {}\n    e\n}\n")
+    );
+    vfs.apply_change(&path, &change((3, 0), (3, 1), "};"));
+    assert_eq!(
+        vfs.get(&path),
+        Some("module M {\n    fun f() {}\n    e\n};\n")
+    );
+
+    // positions past the end of a line or of the buffer are clamped
+    vfs.apply_change(&path, &change((2, 4), (2, 100), ""));
+    vfs.apply_change(&path, &change((10, 0), (10, 0), "// end"));
+    assert_eq!(
+        vfs.get(&path),
+        Some("module M {\n    fun f() {}\n    \n};\n// end")
+    );
+
+    vfs.apply_change(
+        &path,
+        &TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "module N {}".to_string(),
+        },
+    );
+    assert_eq!(vfs.get(&path), Some("module N {}"));
+}
@@ -0,0 +1,81 @@
+use crate::FormatError;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Name of the package manifest, whose `[fmt]` section configures the formatter
+pub const MANIFEST_FILE: &str = "Move.toml";
+/// Name of the standalone formatter configuration file, which takes precedence over the manifest
+pub const CONFIG_FILE: &str = ".movefmt.toml";
+
+/// Configuration of the formatter
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FmtConfig {
+    /// Number of spaces per indentation level
+    pub indent_size: usize,
+    /// Indent with tabs instead of spaces
+    pub use_tabs: bool,
+    /// Maximum number of consecutive blank lines to keep
+    pub max_blank_lines: usize,
+    /// Width beyond which a line is broken at the commas of a list in brackets
+    pub max_width: usize,
+}
+
+impl Default for FmtConfig {
+    fn default() -> Self {
+        Self {
+            indent_size: 4,
+            use_tabs: false,
+            max_blank_lines: 1,
+            max_width: 100,
+        }
+    }
+}
+
+impl FmtConfig {
+    /// Loads the configuration of the package at `package_root`: `.movefmt.toml` if it exists,
+    /// otherwise the `[fmt]` section of `Move.toml`, otherwise the defaults.
+    pub fn load(package_root: &Path) -> Result<Self, FormatError> {
+        let config_path = package_root.join(CONFIG_FILE);
+        if config_path.is_file() {
+            let contents = read(&config_path)?;
+            return toml::from_str(&contents).map_err(|e| FormatError::Config {
+                path: config_path,
+                message: e.message().to_string(),
+            });
+        }
+
+        let manifest_path = package_root.join(MANIFEST_FILE);
+        if !manifest_path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = read(&manifest_path)?;
+        let config_error = |message: String| FormatError::Config {
+            path: manifest_path.clone(),
+            message,
+        };
+        let mut manifest: toml::Table =
+            toml::from_str(&contents).map_err(|e| config_error(e.message().to_string()))?;
+        match manifest.remove("fmt") {
+            Some(section) => section
+                .try_into()
+                .map_err(|e: toml::de::Error| config_error(e.message().to_string())),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub(crate) fn indent(&self, level: usize) -> String {
+        if self.use_tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * self.indent_size)
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, FormatError> {
+    std::fs::read_to_string(path).map_err(|source| FormatError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
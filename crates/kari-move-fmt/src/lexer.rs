@@ -0,0 +1,137 @@
+//! A lexer for the formatter. Unlike the compiler's lexer, it keeps comments as tokens and does
+//! not interpret the source beyond splitting it into tokens.
+
+use crate::FormatError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// Identifiers and keywords
+    Ident,
+    /// Numbers and addresses, including hexadecimal and suffixed numbers
+    Number,
+    /// `b"..."` and `x"..."` literals
+    ByteString,
+    /// `// ...` and `/// ...` comments
+    LineComment,
+    /// `/* ... */` comments, which may span several lines
+    BlockComment,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token<'a> {
+    pub kind: Kind,
+    pub text: &'a str,
+    /// 0-based line the token starts on
+    pub line: usize,
+    /// 0-based line the token ends on
+    pub end_line: usize,
+}
+
+impl Token<'_> {
+    pub fn is(&self, text: &str) -> bool {
+        self.kind == Kind::Punct && self.text == text
+    }
+
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, Kind::LineComment | Kind::BlockComment)
+    }
+
+    pub fn is_opener(&self) -> bool {
+        self.is("(") || self.is("[") || self.is("{")
+    }
+
+    pub fn is_closer(&self) -> bool {
+        self.is(")") || self.is("]") || self.is("}")
+    }
+}
+
+/// Multi-character punctuation, longest first
+const PUNCTUATION: &[&str] = &[
+    "<==>", "==>", "::", "==", "!=", "<=", ">=", "&&", "||", "..", "<<", ">>",
+];
+
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token<'_>>, FormatError> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut line = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        let start = pos;
+        let start_line = line;
+        let kind = if c == b'\n' {
+            line += 1;
+            pos += 1;
+            continue;
+        } else if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        } else if source[pos..].starts_with("//") {
+            pos = source[pos..]
+                .find('\n')
+                .map_or(bytes.len(), |end| pos + end);
+            Kind::LineComment
+        } else if source[pos..].starts_with("/*") {
+            let Some(end) = source[pos + 2..].find("*/") else {
+                return Err(lex_error(start_line, "unterminated block comment"));
+            };
+            pos += 2 + end + 2;
+            line += source[start..pos].matches('\n').count();
+            Kind::BlockComment
+        } else if (c == b'b' || c == b'x') && bytes.get(pos + 1) == Some(&b'"') {
+            pos += 2;
+            loop {
+                match bytes.get(pos) {
+                    None => return Err(lex_error(start_line, "unterminated byte string")),
+                    Some(b'\\') => pos += 2,
+                    Some(b'"') => break,
+                    Some(b'\n') => {
+                        line += 1;
+                        pos += 1;
+                    }
+                    Some(_) => pos += 1,
+                }
+            }
+            pos += 1;
+            Kind::ByteString
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            pos += 1;
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            Kind::Ident
+        } else if c.is_ascii_digit() {
+            pos += 1;
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            Kind::Number
+        } else {
+            let len = PUNCTUATION
+                .iter()
+                .find(|p| source[pos..].starts_with(*p))
+                .map_or_else(
+                    || source[pos..].chars().next().unwrap().len_utf8(),
+                    |p| p.len(),
+                );
+            pos += len;
+            Kind::Punct
+        };
+        let text = source[start..pos].trim_end();
+        tokens.push(Token {
+            kind,
+            text,
+            line: start_line,
+            end_line: line,
+        });
+    }
+    Ok(tokens)
+}
+
+fn lex_error(line: usize, message: &str) -> FormatError {
+    FormatError::Lex {
+        line: line + 1,
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,214 @@
+//! A formatter for Move sources.
+//!
+//! The formatter works on the tokens of a source rather than on its AST, so that it keeps all
+//! comments, including doc comments, and can format sources that do not compile. It keeps the
+//! line breaks of the source and normalizes:
+//! - the indentation of each line, from the brackets enclosing it, with an additional level for
+//!   lines continuing an expression
+//! - the spacing between the tokens of a line
+//! - the width of lines: a line longer than `max_width` is broken at the commas of the first list
+//!   in brackets on it (e.g., the parameters of a function or the arguments of a call), putting
+//!   each element on a line of its own followed by a comma, and the lines it is broken into are
+//!   broken the same way. Lines with comments are not broken.
+//! - blank lines, of which at most `max_blank_lines` consecutive ones are kept, and none at the
+//!   start and end of a file or block
+//! - trailing whitespace and the final newline
+//!
+//! The formatter is configured by a `[fmt]` section in `Move.toml` or by `.movefmt.toml` (see
+//! [`FmtConfig`]).
+
+mod config;
+mod lexer;
+mod printer;
+
+pub use config::{FmtConfig, CONFIG_FILE, MANIFEST_FILE};
+
+use std::{ops::RangeInclusive, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("line {line}: {message}")]
+    Lex { line: usize, message: String },
+    #[error("invalid formatter configuration in {path}: {message}")]
+    Config { path: PathBuf, message: String },
+    #[error("could not read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Formats the Move source `source`
+pub fn format(source: &str, config: &FmtConfig) -> Result<String, FormatError> {
+    format_range(source, config, 0..=usize::MAX)
+}
+
+/// Formats the lines in `lines` (0-based) of the Move source `source`, returning the text that
+/// replaces them, each line ending with a newline. The lines are formatted as part of the whole
+/// source, so that their indentation is the one they have in the formatted source.
+pub fn format_range(
+    source: &str,
+    config: &FmtConfig,
+    lines: RangeInclusive<usize>,
+) -> Result<String, FormatError> {
+    let formatted = printer::format_lines(source, config)?;
+    let mut text = String::new();
+    for line in formatted
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| lines.contains(i))
+        .filter_map(|(_, line)| line.as_ref())
+    {
+        text.push_str(&line.text);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str) -> String {
+        format(source, &FmtConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_format_module() {
+        let source = r#"
+/// The module
+module 0x1::m{
+use std::vector;
+  use std::option::{ Self,Option };
+
+
+    struct S<T:copy+drop> has copy,drop{ a : u64, b:vector<T> }
+  // a constant
+  const E_FAIL : u64=1;
+
+    #[test(a=@0x1)]
+    public(friend) fun f<T>( s:&mut S<T>,x : u64 ):vector<vector<u8>>acquires R{
+        let v=vector::empty<u8>( ); /* trailing */
+        if(x<1&&!is_ok(&s.a)){ abort E_FAIL };
+        *&mut s.a=x*2;
+        let y = x
+        + 1;
+        assert!(y >= 1, 0);
+        let b = b"a{b" ;
+        vector[v]
+    }
+
+}
+"#;
+        let expected = r#"/// The module
+module 0x1::m {
+    use std::vector;
+    use std::option::{Self, Option};
+
+    struct S<T: copy + drop> has copy, drop { a: u64, b: vector<T> }
+    // a constant
+    const E_FAIL: u64 = 1;
+
+    #[test(a = @0x1)]
+    public(friend) fun f<T>(s: &mut S<T>, x: u64): vector<vector<u8>> acquires R {
+        let v = vector::empty<u8>(); /* trailing */
+        if (x < 1 && !is_ok(&s.a)) { abort E_FAIL };
+        *&mut s.a = x * 2;
+        let y = x
+            + 1;
+        assert!(y >= 1, 0);
+        let b = b"a{b";
+        vector[v]
+    }
+}
+"#;
+        assert_eq!(fmt(source), expected);
+        assert_eq!(fmt(expected), expected);
+    }
+
+    #[test]
+    fn test_format_brackets_and_comments() {
+        let source = "module 0x1::m {\nfun f() {\ng(S {\na: 1,\n}, (x\n- 1));\n/* a\n   b */ let c = 0;\nif (a &&\nb) {\nc = 1;\n};\n}\n}";
+        let expected = "module 0x1::m {\n    fun f() {\n        g(S {\n            a: 1,\n        }, (x\n                - 1));\n        /* a\n   b */ let c = 0;\n        if (a &&\n                b) {\n            c = 1;\n        };\n    }\n}\n";
+        assert_eq!(fmt(source), expected);
+        assert_eq!(fmt(expected), expected);
+    }
+
+    #[test]
+    fn test_format_range() {
+        let source = "module 0x1::m {\nfun f() {\nlet a=1;\n  let b=2;\n}\n}\n";
+        assert_eq!(
+            format_range(source, &FmtConfig::default(), 2..=2).unwrap(),
+            "        let a = 1;\n"
+        );
+    }
+
+    #[test]
+    fn test_format_config() {
+        let config = FmtConfig {
+            indent_size: 2,
+            use_tabs: false,
+            max_blank_lines: 0,
+            max_width: 100,
+        };
+        let source = "module 0x1::m {\nfun f() {}\n\nfun g() {}\n}\n";
+        assert_eq!(
+            format(source, &config).unwrap(),
+            "module 0x1::m {\n  fun f() {}\n  fun g() {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_width() {
+        let config = FmtConfig {
+            max_width: 40,
+            ..FmtConfig::default()
+        };
+        let source = "module 0x1::m {\nfun f(a: u64, b: vector<u8>, c: &mut S): u64 {\n\
+                      let s = S { a: g(a, b), b: vector[1, 2, 3], c };\nh(a, (b, c))\n}\n}\n";
+        let expected = "module 0x1::m {\n    fun f(\n        a: u64,\n        b: vector<u8>,\n\
+                        \x20       c: &mut S,\n    ): u64 {\n        let s = S {\n\
+                        \x20           a: g(a, b),\n            b: vector[1, 2, 3],\n\
+                        \x20           c,\n        };\n        h(a, (b, c))\n    }\n}\n";
+        assert_eq!(format(source, &config).unwrap(), expected);
+        assert_eq!(format(expected, &config).unwrap(), expected);
+        assert_eq!(
+            format_range(source, &config, 1..=1).unwrap(),
+            "    fun f(\n        a: u64,\n        b: vector<u8>,\n        c: &mut S,\n    ): u64 {\n"
+        );
+    }
+
+    #[test]
+    fn test_format_lex_error() {
+        assert!(matches!(
+            format("module 0x1::m {\n/* open", &FmtConfig::default()),
+            Err(FormatError::Lex { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(FmtConfig::load(dir.path()).unwrap(), FmtConfig::default());
+
+        std::fs::write(
+            dir.path().join(MANIFEST_FILE),
+            "[package]\nname = \"p\"\n\n[fmt]\nindent_size = 2\n",
+        )
+        .unwrap();
+        assert_eq!(FmtConfig::load(dir.path()).unwrap().indent_size, 2);
+
+        std::fs::write(dir.path().join(CONFIG_FILE), "use_tabs = true\n").unwrap();
+        let config = FmtConfig::load(dir.path()).unwrap();
+        assert!(config.use_tabs);
+        assert_eq!(config.indent_size, 4);
+
+        std::fs::write(dir.path().join(CONFIG_FILE), "indent = 2\n").unwrap();
+        assert!(matches!(
+            FmtConfig::load(dir.path()),
+            Err(FormatError::Config { .. })
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+//! The pretty-printer. It keeps the line structure of the source, i.e., the lines tokens are on
+//! and the comments between them, and normalizes the indentation of each line, the spacing
+//! between the tokens of a line and the blank lines between lines. Lines longer than the maximum
+//! width are broken at the commas of a list in brackets, one element per line.
+
+use crate::{
+    lexer::{tokenize, Kind, Token},
+    FmtConfig, FormatError,
+};
+
+/// Keywords followed by a space before a `(`, e.g., `if (`
+const KEYWORDS_BEFORE_PAREN: &[&str] = &["if", "while", "return", "abort", "let", "else", "in"];
+/// Keywords after which `&` and `*` are unary
+const KEYWORDS_BEFORE_UNARY: &[&str] = &["return", "abort", "else", "in", "copy", "move"];
+/// Tokens that continue an expression from the previous line when they start a line
+const CONTINUATION_STARTS: &[&str] = &[
+    "&&", "||", ".", "+", "-", "/", "%", "==", "!=", "<=", ">=", "==>", "<==>", "|", "^",
+];
+/// Tokens that continue an expression on the next line when they end a line
+const CONTINUATION_ENDS: &[&str] = &[
+    "=", "&&", "||", "+", "-", "*", "/", "%", "==", "!=", "<", ">", "<=", ">=", "==>", "<==>", "|",
+    "^", "&", "<<", ">>",
+];
+
+/// A formatted line, which is several lines of text if the source line was broken
+pub(crate) struct Line {
+    pub text: String,
+    blank: bool,
+    starts_with_closer: bool,
+    ends_with_opener: bool,
+}
+
+/// Formats `source`, returning the formatted line of each of its lines. Lines that are removed,
+/// i.e., excess blank lines and the lines covered by tokens spanning several lines (which are
+/// part of the formatted line they start on), are `None`.
+pub(crate) fn format_lines(
+    source: &str,
+    config: &FmtConfig,
+) -> Result<Vec<Option<Line>>, FormatError> {
+    let tokens = tokenize(source)?;
+    let angles = angle_brackets(&tokens);
+    let groups = use_groups(&tokens);
+    let mut lines: Vec<Option<Line>> = (0..source.lines().count()).map(|_| None).collect();
+
+    // the indentation level of the line each unclosed bracket was opened on
+    let mut brackets: Vec<usize> = vec![];
+    let mut prev_code: Option<usize> = None;
+    let mut idx = 0;
+    let mut next_line = 0;
+    while idx < tokens.len() {
+        let line = tokens[idx].line;
+        for blank in &mut lines[next_line..line] {
+            *blank = Some(Line {
+                text: String::new(),
+                blank: true,
+                starts_with_closer: false,
+                ends_with_opener: false,
+            });
+        }
+
+        // the tokens on this line, including those after a token ending on a later line
+        let mut end = idx + 1;
+        let mut end_line = tokens[idx].end_line;
+        while end < tokens.len() && tokens[end].line <= end_line {
+            end_line = end_line.max(tokens[end].end_line);
+            end += 1;
+        }
+        next_line = end_line + 1;
+
+        let first = &tokens[idx];
+        let mut level = if first.is_closer() {
+            brackets.last().copied().unwrap_or(0)
+        } else {
+            brackets.last().map_or(0, |level| level + 1)
+        };
+        let continues = prev_code.is_some_and(|prev| {
+            let prev = &tokens[prev];
+            !prev.is_opener()
+                && (ends_continuation(prev, angles[prev_code.unwrap()])
+                    || starts_continuation(first))
+        });
+        if !first.is_closer() && continues {
+            level += 1;
+        }
+
+        let printer = Printer {
+            tokens: &tokens,
+            angles: &angles,
+            groups: &groups,
+            config,
+        };
+        let text = printer.print(idx, end, level);
+        // brackets opened after closing a bracket are indented from the line that bracket was
+        // opened on, e.g., the block of an `if` whose condition spans several lines
+        let mut bracket_level = level;
+        for (i, token) in tokens.iter().enumerate().take(end).skip(idx) {
+            if token.is_opener() {
+                brackets.push(bracket_level);
+            } else if token.is_closer() {
+                bracket_level = brackets.pop().unwrap_or(0);
+            }
+            if !token.is_comment() {
+                prev_code = Some(i);
+            }
+        }
+        lines[line] = Some(Line {
+            text,
+            blank: false,
+            starts_with_closer: first.is_closer(),
+            ends_with_opener: tokens[end - 1].is_opener(),
+        });
+        idx = end;
+    }
+
+    remove_blank_lines(&mut lines, config.max_blank_lines);
+    Ok(lines)
+}
+
+struct Printer<'a> {
+    tokens: &'a [Token<'a>],
+    angles: &'a [bool],
+    groups: &'a [bool],
+    config: &'a FmtConfig,
+}
+
+impl Printer<'_> {
+    /// Prints the tokens `start..end` of a line at indentation `level`. If the line is longer
+    /// than `max_width`, the elements of the first list in brackets on it, the one opened first,
+    /// are put on lines of their own, one level deeper and each followed by a comma, and the
+    /// lines before and after the list are printed the same way.
+    fn print(&self, start: usize, end: usize, level: usize) -> String {
+        let text = self.print_flat(start, end, level);
+        if self.width(&text) <= self.config.max_width {
+            return text;
+        }
+        let Some((open, commas, close)) = self.find_list(start, end) else {
+            return text;
+        };
+        let mut text = self.print_flat(start, open + 1, level);
+        let mut element_start = open + 1;
+        for element_end in commas.into_iter().chain([close]) {
+            // a trailing comma ends the list
+            if element_start < element_end {
+                text.push('\n');
+                text.push_str(&self.print(element_start, element_end, level + 1));
+                text.push(',');
+            }
+            element_start = element_end + 1;
+        }
+        text.push('\n');
+        text.push_str(&self.print(close, end, level));
+        text
+    }
+
+    /// Prints the tokens `start..end` on a single line
+    fn print_flat(&self, start: usize, end: usize, level: usize) -> String {
+        let mut text = self.config.indent(level);
+        for i in start..end {
+            if i > start && space_between(self.tokens, self.angles, self.groups, i - 1, i) {
+                text.push(' ');
+            }
+            text.push_str(self.tokens[i].text);
+        }
+        text
+    }
+
+    fn width(&self, text: &str) -> usize {
+        let tabs = text.matches('\t').count();
+        text.chars().count() - tabs + tabs * self.config.indent_size
+    }
+
+    /// Finds the first list in brackets that is opened and closed within the tokens `start..end`
+    /// and has elements separated by commas, returning the indices of its opening bracket, of
+    /// its commas and of its closing bracket. Lines with comments or tokens spanning several
+    /// lines are not broken.
+    fn find_list(&self, start: usize, end: usize) -> Option<(usize, Vec<usize>, usize)> {
+        let unbreakable = |t: &Token| t.is_comment() || t.line != t.end_line;
+        if self.tokens[start..end].iter().any(unbreakable) {
+            return None;
+        }
+        for open in start..end {
+            if !self.tokens[open].is_opener() {
+                continue;
+            }
+            let mut depth = 0;
+            let mut commas = vec![];
+            for i in open + 1..end {
+                let token = &self.tokens[i];
+                if token.is_opener() {
+                    depth += 1;
+                } else if token.is_closer() && depth > 0 {
+                    depth -= 1;
+                } else if token.is_closer() {
+                    if !commas.is_empty() {
+                        return Some((open, commas, i));
+                    }
+                    break;
+                } else if token.is(",") && depth == 0 {
+                    commas.push(i);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Removes blank lines at the start and end of the file, after a line ending with an opening
+/// bracket, before a line starting with a closing bracket, and beyond `max` consecutive ones
+fn remove_blank_lines(lines: &mut [Option<Line>], max: usize) {
+    let mut prev: Option<usize> = None;
+    let mut run = 0;
+    for i in 0..lines.len() {
+        let Some(line) = &lines[i] else {
+            continue;
+        };
+        if !line.blank {
+            prev = Some(i);
+            run = 0;
+            continue;
+        }
+        run += 1;
+        let next = lines[i + 1..].iter().flatten().find(|l| !l.blank);
+        let keep = prev.is_some_and(|p| !lines[p].as_ref().unwrap().ends_with_opener)
+            && next.is_some_and(|n| !n.starts_with_closer)
+            && run <= max;
+        if !keep {
+            lines[i] = None;
+        }
+    }
+}
+
+/// Marks the `<`, `>` and `>>` tokens that are type argument or type parameter brackets, as
+/// opposed to comparisons and shifts
+fn angle_brackets(tokens: &[Token]) -> Vec<bool> {
+    let mut angles = vec![false; tokens.len()];
+    for i in 0..tokens.len() {
+        if !tokens[i].is("<") || i == 0 || tokens[i - 1].kind != Kind::Ident {
+            continue;
+        }
+        let mut depth = 1;
+        let mut brackets = vec![i];
+        for (j, token) in tokens.iter().enumerate().skip(i + 1) {
+            let allowed = match token.kind {
+                Kind::Ident | Kind::Number | Kind::LineComment | Kind::BlockComment => true,
+                Kind::ByteString => false,
+                Kind::Punct => ["::", ",", "&", ":", "+", "<", ">", ">>"].contains(&token.text),
+            };
+            if !allowed {
+                break;
+            }
+            match token.text {
+                "<" if token.kind == Kind::Punct => {
+                    depth += 1;
+                    brackets.push(j);
+                }
+                ">" if token.kind == Kind::Punct => depth -= 1,
+                ">>" => depth -= 2,
+                _ => continue,
+            }
+            if token.text != "<" {
+                brackets.push(j);
+            }
+            if depth <= 0 {
+                for b in brackets {
+                    angles[b] = true;
+                }
+                break;
+            }
+        }
+    }
+    angles
+}
+
+/// Marks the braces of the groups of `use` declarations, e.g., `use std::option::{Self, Option}`
+fn use_groups(tokens: &[Token]) -> Vec<bool> {
+    let mut groups = vec![false; tokens.len()];
+    let mut braces = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is("{") {
+            braces.push(i);
+        } else if token.is("}") {
+            if let Some(open) = braces.pop() {
+                if open > 0 && tokens[open - 1].is("::") {
+                    groups[open] = true;
+                    groups[i] = true;
+                }
+            }
+        }
+    }
+    groups
+}
+
+fn ends_continuation(token: &Token, angle: bool) -> bool {
+    token.kind == Kind::Punct && !angle && CONTINUATION_ENDS.contains(&token.text)
+        || token.kind == Kind::Ident && token.text == "as"
+}
+
+fn starts_continuation(token: &Token) -> bool {
+    token.kind == Kind::Punct && CONTINUATION_STARTS.contains(&token.text)
+        || token.kind == Kind::Ident && token.text == "as"
+}
+
+/// Checks if the `&` or `*` at index `idx` is a unary operator (a reference or a dereference)
+fn is_unary(tokens: &[Token], angles: &[bool], idx: usize) -> bool {
+    let Some(prev) = tokens[..idx].iter().rposition(|t| !t.is_comment()) else {
+        return true;
+    };
+    let token = &tokens[prev];
+    match token.kind {
+        Kind::Punct => !(token.is(")") || token.is("]") || angles[prev]),
+        Kind::Ident => KEYWORDS_BEFORE_UNARY.contains(&token.text),
+        _ => false,
+    }
+}
+
+/// Checks if a space separates the tokens at indices `prev` and `cur` on the same line
+fn space_between(
+    tokens: &[Token],
+    angles: &[bool],
+    groups: &[bool],
+    prev: usize,
+    cur: usize,
+) -> bool {
+    let (p, c) = (&tokens[prev], &tokens[cur]);
+    if p.is_comment() || c.is_comment() {
+        return true;
+    }
+    if p.is("(") || p.is("[") || p.is("::") || p.is(".") || p.is("..") || p.is("@") || p.is("#") {
+        return false;
+    }
+    if c.is(")") || c.is("]") || c.is(",") || c.is(";") || c.is(":") || c.is("::") {
+        return false;
+    }
+    if c.is(".") || c.is("..") {
+        return false;
+    }
+    if p.is("!") || (p.is("&") || p.is("*")) && is_unary(tokens, angles, prev) {
+        return false;
+    }
+    if c.is("!") && p.kind == Kind::Ident {
+        return false;
+    }
+    if angles[cur] || angles[prev] && p.is("<") {
+        return false;
+    }
+    if c.is("(") {
+        return !(p.kind == Kind::Ident && !KEYWORDS_BEFORE_PAREN.contains(&p.text)
+            || angles[prev]);
+    }
+    if c.is("[") {
+        return !(p.kind == Kind::Ident || p.is(")") || p.is("]"));
+    }
+    if p.is("{") && (c.is("}") || groups[prev]) || c.is("}") && groups[cur] {
+        return false;
+    }
+    true
+}
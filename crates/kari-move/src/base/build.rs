@@ -2,8 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::reroot_path;
+use crate::output::BuildReport;
+use anyhow::anyhow;
 use clap::*;
+use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule};
+use move_core_types::account_address::AccountAddress;
 use move_package::{Architecture, BuildConfig};
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
 /// Build the package at `path`. If no path is provided defaults to current directory.
@@ -39,4 +44,82 @@ impl Build {
         }
         Ok(())
     }
+
+    /// Build like `execute`, printing a `BuildReport` instead of the compiler
+    /// output. Compiler diagnostics are returned as the error on failure.
+    pub fn execute_json(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
+        let rerooted_path = reroot_path(path)?;
+        let mut diagnostics = Vec::new();
+        let package = config
+            .compile_package(&rerooted_path, &mut diagnostics)
+            .map_err(|e| anyhow!("{}\n{}", String::from_utf8_lossy(&diagnostics).trim(), e))?;
+
+        let report = BuildReport {
+            package: package.compiled_package_info.package_name.to_string(),
+            modules: package
+                .root_modules()
+                .filter_map(|unit| match &unit.unit {
+                    CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                        Some(module.self_id().short_str_lossless())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            scripts: package
+                .scripts()
+                .map(|unit| unit.unit.name().to_string())
+                .collect(),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        Ok(())
+    }
+}
+
+/// Bytecode of a package, as sent in a publish transaction.
+pub struct PackageBytecode {
+    pub modules: Vec<Vec<u8>>,
+    /// Addresses of the packages the modules depend on.
+    pub dependencies: Vec<AccountAddress>,
+}
+
+/// Compile the package at `path` for publishing. Compiler diagnostics are
+/// returned as the error on failure.
+pub fn compile_for_publish(
+    path: Option<PathBuf>,
+    config: BuildConfig,
+) -> anyhow::Result<PackageBytecode> {
+    let rerooted_path = reroot_path(path)?;
+    let mut diagnostics = Vec::new();
+    let package = config
+        .compile_package(&rerooted_path, &mut diagnostics)
+        .map_err(|e| anyhow!("{}\n{}", String::from_utf8_lossy(&diagnostics).trim(), e))?;
+
+    let mut modules = Vec::new();
+    let mut own_addresses = BTreeSet::new();
+    for unit in package.root_modules() {
+        if let CompiledUnit::Module(NamedCompiledModule { module, .. }) = &unit.unit {
+            own_addresses.insert(*module.self_id().address());
+            modules.push(unit.unit.serialize(None));
+        }
+    }
+    if modules.is_empty() {
+        anyhow::bail!("Package has no modules to publish");
+    }
+    let dependencies = package
+        .deps_compiled_units
+        .iter()
+        .filter_map(|(_, unit)| match &unit.unit {
+            CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                Some(*module.self_id().address())
+            }
+            _ => None,
+        })
+        .filter(|address| !own_addresses.contains(address))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    Ok(PackageBytecode {
+        modules,
+        dependencies,
+    })
 }
@@ -3,14 +3,29 @@
 
 use super::reroot_path;
 use clap::*;
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{CodeOffset, CompiledModule, FunctionDefinitionIndex, TableIndex},
+};
+use move_bytecode_source_map::source_map::SourceMap;
+use move_command_line_common::files::MOVE_COVERAGE_MAP_EXTENSION;
 use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule};
 use move_coverage::{
-    coverage_map::CoverageMap, format_csv_summary, format_human_summary,
-    source_coverage::SourceCoverageBuilder, summary::summarize_inst_cov,
+    coverage_map::{CoverageMap, ExecCoverageMap},
+    format_csv_summary, format_human_summary,
+    source_coverage::{SourceCoverageBuilder, StringSegment},
+    summary::summarize_inst_cov,
 };
 use move_disassembler::disassembler::Disassembler;
-use move_package::BuildConfig;
-use std::path::PathBuf;
+use move_package::{compilation::compiled_package::CompiledPackage, BuildConfig};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[derive(Parser)]
 pub enum CoverageSummaryOptions {
@@ -36,6 +51,24 @@ pub enum CoverageSummaryOptions {
         #[clap(long = "module")]
         module_name: String,
     },
+    /// Export the line coverage of all modules in this package, for editors and CI dashboards
+    #[clap(name = "export")]
+    Export {
+        #[clap(long = "format", value_enum, default_value_t = CoverageFormat::Lcov)]
+        format: CoverageFormat,
+        /// File to write the report to instead of stdout
+        #[clap(long = "output", short = 'o')]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum CoverageFormat {
+    /// LCOV tracefile, as produced by `geninfo`
+    Lcov,
+    /// Cobertura XML report
+    Cobertura,
 }
 
 /// Inspect test coverage for this package. A previous test run with the `--coverage` flag must
@@ -50,17 +83,9 @@ pub struct Coverage {
 impl Coverage {
     pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
         let path = reroot_path(path)?;
-        let coverage_map = CoverageMap::from_binary_file(path.join(".coverage_map.mvcov"))?;
-        let package = config.compile_package(&path, &mut Vec::new())?;
-        let modules: Vec<_> = package
-            .root_modules()
-            .filter_map(|unit| match &unit.unit {
-                CompiledUnit::Module(NamedCompiledModule { module, .. }) => Some(module.clone()),
-                _ => None,
-            })
-            .collect();
         match self.options {
             CoverageSummaryOptions::Source { module_name } => {
+                let (coverage_map, package) = last_test_coverage(&path, config)?;
                 let unit = package.get_module_by_name_from_root(&module_name)?;
                 let source_path = &unit.source_path;
                 let (module, source_map) = match &unit.unit {
@@ -80,6 +105,16 @@ impl Coverage {
                 output_csv,
                 ..
             } => {
+                let (coverage_map, package) = last_test_coverage(&path, config)?;
+                let modules: Vec<_> = package
+                    .root_modules()
+                    .filter_map(|unit| match &unit.unit {
+                        CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                            Some(module.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
                 let coverage_map = coverage_map.to_unified_exec_map();
                 if output_csv {
                     format_csv_summary(
@@ -99,12 +134,411 @@ impl Coverage {
                 }
             }
             CoverageSummaryOptions::Bytecode { module_name } => {
+                let (coverage_map, package) = last_test_coverage(&path, config)?;
                 let unit = package.get_module_by_name_from_root(&module_name)?;
                 let mut disassembler = Disassembler::from_unit(&unit.unit);
                 disassembler.add_coverage_map(coverage_map.to_unified_exec_map());
                 println!("{}", disassembler.disassemble()?);
             }
+            CoverageSummaryOptions::Export { format, output } => {
+                let root = path.canonicalize()?;
+                let coverage = source_coverage(&root, config)?;
+                let mut report = Vec::new();
+                match format {
+                    CoverageFormat::Lcov => format_lcov(&coverage, &mut report)?,
+                    CoverageFormat::Cobertura => format_cobertura(&coverage, &root, &mut report)?,
+                }
+                match output {
+                    Some(output) => fs::write(output, report)?,
+                    None => io::stdout().write_all(&report)?,
+                }
+            }
         }
         Ok(())
     }
 }
+
+/// The coverage map of the last test run with coverage of the package at `path`, and the package
+fn last_test_coverage(
+    path: &Path,
+    config: BuildConfig,
+) -> anyhow::Result<(CoverageMap, CompiledPackage)> {
+    let coverage_map = CoverageMap::from_binary_file(path.join(".coverage_map.mvcov"))?;
+    let package = config.compile_package(path, &mut Vec::new())?;
+    Ok((coverage_map, package))
+}
+
+/// Line coverage of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub name: String,
+    /// Line of the function declaration (1-based)
+    pub line: usize,
+    /// Number of calls of the function
+    pub hits: u64,
+    /// Execution counts of the lines with code, by line (1-based)
+    pub lines: BTreeMap<usize, u64>,
+}
+
+/// Coverage of a module against its source code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleCoverage {
+    /// Fully qualified name, `<address>::<module>`
+    pub name: String,
+    pub source_path: PathBuf,
+    /// Functions with code, i.e., other than native functions
+    pub functions: Vec<FunctionCoverage>,
+    /// Source not covered by tests, as the (0-based) line and character range of each segment
+    /// that `coverage source` displays as uncovered
+    pub uncovered: Vec<(usize, Range<usize>)>,
+}
+
+impl ModuleCoverage {
+    /// Execution counts of the lines with code of all functions, by line (1-based)
+    pub fn lines(&self) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+        for function in &self.functions {
+            for (line, hits) in &function.lines {
+                let count = lines.entry(*line).or_insert(0);
+                *count = (*count).max(*hits);
+            }
+        }
+        lines
+    }
+}
+
+/// Computes the coverage of the root modules of the package at `path` from the coverage map of
+/// its last test run with coverage. The package is compiled with `config` in test mode, like the
+/// tests were.
+pub fn source_coverage(
+    path: &Path,
+    mut config: BuildConfig,
+) -> anyhow::Result<Vec<ModuleCoverage>> {
+    let coverage_map = CoverageMap::from_binary_file(
+        path.join(".coverage_map")
+            .with_extension(MOVE_COVERAGE_MAP_EXTENSION),
+    )?;
+    config.test_mode = true;
+    config.dev_mode = true;
+    let package = config.compile_package(path, &mut Vec::new())?;
+    let exec_map = coverage_map.to_unified_exec_map();
+
+    let mut coverage = vec![];
+    for unit in package.root_modules() {
+        let CompiledUnit::Module(NamedCompiledModule {
+            module, source_map, ..
+        }) = &unit.unit
+        else {
+            continue;
+        };
+        let source = fs::read_to_string(&unit.source_path)?;
+        let id = module.self_id();
+        let uncovered = SourceCoverageBuilder::new(module, &coverage_map, source_map)
+            .compute_source_coverage(&unit.source_path)
+            .annotated_lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line, segments)| uncovered_segments(segments).map(move |s| (line, s)))
+            .collect();
+        coverage.push(ModuleCoverage {
+            name: format!("0x{}::{}", id.address().short_str_lossless(), id.name()),
+            source_path: unit.source_path.clone(),
+            functions: function_coverage(module, source_map, &exec_map, &source),
+            uncovered,
+        });
+    }
+    Ok(coverage)
+}
+
+/// Character ranges of the uncovered segments of a line, without surrounding whitespace
+fn uncovered_segments(segments: &[StringSegment]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = 0;
+    segments.iter().filter_map(move |segment| {
+        let (text, covered) = match segment {
+            StringSegment::Covered(text) => (text, true),
+            StringSegment::Uncovered(text) => (text, false),
+        };
+        let segment_start = start;
+        start += text.chars().count();
+        let code = text.trim();
+        if covered || code.is_empty() {
+            return None;
+        }
+        let leading = text.chars().take_while(|c| c.is_whitespace()).count();
+        let start = segment_start + leading;
+        Some(start..start + code.chars().count())
+    })
+}
+
+/// Line coverage of the functions of a module, mapping the execution counts of their
+/// instructions to the lines the source map places them on
+fn function_coverage(
+    module: &CompiledModule,
+    source_map: &SourceMap,
+    exec_map: &ExecCoverageMap,
+    source: &str,
+) -> Vec<FunctionCoverage> {
+    let line_of = |offset: u32| {
+        source.as_bytes()[..(offset as usize).min(source.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count()
+            + 1
+    };
+    let id = module.self_id();
+    let module_map = exec_map
+        .module_maps
+        .get(&(*id.address(), id.name().to_owned()));
+
+    let mut functions = vec![];
+    for (idx, fdef) in module.function_defs().iter().enumerate() {
+        let Some(code) = &fdef.code else {
+            continue;
+        };
+        let name = module.identifier_at(module.function_handle_at(fdef.function).name);
+        let counts = module_map.and_then(|m| m.function_maps.get(name));
+        let fdef_idx = FunctionDefinitionIndex(idx as TableIndex);
+        let Ok(function_map) = source_map.get_function_source_map(fdef_idx) else {
+            continue;
+        };
+
+        let mut lines = BTreeMap::new();
+        for pc in 0..code.code.len() {
+            let Ok(loc) = source_map.get_code_location(fdef_idx, pc as CodeOffset) else {
+                continue;
+            };
+            let hits = counts
+                .and_then(|c| c.get(&(pc as u64)))
+                .copied()
+                .unwrap_or(0);
+            let count = lines.entry(line_of(loc.start())).or_insert(0);
+            *count = (*count).max(hits);
+        }
+        functions.push(FunctionCoverage {
+            name: name.to_string(),
+            line: line_of(function_map.definition_location.start()),
+            // the first instruction runs once per call
+            hits: counts.and_then(|c| c.get(&0)).copied().unwrap_or(0),
+            lines,
+        });
+    }
+    functions
+}
+
+/// Writes `coverage` as an LCOV tracefile, with a record per source file
+pub fn format_lcov<W: Write>(coverage: &[ModuleCoverage], w: &mut W) -> io::Result<()> {
+    let mut files: BTreeMap<&Path, Vec<&ModuleCoverage>> = BTreeMap::new();
+    for module in coverage {
+        files.entry(&module.source_path).or_default().push(module);
+    }
+
+    writeln!(w, "TN:")?;
+    for (path, modules) in files {
+        writeln!(w, "SF:{}", path.display())?;
+        let functions: Vec<(String, &FunctionCoverage)> = modules
+            .iter()
+            .flat_map(|m| {
+                let module_name = m.name.rsplit("::").next().unwrap_or(&m.name);
+                m.functions
+                    .iter()
+                    .map(move |f| (format!("{}::{}", module_name, f.name), f))
+            })
+            .collect();
+        for (name, function) in &functions {
+            writeln!(w, "FN:{},{}", function.line, name)?;
+        }
+        for (name, function) in &functions {
+            writeln!(w, "FNDA:{},{}", function.hits, name)?;
+        }
+        writeln!(w, "FNF:{}", functions.len())?;
+        writeln!(
+            w,
+            "FNH:{}",
+            functions.iter().filter(|(_, f)| f.hits > 0).count()
+        )?;
+
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+        for module in modules {
+            for (line, hits) in module.lines() {
+                let count = lines.entry(line).or_insert(0);
+                *count = (*count).max(hits);
+            }
+        }
+        for (line, hits) in &lines {
+            writeln!(w, "DA:{},{}", line, hits)?;
+        }
+        writeln!(w, "LF:{}", lines.len())?;
+        writeln!(w, "LH:{}", lines.values().filter(|hits| **hits > 0).count())?;
+        writeln!(w, "end_of_record")?;
+    }
+    Ok(())
+}
+
+fn line_rate(lines: &BTreeMap<usize, u64>) -> f64 {
+    if lines.is_empty() {
+        return 1.0;
+    }
+    lines.values().filter(|hits| **hits > 0).count() as f64 / lines.len() as f64
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_cobertura_lines<W: Write>(
+    w: &mut W,
+    lines: &BTreeMap<usize, u64>,
+    indent: &str,
+) -> io::Result<()> {
+    writeln!(w, "{}<lines>", indent)?;
+    for (line, hits) in lines {
+        writeln!(
+            w,
+            "{}  <line number=\"{}\" hits=\"{}\"/>",
+            indent, line, hits
+        )?;
+    }
+    writeln!(w, "{}</lines>", indent)
+}
+
+/// Writes `coverage` as a Cobertura XML report, with a class per module and file names relative
+/// to `root`, the root directory of the package
+pub fn format_cobertura<W: Write>(
+    coverage: &[ModuleCoverage],
+    root: &Path,
+    w: &mut W,
+) -> io::Result<()> {
+    let all_lines: Vec<BTreeMap<usize, u64>> = coverage.iter().map(|m| m.lines()).collect();
+    let lines_valid: usize = all_lines.iter().map(|lines| lines.len()).sum();
+    let lines_covered: usize = all_lines
+        .iter()
+        .map(|lines| lines.values().filter(|hits| **hits > 0).count())
+        .sum();
+    let rate = if lines_valid == 0 {
+        1.0
+    } else {
+        lines_covered as f64 / lines_valid as f64
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    writeln!(w, "<?xml version=\"1.0\" ?>")?;
+    writeln!(
+        w,
+        "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">"
+    )?;
+    writeln!(
+        w,
+        "<coverage line-rate=\"{:.4}\" branch-rate=\"0\" lines-covered=\"{}\" lines-valid=\"{}\" \
+         branches-covered=\"0\" branches-valid=\"0\" complexity=\"0\" version=\"{}\" \
+         timestamp=\"{}\">",
+        rate,
+        lines_covered,
+        lines_valid,
+        env!("CARGO_PKG_VERSION"),
+        timestamp
+    )?;
+    writeln!(w, "  <sources>")?;
+    writeln!(
+        w,
+        "    <source>{}</source>",
+        xml_escape(&root.display().to_string())
+    )?;
+    writeln!(w, "  </sources>")?;
+    writeln!(w, "  <packages>")?;
+    let package_name = root
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+    writeln!(
+        w,
+        "    <package name=\"{}\" line-rate=\"{:.4}\" branch-rate=\"0\" complexity=\"0\">",
+        xml_escape(&package_name),
+        rate
+    )?;
+    writeln!(w, "      <classes>")?;
+    for (module, lines) in coverage.iter().zip(&all_lines) {
+        let filename = module
+            .source_path
+            .strip_prefix(root)
+            .unwrap_or(&module.source_path);
+        writeln!(
+            w,
+            "        <class name=\"{}\" filename=\"{}\" line-rate=\"{:.4}\" branch-rate=\"0\" \
+             complexity=\"0\">",
+            xml_escape(&module.name),
+            xml_escape(&filename.display().to_string()),
+            line_rate(lines)
+        )?;
+        writeln!(w, "          <methods>")?;
+        for function in &module.functions {
+            writeln!(
+                w,
+                "            <method name=\"{}\" signature=\"\" line-rate=\"{:.4}\" \
+                 branch-rate=\"0\" complexity=\"0\">",
+                xml_escape(&function.name),
+                line_rate(&function.lines)
+            )?;
+            write_cobertura_lines(w, &function.lines, "              ")?;
+            writeln!(w, "            </method>")?;
+        }
+        writeln!(w, "          </methods>")?;
+        write_cobertura_lines(w, lines, "          ")?;
+        writeln!(w, "        </class>")?;
+    }
+    writeln!(w, "      </classes>")?;
+    writeln!(w, "    </package>")?;
+    writeln!(w, "  </packages>")?;
+    writeln!(w, "</coverage>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage() -> Vec<ModuleCoverage> {
+        let function = |name: &str, line, hits, lines: &[(usize, u64)]| FunctionCoverage {
+            name: name.to_string(),
+            line,
+            hits,
+            lines: lines.iter().copied().collect(),
+        };
+        vec![ModuleCoverage {
+            name: "0x2::coin".to_string(),
+            source_path: PathBuf::from("/pkg/sources/coin.move"),
+            functions: vec![
+                function("value", 3, 2, &[(4, 2)]),
+                function("split", 7, 0, &[(8, 0), (9, 0)]),
+            ],
+            uncovered: vec![(7, 8..20), (8, 8..14)],
+        }]
+    }
+
+    #[test]
+    fn test_format_lcov() {
+        let mut out = Vec::new();
+        format_lcov(&coverage(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TN:\nSF:/pkg/sources/coin.move\nFN:3,coin::value\nFN:7,coin::split\n\
+             FNDA:2,coin::value\nFNDA:0,coin::split\nFNF:2\nFNH:1\n\
+             DA:4,2\nDA:8,0\nDA:9,0\nLF:3\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_format_cobertura() {
+        let mut out = Vec::new();
+        format_cobertura(&coverage(), Path::new("/pkg"), &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("lines-covered=\"1\" lines-valid=\"3\""));
+        assert!(xml.contains("<package name=\"pkg\" line-rate=\"0.3333\""));
+        assert!(xml.contains("<class name=\"0x2::coin\" filename=\"sources/coin.move\""));
+        assert!(xml.contains("<method name=\"split\" signature=\"\" line-rate=\"0.0000\""));
+        assert!(xml.contains("<line number=\"4\" hits=\"2\"/>"));
+        assert!(xml.trim_end().ends_with("</coverage>"));
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::reroot_path;
+use clap::*;
+use kari_move_fmt::FmtConfig;
+use move_package::source_package::layout::SourcePackageLayout;
+use std::path::{Path, PathBuf};
+
+/// Format the Move source files of the package. The formatter is configured by the `[fmt]`
+/// section of `Move.toml` or by `.movefmt.toml`.
+#[derive(Parser)]
+#[clap(name = "fmt")]
+pub struct Fmt {
+    /// Check that the source files are formatted instead of formatting them, failing if any is not
+    #[clap(long = "check")]
+    pub check: bool,
+}
+
+impl Fmt {
+    pub fn execute(self, path: Option<PathBuf>) -> anyhow::Result<()> {
+        let rerooted_path = reroot_path(path)?;
+        let config = FmtConfig::load(&rerooted_path)?;
+
+        let mut unformatted = vec![];
+        for file in source_files(&rerooted_path) {
+            let source = std::fs::read_to_string(&file)?;
+            let formatted = kari_move_fmt::format(&source, &config)
+                .map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+            if formatted == source {
+                continue;
+            }
+            if self.check {
+                println!("{} is not formatted", file.display());
+                unformatted.push(file);
+            } else {
+                std::fs::write(&file, formatted)?;
+                println!("Formatted {}", file.display());
+            }
+        }
+
+        if !unformatted.is_empty() {
+            anyhow::bail!(
+                "{} file(s) are not formatted, run `kari move fmt` to format them",
+                unformatted.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the Move files in the source directories of the package at `root`
+fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for dir in [
+        SourcePackageLayout::Sources,
+        SourcePackageLayout::Tests,
+        SourcePackageLayout::Scripts,
+        SourcePackageLayout::Examples,
+    ] {
+        let dir = root.join(dir.path());
+        if !dir.is_dir() {
+            continue;
+        }
+        files.extend(
+            walkdir::WalkDir::new(dir)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(|entry| entry.into_path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "move")),
+        );
+    }
+    files
+}
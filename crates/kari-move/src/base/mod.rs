@@ -6,10 +6,12 @@ pub mod coverage;
 pub mod disassemble;
 pub mod docgen;
 pub mod errmap;
+pub mod fmt;
 pub mod info;
 pub mod new;
 pub mod prove;
 pub mod test;
+pub mod upgrade;
 
 use move_package::source_package::layout::SourcePackageLayout;
 use std::path::PathBuf;
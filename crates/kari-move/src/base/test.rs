@@ -2,26 +2,41 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::reroot_path;
-use crate::NativeFunctionRecord;
+use crate::{
+    output::{TestOutcome, TestReport, TestStatus},
+    sandbox::utils::get_gas_status,
+    NativeFunctionRecord,
+};
 use anyhow::Result;
 use clap::*;
+use move_binary_format::errors::{Location, VMResult};
 use move_command_line_common::files::{FileHash, MOVE_COVERAGE_MAP_EXTENSION};
 use move_compiler::{
-    diagnostics::{self, codes::Severity},
+    compiled_unit::{AnnotatedCompiledUnit, CompiledUnit, NamedCompiledModule},
+    diagnostics::{self, codes::Severity, FilesSourceText},
     shared::{NumberFormat, NumericalAddress},
-    unit_test::{plan_builder::construct_test_plan, TestPlan},
+    unit_test::{plan_builder::construct_test_plan, ExpectedFailure, ModuleTestPlan, TestPlan},
     PASS_CFGIR,
 };
+use move_core_types::{
+    identifier::IdentStr, language_storage::ModuleId, value::serialize_values,
+    vm_status::StatusCode,
+};
 use move_coverage::coverage_map::{output_map_to_file, CoverageMap};
 use move_package::{compilation::build_plan::BuildPlan, BuildConfig};
 use move_unit_test::UnitTestingConfig;
-use move_vm_test_utils::gas_schedule::CostTable;
+use move_vm_runtime::move_vm::MoveVM;
+use move_vm_test_utils::{
+    gas_schedule::{unit_cost_table, CostTable, Gas},
+    InMemoryStorage,
+};
 use std::{
     collections::HashMap,
     fs,
     io::Write,
     path::{Path, PathBuf},
     process::ExitStatus,
+    time::Instant,
 };
 // if windows
 #[cfg(target_family = "windows")]
@@ -93,6 +108,62 @@ impl Test {
         cost_table: Option<CostTable>,
     ) -> anyhow::Result<()> {
         let rerooted_path = reroot_path(path)?;
+        let (unit_test_config, compute_coverage) = self.into_unit_testing_config();
+        let result = run_move_unit_tests(
+            &rerooted_path,
+            config,
+            unit_test_config,
+            natives,
+            cost_table,
+            compute_coverage,
+            &mut std::io::stdout(),
+        )?;
+
+        // Return a non-zero exit code if any test failed
+        if let UnitTestResult::Failure = result {
+            std::process::exit(1)
+        }
+        Ok(())
+    }
+
+    /// Run the tests like `execute`, printing a `TestReport` of their
+    /// outcomes instead of the runner's report.
+    pub fn execute_json(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+        natives: Vec<NativeFunctionRecord>,
+        cost_table: Option<CostTable>,
+    ) -> anyhow::Result<()> {
+        let rerooted_path = reroot_path(path)?;
+        let (unit_test_config, compute_coverage) = self.into_unit_testing_config();
+
+        let mut output = Vec::new();
+        let mut outcomes = Vec::new();
+        let result = run_unit_tests_with_outcomes(
+            &rerooted_path,
+            config,
+            unit_test_config,
+            natives,
+            cost_table,
+            compute_coverage,
+            |_, _| true,
+            |outcome| outcomes.push(outcome),
+            &mut output,
+        )
+        .map_err(|e| anyhow::anyhow!("{}\n{}", String::from_utf8_lossy(&output).trim(), e))?;
+
+        let report = TestReport::new(outcomes);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if let UnitTestResult::Failure = result {
+            std::process::exit(1)
+        }
+        Ok(())
+    }
+
+    // Split the options into the unit test runner's config and whether to
+    // compute coverage
+    fn into_unit_testing_config(self) -> (UnitTestingConfig, bool) {
         let Self {
             gas_limit,
             filter,
@@ -122,21 +193,7 @@ impl Test {
 
             ..UnitTestingConfig::default_with_bound(None)
         };
-        let result = run_move_unit_tests(
-            &rerooted_path,
-            config,
-            unit_test_config,
-            natives,
-            cost_table,
-            compute_coverage,
-            &mut std::io::stdout(),
-        )?;
-
-        // Return a non-zero exit code if any test failed
-        if let UnitTestResult::Failure = result {
-            std::process::exit(1)
-        }
-        Ok(())
+        (unit_test_config, compute_coverage)
     }
 }
 
@@ -149,13 +206,237 @@ pub enum UnitTestResult {
 
 pub fn run_move_unit_tests<W: Write + Send>(
     pkg_path: &Path,
-    mut build_config: move_package::BuildConfig,
+    build_config: move_package::BuildConfig,
+    unit_test_config: UnitTestingConfig,
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: Option<CostTable>,
+    compute_coverage: bool,
+    writer: &mut W,
+) -> Result<UnitTestResult> {
+    run_selected_unit_tests(
+        pkg_path,
+        build_config,
+        unit_test_config,
+        natives,
+        cost_table,
+        compute_coverage,
+        |_, _| true,
+        writer,
+    )
+}
+
+/// Like `run_move_unit_tests`, but only runs the tests for which `select` holds, given their
+/// module and name. Unlike the filter of `unit_test_config`, which matches substrings of test
+/// names, this can select a single test.
+#[allow(clippy::too_many_arguments)]
+pub fn run_selected_unit_tests<W: Write + Send>(
+    pkg_path: &Path,
+    build_config: move_package::BuildConfig,
     mut unit_test_config: UnitTestingConfig,
     natives: Vec<NativeFunctionRecord>,
     cost_table: Option<CostTable>,
     compute_coverage: bool,
+    select: impl Fn(&ModuleId, &str) -> bool,
     writer: &mut W,
 ) -> Result<UnitTestResult> {
+    let (modules, files, units) = build_test_plan(
+        pkg_path,
+        build_config,
+        &mut unit_test_config,
+        select,
+        writer,
+    )?;
+    let no_tests = modules
+        .iter()
+        .all(|module_plan| module_plan.tests.is_empty());
+    let test_plan = TestPlan::new(modules, files, units);
+    run_with_coverage(pkg_path, compute_coverage, no_tests, || {
+        Ok(unit_test_config
+            .run_and_report_unit_tests(test_plan, Some(natives), cost_table, writer)
+            .unwrap()
+            .1)
+    })
+}
+
+/// Like `run_selected_unit_tests`, but runs the tests one at a time and reports the outcome of
+/// each with `on_outcome` once it has run, instead of writing the runner's report. The runner
+/// does not expose its per-test results, so each test is executed here on the Move VM the way
+/// the runner executes it, in a fresh session over the compiled units and under its gas limit,
+/// and its result is checked against the test's expected failure with the runner's rules.
+#[allow(clippy::too_many_arguments)]
+pub fn run_unit_tests_with_outcomes<W: Write + Send>(
+    pkg_path: &Path,
+    build_config: move_package::BuildConfig,
+    mut unit_test_config: UnitTestingConfig,
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: Option<CostTable>,
+    compute_coverage: bool,
+    select: impl Fn(&ModuleId, &str) -> bool,
+    mut on_outcome: impl FnMut(TestOutcome),
+    writer: &mut W,
+) -> Result<UnitTestResult> {
+    // The runner matches its filter against `<module>::<test>`, so it is applied here to keep
+    // tests it would skip out of the report
+    let filter = unit_test_config.filter.take();
+    let selected = |module_id: &ModuleId, name: &str| {
+        let qualified_name = format!("{}::{}", module_id.name(), name);
+        select(module_id, name)
+            && filter
+                .as_ref()
+                .map_or(true, |filter| qualified_name.contains(filter.as_str()))
+    };
+    let (modules, _, units) = build_test_plan(
+        pkg_path,
+        build_config,
+        &mut unit_test_config,
+        selected,
+        writer,
+    )?;
+    let no_tests = modules
+        .iter()
+        .all(|module_plan| module_plan.tests.is_empty());
+
+    let mut storage = InMemoryStorage::new();
+    for unit in units {
+        let unit = unit.into_compiled_unit();
+        if let CompiledUnit::Module(NamedCompiledModule { module, .. }) = &unit {
+            storage.publish_or_overwrite_module(module.self_id(), unit.serialize(None));
+        }
+    }
+    let cost_table = cost_table.unwrap_or_else(unit_cost_table);
+    // The runner's own bound applies when no gas limit is given
+    let gas_limit = unit_test_config
+        .gas_limit
+        .or(UnitTestingConfig::default_with_bound(None).gas_limit);
+    let vm = MoveVM::new(natives)
+        .map_err(|e| anyhow::anyhow!("Failed to create the Move VM: {:?}", e))?;
+
+    run_with_coverage(pkg_path, compute_coverage, no_tests, || {
+        let mut passed = true;
+        for module_plan in &modules {
+            for (name, test_case) in &module_plan.tests {
+                let module_id = &module_plan.module_id;
+                let mut gas_status = get_gas_status(&cost_table, gas_limit)?;
+                let mut session = vm.new_session(&storage);
+                let start = Instant::now();
+                let result = session
+                    .execute_function_bypass_visibility(
+                        module_id,
+                        IdentStr::new(name)?,
+                        vec![],
+                        serialize_values(&test_case.arguments),
+                        &mut gas_status,
+                    )
+                    .map(|_| ());
+                let duration = start.elapsed();
+
+                let failure = check_test_result(test_case.expected_failure.as_ref(), result).err();
+                passed &= failure.is_none();
+                let gas_used = gas_limit.and_then(|limit| {
+                    Gas::new(limit)
+                        .checked_sub(gas_status.remaining_gas())
+                        .map(u64::from)
+                });
+                let (status, failure) = match failure {
+                    None => (TestStatus::Pass, None),
+                    Some((status, failure)) => (status, Some(failure)),
+                };
+                on_outcome(TestOutcome {
+                    name: format!(
+                        "{}::{}::{}",
+                        module_id.address().to_hex_literal(),
+                        module_id.name(),
+                        name
+                    ),
+                    status,
+                    gas_used,
+                    duration_ms: Some(duration.as_secs_f64() * 1000.0),
+                    failure,
+                });
+            }
+        }
+        Ok(passed)
+    })
+}
+
+/// Checks the result of running a test against the failure it is expected to end with, with the
+/// rules of the unit test runner: running out of gas is a timeout, and an expected error must
+/// match the status, abort code and location of the actual one when it gives them. Returns the
+/// status and description of a failing test.
+fn check_test_result(
+    expected_failure: Option<&ExpectedFailure>,
+    result: VMResult<()>,
+) -> std::result::Result<(), (TestStatus, String)> {
+    let fail = |failure: String| Err((TestStatus::Fail, failure));
+    let err = match (expected_failure, result) {
+        (_, Err(err)) if err.major_status() == StatusCode::OUT_OF_GAS => {
+            return Err((TestStatus::Timeout, "Test timed out".to_string()))
+        }
+        (None, Ok(())) => return Ok(()),
+        (Some(_), Ok(())) => return fail("Test did not error as expected".to_string()),
+        (None, Err(err)) => {
+            return fail(format!(
+                "Test was not expected to error, but it ended with {}",
+                describe_error(err.major_status(), err.sub_status(), err.location())
+            ))
+        }
+        (Some(_), Err(err)) => err,
+    };
+    let actual = describe_error(err.major_status(), err.sub_status(), err.location());
+    match expected_failure {
+        Some(ExpectedFailure::ExpectedWithCodeDEPRECATED(code))
+            if err.major_status() != StatusCode::ABORTED || err.sub_status() != Some(*code) =>
+        {
+            fail(format!(
+                "Test did not abort with expected code {}, but it ended with {}",
+                code, actual
+            ))
+        }
+        Some(ExpectedFailure::ExpectedWithError(expected))
+            if (expected.0, expected.1, &expected.2)
+                != (err.major_status(), err.sub_status(), err.location()) =>
+        {
+            fail(format!(
+                "Test did not error as expected. Expected {} but it ended with {}",
+                describe_error(expected.0, expected.1, &expected.2),
+                actual
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+// `an abort with code 3 in 0x2::coin`, or `a <STATUS> error in ...`
+fn describe_error(status: StatusCode, sub_status: Option<u64>, location: &Location) -> String {
+    let origin = match location {
+        Location::Module(module_id) => format!(
+            "{}::{}",
+            module_id.address().to_hex_literal(),
+            module_id.name()
+        ),
+        location => format!("{:?}", location),
+    };
+    match (status, sub_status) {
+        (StatusCode::ABORTED, Some(code)) => format!("an abort with code {} in {}", code, origin),
+        (status, _) => format!("a {:?} error in {}", status, origin),
+    }
+}
+
+/// Compiles the package at `pkg_path` in test mode and returns the plans of the modules with
+/// tests, keeping only the tests for which `select` holds, with the sources and compiled units
+/// they run against. Compilation errors are returned rather than reported with
+/// `diagnostics::report_diagnostics`, which exits the process.
+fn build_test_plan<W: Write + Send>(
+    pkg_path: &Path,
+    mut build_config: move_package::BuildConfig,
+    unit_test_config: &mut UnitTestingConfig,
+    select: impl Fn(&ModuleId, &str) -> bool,
+    writer: &mut W,
+) -> Result<(
+    Vec<ModuleTestPlan>,
+    FilesSourceText,
+    Vec<AnnotatedCompiledUnit>,
+)> {
     let mut test_plan = None;
     build_config.test_mode = true;
     build_config.dev_mode = true;
@@ -199,10 +480,14 @@ pub fn run_move_unit_tests<W: Write + Send>(
     // Move package system, to first grab the compilation env, construct the test plan from it, and
     // then save it, before resuming the rest of the compilation and returning the results and
     // control back to the Move package system.
+    let compile_error = |files: &FilesSourceText, diags| {
+        let report = diagnostics::report_diagnostics_to_buffer(files, diags);
+        anyhow::anyhow!("{}", String::from_utf8_lossy(&report).trim_end())
+    };
     build_plan.compile_with_driver(writer, None, |compiler| {
-        let (files, comments_and_compiler_res) = compiler.run::<PASS_CFGIR>().unwrap();
+        let (files, comments_and_compiler_res) = compiler.run::<PASS_CFGIR>()?;
         let (_, compiler) =
-            diagnostics::unwrap_or_report_diagnostics(&files, comments_and_compiler_res);
+            comments_and_compiler_res.map_err(|diags| compile_error(&files, diags))?;
         let (mut compiler, cfgir) = compiler.into_ast();
         let compilation_env = compiler.compilation_env();
         let built_test_plan = construct_test_plan(compilation_env, Some(root_package), &cfgir);
@@ -213,22 +498,34 @@ pub fn run_move_unit_tests<W: Write + Send>(
                 Severity::Warning
             },
         ) {
-            diagnostics::report_diagnostics(&files, diags);
+            return Err(compile_error(&files, diags));
         }
 
         let compilation_result = compiler.at_cfgir(cfgir).build();
 
-        let (units, _) = diagnostics::unwrap_or_report_diagnostics(&files, compilation_result);
+        let (units, _) = compilation_result.map_err(|diags| compile_error(&files, diags))?;
         test_plan = Some((built_test_plan, files.clone(), units.clone()));
         Ok((files, units))
     })?;
 
     let (test_plan, mut files, units) = test_plan.unwrap();
     files.extend(dep_file_map);
-    let test_plan = test_plan.unwrap();
-    let no_tests = test_plan.is_empty();
-    let test_plan = TestPlan::new(test_plan, files, units);
+    let mut test_plan = test_plan.unwrap();
+    for module_plan in &mut test_plan {
+        let module_id = &module_plan.module_id;
+        module_plan.tests.retain(|name, _| select(module_id, name));
+    }
+    Ok((test_plan, files, units))
+}
 
+/// Runs the tests with `run`, which returns whether they all passed, tracing their execution to
+/// compute the coverage map of the package if `compute_coverage` is set.
+fn run_with_coverage(
+    pkg_path: &Path,
+    compute_coverage: bool,
+    no_tests: bool,
+    run: impl FnOnce() -> Result<bool>,
+) -> Result<UnitTestResult> {
     let trace_path = pkg_path.join(".trace");
     let coverage_map_path = pkg_path
         .join(".coverage_map")
@@ -249,11 +546,7 @@ pub fn run_move_unit_tests<W: Write + Send>(
 
     // Run the tests. If any of the tests fail, then we don't produce a coverage report, so cleanup
     // the trace files.
-    if !unit_test_config
-        .run_and_report_unit_tests(test_plan, Some(natives), cost_table, writer)
-        .unwrap()
-        .1
-    {
+    if !run()? {
         cleanup_trace();
         return Ok(UnitTestResult::Failure);
     }
@@ -274,3 +567,58 @@ impl From<UnitTestResult> for ExitStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_binary_format::errors::PartialVMError;
+    use move_compiler::unit_test::ExpectedMoveError;
+    use move_core_types::{account_address::AccountAddress, identifier::Identifier};
+
+    #[test]
+    fn test_check_test_result() {
+        let module = ModuleId::new(AccountAddress::TWO, Identifier::new("coin").unwrap());
+        let abort = |code| {
+            Err(PartialVMError::new(StatusCode::ABORTED)
+                .with_sub_status(code)
+                .finish(Location::Module(module.clone())))
+        };
+        let expected = |code| {
+            ExpectedFailure::ExpectedWithError(ExpectedMoveError(
+                StatusCode::ABORTED,
+                Some(code),
+                Location::Module(module.clone()),
+            ))
+        };
+
+        assert_eq!(check_test_result(None, Ok(())), Ok(()));
+        assert_eq!(check_test_result(Some(&expected(3)), abort(3)), Ok(()));
+        assert_eq!(
+            check_test_result(Some(&ExpectedFailure::Expected), abort(4)),
+            Ok(())
+        );
+        assert_eq!(
+            check_test_result(None, abort(3)),
+            Err((
+                TestStatus::Fail,
+                "Test was not expected to error, but it ended with an abort with code 3 in \
+                 0x2::coin"
+                    .to_string()
+            ))
+        );
+        assert!(matches!(
+            check_test_result(Some(&expected(3)), abort(4)),
+            Err((TestStatus::Fail, _))
+        ));
+        assert!(matches!(
+            check_test_result(Some(&expected(3)), Ok(())),
+            Err((TestStatus::Fail, _))
+        ));
+
+        let out_of_gas = PartialVMError::new(StatusCode::OUT_OF_GAS).finish(Location::Undefined);
+        assert_eq!(
+            check_test_result(Some(&ExpectedFailure::Expected), Err(out_of_gas)),
+            Err((TestStatus::Timeout, "Test timed out".to_string()))
+        );
+    }
+}
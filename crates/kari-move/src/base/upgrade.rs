@@ -0,0 +1,195 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::output::UpgradeResult;
+use crate::sandbox::utils::{module, OnDiskStateView, PackageContext};
+use crate::DEFAULT_STORAGE_DIR;
+use anyhow::{bail, Context};
+use clap::*;
+use colored::Colorize;
+use framework::upgrade::{check_upgrade, package_id, ModuleChange, UpgradeCap, UpgradePolicy};
+use move_binary_format::CompiledModule;
+use move_command_line_common::env::get_bytecode_version_from_env;
+use move_core_types::account_address::AccountAddress;
+use move_package::BuildConfig;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the storage holding the `UpgradeCap` of each published package.
+const UPGRADE_CAPS_DIR: &str = "upgrade_caps";
+
+/// Upgrade the package at `path`, previously published to the sandbox
+/// storage, under the policy of its `UpgradeCap`.
+///
+/// The sandbox keeps the modules at their original address; the versioned
+/// ID of the upgraded package is recorded in its cap.
+#[derive(Parser)]
+#[clap(name = "upgrade")]
+pub struct Upgrade {
+    /// Only report whether the upgrade is compatible, without applying it.
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Check the upgrade against this policy instead of the cap's, which
+    /// it may only make stricter: compatible, additive or dep-only.
+    #[clap(long = "policy")]
+    pub policy: Option<UpgradePolicy>,
+
+    /// Restrict the cap to this policy once the upgrade is applied.
+    #[clap(long = "restrict")]
+    pub restrict: Option<UpgradePolicy>,
+
+    /// Directory storing Move resources, events, and module bytecodes produced by module publishing
+    /// and script execution.
+    #[clap(long, default_value = DEFAULT_STORAGE_DIR, value_parser = value_parser!(PathBuf))]
+    pub storage_dir: PathBuf,
+}
+
+fn cap_path(storage_dir: &Path, package: AccountAddress) -> PathBuf {
+    storage_dir
+        .join(UPGRADE_CAPS_DIR)
+        .join(format!("{}.json", package.to_hex_literal()))
+}
+
+// Cap of the package at `package`, a fresh one if it was never upgraded
+fn load_cap(storage_dir: &Path, package: AccountAddress) -> anyhow::Result<UpgradeCap> {
+    let path = cap_path(storage_dir, package);
+    if !path.exists() {
+        return Ok(UpgradeCap::new(package));
+    }
+    let cap = fs::read_to_string(&path)?;
+    serde_json::from_str(&cap).with_context(|| format!("Invalid UpgradeCap {}", path.display()))
+}
+
+fn save_cap(storage_dir: &Path, cap: &UpgradeCap) -> anyhow::Result<()> {
+    let path = cap_path(storage_dir, cap.original_id);
+    fs::create_dir_all(path.parent().unwrap())?;
+    Ok(fs::write(path, serde_json::to_string_pretty(cap)?)?)
+}
+
+// Modules of the package currently published at `package`
+fn published_modules(
+    state: &OnDiskStateView,
+    package: AccountAddress,
+) -> anyhow::Result<Vec<CompiledModule>> {
+    Ok(state
+        .get_all_modules()?
+        .into_iter()
+        .filter(|module| *module.self_id().address() == package)
+        .collect())
+}
+
+impl Upgrade {
+    pub fn execute(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+        json: bool,
+    ) -> anyhow::Result<()> {
+        let context = PackageContext::new(&path, &config)?;
+        let state = context.prepare_state(None, &self.storage_dir)?;
+
+        let new_modules = context
+            .package()
+            .root_modules()
+            .map(|unit| module(&unit.unit).cloned())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let package = match new_modules.first() {
+            Some(first) => *first.self_id().address(),
+            None => bail!("Package has no modules to upgrade"),
+        };
+        if new_modules
+            .iter()
+            .any(|module| *module.self_id().address() != package)
+        {
+            bail!("All modules of an upgraded package must share the same address");
+        }
+
+        let old_modules = published_modules(&state, package)?;
+        if old_modules.is_empty() {
+            bail!(
+                "No package is published at {}, publish it with `sandbox publish` first",
+                package.to_hex_literal()
+            );
+        }
+
+        let mut cap = load_cap(&self.storage_dir, package)?;
+        let policy = self.policy.unwrap_or(cap.policy);
+        let report = check_upgrade(&old_modules, &new_modules);
+        if !self.dry_run {
+            cap.authorize(policy, &report)?;
+            if let Some(restrict) = self.restrict {
+                cap.restrict(restrict)?;
+            }
+
+            let bytecode_version = get_bytecode_version_from_env(None);
+            let mut serialized_modules = vec![];
+            for module in &new_modules {
+                let mut module_bytes = vec![];
+                module.serialize_for_version(bytecode_version, &mut module_bytes)?;
+                serialized_modules.push((module.self_id(), module_bytes));
+            }
+            state.save_modules(&serialized_modules)?;
+            cap.commit();
+            save_cap(&self.storage_dir, &cap)?;
+        }
+
+        let result = UpgradeResult {
+            cap,
+            policy,
+            report,
+            committed: !self.dry_run,
+        };
+        if json {
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        } else {
+            print_result(&result);
+        }
+        Ok(())
+    }
+}
+
+fn print_result(result: &UpgradeResult) {
+    for module in &result.report.modules {
+        let change = match module.change {
+            ModuleChange::Added => "added".green(),
+            ModuleChange::Unchanged => "unchanged".normal(),
+            ModuleChange::Extended => "extended".green(),
+            ModuleChange::Changed => "changed".yellow(),
+            ModuleChange::Removed => "removed".red(),
+        };
+        let check = |ok: bool| if ok { "ok".green() } else { "broken".red() };
+        println!(
+            "{:<40} {:<10} linking {}, layout {}",
+            module.module,
+            change,
+            check(module.linking_compatible),
+            check(module.layout_compatible)
+        );
+    }
+    match result.report.strictest_policy {
+        Some(strictest) => println!("Strictest policy allowing the upgrade: {}", strictest),
+        None => println!("{}", "No policy allows the upgrade".red()),
+    }
+
+    let cap = &result.cap;
+    if result.committed {
+        println!(
+            "Upgraded {} to version {} ({}), policy {}",
+            cap.original_id.to_hex_literal(),
+            cap.version,
+            cap.package.to_hex_literal(),
+            cap.policy
+        );
+    } else {
+        match cap.authorize(result.policy, &result.report) {
+            Ok(()) => println!(
+                "Upgrade is allowed by the {} policy, version {} would be {}",
+                result.policy,
+                cap.version + 1,
+                package_id(cap.original_id, cap.version + 1).to_hex_literal()
+            ),
+            Err(err) => println!("{}", err.to_string().red()),
+        }
+    }
+}
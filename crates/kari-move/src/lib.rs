@@ -4,12 +4,13 @@
 
 use base::{
     build::Build, coverage::Coverage, disassemble::Disassemble, docgen::Docgen, errmap::Errmap,
-    info::Info, new::New, prove::Prove, test::Test,
+    fmt::Fmt, info::Info, new::New, prove::Prove, test::Test, upgrade::Upgrade,
 };
 use move_package::BuildConfig;
 
 pub mod base;
 pub mod experimental;
+pub mod output;
 pub mod sandbox;
 
 /// Default directory where saved Move resources live
@@ -44,8 +45,13 @@ pub struct Move {
     pub verbose: bool,
 
     /// Package build options
-    #[clap(skip)]
+    #[clap(flatten)]
     pub build_config: BuildConfig,
+
+    /// Print `build`, `test`, `upgrade` and `sandbox run` results as JSON (see `output`).
+    /// Set by the embedding CLI.
+    #[clap(skip)]
+    pub json: bool,
 }
 
 /// MoveCLI is the CLI that will be executed by the `move-cli` command
@@ -67,10 +73,12 @@ pub enum Command {
     Disassemble(Disassemble),
     Docgen(Docgen),
     Errmap(Errmap),
+    Fmt(Fmt),
     Info(Info),
     New(New),
     Prove(Prove),
     Test(Test),
+    Upgrade(Upgrade),
     /// Execute a sandbox command.
     #[clap(name = "sandbox")]
     Sandbox {
@@ -104,20 +112,35 @@ pub fn run_cli(
     //         1. It's still using the old CostTable.
     //         2. The CostTable only affects sandbox runs, but not unit tests, which use a unit cost table.
     match cmd {
+        Command::Build(c) if move_args.json => {
+            c.execute_json(move_args.package_path, move_args.build_config)
+        }
         Command::Build(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Coverage(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Disassemble(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Docgen(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Errmap(c) => c.execute(move_args.package_path, move_args.build_config),
+        Command::Fmt(c) => c.execute(move_args.package_path),
         Command::Info(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::New(c) => c.execute_with_defaults(move_args.package_path),
         Command::Prove(c) => c.execute(move_args.package_path, move_args.build_config),
+        Command::Test(c) if move_args.json => c.execute_json(
+            move_args.package_path,
+            move_args.build_config,
+            natives,
+            Some(cost_table.clone()),
+        ),
         Command::Test(c) => c.execute(
             move_args.package_path,
             move_args.build_config,
             natives,
             Some(cost_table.clone()),
         ),
+        Command::Upgrade(c) => c.execute(
+            move_args.package_path,
+            move_args.build_config,
+            move_args.json,
+        ),
         Command::Sandbox { storage_dir, cmd } => cmd.handle_command(
             natives,
             cost_table,
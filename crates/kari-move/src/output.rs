@@ -0,0 +1,125 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! JSON reports printed by the `build`, `test`, `upgrade` and `sandbox run`
+//! commands when the embedding CLI asks for machine-readable output
+//! (`Move::json`).
+
+use framework::upgrade::{UpgradeCap, UpgradePolicy, UpgradeReport};
+use serde::{Deserialize, Serialize};
+
+/// Result of `build`.
+#[derive(Serialize, Debug)]
+pub struct BuildReport {
+    pub package: String,
+    /// Modules of the root package, as `<address>::<name>`.
+    pub modules: Vec<String>,
+    /// Scripts of the root package.
+    pub scripts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Pass,
+    Fail,
+    Timeout,
+}
+
+/// Outcome of a single unit test.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TestOutcome {
+    /// Fully qualified name, `<address>::<module>::<function>`.
+    pub name: String,
+    pub status: TestStatus,
+    /// Gas used, which is the number of instructions executed under the unit cost table.
+    pub gas_used: Option<u64>,
+    pub duration_ms: Option<f64>,
+    /// Failure report of a failing test.
+    pub failure: Option<String>,
+}
+
+/// Result of `test`.
+#[derive(Serialize, Debug)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub tests: Vec<TestOutcome>,
+}
+
+impl TestReport {
+    pub fn new(tests: Vec<TestOutcome>) -> Self {
+        let passed = tests
+            .iter()
+            .filter(|test| test.status == TestStatus::Pass)
+            .count();
+        TestReport {
+            passed,
+            failed: tests.len() - passed,
+            tests,
+        }
+    }
+}
+
+/// An event emitted by a transaction.
+#[derive(Serialize, Debug)]
+pub struct EventReport {
+    /// Hex encoded event key.
+    pub key: String,
+    pub sequence_number: u64,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Hex encoded BCS bytes of the event.
+    pub data: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceOp {
+    Added,
+    Changed,
+    Deleted,
+}
+
+/// A resource written by a transaction.
+#[derive(Serialize, Debug)]
+pub struct ResourceChange {
+    pub address: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub op: ResourceOp,
+    pub bytes_written: usize,
+    /// The resource after the change, absent when deleted.
+    pub value: Option<String>,
+}
+
+/// Effects of `sandbox run`.
+#[derive(Serialize, Debug)]
+pub struct ExecutionEffects {
+    pub events: Vec<EventReport>,
+    pub changes: Vec<ResourceChange>,
+    pub total_bytes_written: usize,
+    /// Whether the effects were saved, `false` with `--dry-run`.
+    pub committed: bool,
+}
+
+/// Result of `upgrade`.
+#[derive(Serialize, Debug)]
+pub struct UpgradeResult {
+    /// The cap of the package, after the upgrade unless `--dry-run`.
+    pub cap: UpgradeCap,
+    /// Policy the upgrade was checked against.
+    pub policy: UpgradePolicy,
+    pub report: UpgradeReport,
+    /// Whether the upgrade was applied, `false` with `--dry-run`.
+    pub committed: bool,
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
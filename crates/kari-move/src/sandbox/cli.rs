@@ -105,6 +105,31 @@ pub enum SandboxCommand {
         /// deleted resources) will NOT be committed to disk.
         #[clap(long = "dry-run", short = 'n')]
         dry_run: bool,
+        /// Timestamp to set the `0x2::clock::Clock` at `0x6` to before the run, in milliseconds.
+        /// By default, the clock keeps the time of the previous run.
+        #[clap(long = "clock-ms")]
+        clock_ms: Option<u64>,
+        /// Seed of the deterministic beacon that advances the `0x2::random::RandomInner` at
+        /// `0x8` by one round before the run. By default, the random state is left as is.
+        #[clap(long = "random-seed")]
+        random_seed: Option<u64>,
+    },
+    /// Add an address to the deny list of a regulated coin type, or remove it, by updating the
+    /// `0x2::deny_list::DenyList` at `0x403` like `coin::deny_list_add` and
+    /// `coin::deny_list_remove` do (their natives are not available in the sandbox). A denied
+    /// sender can no longer run transactions that take the coin type as a type argument or a
+    /// `Coin<T>` of it as an object argument.
+    #[clap(name = "deny")]
+    Deny {
+        /// Coin type `T` of the regulated `Coin<T>` (e.g., `0x42::my_coin::MY_COIN`).
+        #[clap(name = "coin-type")]
+        coin_type: String,
+        /// Address to deny.
+        #[clap(name = "address")]
+        address: String,
+        /// Remove the address from the deny list instead.
+        #[clap(long = "remove")]
+        remove: bool,
     },
     /// Run expected value tests using the given batch file.
     #[clap(name = "exp-test")]
@@ -232,6 +257,8 @@ impl SandboxCommand {
                 type_args,
                 gas_budget,
                 dry_run,
+                clock_ms,
+                random_seed,
             } => {
                 let context =
                     PackageContext::new(&move_args.package_path, &move_args.build_config)?;
@@ -250,9 +277,21 @@ impl SandboxCommand {
                     *gas_budget,
                     bytecode_version,
                     *dry_run,
+                    *clock_ms,
+                    *random_seed,
                     move_args.verbose,
+                    move_args.json,
                 )
             }
+            SandboxCommand::Deny {
+                coin_type,
+                address,
+                remove,
+            } => {
+                let state = PackageContext::new(&move_args.package_path, &move_args.build_config)?
+                    .prepare_state(bytecode_version, storage_dir)?;
+                sandbox::commands::deny(&state, coin_type, address, *remove)
+            }
             SandboxCommand::Test {
                 use_temp_dir,
                 track_cov,
@@ -0,0 +1,24 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sandbox::utils::{deny_list, on_disk_state_view::OnDiskStateView};
+use anyhow::{bail, Result};
+use move_core_types::{account_address::AccountAddress, parser::parse_type_tag};
+
+/// Add `address` to the deny list of `coin_type` in the stored `DenyList`, or
+/// remove it if `remove` is set. Runs whose sender is denied a coin type fail
+/// when they use it.
+pub fn deny(state: &OnDiskStateView, coin_type: &str, address: &str, remove: bool) -> Result<()> {
+    let tag = parse_type_tag(coin_type)?;
+    let addr = AccountAddress::from_hex_literal(address)?;
+    if remove {
+        if !deny_list::remove(state, &tag, addr)? {
+            bail!("{} is not on the deny list of {}", address, coin_type)
+        }
+        println!("Removed {} from the deny list of {}", address, coin_type);
+    } else {
+        deny_list::add(state, &tag, addr)?;
+        println!("Added {} to the deny list of {}", address, coin_type);
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod deny;
 pub mod doctor;
 pub mod generate;
 pub mod publish;
@@ -9,6 +10,7 @@ pub mod run;
 pub mod test;
 pub mod view;
 
+pub use deny::*;
 pub use doctor::*;
 pub use publish::*;
 pub use run::*;
@@ -4,26 +4,37 @@
 
 use crate::{
     sandbox::utils::{
-        contains_module, explain_execution_effects, explain_execution_error, get_gas_status,
-        is_bytecode_file, maybe_commit_effects, on_disk_state_view::OnDiskStateView,
+        contains_module, deny_list, execution_effects_report, explain_execution_effects,
+        explain_execution_error, get_gas_status, is_bytecode_file, maybe_commit_effects,
+        on_disk_state_view::OnDiskStateView, ExecutionError,
     },
     NativeFunctionRecord,
 };
 use anyhow::{anyhow, bail, Result};
+use mona_client::system::{
+    DevnetBeacon, RandomState, CLOCK_OBJECT_ID, CLOCK_TYPE, RANDOM_OBJECT_ID,
+};
 use move_binary_format::file_format::CompiledModule;
 use move_command_line_common::env::get_bytecode_version_from_env;
 use move_core_types::{
     account_address::AccountAddress,
+    effects::{ChangeSet, Op},
     errmap::ErrorMapping,
     identifier::IdentStr,
-    language_storage::TypeTag,
+    language_storage::{ModuleId, StructTag, TypeTag},
+    parser::parse_struct_tag,
+    resolver::{ModuleResolver, ResourceResolver},
     transaction_argument::{convert_txn_args, TransactionArgument},
     value::MoveValue,
 };
 use move_package::compilation::compiled_package::CompiledPackage;
 use move_vm_runtime::move_vm::MoveVM;
 use move_vm_test_utils::gas_schedule::CostTable;
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Type of the state behind the `Random` object, stored as a resource at its ID.
+const RANDOM_INNER_TYPE: &str = "0x2::random::RandomInner";
+const COIN_TYPE: &str = "0x2::coin::Coin";
 
 #[allow(clippy::too_many_arguments)]
 pub fn run(
@@ -40,7 +51,10 @@ pub fn run(
     gas_budget: Option<u64>,
     bytecode_version: Option<u32>,
     dry_run: bool,
+    clock_ms: Option<u64>,
+    random_seed: Option<u64>,
     verbose: bool,
+    json: bool,
 ) -> Result<()> {
     if !script_path.exists() {
         bail!("Script file {:?} does not exist", script_path)
@@ -76,9 +90,14 @@ move run` must be applied to a module inside `storage/`",
     // TODO: parse Value's directly instead of going through the indirection of TransactionArgument?
     let vm_args: Vec<Vec<u8>> = convert_txn_args(txn_args);
 
+    // The system transactions run before the script, which sees their writes
+    let system_state = SystemUpdates {
+        state,
+        resources: system_updates(state, clock_ms, random_seed)?,
+    };
     let vm = MoveVM::new(natives).unwrap();
     let mut gas_status = get_gas_status(cost_table, gas_budget)?;
-    let mut session = vm.new_session(state);
+    let mut session = vm.new_session(&system_state);
 
     let script_type_parameters = vec![];
     let script_parameters = vec![];
@@ -92,28 +111,36 @@ move run` must be applied to a module inside `storage/`",
         })
         .chain(vm_args)
         .collect();
-    let res = match script_name_opt {
-        Some(script_name) => {
-            // script fun. parse module, extract script ID to pass to VM
-            let module = CompiledModule::deserialize(&bytecode)
-                .map_err(|e| anyhow!("Error deserializing module: {:?}", e))?;
-            session.execute_entry_function(
-                &module.self_id(),
-                IdentStr::new(script_name)?,
+    // A sender on the deny list of a coin may not use it as an input
+    let res = match check_deny_list(state, &signer_addresses, &vm_type_args, txn_args)? {
+        Some(denied) => Err(denied),
+        None => match script_name_opt {
+            Some(script_name) => {
+                // script fun. parse module, extract script ID to pass to VM
+                let module = CompiledModule::deserialize(&bytecode)
+                    .map_err(|e| anyhow!("Error deserializing module: {:?}", e))?;
+                session.execute_entry_function(
+                    &module.self_id(),
+                    IdentStr::new(script_name)?,
+                    vm_type_args.clone(),
+                    vm_args,
+                    &mut gas_status,
+                )
+            }
+            None => session.execute_script(
+                bytecode.to_vec(),
                 vm_type_args.clone(),
                 vm_args,
                 &mut gas_status,
-            )
+            ),
         }
-        None => session.execute_script(
-            bytecode.to_vec(),
-            vm_type_args.clone(),
-            vm_args,
-            &mut gas_status,
-        ),
+        .map_err(ExecutionError::from),
     };
 
     if let Err(err) = res {
+        if json {
+            bail!("Execution failed: {}", err)
+        }
         explain_execution_error(
             error_descriptions,
             err,
@@ -125,10 +152,145 @@ move run` must be applied to a module inside `storage/`",
             txn_args,
         )
     } else {
-        let (changeset, events) = session.finish().map_err(|e| e.into_vm_status())?;
-        if verbose {
+        let (mut changeset, events) = session.finish().map_err(|e| e.into_vm_status())?;
+        system_state.add_to(&mut changeset)?;
+        if json {
+            let effects = execution_effects_report(&changeset, &events, state, !dry_run)?;
+            println!("{}", serde_json::to_string_pretty(&effects)?);
+            // Effects of a dry run are dropped without the usual notice
+            if dry_run {
+                return Ok(());
+            }
+        } else if verbose {
             explain_execution_effects(&changeset, &events, state)?
         }
         maybe_commit_effects(!dry_run, changeset, events, state)
     }
 }
+
+/// The error of a run whose sender (the first signer) is on the deny list of a
+/// coin type it uses: as a type argument, possibly nested in vectors or type
+/// parameters, or as a `Coin<T>` object argument.
+fn check_deny_list(
+    state: &OnDiskStateView,
+    signers: &[AccountAddress],
+    type_args: &[TypeTag],
+    txn_args: &[TransactionArgument],
+) -> Result<Option<ExecutionError>> {
+    let Some(sender) = signers.first() else {
+        return Ok(None);
+    };
+    let mut coin_types = vec![];
+    let mut pending: Vec<&TypeTag> = type_args.iter().collect();
+    while let Some(type_arg) = pending.pop() {
+        match type_arg {
+            TypeTag::Vector(element) => pending.push(element),
+            TypeTag::Struct(tag) => {
+                coin_types.push(type_arg.clone());
+                pending.extend(&tag.type_params);
+            }
+            _ => (),
+        }
+    }
+    // objects are stored as resources at their ID
+    let coin = parse_struct_tag(COIN_TYPE)?;
+    for txn_arg in txn_args {
+        if let TransactionArgument::Address(id) = txn_arg {
+            for tag in state.get_resource_types(*id)? {
+                if tag.address == coin.address && tag.module == coin.module && tag.name == coin.name
+                {
+                    coin_types.extend(tag.type_params);
+                }
+            }
+        }
+    }
+    for coin_type in coin_types {
+        if deny_list::contains(state, &coin_type, *sender)? {
+            return Ok(Some(ExecutionError::AddressDenied {
+                address: *sender,
+                coin_type: deny_list::display_name(&coin_type),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// The writes of the system transactions requested by `--clock-ms` and
+/// `--random-seed`, by address and type of the updated object.
+fn system_updates(
+    state: &OnDiskStateView,
+    clock_ms: Option<u64>,
+    random_seed: Option<u64>,
+) -> Result<BTreeMap<(AccountAddress, StructTag), Vec<u8>>> {
+    let mut updates = BTreeMap::new();
+    if let Some(clock_ms) = clock_ms {
+        let clock = AccountAddress::from_hex_literal(CLOCK_OBJECT_ID)?;
+        // `clock::Clock { id: UID, timestamp_ms: u64 }`
+        let blob = bcs::to_bytes(&(clock, clock_ms))?;
+        updates.insert((clock, parse_struct_tag(CLOCK_TYPE)?), blob);
+    }
+    if let Some(seed) = random_seed {
+        let random = AccountAddress::from_hex_literal(RANDOM_OBJECT_ID)?;
+        let tag = parse_struct_tag(RANDOM_INNER_TYPE)?;
+        let mut random_state = match state.get_resource_bytes(random, tag.clone())? {
+            Some(bytes) => bcs::from_bytes::<RandomState>(&bytes)?,
+            None => RandomState::default(),
+        };
+        random_state.advance(&DevnetBeacon::new(seed), random_state.epoch);
+        updates.insert((random, tag), bcs::to_bytes(&random_state)?);
+    }
+    Ok(updates)
+}
+
+/// `state` with the writes of the system transactions applied, which the run
+/// executes against.
+struct SystemUpdates<'a> {
+    state: &'a OnDiskStateView,
+    resources: BTreeMap<(AccountAddress, StructTag), Vec<u8>>,
+}
+
+impl SystemUpdates<'_> {
+    /// Add the writes that the run did not overwrite to `changeset`, so that
+    /// they are committed (or dropped by `--dry-run`) together with the run.
+    fn add_to(&self, changeset: &mut ChangeSet) -> Result<()> {
+        for ((addr, tag), blob) in &self.resources {
+            let written = changeset
+                .accounts()
+                .get(addr)
+                .is_some_and(|account| account.resources().contains_key(tag));
+            if written {
+                continue;
+            }
+            let op = if self.state.get_resource_bytes(*addr, tag.clone())?.is_some() {
+                Op::Modify(blob.clone())
+            } else {
+                Op::New(blob.clone())
+            };
+            changeset.add_resource_op(*addr, tag.clone(), op)?;
+        }
+        Ok(())
+    }
+}
+
+impl ModuleResolver for SystemUpdates<'_> {
+    type Error = anyhow::Error;
+
+    fn get_module(&self, module_id: &ModuleId) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.state.get_module(module_id)
+    }
+}
+
+impl ResourceResolver for SystemUpdates<'_> {
+    type Error = anyhow::Error;
+
+    fn get_resource(
+        &self,
+        address: &AccountAddress,
+        struct_tag: &StructTag,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self.resources.get(&(*address, struct_tag.clone())) {
+            Some(blob) => Ok(Some(blob.clone())),
+            None => self.state.get_resource(address, struct_tag),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The shared `0x2::deny_list::DenyList` of the sandbox storage. Like other objects, it is stored
+//! as a resource at its ID (`0x403`), and the entries of its `Bag` and `Table`s as the
+//! `0x2::dynamic_field::Field` objects of `mona_types::dynamic_field`. Reading it follows
+//! `deny_list::contains`, and updating it `deny_list::add` and `deny_list::remove`, which
+//! `coin::deny_list_add` and `coin::deny_list_remove` call.
+
+use crate::sandbox::utils::on_disk_state_view::OnDiskStateView;
+use anyhow::{bail, Result};
+use mona_client::system::{DENY_LIST_OBJECT_ID, DENY_LIST_TYPE};
+use mona_types::dynamic_field::{field_id, field_type};
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{StructTag, TypeTag},
+    parser::parse_struct_tag,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// `deny_list::COIN_INDEX`, the key of the list of coin types in the `DenyList` bag.
+const COIN_INDEX: u64 = 0;
+const PER_TYPE_LIST_TYPE: &str = "0x2::deny_list::PerTypeList";
+const VEC_SET_TYPE: &str = "0x2::vec_set::VecSet";
+
+#[derive(Serialize, Deserialize)]
+struct DenyList {
+    id: AccountAddress,
+    /// `bag::Bag`
+    lists: Collection,
+}
+
+/// `bag::Bag` and `table::Table`: the ID of the collection and its number of entries.
+#[derive(Serialize, Deserialize)]
+struct Collection {
+    id: AccountAddress,
+    size: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PerTypeList {
+    id: AccountAddress,
+    denied_count: Collection,
+    denied_addresses: Collection,
+}
+
+/// `dynamic_field::Field<Name, Value>`
+#[derive(Serialize, Deserialize)]
+struct Field<Name, Value> {
+    id: AccountAddress,
+    name: Name,
+    value: Value,
+}
+
+/// An entry of a bag or table: the ID and type of its field object.
+struct Entry {
+    id: AccountAddress,
+    tag: StructTag,
+}
+
+impl Entry {
+    fn new<Name: Serialize>(
+        parent: AccountAddress,
+        name_type: TypeTag,
+        name: &Name,
+        value_type: TypeTag,
+    ) -> Result<Self> {
+        Ok(Entry {
+            id: field_id(parent, &name_type, &bcs::to_bytes(name)?),
+            tag: field_type(name_type, value_type),
+        })
+    }
+
+    fn read<Name: DeserializeOwned, Value: DeserializeOwned>(
+        &self,
+        state: &OnDiskStateView,
+    ) -> Result<Option<Field<Name, Value>>> {
+        match state.get_resource_bytes(self.id, self.tag.clone())? {
+            Some(bytes) => Ok(Some(bcs::from_bytes(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn write<Name: Serialize, Value: Serialize>(
+        &self,
+        state: &OnDiskStateView,
+        name: Name,
+        value: Value,
+    ) -> Result<()> {
+        let field = Field {
+            id: self.id,
+            name,
+            value,
+        };
+        state.save_resource(self.id, self.tag.clone(), &bcs::to_bytes(&field)?)
+    }
+}
+
+/// The type name `type_name::get_with_original_ids` gives `tag`, which keys its deny list.
+pub fn type_name(tag: &TypeTag) -> String {
+    format_type(tag, &|address| address.to_hex())
+}
+
+/// `type_name(tag)` with short `0x` addresses, for messages.
+pub fn display_name(tag: &TypeTag) -> String {
+    format_type(tag, &|address| address.to_hex_literal())
+}
+
+fn format_type(tag: &TypeTag, address: &dyn Fn(&AccountAddress) -> String) -> String {
+    match tag {
+        TypeTag::Struct(tag) => {
+            let mut name = format!("{}::{}::{}", address(&tag.address), tag.module, tag.name);
+            if !tag.type_params.is_empty() {
+                let params: Vec<String> = tag
+                    .type_params
+                    .iter()
+                    .map(|param| format_type(param, address))
+                    .collect();
+                name.push_str(&format!("<{}>", params.join(",")));
+            }
+            name
+        }
+        TypeTag::Vector(element) => format!("vector<{}>", format_type(element, address)),
+        tag => tag.to_string(),
+    }
+}
+
+fn vec_set_type() -> Result<TypeTag> {
+    let mut tag = parse_struct_tag(VEC_SET_TYPE)?;
+    tag.type_params.push(TypeTag::Address);
+    Ok(TypeTag::Struct(Box::new(tag)))
+}
+
+fn per_type_list_entry(deny_list: &DenyList) -> Result<Entry> {
+    let per_type_list = TypeTag::Struct(Box::new(parse_struct_tag(PER_TYPE_LIST_TYPE)?));
+    Entry::new(deny_list.lists.id, TypeTag::U64, &COIN_INDEX, per_type_list)
+}
+
+fn denied_count_entry(list: &PerTypeList, address: AccountAddress) -> Result<Entry> {
+    Entry::new(
+        list.denied_count.id,
+        TypeTag::Address,
+        &address,
+        TypeTag::U64,
+    )
+}
+
+fn denied_addresses_entry(list: &PerTypeList, coin_type: &[u8]) -> Result<Entry> {
+    let name_type = TypeTag::Vector(Box::new(TypeTag::U8));
+    Entry::new(
+        list.denied_addresses.id,
+        name_type,
+        &coin_type,
+        vec_set_type()?,
+    )
+}
+
+/// The coin list of the `DenyList` stored in `state`, if any.
+fn coin_list(state: &OnDiskStateView) -> Result<Option<(DenyList, Field<u64, PerTypeList>)>> {
+    let id = AccountAddress::from_hex_literal(DENY_LIST_OBJECT_ID)?;
+    let Some(bytes) = state.get_resource_bytes(id, parse_struct_tag(DENY_LIST_TYPE)?)? else {
+        return Ok(None);
+    };
+    let deny_list: DenyList = bcs::from_bytes(&bytes)?;
+    match per_type_list_entry(&deny_list)?.read(state)? {
+        Some(list) => Ok(Some((deny_list, list))),
+        None => bail!("The DenyList of the storage has no list of coin types"),
+    }
+}
+
+/// Checks if `address` is on the deny list of coin type `coin_type`, like
+/// `coin::deny_list_contains`.
+pub fn contains(
+    state: &OnDiskStateView,
+    coin_type: &TypeTag,
+    address: AccountAddress,
+) -> Result<bool> {
+    let Some((_, list)) = coin_list(state)? else {
+        return Ok(false);
+    };
+    let denied_count: Option<Field<AccountAddress, u64>> =
+        denied_count_entry(&list.value, address)?.read(state)?;
+    if denied_count.map_or(0, |count| count.value) == 0 {
+        return Ok(false);
+    }
+    let coin_type = type_name(coin_type).into_bytes();
+    // `VecSet<address>` has the BCS encoding of its `vector<address>`
+    let denied: Option<Field<Vec<u8>, Vec<AccountAddress>>> =
+        denied_addresses_entry(&list.value, &coin_type)?.read(state)?;
+    Ok(denied.is_some_and(|denied| denied.value.contains(&address)))
+}
+
+/// Adds `address` to the deny list of `coin_type`, creating the `DenyList` if the storage has
+/// none. Returns `false` if the address was already denied.
+pub fn add(state: &OnDiskStateView, coin_type: &TypeTag, address: AccountAddress) -> Result<bool> {
+    let (deny_list, mut list) = match coin_list(state)? {
+        Some(coin_list) => coin_list,
+        None => create(state)?,
+    };
+    let coin_type = type_name(coin_type).into_bytes();
+    let addresses_entry = denied_addresses_entry(&list.value, &coin_type)?;
+    let mut denied = match addresses_entry.read::<Vec<u8>, Vec<AccountAddress>>(state)? {
+        Some(denied) => denied.value,
+        None => {
+            list.value.denied_addresses.size += 1;
+            vec![]
+        }
+    };
+    if denied.contains(&address) {
+        return Ok(false);
+    }
+    denied.push(address);
+    addresses_entry.write(state, coin_type, denied)?;
+
+    let count_entry = denied_count_entry(&list.value, address)?;
+    let count = match count_entry.read::<AccountAddress, u64>(state)? {
+        Some(count) => count.value,
+        None => {
+            list.value.denied_count.size += 1;
+            0
+        }
+    };
+    count_entry.write(state, address, count + 1)?;
+    per_type_list_entry(&deny_list)?.write(state, COIN_INDEX, list.value)?;
+    Ok(true)
+}
+
+/// Removes `address` from the deny list of `coin_type`. Returns `false` if it was not denied,
+/// where `deny_list::remove` aborts with `ENotDenied`.
+pub fn remove(
+    state: &OnDiskStateView,
+    coin_type: &TypeTag,
+    address: AccountAddress,
+) -> Result<bool> {
+    let Some((deny_list, mut list)) = coin_list(state)? else {
+        return Ok(false);
+    };
+    let coin_type = type_name(coin_type).into_bytes();
+    let addresses_entry = denied_addresses_entry(&list.value, &coin_type)?;
+    let Some(mut denied) = addresses_entry.read::<Vec<u8>, Vec<AccountAddress>>(state)? else {
+        return Ok(false);
+    };
+    let Some(index) = denied.value.iter().position(|denied| *denied == address) else {
+        return Ok(false);
+    };
+    // like `vec_set::remove`, the set of the coin type is kept even if it is now empty
+    denied.value.remove(index);
+    addresses_entry.write(state, coin_type, denied.value)?;
+
+    let count_entry = denied_count_entry(&list.value, address)?;
+    let count = count_entry
+        .read::<AccountAddress, u64>(state)?
+        .map_or(0, |count| count.value);
+    if count <= 1 {
+        state.delete_resource(count_entry.id, count_entry.tag)?;
+        list.value.denied_count.size -= 1;
+    } else {
+        count_entry.write(state, address, count - 1)?;
+    }
+    per_type_list_entry(&deny_list)?.write(state, COIN_INDEX, list.value)?;
+    Ok(true)
+}
+
+/// Creates the `DenyList` with an empty list of coin types, like `deny_list::create`.
+fn create(state: &OnDiskStateView) -> Result<(DenyList, Field<u64, PerTypeList>)> {
+    let deny_list = DenyList {
+        id: AccountAddress::from_hex_literal(DENY_LIST_OBJECT_ID)?,
+        lists: Collection {
+            id: AccountAddress::random(),
+            size: 1,
+        },
+    };
+    state.save_resource(
+        deny_list.id,
+        parse_struct_tag(DENY_LIST_TYPE)?,
+        &bcs::to_bytes(&deny_list)?,
+    )?;
+    let entry = per_type_list_entry(&deny_list)?;
+    let list = Field {
+        id: entry.id,
+        name: COIN_INDEX,
+        value: PerTypeList {
+            id: AccountAddress::random(),
+            denied_count: Collection {
+                id: AccountAddress::random(),
+                size: 0,
+            },
+            denied_addresses: Collection {
+                id: AccountAddress::random(),
+                size: 0,
+            },
+        },
+    };
+    state.save_resource(entry.id, entry.tag, &bcs::to_bytes(&list)?)?;
+    Ok((deny_list, list))
+}
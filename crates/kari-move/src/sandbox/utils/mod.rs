@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 // use crate::sandbox::utils::on_disk_state_view::OnDiskStateView;
+use crate::output::{to_hex, EventReport, ExecutionEffects, ResourceChange, ResourceOp};
 use anyhow::{bail, Result};
 use colored::Colorize;
 use difference::{Changeset, Difference};
@@ -33,10 +34,11 @@ use move_resource_viewer::{AnnotatedMoveStruct, MoveValueAnnotator};
 use move_vm_test_utils::gas_schedule::Gas;
 use std::{
     collections::{BTreeMap, HashMap},
-    fs,
+    fmt, fs,
     path::Path,
 };
 
+pub mod deny_list;
 pub mod on_disk_state_view;
 pub mod package_context;
 
@@ -243,6 +245,61 @@ pub(crate) fn explain_execution_effects(
     Ok(())
 }
 
+/// The effects `explain_execution_effects` describes, as an `ExecutionEffects` report.
+pub(crate) fn execution_effects_report(
+    changeset: &ChangeSet,
+    events: &[Event],
+    state: &OnDiskStateView,
+    committed: bool,
+) -> Result<ExecutionEffects> {
+    let events = events
+        .iter()
+        .map(
+            |(event_key, event_sequence_number, event_type, event_data)| EventReport {
+                key: to_hex(event_key),
+                sequence_number: *event_sequence_number,
+                type_: event_type.to_string(),
+                data: to_hex(event_data),
+            },
+        )
+        .collect();
+
+    let mut changes = Vec::new();
+    let mut total_bytes_written = 0;
+    for (addr, account) in changeset.accounts() {
+        for (struct_tag, write_op) in account.resources() {
+            let mut bytes_written = struct_tag.access_vector().len();
+            let (op, value) = match write_op {
+                Op::New(blob) | Op::Modify(blob) => {
+                    bytes_written += blob.len();
+                    let resource = MoveValueAnnotator::new(state).view_resource(struct_tag, blob)?;
+                    let op = match write_op {
+                        Op::New(_) => ResourceOp::Added,
+                        _ => ResourceOp::Changed,
+                    };
+                    (op, Some(resource.to_string()))
+                }
+                Op::Delete => (ResourceOp::Deleted, None),
+            };
+            total_bytes_written += bytes_written;
+            changes.push(ResourceChange {
+                address: addr.to_hex_literal(),
+                type_: struct_tag.to_string(),
+                op,
+                bytes_written,
+                value,
+            });
+        }
+    }
+
+    Ok(ExecutionEffects {
+        events,
+        changes,
+        total_bytes_written,
+        committed,
+    })
+}
+
 /// Commit the resources and events modified by a transaction to disk
 pub(crate) fn maybe_commit_effects(
     commit: bool,
@@ -452,10 +509,42 @@ pub(crate) fn explain_publish_error(
     Ok(())
 }
 
+/// Why a transaction failed to execute.
+pub(crate) enum ExecutionError {
+    Vm(VMError),
+    /// The sender is on the deny list of a regulated coin type the
+    /// transaction uses.
+    AddressDenied {
+        address: AccountAddress,
+        coin_type: String,
+    },
+}
+
+impl From<VMError> for ExecutionError {
+    fn from(error: VMError) -> Self {
+        ExecutionError::Vm(error)
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Vm(error) => write!(f, "{:?}", error.clone().into_vm_status()),
+            ExecutionError::AddressDenied { address, coin_type } => write!(
+                f,
+                "the sender {} is on the deny list of the regulated coin type {} in the \
+                 `0x2::deny_list::DenyList` (see `coin::deny_list_add`)",
+                address.to_hex_literal(),
+                coin_type
+            ),
+        }
+    }
+}
+
 /// Explain an execution error
 pub(crate) fn explain_execution_error(
     error_descriptions: &ErrorMapping,
-    error: VMError,
+    error: ExecutionError,
     state: &OnDiskStateView,
     script_type_parameters: &[AbilitySet],
     script_parameters: &[SignatureToken],
@@ -464,6 +553,13 @@ pub(crate) fn explain_execution_error(
     txn_args: &[TransactionArgument],
 ) -> Result<()> {
     use StatusCode::*;
+    let error = match error {
+        ExecutionError::Vm(error) => error,
+        denied @ ExecutionError::AddressDenied { .. } => {
+            println!("Execution failed because {}", denied);
+            return Ok(());
+        }
+    };
     match error.into_vm_status() {
         VMStatus::MoveAbort(AbortLocation::Module(id), abort_code) => {
             // try to use move-explain to explain the abort
@@ -158,6 +158,26 @@ impl OnDiskStateView {
         Self::get_bytes(&self.get_resource_path(addr, tag))
     }
 
+    /// The types of the resources stored on-disk at `addr`
+    pub fn get_resource_types(&self, addr: AccountAddress) -> Result<Vec<StructTag>> {
+        let dir = self.get_addr_path(&addr).join(RESOURCES_DIR);
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut types = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_stem() else {
+                continue;
+            };
+            match parser::parse_type_tag(&name.to_string_lossy())? {
+                TypeTag::Struct(s) => types.push(*s),
+                t => bail!("Expected to parse struct tag, but got {}", t),
+            }
+        }
+        Ok(types)
+    }
+
     /// Read the resource bytes stored on-disk at `addr`/`tag`
     fn get_module_bytes(&self, module_id: &ModuleId) -> Result<Option<Vec<u8>>> {
         Self::get_bytes(&self.get_module_path(module_id))
@@ -0,0 +1,71 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use kari_move::base::{
+    coverage::{format_lcov, source_coverage},
+    test::{run_move_unit_tests, UnitTestResult},
+};
+use move_package::BuildConfig;
+use move_unit_test::UnitTestingConfig;
+use std::{fs, path::Path};
+
+// Runs the tests of a package with coverage (in a process of its own, as the VM reads whether
+// to trace execution once) and checks its line coverage and the source reported as uncovered
+#[test]
+fn test_source_coverage() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("basic");
+    let fixture = Path::new("tests/coverage_tests/basic");
+    fs::create_dir_all(package.join("sources")).unwrap();
+    for file in ["Move.toml", "sources/m.move"] {
+        fs::copy(fixture.join(file), package.join(file)).unwrap();
+    }
+
+    let result = run_move_unit_tests(
+        &package,
+        BuildConfig::default(),
+        UnitTestingConfig::default_with_bound(None),
+        vec![],
+        None,
+        /* compute_coverage */ true,
+        &mut Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(result, UnitTestResult::Success);
+
+    let coverage = source_coverage(&package, BuildConfig::default()).unwrap();
+    assert_eq!(coverage.len(), 1);
+    let module = &coverage[0];
+    assert_eq!(module.name, "0x42::m");
+
+    let mut lcov = Vec::new();
+    format_lcov(&coverage, &mut lcov).unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    for record in [
+        "FN:2,m::clamp\n",
+        "FNDA:1,m::clamp\n",
+        "FNDA:1,m::test_clamp\n",
+        // the condition and the `else` branch run, the `then` branch does not
+        "DA:3,1\n",
+        "DA:4,0\n",
+        "DA:6,1\n",
+    ] {
+        assert!(lcov.contains(record), "{} not in\n{}", record.trim(), lcov);
+    }
+
+    // `max` of the `then` branch, on the (0-based) line 3, is not covered, and nothing outside
+    // of the `if` is
+    assert!(
+        module.uncovered.contains(&(3, 12..15)),
+        "{:?}",
+        module.uncovered
+    );
+    assert!(
+        module
+            .uncovered
+            .iter()
+            .all(|(line, _)| (2..=6).contains(line)),
+        "{:?}",
+        module.uncovered
+    );
+}
@@ -0,0 +1,14 @@
+module 0x42::m {
+    public fun clamp(x: u64, max: u64): u64 {
+        if (x > max) {
+            max
+        } else {
+            x
+        }
+    }
+
+    #[test]
+    fun test_clamp() {
+        let _ = clamp(1, 2);
+    }
+}
@@ -0,0 +1,6 @@
+script {
+    use 0x2::coin;
+    fun mint<T>(account: signer) {
+        coin::mint<T>(&account, 100);
+    }
+}
@@ -0,0 +1,3 @@
+script {
+    fun spend(_account: signer, _coin: address) {}
+}
@@ -0,0 +1,6 @@
+script {
+    use 0x42::my_coin;
+    fun transfer<T>(_account: signer) {
+        my_coin::touch<T>();
+    }
+}
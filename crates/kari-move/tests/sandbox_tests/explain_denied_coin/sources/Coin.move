@@ -0,0 +1,9 @@
+address 0x2 {
+module coin {
+    struct Coin<phantom T> has key { value: u64 }
+
+    public fun mint<T>(account: &signer, value: u64) {
+        move_to(account, Coin<T> { value })
+    }
+}
+}
@@ -0,0 +1,7 @@
+address 0x42 {
+module my_coin {
+    struct MY_COIN has drop {}
+
+    public fun touch<T>() {}
+}
+}
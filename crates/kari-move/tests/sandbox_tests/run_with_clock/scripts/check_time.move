@@ -0,0 +1,6 @@
+script {
+    use 0x2::clock;
+    fun check_time(_account: signer, expected: u64) {
+        assert!(clock::timestamp_ms() == expected, 42);
+    }
+}
@@ -0,0 +1,12 @@
+address 0x2 {
+module clock {
+    struct Clock has key {
+        id: address,
+        timestamp_ms: u64,
+    }
+
+    public fun timestamp_ms(): u64 acquires Clock {
+        borrow_global<Clock>(@0x6).timestamp_ms
+    }
+}
+}
@@ -1,149 +1,269 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, ExitCode};
 
-use std::process::{exit, Command};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use colored::Colorize;
-// use command::keytool_cli::handle_keytool_command;
+use command::client_cli::{handle_client_command, ClientCommand};
+use command::config_cli::{handle_config_command, ConfigCommand};
+use command::genesis_cli::{handle_genesis_command, GenesisCommand};
+use command::keytool_cli::{handle_keytool_command, KeytoolCommand};
 use command::move_cli::handle_move_command;
+use command::output::{error_json, print_json};
+use command::public_cli::{handle_public_command, PublicCommand};
+use command::update_cli::{handle_update_command, UpdateArgs};
+use kari_move::MoveCLI;
+use mona_config::KariConfig;
 
-use command::public_cli::handle_public_command;
+static VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[derive(Parser)]
+#[clap(
+    name = "kari",
+    version,
+    about = "Kari command line tools",
+    arg_required_else_help = true
+)]
+struct Kari {
+    /// Configuration file to use instead of $KARI_HOME/kari.toml
+    #[clap(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
 
+    /// Print results as JSON (see `command::output` for the format)
+    #[clap(long, global = true)]
+    json: bool,
 
-static VERSION: &str = env!("CARGO_PKG_VERSION");
+    #[clap(subcommand)]
+    command: KariCommand,
+}
+
+#[derive(Subcommand)]
+enum KariCommand {
+    // /// Start a local Kari blockchain node
+    // Start,
+    /// Manage Web3 public files and IPFS storage
+    #[clap(subcommand)]
+    Public(PublicCommand),
+    /// Execute and manage Move VM smart contracts
+    Move(MoveCLI),
+    /// Send transactions to and query a Kari node
+    #[clap(subcommand)]
+    Client(ClientCommand),
+    /// Build and verify the genesis of a Kari network
+    #[clap(subcommand)]
+    Genesis(GenesisCommand),
+    /// Manage Kari accounts and cryptographic keys
+    #[clap(subcommand)]
+    Keytool(KeytoolCommand),
+    /// Update Kari tools to the latest release
+    Update(UpdateArgs),
+    /// Show and edit the Kari configuration
+    Config {
+        #[clap(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+    /// Generate shell completions or man pages
+    Completions {
+        target: CompletionTarget,
+        /// Write the output into this directory instead of stdout
+        #[clap(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Display CLI version information
+    #[clap(long_flag_alias = "V")]
+    Version,
+    /// Display information about the Kari node
+    #[clap(long_flag_alias = "i")]
+    Info,
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionTarget {
+    Bash,
+    Zsh,
+    Fish,
+    /// Man pages for `kari` and each of its subcommands
+    Man,
+}
 
-struct CommandInfo {
-    name: &'static str,
-    alias: Option<&'static str>,
-    description: &'static str,
+fn generate_completions(target: CompletionTarget, out_dir: Option<&Path>) -> Result<()> {
+    let mut cmd = Kari::command();
+    let shell = match target {
+        CompletionTarget::Bash => Shell::Bash,
+        CompletionTarget::Zsh => Shell::Zsh,
+        CompletionTarget::Fish => Shell::Fish,
+        CompletionTarget::Man => {
+            return match out_dir {
+                Some(dir) => {
+                    std::fs::create_dir_all(dir)?;
+                    clap_mangen::generate_to(cmd, dir)
+                        .with_context(|| format!("Failed to write man pages to {}", dir.display()))
+                }
+                None => Ok(clap_mangen::Man::new(cmd).render(&mut io::stdout())?),
+            };
+        }
+    };
+    match out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            let path = clap_complete::generate_to(shell, &mut cmd, "kari", dir)
+                .with_context(|| format!("Failed to write completions to {}", dir.display()))?;
+            println!("Wrote {}", path.display());
+        }
+        None => clap_complete::generate(shell, &mut cmd, "kari", &mut io::stdout()),
+    }
+    Ok(())
 }
 
+fn open_docs() -> Result<()> {
+    println!("{}", "Opening Kari documentation...".bright_yellow());
+    #[cfg(target_os = "windows")]
+    Process::new("cmd")
+        .args(["/C", "start", "https://docs.kanari.network"])
+        .spawn()
+        .context("Failed to open documentation")?;
 
-const COMMANDS: &[CommandInfo] = &[
-    // CommandInfo { 
-    //     name: "start", 
-    //     alias: None, 
-    //     description: "Start a local Kari blockchain node" 
-    // },
-    CommandInfo { 
-        name: "public", 
-        alias: None, 
-        description: "Manage Web3 public files and IPFS storage" 
-    },
-    CommandInfo { 
-        name: "move", 
-        alias: None, 
-        description: "Execute and manage Move VM smart contracts" 
-    },
-    // CommandInfo { 
-    //     name: "keytool", 
-    //     alias: None, 
-    //     description: "Manage Kari accounts and cryptographic keys" 
-    // },
-    // CommandInfo { 
-    //     name: "update", 
-    //     alias: Some("--up"), 
-    //     description: "Update Kari tools to latest version from GitHub" 
-    // },
-    CommandInfo { 
-        name: "version", 
-        alias: Some("--V"), 
-        description: "Display CLI version information" 
-    },
-    CommandInfo {
-        name: "help",
-        alias: Some("-h"),
-        description: "Display this help message"
-    },
-    CommandInfo { 
-        name: "info", 
-        alias: Some("--i"), 
-        description: "Display information about the Kari node" 
-    },
-];
+    #[cfg(target_os = "linux")]
+    Process::new("xdg-open")
+        .arg("https://docs.kanari.network")
+        .spawn()
+        .context("Failed to open documentation")?;
 
-fn display_help(show_error: bool) {
-    if show_error {
-        println!("\n{}", "ERROR: Invalid command".red().bold());
+    #[cfg(target_os = "macos")]
+    Process::new("open")
+        .arg("https://docs.kanari.network")
+        .spawn()
+        .context("Failed to open documentation")?;
+    Ok(())
+}
+
+fn run(cli: Kari) -> Result<()> {
+    let config = KariConfig::load(cli.config.as_deref())?;
+
+    let json = cli.json;
+    match cli.command {
+        KariCommand::Public(command) => handle_public_command(command, &config, json),
+        KariCommand::Move(move_cli) => handle_move_command(move_cli, &config, json),
+        KariCommand::Client(command) => handle_client_command(command, &config, json),
+        KariCommand::Genesis(command) => handle_genesis_command(command, &config, json),
+        KariCommand::Keytool(command) => handle_keytool_command(command, &config, json),
+        KariCommand::Config { command } => {
+            handle_config_command(command.unwrap_or(ConfigCommand::Show), config, json)
+        }
+        KariCommand::Update(args) => handle_update_command(args, &config, json),
+        KariCommand::Completions { target, out_dir } => {
+            generate_completions(target, out_dir.as_deref())
+        }
+        KariCommand::Version if json => print_json(&serde_json::json!({ "version": VERSION })),
+        KariCommand::Version => {
+            println!("CLI Version: {}", VERSION);
+            Ok(())
+        }
+        KariCommand::Info => open_docs(),
     }
+}
 
-    // Usage
-    println!("{}", "USAGE:".bright_yellow().bold());
-    println!("kari <command> [options]\n");
-
-    // Commands
-    println!("{}", "COMMANDS:".bright_yellow().bold());
-    
-    let max_name_len = COMMANDS.iter()
-        .map(|cmd| cmd.name.len() + cmd.alias.map_or(0, |a| a.len() + 2))
-        .max()
-        .unwrap_or(0);
-    
-    for cmd in COMMANDS {
-        let name = match cmd.alias {
-            Some(alias) => format!("{}, {}", cmd.name, alias),
-            None => cmd.name.to_string()
-        };
-        
-        println!(
-            "  {}{}  {}", 
-            name.green().bold(),
-            " ".repeat(max_name_len - name.len() + 2),
-            cmd.description.bright_white()
-        );
+fn main() -> ExitCode {
+    // Help and usage errors are reported by clap with its own exit codes
+    let cli = Kari::parse();
+    let json = cli.json;
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if json => {
+            println!("{:#}", error_json(&e));
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}: {:#}", "ERROR".red().bold(), e);
+            ExitCode::FAILURE
+        }
     }
-    println!();
-    
-    exit(1);
 }
 
-#[tokio::main]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if args.len() <= 1 {
-        display_help(false);
+    fn parse(line: &str) -> Result<Kari, clap::Error> {
+        Kari::try_parse_from(line.split_whitespace())
     }
 
-    match args.get(1).map(|s| s.as_str()) {
-        // Some("start") => start_node().await,
-        Some("public") => {
-            let _ = handle_public_command();
-        },
-        Some("move") => handle_move_command(),
-        // Some("keytool") => {
-        //     let _ = handle_keytool_command();
-        // },
-        // Some("update") | Some("--up") => {
-        //     if let Err(err) = handle_update().await {
-        //         eprintln!("Update failed: {}", err);
-        //         exit(1);
-        //     }
-        // },
-        Some("version") | Some("--V") => println!("CLI Version: {}", VERSION),
-        Some("help") | Some("--h") => display_help(false),
-        Some("info") | Some("--i") => {
-            println!("{}", "Opening Kari documentation...".bright_yellow());
-            #[cfg(target_os = "windows")]
-            Command::new("cmd")
-                .args(["/C", "start", "https://docs.kanari.network"])
-                .spawn()
-                .expect("Failed to open documentation");
-        
-            #[cfg(target_os = "linux")]
-            Command::new("xdg-open")
-                .arg("https://docs.kanari.network")
-                .spawn()
-                .expect("Failed to open documentation");
-        
-            #[cfg(target_os = "macos")]
-            Command::new("open")
-                .arg("https://docs.kanari.network")
-                .spawn()
-                .expect("Failed to open documentation");
-        },
-        _ => display_help(true),
+    #[test]
+    fn test_command_tree() {
+        Kari::command().debug_assert();
     }
-}
 
+    #[test]
+    fn test_parse() {
+        let cli = parse("kari public upload a.txt --name docs/a --config /tmp/kari.toml").unwrap();
+        assert_eq!(cli.config, Some(PathBuf::from("/tmp/kari.toml")));
+        assert!(!cli.json);
+        assert!(parse("kari move --json build").unwrap().json);
+        assert!(matches!(
+            cli.command,
+            KariCommand::Public(PublicCommand::Upload {
+                name: Some(_),
+                register: false,
+                ..
+            })
+        ));
 
+        let cli = parse("kari move --path pkg coverage source --module coin").unwrap();
+        let KariCommand::Move(move_cli) = cli.command else {
+            panic!("expected move command");
+        };
+        assert_eq!(move_cli.move_args.package_path, Some(PathBuf::from("pkg")));
+        assert!(matches!(move_cli.cmd, kari_move::Command::Coverage(_)));
+        assert!(parse("kari move coverage export --format cobertura -o coverage.xml").is_ok());
+        assert!(parse("kari move coverage export --format html").is_err());
+        let KariCommand::Move(move_cli) = parse("kari move fmt --check").unwrap().command else {
+            panic!("expected move command");
+        };
+        assert!(matches!(
+            move_cli.cmd,
+            kari_move::Command::Fmt(kari_move::base::fmt::Fmt { check: true })
+        ));
 
+        assert!(parse("kari public diff docs/a v1 two").is_err());
+        assert!(parse("kari public lookup").is_err());
+
+        let cli = parse("kari client dry-run pay --coins 0x5 --recipients 0xa 0xb --amounts 1 2")
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            KariCommand::Client(ClientCommand::DryRun { .. })
+        ));
+        assert!(parse("kari client split-coin --coin 0x5").is_err());
+        assert!(parse("kari update --check --index http://127.0.0.1:8000/index.json").is_ok());
+        assert!(parse("kari update --rollback --force").is_err());
+        assert!(parse("kari genesis verify g.blob --from genesis.toml --config kari.toml").is_ok());
+        assert!(parse("kari keytool import 0x01").is_ok());
+        // The flags of the previous command table still work
+        assert!(matches!(
+            parse("kari --V").unwrap().command,
+            KariCommand::Version
+        ));
+        assert!(matches!(
+            parse("kari --i").unwrap().command,
+            KariCommand::Info
+        ));
+        assert!(parse("kari frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_completions() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut out = Vec::new();
+            clap_complete::generate(shell, &mut Kari::command(), "kari", &mut out);
+            assert!(String::from_utf8(out).unwrap().contains("public"));
+        }
+        let mut out = Vec::new();
+        clap_mangen::Man::new(Kari::command())
+            .render(&mut out)
+            .unwrap();
+        assert!(!out.is_empty());
+    }
+}
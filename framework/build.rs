@@ -0,0 +1,127 @@
+//! Compile the system packages under `packages/` and embed them in the crate.
+//!
+//! Every package is compiled with docs. Its modules, in dependency order, are
+//! written to `$OUT_DIR/<package>` as a BCS `Vec<Vec<u8>>`, next to
+//! `manifest.json` holding the SHA3-256 digests of the modules.
+//!
+//! The bytecode (`packages_compiled/`), manifest and docs are also committed,
+//! so changes to the system packages show up in review. Missing ones are
+//! generated; the build fails when committed ones are stale, rebuild with
+//! `UPDATE_FRAMEWORK=1` to regenerate them.
+
+use move_binary_format::CompiledModule;
+use move_bytecode_utils::Modules;
+use move_package::BuildConfig;
+use serde_json::{json, Map, Value};
+use sha3::{Digest, Sha3_256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under `packages/`, directory of its docs and address of each
+/// system package, in dependency order.
+const PACKAGES: &[(&str, &str, &str)] = &[
+    ("move-stdlib", "docs", "0x1"),
+    ("kanari-framework", "doc", "0x2"),
+    ("kanari-system", "docs", "0x3"),
+];
+
+const UPDATE_ENV: &str = "UPDATE_FRAMEWORK";
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let update = env::var_os(UPDATE_ENV).is_some();
+    println!("cargo:rerun-if-changed=packages");
+    println!("cargo:rerun-if-env-changed={}", UPDATE_ENV);
+
+    let mut stale = Vec::new();
+    let mut manifest = Map::new();
+    for (name, docs_dir, address) in PACKAGES {
+        let package_dir = manifest_dir.join("packages").join(name);
+        let config = BuildConfig {
+            generate_docs: true,
+            install_dir: Some(out_dir.join("build")),
+            ..Default::default()
+        };
+        let package = config
+            .compile_package(&package_dir, &mut std::io::stderr())
+            .unwrap_or_else(|e| panic!("Failed to build {}: {}", name, e));
+
+        let modules: Vec<CompiledModule> = package
+            .root_modules()
+            .map(|unit| match &unit.unit {
+                move_compiler::compiled_unit::CompiledUnitEnum::Module(m) => m.module.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        let modules = Modules::new(&modules);
+        let graph = modules.compute_dependency_graph();
+        let mut bytes = Vec::new();
+        let mut digests = Map::new();
+        for module in graph.compute_topological_order().unwrap() {
+            let mut module_bytes = Vec::new();
+            module.serialize(&mut module_bytes).unwrap();
+            digests.insert(
+                module.self_id().name().to_string(),
+                json!(hex::encode(Sha3_256::digest(&module_bytes))),
+            );
+            bytes.push(module_bytes);
+        }
+        let bundle = bcs::to_bytes(&bytes).unwrap();
+        manifest.insert(
+            name.to_string(),
+            json!({
+                "id": address,
+                "digest": hex::encode(Sha3_256::digest(&bundle)),
+                "modules": digests,
+            }),
+        );
+
+        fs::write(out_dir.join(name), &bundle).unwrap();
+        check_or_update(
+            &manifest_dir.join("packages_compiled").join(name),
+            &bundle,
+            update,
+            &mut stale,
+        );
+        for (doc_name, doc) in package.compiled_docs.iter().flatten() {
+            let path = package_dir
+                .join(docs_dir)
+                .join(doc_name)
+                .with_extension("md");
+            check_or_update(&path, doc.as_bytes(), update, &mut stale);
+        }
+    }
+
+    let manifest = serde_json::to_string_pretty(&Value::Object(manifest)).unwrap() + "\n";
+    fs::write(out_dir.join("manifest.json"), &manifest).unwrap();
+    check_or_update(
+        &manifest_dir.join("packages_compiled").join("manifest.json"),
+        manifest.as_bytes(),
+        update,
+        &mut stale,
+    );
+
+    if !stale.is_empty() {
+        panic!(
+            "The committed bytecode or docs of the system packages are stale:\n  {}\n\
+             Rebuild with {}=1 to update them.",
+            stale.join("\n  "),
+            UPDATE_ENV
+        );
+    }
+}
+
+// Compare the committed `path` with the freshly built `contents`, writing it
+// when updating or when it was never generated
+fn check_or_update(path: &Path, contents: &[u8], update: bool, stale: &mut Vec<String>) {
+    match fs::read(path) {
+        Ok(committed) if committed == contents => {}
+        Ok(_) if !update => stale.push(path.display().to_string()),
+        _ => {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+}
@@ -0,0 +1,293 @@
+/// On-chain registry of file metadata.
+///
+/// Every registered file is described by a shared `MetadataRecord` holding the
+/// content hash, the current owner, a monotonically increasing version and the
+/// access permissions granted to other addresses. All state changes emit events
+/// so that off-chain indexers can look records up by owner or by content hash.
+module kanari_framework::metadata {
+    use std::vector;
+    use kanari_framework::clock::{Self, Clock};
+    use kanari_framework::event;
+    use kanari_framework::object::{Self, ID, UID};
+    use kanari_framework::transfer;
+    use kanari_framework::tx_context::{Self, TxContext};
+    use kanari_framework::vec_map::{Self, VecMap};
+
+    /// The content hash provided is empty.
+    const EEmptyHash: u64 = 0;
+    /// The sender does not hold the permission required for this operation.
+    const EPermissionDenied: u64 = 1;
+    /// The permission bitmask contains unknown flags.
+    const EInvalidPermissions: u64 = 2;
+    /// The new owner is already the owner of the record.
+    const ESameOwner: u64 = 3;
+
+    /// Allows reading the content referenced by a record.
+    const PERM_READ: u8 = 1;
+    /// Allows publishing new versions of a record.
+    const PERM_WRITE: u8 = 2;
+    /// Allows changing the permissions of a record.
+    const PERM_ADMIN: u8 = 4;
+    /// Union of all known permission flags.
+    const PERM_ALL: u8 = 7;
+
+    /// Access permissions attached to a `MetadataRecord`. The owner implicitly
+    /// holds every permission.
+    struct Permissions has store, copy, drop {
+        /// Whether the content may be read by any address.
+        public_read: bool,
+        /// Per-address grants, each a bitmask of `PERM_*` flags.
+        grants: VecMap<address, u8>,
+    }
+
+    /// Metadata describing a single stored file.
+    struct MetadataRecord has key {
+        id: UID,
+        /// Hash of the file content (SHA-256).
+        hash: vector<u8>,
+        /// Current owner of the record.
+        owner: address,
+        /// Number of the current version, starting at 1.
+        version: u64,
+        /// Timestamp in milliseconds of the last registration or update.
+        timestamp: u64,
+        /// Access permissions granted to other addresses.
+        permissions: Permissions,
+    }
+
+    // === Events ===
+
+    /// Emitted when a new record is registered.
+    struct MetadataRegistered has copy, drop {
+        id: ID,
+        owner: address,
+        hash: vector<u8>,
+        timestamp: u64,
+    }
+
+    /// Emitted when a new version of the content is published.
+    struct MetadataUpdated has copy, drop {
+        id: ID,
+        updated_by: address,
+        previous_hash: vector<u8>,
+        hash: vector<u8>,
+        version: u64,
+        timestamp: u64,
+    }
+
+    /// Emitted when the ownership of a record changes.
+    struct MetadataTransferred has copy, drop {
+        id: ID,
+        from: address,
+        to: address,
+    }
+
+    /// Emitted when the permissions granted to an address change.
+    struct PermissionsChanged has copy, drop {
+        id: ID,
+        changed_by: address,
+        grantee: address,
+        flags: u8,
+    }
+
+    /// Emitted when the public readability of a record changes.
+    struct PublicReadChanged has copy, drop {
+        id: ID,
+        changed_by: address,
+        public_read: bool,
+    }
+
+    /// Create a new record owned by the sender, at version 1.
+    public fun new(hash: vector<u8>, clock: &Clock, ctx: &mut TxContext): MetadataRecord {
+        assert!(!vector::is_empty(&hash), EEmptyHash);
+
+        let owner = tx_context::sender(ctx);
+        let timestamp = clock::timestamp_ms(clock);
+        let record = MetadataRecord {
+            id: object::new(ctx),
+            hash,
+            owner,
+            version: 1,
+            timestamp,
+            permissions: Permissions {
+                public_read: false,
+                grants: vec_map::empty(),
+            },
+        };
+
+        event::emit(MetadataRegistered {
+            id: object::id(&record),
+            owner,
+            hash: record.hash,
+            timestamp,
+        });
+        record
+    }
+
+    /// Register the metadata of a file and share the resulting record.
+    public entry fun register(hash: vector<u8>, clock: &Clock, ctx: &mut TxContext) {
+        transfer::share_object(new(hash, clock, ctx))
+    }
+
+    /// Publish a new version of the content referenced by `record`.
+    /// The sender must be the owner or hold `PERM_WRITE`.
+    public entry fun update_version(
+        record: &mut MetadataRecord,
+        hash: vector<u8>,
+        clock: &Clock,
+        ctx: &TxContext,
+    ) {
+        assert!(!vector::is_empty(&hash), EEmptyHash);
+        let sender = tx_context::sender(ctx);
+        assert!(has_permission(record, sender, PERM_WRITE), EPermissionDenied);
+
+        let previous_hash = record.hash;
+        record.hash = hash;
+        record.version = record.version + 1;
+        record.timestamp = clock::timestamp_ms(clock);
+
+        event::emit(MetadataUpdated {
+            id: object::id(record),
+            updated_by: sender,
+            previous_hash,
+            hash: record.hash,
+            version: record.version,
+            timestamp: record.timestamp,
+        });
+    }
+
+    /// Transfer the ownership of `record` to `recipient`. Only the owner can
+    /// transfer a record.
+    public entry fun transfer(record: &mut MetadataRecord, recipient: address, ctx: &TxContext) {
+        let sender = tx_context::sender(ctx);
+        assert!(record.owner == sender, EPermissionDenied);
+        assert!(recipient != sender, ESameOwner);
+
+        record.owner = recipient;
+        // The new owner implicitly holds every permission.
+        if (vec_map::contains(&record.permissions.grants, &recipient)) {
+            vec_map::remove(&mut record.permissions.grants, &recipient);
+        };
+
+        event::emit(MetadataTransferred {
+            id: object::id(record),
+            from: sender,
+            to: recipient,
+        });
+    }
+
+    /// Grant `flags` to `grantee`, replacing any previous grant. Passing `0`
+    /// revokes every permission of `grantee`. The sender must be the owner or
+    /// hold `PERM_ADMIN`.
+    public entry fun set_permissions(
+        record: &mut MetadataRecord,
+        grantee: address,
+        flags: u8,
+        ctx: &TxContext,
+    ) {
+        assert!((flags & PERM_ALL) == flags, EInvalidPermissions);
+        let sender = tx_context::sender(ctx);
+        assert!(has_permission(record, sender, PERM_ADMIN), EPermissionDenied);
+
+        let grants = &mut record.permissions.grants;
+        if (vec_map::contains(grants, &grantee)) {
+            vec_map::remove(grants, &grantee);
+        };
+        if (flags != 0) {
+            vec_map::insert(grants, grantee, flags);
+        };
+
+        event::emit(PermissionsChanged {
+            id: object::id(record),
+            changed_by: sender,
+            grantee,
+            flags,
+        });
+    }
+
+    /// Make the content of `record` readable (or not) by any address. The
+    /// sender must be the owner or hold `PERM_ADMIN`.
+    public entry fun set_public_read(record: &mut MetadataRecord, public_read: bool, ctx: &TxContext) {
+        let sender = tx_context::sender(ctx);
+        assert!(has_permission(record, sender, PERM_ADMIN), EPermissionDenied);
+
+        record.permissions.public_read = public_read;
+
+        event::emit(PublicReadChanged {
+            id: object::id(record),
+            changed_by: sender,
+            public_read,
+        });
+    }
+
+    /// Check whether `addr` holds every flag of `flags` on `record`.
+    public fun has_permission(record: &MetadataRecord, addr: address, flags: u8): bool {
+        if (addr == record.owner) return true;
+        if (flags == PERM_READ && record.permissions.public_read) return true;
+
+        let grants = &record.permissions.grants;
+        if (!vec_map::contains(grants, &addr)) return false;
+        (*vec_map::get(grants, &addr) & flags) == flags
+    }
+
+    /// Check whether `addr` may read the content referenced by `record`.
+    public fun can_read(record: &MetadataRecord, addr: address): bool {
+        has_permission(record, addr, PERM_READ)
+    }
+
+    // === Accessors ===
+
+    /// The hash of the current version of the content.
+    public fun hash(record: &MetadataRecord): &vector<u8> {
+        &record.hash
+    }
+
+    /// The current owner of the record.
+    public fun owner(record: &MetadataRecord): address {
+        record.owner
+    }
+
+    /// The current version of the record.
+    public fun version(record: &MetadataRecord): u64 {
+        record.version
+    }
+
+    /// The timestamp in milliseconds of the last registration or update.
+    public fun timestamp(record: &MetadataRecord): u64 {
+        record.timestamp
+    }
+
+    /// The access permissions of the record.
+    public fun permissions(record: &MetadataRecord): &Permissions {
+        &record.permissions
+    }
+
+    /// Whether the content is readable by any address.
+    public fun is_public_read(permissions: &Permissions): bool {
+        permissions.public_read
+    }
+
+    /// The permission flags granted to `addr`, `0` if none.
+    public fun grant_of(permissions: &Permissions, addr: address): u8 {
+        if (vec_map::contains(&permissions.grants, &addr)) {
+            *vec_map::get(&permissions.grants, &addr)
+        } else {
+            0
+        }
+    }
+
+    /// The flag granting read access.
+    public fun perm_read(): u8 { PERM_READ }
+
+    /// The flag granting write access.
+    public fun perm_write(): u8 { PERM_WRITE }
+
+    /// The flag granting permission management.
+    public fun perm_admin(): u8 { PERM_ADMIN }
+
+    #[test_only]
+    public fun destroy_for_testing(record: MetadataRecord) {
+        let MetadataRecord { id, hash: _, owner: _, version: _, timestamp: _, permissions: _ } = record;
+        object::delete(id);
+    }
+}
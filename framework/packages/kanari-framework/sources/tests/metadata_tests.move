@@ -0,0 +1,82 @@
+#[test_only]
+module kanari_framework::metadata_tests {
+    use kanari_framework::clock;
+    use kanari_framework::metadata;
+    use kanari_framework::tx_context;
+
+    const OWNER: address = @0xA;
+    const OTHER: address = @0xB;
+
+    #[test]
+    fun test_register_and_update() {
+        let ctx = tx_context::new_from_hint(OWNER, 0, 0, 0, 0);
+        let clock = clock::create_for_testing(&mut ctx);
+        clock::set_for_testing(&mut clock, 1000);
+
+        let record = metadata::new(x"01", &clock, &mut ctx);
+        assert!(metadata::owner(&record) == OWNER, 0);
+        assert!(metadata::version(&record) == 1, 1);
+        assert!(metadata::timestamp(&record) == 1000, 2);
+
+        clock::increment_for_testing(&mut clock, 500);
+        metadata::update_version(&mut record, x"02", &clock, &ctx);
+        assert!(*metadata::hash(&record) == x"02", 3);
+        assert!(metadata::version(&record) == 2, 4);
+        assert!(metadata::timestamp(&record) == 1500, 5);
+
+        metadata::destroy_for_testing(record);
+        clock::destroy_for_testing(clock);
+    }
+
+    #[test]
+    fun test_grants_and_transfer() {
+        let ctx = tx_context::new_from_hint(OWNER, 0, 0, 0, 0);
+        let clock = clock::create_for_testing(&mut ctx);
+        let record = metadata::new(x"01", &clock, &mut ctx);
+        assert!(!metadata::can_read(&record, OTHER), 0);
+
+        metadata::set_permissions(&mut record, OTHER, metadata::perm_write(), &ctx);
+        assert!(metadata::has_permission(&record, OTHER, metadata::perm_write()), 1);
+        assert!(!metadata::has_permission(&record, OTHER, metadata::perm_admin()), 2);
+
+        let other_ctx = tx_context::new_from_hint(OTHER, 1, 0, 0, 0);
+        metadata::update_version(&mut record, x"02", &clock, &other_ctx);
+        assert!(metadata::version(&record) == 2, 3);
+
+        metadata::set_public_read(&mut record, true, &ctx);
+        assert!(metadata::can_read(&record, @0xC), 4);
+
+        metadata::transfer(&mut record, OTHER, &ctx);
+        assert!(metadata::owner(&record) == OTHER, 5);
+        assert!(metadata::grant_of(metadata::permissions(&record), OTHER) == 0, 6);
+        assert!(!metadata::has_permission(&record, OWNER, metadata::perm_write()), 7);
+
+        metadata::destroy_for_testing(record);
+        clock::destroy_for_testing(clock);
+    }
+
+    #[test]
+    #[expected_failure(abort_code = metadata::EPermissionDenied)]
+    fun test_update_without_permission() {
+        let ctx = tx_context::new_from_hint(OWNER, 0, 0, 0, 0);
+        let clock = clock::create_for_testing(&mut ctx);
+        let record = metadata::new(x"01", &clock, &mut ctx);
+
+        let other_ctx = tx_context::new_from_hint(OTHER, 1, 0, 0, 0);
+        metadata::update_version(&mut record, x"02", &clock, &other_ctx);
+
+        metadata::destroy_for_testing(record);
+        clock::destroy_for_testing(clock);
+    }
+
+    #[test]
+    #[expected_failure(abort_code = metadata::EEmptyHash)]
+    fun test_register_empty_hash() {
+        let ctx = tx_context::new_from_hint(OWNER, 0, 0, 0, 0);
+        let clock = clock::create_for_testing(&mut ctx);
+        let record = metadata::new(x"", &clock, &mut ctx);
+
+        metadata::destroy_for_testing(record);
+        clock::destroy_for_testing(clock);
+    }
+}
@@ -0,0 +1,71 @@
+module kanari_system::validator_set {
+    use std::vector;
+
+    /// The validator is already in the set.
+    const EDuplicateValidator: u64 = 0;
+
+    /// Validators must have a stake.
+    const EZeroStake: u64 = 1;
+
+    /// A validator and the KARI it stakes, in MIST.
+    struct ValidatorInfo has copy, drop, store {
+        addr: address,
+        stake: u64,
+    }
+
+    /// The validators of an epoch, in the order they joined.
+    struct ValidatorSet has store {
+        validators: vector<ValidatorInfo>,
+        total_stake: u64,
+    }
+
+    /// Create an empty `ValidatorSet`.
+    public fun new(): ValidatorSet {
+        ValidatorSet { validators: vector[], total_stake: 0 }
+    }
+
+    /// Add the validator `addr` with `stake` to `self`.
+    /// Aborts if it is already in the set or has no stake.
+    public fun add_validator(self: &mut ValidatorSet, addr: address, stake: u64) {
+        assert!(stake > 0, EZeroStake);
+        assert!(!contains(self, addr), EDuplicateValidator);
+        vector::push_back(&mut self.validators, ValidatorInfo { addr, stake });
+        self.total_stake = self.total_stake + stake;
+    }
+
+    /// Whether `addr` is a validator of `self`.
+    public fun contains(self: &ValidatorSet, addr: address): bool {
+        let i = 0;
+        let len = vector::length(&self.validators);
+        while (i < len) {
+            if (vector::borrow(&self.validators, i).addr == addr) return true;
+            i = i + 1;
+        };
+        false
+    }
+
+    /// The stake of the validator `addr`, 0 if it is not in `self`.
+    public fun stake_of(self: &ValidatorSet, addr: address): u64 {
+        let i = 0;
+        let len = vector::length(&self.validators);
+        while (i < len) {
+            let validator = vector::borrow(&self.validators, i);
+            if (validator.addr == addr) return validator.stake;
+            i = i + 1;
+        };
+        0
+    }
+
+    public fun validator_count(self: &ValidatorSet): u64 {
+        vector::length(&self.validators)
+    }
+
+    public fun total_stake(self: &ValidatorSet): u64 {
+        self.total_stake
+    }
+
+    #[test_only]
+    public fun destroy_for_testing(self: ValidatorSet) {
+        let ValidatorSet { validators: _, total_stake: _ } = self;
+    }
+}
@@ -1,4 +1,25 @@
 #[test_only]
 module kanari_system::kanari_system_tests {
+    use kanari_system::validator_set;
 
-}
\ No newline at end of file
+    #[test]
+    fun test_add_validators() {
+        let set = validator_set::new();
+        validator_set::add_validator(&mut set, @0xA, 100);
+        validator_set::add_validator(&mut set, @0xB, 50);
+        assert!(validator_set::validator_count(&set) == 2, 0);
+        assert!(validator_set::total_stake(&set) == 150, 1);
+        assert!(validator_set::stake_of(&set, @0xB) == 50, 2);
+        assert!(!validator_set::contains(&set, @0xC), 3);
+        validator_set::destroy_for_testing(set);
+    }
+
+    #[test]
+    #[expected_failure(abort_code = kanari_system::validator_set::EDuplicateValidator)]
+    fun test_duplicate_validator() {
+        let set = validator_set::new();
+        validator_set::add_validator(&mut set, @0xA, 100);
+        validator_set::add_validator(&mut set, @0xA, 1);
+        validator_set::destroy_for_testing(set);
+    }
+}
@@ -1,14 +1,161 @@
-// use move_binary_format::binary_config::BinaryConfig;
-// use move_binary_format::compatibility::Compatibility;
-// use move_binary_format::file_format::{Ability, AbilitySet};
-// use move_binary_format::CompiledModule;
-// use move_core_types::gas_algebra::InternalGas;
-// use once_cell::sync::Lazy;
-// use serde::{Deserialize, Serialize};
-
-// // #[derive(Clone, Serialize, PartialEq, Eq, Deserialize)]
-// // pub struct SystemPackage {
-// //     pub id: ObjectID,
-// //     pub bytes: Vec<Vec<u8>>,
-// //     pub dependencies: Vec<ObjectID>,
-// // }
+//! The system packages, compiled by `build.rs` and embedded in the crate.
+//!
+//! `move-stdlib` lives at `0x1`, `kanari-framework` at `0x2` and
+//! `kanari-system` at `0x3`. Genesis publishes them with
+//! [`BuiltInFramework::iter_system_packages`], in dependency order.
+
+use mona_types::addresses::{KANARI_FRAMEWORK_ADDRESS, KANARI_SYSTEM_ADDRESS, MOVE_STD_ADDRESS};
+use move_binary_format::CompiledModule;
+use move_core_types::account_address::AccountAddress;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+
+pub mod upgrade;
+
+/// A package published at genesis.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Deserialize)]
+pub struct SystemPackage {
+    pub id: AccountAddress,
+    /// Serialized modules, in dependency order.
+    pub bytes: Vec<Vec<u8>>,
+    pub dependencies: Vec<AccountAddress>,
+}
+
+impl SystemPackage {
+    /// Package at `id` from `raw_bytes`, the BCS encoded modules written by
+    /// `build.rs`.
+    pub fn new(id: AccountAddress, raw_bytes: &[u8], dependencies: &[AccountAddress]) -> Self {
+        SystemPackage {
+            id,
+            bytes: bcs::from_bytes(raw_bytes).expect("system package bytes are valid BCS"),
+            dependencies: dependencies.to_vec(),
+        }
+    }
+
+    pub fn id(&self) -> &AccountAddress {
+        &self.id
+    }
+
+    pub fn bytes(&self) -> &[Vec<u8>] {
+        &self.bytes
+    }
+
+    pub fn dependencies(&self) -> &[AccountAddress] {
+        &self.dependencies
+    }
+
+    pub fn modules(&self) -> Vec<CompiledModule> {
+        self.bytes
+            .iter()
+            .map(|bytes| CompiledModule::deserialize(bytes).expect("system modules deserialize"))
+            .collect()
+    }
+
+    /// SHA3-256 digest of the package, as recorded in the [`manifest`].
+    pub fn digest(&self) -> [u8; 32] {
+        Sha3_256::digest(bcs::to_bytes(&self.bytes).unwrap()).into()
+    }
+}
+
+/// Digests of a system package, as written by `build.rs`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct PackageManifest {
+    pub id: String,
+    /// Hex encoded digest of the package.
+    pub digest: String,
+    /// Hex encoded digest of each module, by name.
+    pub modules: BTreeMap<String, String>,
+}
+
+static SYSTEM_PACKAGES: Lazy<Vec<SystemPackage>> = Lazy::new(|| {
+    vec![
+        SystemPackage::new(
+            MOVE_STD_ADDRESS,
+            include_bytes!(concat!(env!("OUT_DIR"), "/move-stdlib")),
+            &[],
+        ),
+        SystemPackage::new(
+            KANARI_FRAMEWORK_ADDRESS,
+            include_bytes!(concat!(env!("OUT_DIR"), "/kanari-framework")),
+            &[MOVE_STD_ADDRESS],
+        ),
+        SystemPackage::new(
+            KANARI_SYSTEM_ADDRESS,
+            include_bytes!(concat!(env!("OUT_DIR"), "/kanari-system")),
+            &[MOVE_STD_ADDRESS, KANARI_FRAMEWORK_ADDRESS],
+        ),
+    ]
+});
+
+static MANIFEST: Lazy<BTreeMap<String, PackageManifest>> = Lazy::new(|| {
+    serde_json::from_str(include_str!(concat!(env!("OUT_DIR"), "/manifest.json")))
+        .expect("manifest is valid JSON")
+});
+
+/// Digests of the system packages, by package directory name.
+pub fn manifest() -> &'static BTreeMap<String, PackageManifest> {
+    &MANIFEST
+}
+
+pub struct BuiltInFramework;
+
+impl BuiltInFramework {
+    /// The system packages, in dependency order.
+    pub fn iter_system_packages() -> impl Iterator<Item = &'static SystemPackage> {
+        SYSTEM_PACKAGES.iter()
+    }
+
+    pub fn all_package_ids() -> Vec<AccountAddress> {
+        Self::iter_system_packages().map(|p| p.id).collect()
+    }
+
+    pub fn get_package_by_id(id: &AccountAddress) -> &'static SystemPackage {
+        Self::iter_system_packages()
+            .find(|p| p.id() == id)
+            .unwrap_or_else(|| panic!("{} is not a system package", id.to_hex_literal()))
+    }
+
+    /// Modules of every system package, in dependency order.
+    pub fn genesis_modules() -> impl Iterator<Item = CompiledModule> {
+        Self::iter_system_packages().flat_map(|package| package.modules())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packages_match_manifest() {
+        let manifest = manifest();
+        assert_eq!(manifest.len(), 3);
+        for package in BuiltInFramework::iter_system_packages() {
+            let entry = manifest
+                .values()
+                .find(|entry| entry.id == package.id.to_hex_literal())
+                .unwrap();
+            assert_eq!(entry.digest, hex::encode(package.digest()));
+            assert_eq!(entry.modules.len(), package.bytes().len());
+            for module in package.modules() {
+                assert_eq!(module.self_id().address(), package.id());
+                assert!(entry.modules.contains_key(module.self_id().name().as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dependencies_come_first() {
+        let ids = BuiltInFramework::all_package_ids();
+        for (i, package) in BuiltInFramework::iter_system_packages().enumerate() {
+            for dependency in package.dependencies() {
+                assert!(ids[..i].contains(dependency));
+            }
+        }
+        assert_eq!(
+            BuiltInFramework::get_package_by_id(&KANARI_FRAMEWORK_ADDRESS).dependencies(),
+            &[MOVE_STD_ADDRESS]
+        );
+    }
+}
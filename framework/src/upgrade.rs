@@ -0,0 +1,574 @@
+//! Package upgrades, as authorized by `kanari_framework::package::UpgradeCap`.
+//!
+//! An upgrade replaces the modules of a package and gives the new version a
+//! package ID of its own, see [`package_id`]. What an upgrade may change is
+//! set by the policy of the package's `UpgradeCap`, from most to least
+//! permissive:
+//!
+//! - `compatible`: public functions keep their signatures and structs keep
+//!   their layout (`move_binary_format::compatibility`); anything else may
+//!   change and modules may be added.
+//! - `additive`: existing modules keep their interface, that is their structs
+//!   and their public, friend and entry functions, and the code of all their
+//!   functions, and may only gain new ones.
+//! - `dep-only`: existing modules keep their bytecode, only the dependencies
+//!   they link against may change.
+//!
+//! No policy allows removing a module. A cap can be restricted to a stricter
+//! policy but never relaxed.
+
+use move_binary_format::access::ModuleAccess;
+use move_binary_format::compatibility::Compatibility;
+use move_binary_format::file_format::{
+    Bytecode, FieldHandleIndex, FieldInstantiationIndex, FunctionHandleIndex, SignatureIndex,
+    StructDefInstantiationIndex, StructDefinitionIndex, Visibility,
+};
+use move_binary_format::normalized;
+use move_binary_format::CompiledModule;
+use move_core_types::account_address::AccountAddress;
+use move_core_types::identifier::IdentStr;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Upgrade policies, ordered from most to least permissive like their
+/// on-chain values.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpgradePolicy {
+    Compatible,
+    Additive,
+    DepOnly,
+}
+
+impl UpgradePolicy {
+    /// Value of the policy in `package.move`.
+    pub fn as_u8(self) -> u8 {
+        match self {
+            UpgradePolicy::Compatible => 0,
+            UpgradePolicy::Additive => 128,
+            UpgradePolicy::DepOnly => 192,
+        }
+    }
+
+    pub fn from_u8(policy: u8) -> Option<Self> {
+        match policy {
+            0 => Some(UpgradePolicy::Compatible),
+            128 => Some(UpgradePolicy::Additive),
+            192 => Some(UpgradePolicy::DepOnly),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UpgradePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UpgradePolicy::Compatible => "compatible",
+            UpgradePolicy::Additive => "additive",
+            UpgradePolicy::DepOnly => "dep-only",
+        })
+    }
+}
+
+impl FromStr for UpgradePolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "compatible" => Ok(UpgradePolicy::Compatible),
+            "additive" => Ok(UpgradePolicy::Additive),
+            "dep-only" | "dep_only" => Ok(UpgradePolicy::DepOnly),
+            _ => Err(format!(
+                "unknown upgrade policy '{}', expected compatible, additive or dep-only",
+                policy
+            )),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum UpgradeError {
+    #[error("Policy {requested} is more permissive than the {allowed} policy of the UpgradeCap")]
+    TooPermissive {
+        requested: UpgradePolicy,
+        allowed: UpgradePolicy,
+    },
+
+    #[error("Upgrade is not allowed by the {0} policy")]
+    Incompatible(UpgradePolicy),
+}
+
+/// ID of version `version` of the package first published at `original_id`.
+/// Version 1 is the original package.
+pub fn package_id(original_id: AccountAddress, version: u64) -> AccountAddress {
+    if version <= 1 {
+        return original_id;
+    }
+    let mut hasher = Sha3_256::new();
+    hasher.update(original_id.as_ref());
+    hasher.update(version.to_le_bytes());
+    let hash = hasher.finalize();
+    AccountAddress::from_bytes(&hash[..AccountAddress::LENGTH]).expect("hash is long enough")
+}
+
+/// Off-chain mirror of `kanari_framework::package::UpgradeCap`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeCap {
+    /// ID of the first version of the package.
+    pub original_id: AccountAddress,
+    /// ID of the latest version of the package.
+    pub package: AccountAddress,
+    /// Number of the latest version, 1 for the original package.
+    pub version: u64,
+    pub policy: UpgradePolicy,
+}
+
+impl UpgradeCap {
+    /// Cap of a package that was just published at `package`.
+    pub fn new(package: AccountAddress) -> Self {
+        UpgradeCap {
+            original_id: package,
+            package,
+            version: 1,
+            policy: UpgradePolicy::Compatible,
+        }
+    }
+
+    /// Check that an upgrade described by `report` may be applied under
+    /// `policy`, which must be at least as strict as the cap's.
+    pub fn authorize(
+        &self,
+        policy: UpgradePolicy,
+        report: &UpgradeReport,
+    ) -> Result<(), UpgradeError> {
+        if policy < self.policy {
+            return Err(UpgradeError::TooPermissive {
+                requested: policy,
+                allowed: self.policy,
+            });
+        }
+        if !report.allowed_by(policy) {
+            return Err(UpgradeError::Incompatible(policy));
+        }
+        Ok(())
+    }
+
+    /// Move the cap to the next version of the package, returning its ID.
+    pub fn commit(&mut self) -> AccountAddress {
+        self.version += 1;
+        self.package = package_id(self.original_id, self.version);
+        self.package
+    }
+
+    /// Only allow upgrades under `policy` from now on.
+    pub fn restrict(&mut self, policy: UpgradePolicy) -> Result<(), UpgradeError> {
+        if policy < self.policy {
+            return Err(UpgradeError::TooPermissive {
+                requested: policy,
+                allowed: self.policy,
+            });
+        }
+        self.policy = policy;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleChange {
+    Added,
+    Removed,
+    /// Same bytecode.
+    Unchanged,
+    /// Same interface and function bodies plus new structs or functions.
+    Extended,
+    Changed,
+}
+
+/// How one module of the package changes.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModuleReport {
+    pub module: String,
+    pub change: ModuleChange,
+    /// Public functions and structs can still be linked against.
+    pub linking_compatible: bool,
+    /// Struct layouts are unchanged, so stored values stay readable.
+    pub layout_compatible: bool,
+}
+
+/// Compatibility of a new version of a package with the current one.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeReport {
+    pub modules: Vec<ModuleReport>,
+    /// The strictest policy that allows the upgrade, `None` if none does.
+    pub strictest_policy: Option<UpgradePolicy>,
+}
+
+impl UpgradeReport {
+    pub fn allowed_by(&self, policy: UpgradePolicy) -> bool {
+        self.strictest_policy
+            .is_some_and(|strictest| strictest >= policy)
+    }
+}
+
+/// Definition of a function, with the indices into the tables of its module
+/// resolved, so that it does not change when the module gains new entries.
+#[derive(Debug, PartialEq, Eq)]
+struct FunctionDefinition {
+    visibility: Visibility,
+    is_entry: bool,
+    parameters: Vec<normalized::Type>,
+    return_: Vec<normalized::Type>,
+    /// Locals and instructions, `None` for native functions.
+    code: Option<(Vec<normalized::Type>, Vec<String>)>,
+}
+
+fn types(module: &CompiledModule, signature: SignatureIndex) -> Vec<normalized::Type> {
+    let signature = module.signature_at(signature);
+    signature
+        .0
+        .iter()
+        .map(|token| normalized::Type::new(module, token))
+        .collect()
+}
+
+fn function_name(module: &CompiledModule, handle: FunctionHandleIndex) -> String {
+    let handle = module.function_handle_at(handle);
+    let id = module.module_id_for_handle(module.module_handle_at(handle.module));
+    format!(
+        "{}::{}",
+        id.short_str_lossless(),
+        module.identifier_at(handle.name)
+    )
+}
+
+fn struct_name(module: &CompiledModule, def: StructDefinitionIndex) -> &IdentStr {
+    let handle = module.struct_handle_at(module.struct_def_at(def).struct_handle);
+    module.identifier_at(handle.name)
+}
+
+fn field_name(module: &CompiledModule, field: FieldHandleIndex) -> String {
+    let handle = module.field_handle_at(field);
+    format!("{}.{}", struct_name(module, handle.owner), handle.field)
+}
+
+/// `instruction` with its indices replaced by what they point to.
+fn resolve(module: &CompiledModule, instruction: &Bytecode) -> String {
+    use Bytecode::*;
+    let generic = |def: StructDefInstantiationIndex| {
+        let inst = module.struct_instantiation_at(def);
+        format!(
+            "{}{:?}",
+            struct_name(module, inst.def),
+            types(module, inst.type_parameters)
+        )
+    };
+    let generic_field = |field: FieldInstantiationIndex| {
+        let inst = module.field_instantiation_at(field);
+        format!(
+            "{}{:?}",
+            field_name(module, inst.handle),
+            types(module, inst.type_parameters)
+        )
+    };
+    match instruction {
+        LdConst(idx) => {
+            let constant = module.constant_at(*idx);
+            format!("LdConst({:?}, {:?})", constant.type_, constant.data)
+        }
+        Call(idx) => format!("Call({})", function_name(module, *idx)),
+        CallGeneric(idx) => {
+            let inst = module.function_instantiation_at(*idx);
+            format!(
+                "CallGeneric({}{:?})",
+                function_name(module, inst.handle),
+                types(module, inst.type_parameters)
+            )
+        }
+        Pack(idx) => format!("Pack({})", struct_name(module, *idx)),
+        Unpack(idx) => format!("Unpack({})", struct_name(module, *idx)),
+        MutBorrowGlobal(idx) => format!("MutBorrowGlobal({})", struct_name(module, *idx)),
+        ImmBorrowGlobal(idx) => format!("ImmBorrowGlobal({})", struct_name(module, *idx)),
+        Exists(idx) => format!("Exists({})", struct_name(module, *idx)),
+        MoveFrom(idx) => format!("MoveFrom({})", struct_name(module, *idx)),
+        MoveTo(idx) => format!("MoveTo({})", struct_name(module, *idx)),
+        PackGeneric(idx) => format!("PackGeneric({})", generic(*idx)),
+        UnpackGeneric(idx) => format!("UnpackGeneric({})", generic(*idx)),
+        MutBorrowGlobalGeneric(idx) => format!("MutBorrowGlobalGeneric({})", generic(*idx)),
+        ImmBorrowGlobalGeneric(idx) => format!("ImmBorrowGlobalGeneric({})", generic(*idx)),
+        ExistsGeneric(idx) => format!("ExistsGeneric({})", generic(*idx)),
+        MoveFromGeneric(idx) => format!("MoveFromGeneric({})", generic(*idx)),
+        MoveToGeneric(idx) => format!("MoveToGeneric({})", generic(*idx)),
+        MutBorrowField(idx) => format!("MutBorrowField({})", field_name(module, *idx)),
+        ImmBorrowField(idx) => format!("ImmBorrowField({})", field_name(module, *idx)),
+        MutBorrowFieldGeneric(idx) => format!("MutBorrowFieldGeneric({})", generic_field(*idx)),
+        ImmBorrowFieldGeneric(idx) => format!("ImmBorrowFieldGeneric({})", generic_field(*idx)),
+        VecPack(idx, n) => format!("VecPack({:?}, {})", types(module, *idx), n),
+        VecUnpack(idx, n) => format!("VecUnpack({:?}, {})", types(module, *idx), n),
+        VecLen(idx) => format!("VecLen({:?})", types(module, *idx)),
+        VecImmBorrow(idx) => format!("VecImmBorrow({:?})", types(module, *idx)),
+        VecMutBorrow(idx) => format!("VecMutBorrow({:?})", types(module, *idx)),
+        VecPushBack(idx) => format!("VecPushBack({:?})", types(module, *idx)),
+        VecPopBack(idx) => format!("VecPopBack({:?})", types(module, *idx)),
+        VecSwap(idx) => format!("VecSwap({:?})", types(module, *idx)),
+        instruction => format!("{:?}", instruction),
+    }
+}
+
+/// The functions defined in `module`, by name.
+fn function_definitions(module: &CompiledModule) -> BTreeMap<&IdentStr, FunctionDefinition> {
+    module
+        .function_defs()
+        .iter()
+        .map(|def| {
+            let handle = module.function_handle_at(def.function);
+            let definition = FunctionDefinition {
+                visibility: def.visibility,
+                is_entry: def.is_entry,
+                parameters: types(module, handle.parameters),
+                return_: types(module, handle.return_),
+                code: def.code.as_ref().map(|code| {
+                    let instructions = code
+                        .code
+                        .iter()
+                        .map(|instruction| resolve(module, instruction))
+                        .collect();
+                    (types(module, code.locals), instructions)
+                }),
+            };
+            (module.identifier_at(handle.name), definition)
+        })
+        .collect()
+}
+
+// Whether `new` keeps every struct, exposed function and friend of `old`, and
+// the definition of each of its functions
+fn extends(
+    old: &CompiledModule,
+    new: &CompiledModule,
+    old_api: &normalized::Module,
+    new_api: &normalized::Module,
+) -> bool {
+    let new_functions = function_definitions(new);
+    old_api
+        .structs
+        .iter()
+        .all(|(name, s)| new_api.structs.get(name) == Some(s))
+        && old_api
+            .exposed_functions
+            .iter()
+            .all(|(name, f)| new_api.exposed_functions.get(name) == Some(f))
+        && old_api
+            .friends
+            .iter()
+            .all(|friend| new_api.friends.contains(friend))
+        && function_definitions(old)
+            .iter()
+            .all(|(name, f)| new_functions.get(name) == Some(f))
+}
+
+/// Compare the modules of the current version of a package, `old`, with
+/// those of the new version.
+pub fn check_upgrade(old: &[CompiledModule], new: &[CompiledModule]) -> UpgradeReport {
+    let old: BTreeMap<_, _> = old.iter().map(|m| (m.self_id(), m)).collect();
+    let new: BTreeMap<_, _> = new.iter().map(|m| (m.self_id(), m)).collect();
+
+    let mut modules = Vec::new();
+    for (id, old_module) in &old {
+        let report = match new.get(id) {
+            None => ModuleReport {
+                module: id.short_str_lossless(),
+                change: ModuleChange::Removed,
+                linking_compatible: false,
+                layout_compatible: false,
+            },
+            Some(new_module) => {
+                let old_api = normalized::Module::new(old_module);
+                let new_api = normalized::Module::new(new_module);
+                let change = if old_module == new_module {
+                    ModuleChange::Unchanged
+                } else if extends(old_module, new_module, &old_api, &new_api) {
+                    ModuleChange::Extended
+                } else {
+                    ModuleChange::Changed
+                };
+                ModuleReport {
+                    module: id.short_str_lossless(),
+                    change,
+                    linking_compatible: Compatibility::new(true, false, false)
+                        .check(&old_api, &new_api)
+                        .is_ok(),
+                    layout_compatible: Compatibility::new(false, true, false)
+                        .check(&old_api, &new_api)
+                        .is_ok(),
+                }
+            }
+        };
+        modules.push(report);
+    }
+    for id in new.keys().filter(|id| !old.contains_key(*id)) {
+        modules.push(ModuleReport {
+            module: id.short_str_lossless(),
+            change: ModuleChange::Added,
+            linking_compatible: true,
+            layout_compatible: true,
+        });
+    }
+
+    let all = |f: fn(&ModuleReport) -> bool| modules.iter().all(f);
+    let strictest_policy = if all(|m| m.change == ModuleChange::Unchanged) {
+        Some(UpgradePolicy::DepOnly)
+    } else if all(|m| {
+        matches!(
+            m.change,
+            ModuleChange::Unchanged | ModuleChange::Extended | ModuleChange::Added
+        )
+    }) {
+        Some(UpgradePolicy::Additive)
+    } else if all(|m| {
+        m.change != ModuleChange::Removed && m.linking_compatible && m.layout_compatible
+    }) {
+        Some(UpgradePolicy::Compatible)
+    } else {
+        None
+    };
+    UpgradeReport {
+        modules,
+        strictest_policy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_binary_format::file_format::{
+        empty_module, CodeUnit, FunctionDefinition as Definition, FunctionHandle, IdentifierIndex,
+        ModuleHandleIndex,
+    };
+    use move_core_types::identifier::Identifier;
+
+    fn module(name: &str) -> CompiledModule {
+        let mut module = empty_module();
+        module.identifiers[0] = Identifier::new(name).unwrap();
+        module
+    }
+
+    /// `module(name)` with a private function `f` whose body is `code`.
+    fn module_with_function(name: &str, code: Vec<Bytecode>) -> CompiledModule {
+        let mut module = module(name);
+        module.identifiers.push(Identifier::new("f").unwrap());
+        module.function_handles.push(FunctionHandle {
+            module: ModuleHandleIndex(0),
+            name: IdentifierIndex(1),
+            parameters: SignatureIndex(0),
+            return_: SignatureIndex(0),
+            type_parameters: vec![],
+        });
+        module.function_defs.push(Definition {
+            function: FunctionHandleIndex(0),
+            visibility: Visibility::Private,
+            is_entry: false,
+            acquires_global_resources: vec![],
+            code: Some(CodeUnit {
+                locals: SignatureIndex(0),
+                code,
+            }),
+        });
+        module
+    }
+
+    #[test]
+    fn test_strictest_policy() {
+        let old = vec![module("coin")];
+
+        let report = check_upgrade(&old, &[module("coin")]);
+        assert_eq!(report.strictest_policy, Some(UpgradePolicy::DepOnly));
+
+        let report = check_upgrade(&old, &[module("coin"), module("pool")]);
+        assert_eq!(report.strictest_policy, Some(UpgradePolicy::Additive));
+        assert!(report.allowed_by(UpgradePolicy::Compatible));
+        assert!(!report.allowed_by(UpgradePolicy::DepOnly));
+        assert_eq!(report.modules[1].change, ModuleChange::Added);
+
+        let report = check_upgrade(&old, &[module("pool")]);
+        assert_eq!(report.strictest_policy, None);
+        assert_eq!(report.modules[0].change, ModuleChange::Removed);
+    }
+
+    #[test]
+    fn test_changed_function_body() {
+        let old = vec![module_with_function("coin", vec![Bytecode::Ret])];
+
+        // A new function keeps the module additive
+        let mut extended = module_with_function("coin", vec![Bytecode::Ret]);
+        extended.identifiers.push(Identifier::new("g").unwrap());
+        extended.function_handles.push(FunctionHandle {
+            module: ModuleHandleIndex(0),
+            name: IdentifierIndex(2),
+            parameters: SignatureIndex(0),
+            return_: SignatureIndex(0),
+            type_parameters: vec![],
+        });
+        extended.function_defs.push(Definition {
+            function: FunctionHandleIndex(1),
+            visibility: Visibility::Private,
+            is_entry: false,
+            acquires_global_resources: vec![],
+            code: Some(CodeUnit {
+                locals: SignatureIndex(0),
+                code: vec![Bytecode::Call(FunctionHandleIndex(0)), Bytecode::Ret],
+            }),
+        });
+        let report = check_upgrade(&old, &[extended]);
+        assert_eq!(report.modules[0].change, ModuleChange::Extended);
+        assert_eq!(report.strictest_policy, Some(UpgradePolicy::Additive));
+
+        // A new body with the same interface is only compatible
+        let changed =
+            module_with_function("coin", vec![Bytecode::LdTrue, Bytecode::Pop, Bytecode::Ret]);
+        let report = check_upgrade(&old, &[changed]);
+        assert_eq!(report.modules[0].change, ModuleChange::Changed);
+        assert_eq!(report.strictest_policy, Some(UpgradePolicy::Compatible));
+        assert_eq!(
+            UpgradeCap::new(AccountAddress::ONE).authorize(UpgradePolicy::Additive, &report),
+            Err(UpgradeError::Incompatible(UpgradePolicy::Additive))
+        );
+    }
+
+    #[test]
+    fn test_cap_policies_and_versions() {
+        let original = AccountAddress::from_hex_literal("0xcafe").unwrap();
+        let mut cap = UpgradeCap::new(original);
+        let report = check_upgrade(&[module("coin")], &[module("coin"), module("pool")]);
+
+        cap.restrict(UpgradePolicy::Additive).unwrap();
+        assert_eq!(
+            cap.authorize(UpgradePolicy::Compatible, &report),
+            Err(UpgradeError::TooPermissive {
+                requested: UpgradePolicy::Compatible,
+                allowed: UpgradePolicy::Additive,
+            })
+        );
+        cap.authorize(UpgradePolicy::Additive, &report).unwrap();
+        assert_eq!(
+            cap.authorize(UpgradePolicy::DepOnly, &report),
+            Err(UpgradeError::Incompatible(UpgradePolicy::DepOnly))
+        );
+        assert!(cap.restrict(UpgradePolicy::Compatible).is_err());
+
+        let v2 = cap.commit();
+        assert_eq!(cap.version, 2);
+        assert_ne!(v2, original);
+        assert_eq!(v2, package_id(original, 2));
+        assert_eq!(package_id(original, 1), original);
+        assert_eq!(
+            UpgradePolicy::from_u8(UpgradePolicy::DepOnly.as_u8()),
+            Some(UpgradePolicy::DepOnly)
+        );
+        assert_eq!("dep-only".parse(), Ok(UpgradePolicy::DepOnly));
+    }
+}
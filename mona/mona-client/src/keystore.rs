@@ -0,0 +1,242 @@
+//! Keys that sign transactions.
+//!
+//! The keystore is a JSON file, `keystore_path` of the configuration, listing
+//! hex encoded secp256k1 private keys together with their addresses. The keys
+//! are not encrypted, so the file is only readable by its owner.
+//!
+//! A signature is `flag || compact signature || compressed public key`, hex
+//! encoded, over the SHA3-256 of the intent prefix followed by the BCS bytes of
+//! the transaction. The address of a key is the SHA3-256 of `flag || public key`.
+
+use crate::types::{normalize_address, ClientError, TransactionData};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Signature scheme flag of secp256k1 keys.
+pub const SECP256K1_FLAG: u8 = 0x01;
+
+/// Intent prefix of transaction data: scope, version, app ID.
+const TRANSACTION_INTENT: [u8; 3] = [0, 0, 0];
+
+const SIGNATURE_LEN: usize = 1 + 64 + 33;
+
+#[derive(Serialize, Deserialize)]
+struct StoredKey {
+    address: String,
+    scheme: String,
+    private_key: String,
+}
+
+pub struct Keystore {
+    path: PathBuf,
+    keys: BTreeMap<String, SecretKey>,
+}
+
+/// Address of the key `public_key`.
+pub fn address_of(public_key: &PublicKey) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update([SECP256K1_FLAG]);
+    hasher.update(public_key.serialize());
+    format!("0x{}", hex::encode(hasher.finalize()))
+}
+
+// Digest that is signed for the transaction bytes `tx_bytes`
+fn signing_digest(tx_bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(TRANSACTION_INTENT);
+    hasher.update(tx_bytes);
+    hasher.finalize().into()
+}
+
+/// Check `signature` over `tx_bytes`, returning the address of the signer.
+pub fn verify_signature(tx_bytes: &[u8], signature: &str) -> Result<String, ClientError> {
+    let bytes = hex::decode(signature.trim_start_matches("0x"))
+        .map_err(|_| ClientError::InvalidSignature)?;
+    if bytes.len() != SIGNATURE_LEN || bytes[0] != SECP256K1_FLAG {
+        return Err(ClientError::InvalidSignature);
+    }
+    let sig = Signature::from_compact(&bytes[1..65]).map_err(|_| ClientError::InvalidSignature)?;
+    let public_key =
+        PublicKey::from_slice(&bytes[65..]).map_err(|_| ClientError::InvalidSignature)?;
+    let message = Message::from_digest(signing_digest(tx_bytes));
+    Secp256k1::verification_only()
+        .verify_ecdsa(&message, &sig, &public_key)
+        .map_err(|_| ClientError::InvalidSignature)?;
+    Ok(address_of(&public_key))
+}
+
+impl Keystore {
+    /// Open the keystore at `path`; a missing file is an empty keystore.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ClientError> {
+        let path = path.as_ref().to_path_buf();
+        let mut keys = BTreeMap::new();
+        if path.exists() {
+            let stored: Vec<StoredKey> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            for key in stored {
+                let secret = parse_secret_key(&key.private_key)?;
+                keys.insert(normalize_address(&key.address)?, secret);
+            }
+        }
+        Ok(Keystore { path, keys })
+    }
+
+    pub fn save(&self) -> Result<(), ClientError> {
+        let stored: Vec<StoredKey> = self
+            .keys
+            .iter()
+            .map(|(address, secret)| StoredKey {
+                address: address.clone(),
+                scheme: "secp256k1".to_string(),
+                private_key: hex::encode(secret.secret_bytes()),
+            })
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Written to a file only readable by its owner next to the keystore and
+        // renamed over it, so an interrupted save leaves the old keys in place
+        let contents = serde_json::to_string_pretty(&stored)?;
+        let file_name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_name = format!(".{}.{}.tmp", file_name, std::process::id());
+        let temp_path = self.path.with_file_name(temp_name);
+        let _ = fs::remove_file(&temp_path);
+        let written = write_private(&temp_path, contents.as_bytes())
+            .and_then(|()| fs::rename(&temp_path, &self.path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok(written?)
+    }
+
+    /// Add a new random key, returning its address.
+    pub fn generate(&mut self) -> String {
+        let (secret, public) = Secp256k1::new().generate_keypair(&mut rand::thread_rng());
+        let address = address_of(&public);
+        self.keys.insert(address.clone(), secret);
+        address
+    }
+
+    /// Add the hex encoded private key `private_key`, returning its address.
+    pub fn import(&mut self, private_key: &str) -> Result<String, ClientError> {
+        let secret = parse_secret_key(private_key)?;
+        let address = address_of(&secret.public_key(&Secp256k1::signing_only()));
+        self.keys.insert(address.clone(), secret);
+        Ok(address)
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    /// The address to sign with: `preferred` if given, the first key otherwise.
+    pub fn signer(&self, preferred: Option<&str>) -> Result<String, ClientError> {
+        match preferred {
+            Some(address) => {
+                let address = normalize_address(address)?;
+                if !self.keys.contains_key(&address) {
+                    return Err(ClientError::KeyNotFound(address));
+                }
+                Ok(address)
+            }
+            None => self
+                .keys
+                .keys()
+                .next()
+                .cloned()
+                .ok_or(ClientError::EmptyKeystore),
+        }
+    }
+
+    /// Sign `tx` with the key of its sender, returning the hex encoded signature.
+    pub fn sign(&self, tx: &TransactionData) -> Result<String, ClientError> {
+        let sender = normalize_address(&tx.sender)?;
+        let secret = self
+            .keys
+            .get(&sender)
+            .ok_or_else(|| ClientError::KeyNotFound(sender.clone()))?;
+        let secp = Secp256k1::signing_only();
+        let message = Message::from_digest(signing_digest(&tx.to_bytes()?));
+        let signature = secp.sign_ecdsa(&message, secret);
+
+        let mut bytes = Vec::with_capacity(SIGNATURE_LEN);
+        bytes.push(SECP256K1_FLAG);
+        bytes.extend_from_slice(&signature.serialize_compact());
+        bytes.extend_from_slice(&secret.public_key(&secp).serialize());
+        Ok(hex::encode(bytes))
+    }
+}
+
+// Create `path`, readable only by its owner, with `contents`
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn parse_secret_key(private_key: &str) -> Result<SecretKey, ClientError> {
+    let invalid = || ClientError::InvalidKey("expected 32 hex encoded bytes".to_string());
+    let bytes: [u8; 32] = hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|_| invalid())?
+        .try_into()
+        .map_err(|_| invalid())?;
+    SecretKey::from_byte_array(&bytes).map_err(|e| ClientError::InvalidKey(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransactionKind;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kari.keystore");
+        let mut keystore = Keystore::open(&path).unwrap();
+        let sender = keystore.generate();
+        keystore.save().unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // Only the keystore is left in the directory
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let keystore = Keystore::open(&path).unwrap();
+        assert_eq!(keystore.signer(None).unwrap(), sender);
+        let tx = TransactionData {
+            sender: sender.clone(),
+            kind: TransactionKind::SplitCoin {
+                coin: normalize_address("0x5").unwrap(),
+                amounts: vec![10],
+            },
+            gas_payment: None,
+            gas_budget: 1_000,
+            gas_price: 1,
+        };
+        let signature = keystore.sign(&tx).unwrap();
+        let tx_bytes = tx.to_bytes().unwrap();
+        assert_eq!(verify_signature(&tx_bytes, &signature).unwrap(), sender);
+
+        let mut tampered = tx.clone();
+        tampered.gas_budget = 2_000;
+        assert_ne!(
+            verify_signature(&tampered.to_bytes().unwrap(), &signature).ok(),
+            Some(sender)
+        );
+    }
+}
@@ -0,0 +1,14 @@
+//! Client side of a Kari node: transaction types, the local keystore and the
+//! node's JSON-RPC API and system transactions, plus an in-process node to
+//! test clients against.
+
+pub mod keystore;
+pub mod local_node;
+pub mod rpc;
+pub mod system;
+pub mod types;
+
+pub use keystore::Keystore;
+pub use local_node::LocalNode;
+pub use rpc::{HttpTransport, RpcClient, RpcTransport};
+pub use types::{ClientError, TransactionData, TransactionKind, TransactionResponse};
@@ -0,0 +1,1082 @@
+//! An in-process node answering the JSON-RPC API from memory.
+//!
+//! `LocalNode` keeps objects, transactions and events in memory and executes
+//! the built-in transaction kinds, so clients can be tested without a network.
+//! Move code is not run: a `MoveCall` only checks that the package exists and
+//! records an event typed after the called function. Gas is `BASE_GAS_UNITS`
+//! per transaction plus `PUBLISH_BYTE_UNITS` per published module byte, times
+//! the gas price.
+//!
+//! Every committed transaction is a block of its own, opened by the system
+//! transactions of [`crate::system`]: the `Clock` at `0x6` is set to the wall
+//! clock, or to the time fixed with [`LocalNode::set_clock_ms`], and the
+//! `Random` at `0x8` moves to the next round of a [`DevnetBeacon`].
+//!
+//! Transactions whose sender is on the `DenyList` of a coin type are rejected
+//! when they take a coin of that type as input, or name it as a type argument.
+
+use crate::keystore::verify_signature;
+use crate::rpc::RpcTransport;
+use crate::system::{
+    DenyList, DevnetBeacon, RandomState, SystemTransaction, CLOCK_OBJECT_ID, CLOCK_TYPE,
+    DENY_LIST_OBJECT_ID, DENY_LIST_TYPE, RANDOM_OBJECT_ID, RANDOM_TYPE,
+};
+use crate::types::{
+    normalize_address, Balance, ClientError, EventFilter, EventInfo, ExecutionStatus, ObjectInfo,
+    Owner, TransactionData, TransactionKind, TransactionResponse, GAS_COIN_TYPE,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BASE_GAS_UNITS: u64 = 1_000;
+pub const PUBLISH_BYTE_UNITS: u64 = 10;
+
+/// Type of package objects.
+pub const PACKAGE_TYPE: &str = "package";
+/// Type of the capability handed to the publisher of a package.
+pub const UPGRADE_CAP_TYPE: &str = "0x2::package::UpgradeCap";
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const TRANSACTION_REJECTED: i64 = -32002;
+
+#[derive(Default)]
+struct Inner {
+    objects: BTreeMap<String, ObjectInfo>,
+    transactions: BTreeMap<String, TransactionResponse>,
+    events: Vec<EventInfo>,
+    faucet_requests: u64,
+    round: u64,
+    /// Time of the next blocks, the wall clock if unset.
+    clock_ms: Option<u64>,
+    beacon: DevnetBeacon,
+    random: RandomState,
+    system_transactions: Vec<SystemTransaction>,
+}
+
+impl Inner {
+    fn update_system_object(&mut self, object_id: &str, fields: Value) {
+        let object = self
+            .objects
+            .get_mut(object_id)
+            .expect("system objects exist");
+        object.fields = fields;
+        object.version += 1;
+        seal(object);
+    }
+
+    fn deny_list(&self) -> DenyList {
+        serde_json::from_value(self.objects[DENY_LIST_OBJECT_ID].fields.clone())
+            .expect("the deny list object holds a `DenyList`")
+    }
+
+    // Run the system transactions opening the next block
+    fn begin_block(&mut self) {
+        let epoch = 0;
+        self.round += 1;
+        let previous = self.objects[CLOCK_OBJECT_ID].fields["timestamp_ms"]
+            .as_u64()
+            .unwrap_or_default();
+        // The clock never goes backwards
+        let commit_timestamp_ms = self.clock_ms.unwrap_or_else(now_ms).max(previous);
+        self.update_system_object(
+            CLOCK_OBJECT_ID,
+            json!({ "timestamp_ms": commit_timestamp_ms }),
+        );
+        self.system_transactions
+            .push(SystemTransaction::ConsensusCommitPrologue {
+                epoch,
+                round: self.round,
+                commit_timestamp_ms,
+            });
+
+        let update = self.random.advance(&self.beacon, epoch);
+        let fields = random_fields(&self.random);
+        self.update_system_object(RANDOM_OBJECT_ID, fields);
+        self.system_transactions.push(update);
+    }
+}
+
+/// In-memory node. Clones share the same state.
+#[derive(Clone)]
+pub struct LocalNode {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for LocalNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid_params(message: impl ToString) -> ClientError {
+    ClientError::Rpc {
+        code: INVALID_PARAMS,
+        message: message.to_string(),
+    }
+}
+
+fn rejected(message: impl ToString) -> ClientError {
+    ClientError::Rpc {
+        code: TRANSACTION_REJECTED,
+        message: message.to_string(),
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, ClientError> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value)
+        .map_err(|e| invalid_params(format!("Invalid parameter {}: {}", index, e)))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// ID of the `index`th object created by the transaction `digest`
+fn derive_id(digest: &str, index: usize) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(digest.as_bytes());
+    hasher.update((index as u64).to_le_bytes());
+    format!("0x{}", hex::encode(hasher.finalize()))
+}
+
+fn seal(object: &mut ObjectInfo) {
+    let content = json!([
+        object.object_id,
+        object.version,
+        object.type_,
+        object.owner,
+        object.fields
+    ]);
+    object.digest = hex::encode(Sha3_256::digest(content.to_string()));
+}
+
+fn new_object(object_id: String, type_: &str, owner: Owner, fields: Value) -> ObjectInfo {
+    let mut object = ObjectInfo {
+        object_id,
+        version: 1,
+        digest: String::new(),
+        type_: type_.to_string(),
+        owner,
+        fields,
+    };
+    seal(&mut object);
+    object
+}
+
+fn random_fields(random: &RandomState) -> Value {
+    json!({
+        "epoch": random.epoch,
+        "randomness_round": random.randomness_round,
+        "random_bytes": hex::encode(&random.random_bytes),
+    })
+}
+
+// Coin types of the coins `kind` and its gas coin take as input, followed by
+// the type arguments of a Move call
+fn input_coin_types(
+    objects: &BTreeMap<String, ObjectInfo>,
+    kind: &TransactionKind,
+    gas_coin: &str,
+) -> Vec<String> {
+    let (object_ids, type_args): (Vec<&String>, &[String]) = match kind {
+        TransactionKind::MoveCall {
+            type_args, args, ..
+        } => (args.iter().collect(), type_args),
+        TransactionKind::Publish { .. } => (Vec::new(), &[]),
+        TransactionKind::TransferObject { object_id, .. } => (vec![object_id], &[]),
+        TransactionKind::SplitCoin { coin, .. } => (vec![coin], &[]),
+        TransactionKind::MergeCoins { primary, coins } => {
+            (std::iter::once(primary).chain(coins).collect(), &[])
+        }
+        TransactionKind::Pay { coins, .. } => (coins.iter().collect(), &[]),
+    };
+    object_ids
+        .into_iter()
+        .map(String::as_str)
+        .chain([gas_coin])
+        .filter_map(|object_id| {
+            let object = objects.get(&normalize_address(object_id).ok()?)?;
+            object.coin_type().map(str::to_string)
+        })
+        .chain(type_args.iter().cloned())
+        .collect()
+}
+
+fn gas_units(kind: &TransactionKind) -> u64 {
+    match kind {
+        TransactionKind::Publish { modules, .. } => {
+            let bytes: usize = modules.iter().map(Vec::len).sum();
+            BASE_GAS_UNITS + bytes as u64 * PUBLISH_BYTE_UNITS
+        }
+        _ => BASE_GAS_UNITS,
+    }
+}
+
+// Effects of a transaction on a copy of the objects
+struct Execution {
+    objects: BTreeMap<String, ObjectInfo>,
+    sender: String,
+    digest: String,
+    timestamp_ms: u64,
+    created: Vec<String>,
+    mutated: BTreeSet<String>,
+    deleted: Vec<String>,
+    events: Vec<EventInfo>,
+}
+
+impl Execution {
+    fn new(objects: &BTreeMap<String, ObjectInfo>, sender: &str, digest: &str) -> Self {
+        Execution {
+            objects: objects.clone(),
+            sender: sender.to_string(),
+            digest: digest.to_string(),
+            timestamp_ms: now_ms(),
+            created: Vec::new(),
+            mutated: BTreeSet::new(),
+            deleted: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn owned(&self, object_id: &str) -> Result<&ObjectInfo, String> {
+        let object_id = normalize_address(object_id).map_err(|e| e.to_string())?;
+        let object = self
+            .objects
+            .get(&object_id)
+            .ok_or_else(|| format!("Object {} not found", object_id))?;
+        if object.owner != Owner::AddressOwner(self.sender.clone()) {
+            return Err(format!("Object {} is not owned by the sender", object_id));
+        }
+        Ok(object)
+    }
+
+    // Coin type and balance of a coin owned by the sender
+    fn owned_coin(&self, object_id: &str) -> Result<(String, u64), String> {
+        let coin = self.owned(object_id)?;
+        match coin.coin_balance() {
+            Some(balance) => Ok((coin.type_.clone(), balance)),
+            None => Err(format!("Object {} is not a coin", coin.object_id)),
+        }
+    }
+
+    fn object_mut(&mut self, object_id: &str) -> &mut ObjectInfo {
+        let object_id = normalize_address(object_id).expect("checked by `owned`");
+        self.mutated.insert(object_id.clone());
+        self.objects
+            .get_mut(&object_id)
+            .expect("checked by `owned`")
+    }
+
+    fn set_balance(&mut self, coin: &str, balance: u64) {
+        self.object_mut(coin).fields["balance"] = json!(balance);
+    }
+
+    fn create(&mut self, type_: &str, owner: Owner, fields: Value) -> String {
+        let object_id = derive_id(&self.digest, self.created.len());
+        let object = new_object(object_id.clone(), type_, owner, fields);
+        self.objects.insert(object_id.clone(), object);
+        self.created.push(object_id.clone());
+        object_id
+    }
+
+    fn delete(&mut self, object_id: &str) {
+        let object_id = normalize_address(object_id).expect("checked by `owned`");
+        self.objects.remove(&object_id);
+        self.mutated.remove(&object_id);
+        self.deleted.push(object_id);
+    }
+
+    // Merge `coins` into `primary`
+    fn merge(&mut self, primary: &str, coins: &[String]) -> Result<(), String> {
+        let (coin_type, mut balance) = self.owned_coin(primary)?;
+        let mut seen = BTreeSet::from([normalize_address(primary).map_err(|e| e.to_string())?]);
+        for coin in coins {
+            let (other_type, amount) = self.owned_coin(coin)?;
+            if !seen.insert(normalize_address(coin).map_err(|e| e.to_string())?) {
+                return Err(format!("Coin {} is merged twice", coin));
+            }
+            if other_type != coin_type {
+                return Err(format!("Cannot merge {} into {}", other_type, coin_type));
+            }
+            balance = balance.checked_add(amount).ok_or("Coin balance overflow")?;
+            self.delete(coin);
+        }
+        self.set_balance(primary, balance);
+        Ok(())
+    }
+
+    // Split coins of `amounts` off `coin`, owned by the matching `owners`
+    fn split(&mut self, coin: &str, amounts: &[u64], owners: &[String]) -> Result<(), String> {
+        let (coin_type, balance) = self.owned_coin(coin)?;
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or("Amount overflow")?;
+        if total > balance {
+            return Err(format!(
+                "Insufficient balance: coin has {}, needs {}",
+                balance, total
+            ));
+        }
+        self.set_balance(coin, balance - total);
+        for (amount, owner) in amounts.iter().zip(owners) {
+            self.create(
+                &coin_type,
+                Owner::AddressOwner(owner.clone()),
+                json!({ "balance": amount }),
+            );
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, kind: &TransactionKind) -> Result<(), String> {
+        match kind {
+            TransactionKind::MoveCall {
+                package,
+                module,
+                function,
+                type_args,
+                args,
+            } => {
+                let package = normalize_address(package).map_err(|e| e.to_string())?;
+                if self
+                    .objects
+                    .get(&package)
+                    .is_none_or(|o| o.type_ != PACKAGE_TYPE)
+                {
+                    return Err(format!("Package {} not found", package));
+                }
+                self.events.push(EventInfo {
+                    tx_digest: self.digest.clone(),
+                    sequence: self.events.len() as u64,
+                    sender: self.sender.clone(),
+                    type_: format!("{}::{}::{}", package, module, function),
+                    data: json!({ "type_args": type_args, "args": args }),
+                    timestamp_ms: self.timestamp_ms,
+                });
+            }
+            TransactionKind::Publish {
+                modules,
+                dependencies,
+            } => {
+                if modules.is_empty() {
+                    return Err("No modules to publish".to_string());
+                }
+                let mut deps = Vec::new();
+                for dep in dependencies {
+                    let dep = normalize_address(dep).map_err(|e| e.to_string())?;
+                    if self
+                        .objects
+                        .get(&dep)
+                        .is_none_or(|o| o.type_ != PACKAGE_TYPE)
+                    {
+                        return Err(format!("Dependency {} not found", dep));
+                    }
+                    deps.push(dep);
+                }
+                let package = self.create(
+                    PACKAGE_TYPE,
+                    Owner::Immutable,
+                    json!({ "modules": modules.len(), "dependencies": deps }),
+                );
+                self.create(
+                    UPGRADE_CAP_TYPE,
+                    Owner::AddressOwner(self.sender.clone()),
+                    json!({ "package": package, "version": 1 }),
+                );
+            }
+            TransactionKind::TransferObject {
+                object_id,
+                recipient,
+            } => {
+                let recipient = normalize_address(recipient).map_err(|e| e.to_string())?;
+                self.owned(object_id)?;
+                self.object_mut(object_id).owner = Owner::AddressOwner(recipient);
+            }
+            TransactionKind::SplitCoin { coin, amounts } => {
+                let owners = vec![self.sender.clone(); amounts.len()];
+                self.split(coin, amounts, &owners)?;
+            }
+            TransactionKind::MergeCoins { primary, coins } => {
+                self.merge(primary, coins)?;
+            }
+            TransactionKind::Pay {
+                coins,
+                recipients,
+                amounts,
+            } => {
+                if recipients.len() != amounts.len() {
+                    return Err("Expected one amount per recipient".to_string());
+                }
+                let (primary, rest) = coins.split_first().ok_or("No coins to pay with")?;
+                let recipients = recipients
+                    .iter()
+                    .map(|r| normalize_address(r))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())?;
+                self.merge(primary, rest)?;
+                self.split(primary, amounts, &recipients)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Bump the version of the mutated objects and build the response
+    fn finish(&mut self, status: ExecutionStatus, gas_used: u64) -> TransactionResponse {
+        for object_id in &self.mutated {
+            if let Some(object) = self.objects.get_mut(object_id) {
+                if !self.created.contains(object_id) {
+                    object.version += 1;
+                }
+                seal(object);
+            }
+        }
+        let mutated = self
+            .mutated
+            .iter()
+            .filter(|id| !self.created.contains(id))
+            .cloned()
+            .collect();
+        TransactionResponse {
+            digest: self.digest.clone(),
+            status,
+            gas_used,
+            created: self.created.clone(),
+            mutated,
+            deleted: self.deleted.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl LocalNode {
+    /// A node holding only the framework packages at `0x1` and `0x2` and the
+    /// `Clock`, `Random` and `DenyList` system objects.
+    pub fn new() -> Self {
+        let mut inner = Inner::default();
+        for (address, name) in [("0x1", "MoveStdlib"), ("0x2", "KanariFramework")] {
+            let object_id = normalize_address(address).expect("valid address");
+            let package = new_object(
+                object_id.clone(),
+                PACKAGE_TYPE,
+                Owner::Immutable,
+                json!({ "name": name }),
+            );
+            inner.objects.insert(object_id, package);
+        }
+        let clock = new_object(
+            CLOCK_OBJECT_ID.to_string(),
+            CLOCK_TYPE,
+            Owner::Shared,
+            json!({ "timestamp_ms": 0 }),
+        );
+        inner.objects.insert(CLOCK_OBJECT_ID.to_string(), clock);
+        let random = new_object(
+            RANDOM_OBJECT_ID.to_string(),
+            RANDOM_TYPE,
+            Owner::Shared,
+            random_fields(&inner.random),
+        );
+        inner.objects.insert(RANDOM_OBJECT_ID.to_string(), random);
+        let deny_list = new_object(
+            DENY_LIST_OBJECT_ID.to_string(),
+            DENY_LIST_TYPE,
+            Owner::Shared,
+            json!(DenyList::default()),
+        );
+        inner
+            .objects
+            .insert(DENY_LIST_OBJECT_ID.to_string(), deny_list);
+        LocalNode {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fix the time of the next blocks instead of following the wall clock.
+    pub fn set_clock_ms(&self, clock_ms: u64) {
+        self.lock().clock_ms = Some(clock_ms);
+    }
+
+    /// Draw the randomness of the next blocks from the beacon seeded with `seed`.
+    pub fn set_random_seed(&self, seed: u64) {
+        self.lock().beacon = DevnetBeacon::new(seed);
+    }
+
+    /// System transactions run so far, oldest first.
+    pub fn system_transactions(&self) -> Vec<SystemTransaction> {
+        self.lock().system_transactions.clone()
+    }
+
+    /// Deny `address` the use of coins of `coin_type`, as the holder of its
+    /// `DenyCap` would with `coin::deny_list_add`.
+    pub fn deny_list_add(&self, coin_type: &str, address: &str) -> Result<(), ClientError> {
+        let mut inner = self.lock();
+        let mut deny_list = inner.deny_list();
+        if deny_list.add(coin_type, address)? {
+            inner.update_system_object(DENY_LIST_OBJECT_ID, json!(deny_list));
+        }
+        Ok(())
+    }
+
+    /// Allow `address` to use coins of `coin_type` again, like
+    /// `coin::deny_list_remove`.
+    pub fn deny_list_remove(&self, coin_type: &str, address: &str) -> Result<(), ClientError> {
+        let mut inner = self.lock();
+        let mut deny_list = inner.deny_list();
+        if !deny_list.remove(coin_type, address)? {
+            return Err(invalid_params(format!(
+                "{} is not on the deny list of {}",
+                address, coin_type
+            )));
+        }
+        inner.update_system_object(DENY_LIST_OBJECT_ID, json!(deny_list));
+        Ok(())
+    }
+
+    /// Mint a gas coin of `amount` to `address`, returning its ID.
+    pub fn fund(&self, address: &str, amount: u64) -> Result<String, ClientError> {
+        let owner = normalize_address(address)?;
+        let mut inner = self.lock();
+        inner.faucet_requests += 1;
+        let object_id = derive_id("faucet", inner.faucet_requests as usize);
+        let coin = new_object(
+            object_id.clone(),
+            GAS_COIN_TYPE,
+            Owner::AddressOwner(owner),
+            json!({ "balance": amount }),
+        );
+        inner.objects.insert(object_id.clone(), coin);
+        Ok(object_id)
+    }
+
+    pub fn object(&self, object_id: &str) -> Result<Option<ObjectInfo>, ClientError> {
+        Ok(self
+            .lock()
+            .objects
+            .get(&normalize_address(object_id)?)
+            .cloned())
+    }
+
+    pub fn owned_objects(&self, owner: &str) -> Result<Vec<ObjectInfo>, ClientError> {
+        let owner = Owner::AddressOwner(normalize_address(owner)?);
+        Ok(self
+            .lock()
+            .objects
+            .values()
+            .filter(|object| object.owner == owner)
+            .cloned()
+            .collect())
+    }
+
+    pub fn balances(
+        &self,
+        owner: &str,
+        coin_type: Option<&str>,
+    ) -> Result<Vec<Balance>, ClientError> {
+        let mut balances: BTreeMap<String, Balance> = BTreeMap::new();
+        for object in self.owned_objects(owner)? {
+            let (Some(type_), Some(amount)) = (object.coin_type(), object.coin_balance()) else {
+                continue;
+            };
+            if coin_type.is_some_and(|coin_type| coin_type != type_) {
+                continue;
+            }
+            let balance = balances.entry(type_.to_string()).or_insert(Balance {
+                coin_type: type_.to_string(),
+                coin_object_count: 0,
+                total_balance: 0,
+            });
+            balance.coin_object_count += 1;
+            balance.total_balance = balance.total_balance.saturating_add(amount);
+        }
+        Ok(balances.into_values().collect())
+    }
+
+    pub fn transaction(&self, digest: &str) -> Option<TransactionResponse> {
+        self.lock().transactions.get(digest).cloned()
+    }
+
+    pub fn events(&self, filter: &EventFilter, limit: usize) -> Vec<EventInfo> {
+        self.lock()
+            .events
+            .iter()
+            .filter(|event| filter.matches(event))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Execute the transaction `tx_bytes`. Its signature is checked unless it
+    /// is a dry run, whose effects are not committed.
+    pub fn execute(
+        &self,
+        tx_bytes: &[u8],
+        signature: Option<&str>,
+    ) -> Result<TransactionResponse, ClientError> {
+        let tx = TransactionData::from_bytes(tx_bytes).map_err(invalid_params)?;
+        let sender = normalize_address(&tx.sender)?;
+        if let Some(signature) = signature {
+            let signer = verify_signature(tx_bytes, signature).map_err(rejected)?;
+            if signer != sender {
+                return Err(rejected("Transaction is not signed by its sender"));
+            }
+        }
+        let digest = tx.digest()?;
+
+        let mut inner = self.lock();
+        if inner.transactions.contains_key(&digest) {
+            return Err(rejected(format!(
+                "Transaction {} was already executed",
+                digest
+            )));
+        }
+        let gas_coin = match &tx.gas_payment {
+            Some(coin) => normalize_address(coin)?,
+            None => inner
+                .objects
+                .values()
+                .filter(|o| o.owner == Owner::AddressOwner(sender.clone()) && o.is_gas_coin())
+                .max_by_key(|o| o.coin_balance())
+                .map(|o| o.object_id.clone())
+                .ok_or_else(|| rejected(format!("No gas coin owned by {}", sender)))?,
+        };
+        let gas_balance = match inner.objects.get(&gas_coin) {
+            Some(coin)
+                if coin.is_gas_coin() && coin.owner == Owner::AddressOwner(sender.clone()) =>
+            {
+                coin.coin_balance().unwrap_or_default()
+            }
+            _ => {
+                return Err(rejected(format!(
+                    "{} is not a gas coin of the sender",
+                    gas_coin
+                )))
+            }
+        };
+        if gas_balance < tx.gas_budget {
+            return Err(rejected(format!(
+                "Gas coin balance {} is below the gas budget {}",
+                gas_balance, tx.gas_budget
+            )));
+        }
+
+        let input_coin_types = input_coin_types(&inner.objects, &tx.kind, &gas_coin);
+        if let Some(coin_type) = inner
+            .deny_list()
+            .denied(&sender, input_coin_types.iter().map(String::as_str))
+        {
+            return Err(rejected(format!(
+                "Address {} is denied for coin type {}",
+                sender, coin_type
+            )));
+        }
+
+        if signature.is_some() {
+            inner.begin_block();
+        }
+
+        let gas_used = gas_units(&tx.kind).saturating_mul(tx.gas_price);
+        let charged = gas_used.min(tx.gas_budget);
+        let charge = |objects: &BTreeMap<String, ObjectInfo>| {
+            let mut execution = Execution::new(objects, &sender, &digest);
+            execution.set_balance(&gas_coin, gas_balance - charged);
+            execution
+        };
+        let mut execution = charge(&inner.objects);
+        let result = if gas_used > tx.gas_budget {
+            Err(format!(
+                "Insufficient gas: needs {}, budget is {}",
+                gas_used, tx.gas_budget
+            ))
+        } else {
+            execution.apply(&tx.kind)
+        };
+        let status = match result {
+            Ok(()) => ExecutionStatus::Success,
+            Err(error) => {
+                // A failed transaction only pays for gas
+                execution = charge(&inner.objects);
+                ExecutionStatus::Failure { error }
+            }
+        };
+        let response = execution.finish(status, charged);
+
+        if signature.is_some() {
+            inner.objects = execution.objects;
+            inner.events.extend(response.events.iter().cloned());
+            inner.transactions.insert(digest, response.clone());
+        }
+        Ok(response)
+    }
+}
+
+impl RpcTransport for LocalNode {
+    fn request(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let params = match params {
+            Value::Array(params) => params,
+            Value::Null => Vec::new(),
+            _ => return Err(invalid_params("Expected positional parameters")),
+        };
+        let result = match method {
+            "kari_getObject" => json!(self.object(&param::<String>(&params, 0)?)?),
+            "kari_getOwnedObjects" => json!(self.owned_objects(&param::<String>(&params, 0)?)?),
+            "kari_getBalance" => {
+                let coin_type: Option<String> = param(&params, 1)?;
+                json!(self.balances(&param::<String>(&params, 0)?, coin_type.as_deref())?)
+            }
+            "kari_getTransactionBlock" => json!(self.transaction(&param::<String>(&params, 0)?)),
+            "kari_queryEvents" => {
+                let limit: Option<usize> = param(&params, 1)?;
+                json!(self.events(&param(&params, 0)?, limit.unwrap_or(usize::MAX)))
+            }
+            "kari_executeTransactionBlock" | "kari_dryRunTransactionBlock" => {
+                let tx_bytes = hex::decode(param::<String>(&params, 0)?.trim_start_matches("0x"))
+                    .map_err(invalid_params)?;
+                let signature: Option<String> = match method {
+                    "kari_executeTransactionBlock" => Some(param(&params, 1)?),
+                    _ => None,
+                };
+                json!(self.execute(&tx_bytes, signature.as_deref())?)
+            }
+            _ => {
+                return Err(ClientError::Rpc {
+                    code: METHOD_NOT_FOUND,
+                    message: format!("Method not found: {}", method),
+                })
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::Keystore;
+    use crate::rpc::RpcClient;
+
+    struct Setup {
+        node: LocalNode,
+        client: RpcClient,
+        keystore: Keystore,
+        sender: String,
+        _dir: tempfile::TempDir,
+    }
+
+    fn setup() -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = Keystore::open(dir.path().join("kari.keystore")).unwrap();
+        let sender = keystore.generate();
+        let node = LocalNode::new();
+        node.fund(&sender, 1_000_000).unwrap();
+        Setup {
+            client: RpcClient::new(node.clone()),
+            node,
+            keystore,
+            sender,
+            _dir: dir,
+        }
+    }
+
+    fn tx(sender: &str, kind: TransactionKind) -> TransactionData {
+        TransactionData {
+            sender: sender.to_string(),
+            kind,
+            gas_payment: None,
+            gas_budget: 10_000,
+            gas_price: 1,
+        }
+    }
+
+    fn execute(setup: &Setup, kind: TransactionKind) -> TransactionResponse {
+        let tx = tx(&setup.sender, kind);
+        let signature = setup.keystore.sign(&tx).unwrap();
+        setup.client.execute_transaction(&tx, &signature).unwrap()
+    }
+
+    #[test]
+    fn test_split_merge_and_pay() {
+        let setup = setup();
+        let gas = setup.client.get_gas_coins(&setup.sender).unwrap()[0]
+            .object_id
+            .clone();
+
+        let split = execute(
+            &setup,
+            TransactionKind::SplitCoin {
+                coin: gas.clone(),
+                amounts: vec![100, 200],
+            },
+        );
+        assert_eq!(split.status, ExecutionStatus::Success);
+        assert_eq!(split.created.len(), 2);
+        assert_eq!(split.mutated, vec![gas.clone()]);
+        assert_eq!(split.gas_used, BASE_GAS_UNITS);
+
+        let merge = execute(
+            &setup,
+            TransactionKind::MergeCoins {
+                primary: split.created[0].clone(),
+                coins: vec![split.created[1].clone()],
+            },
+        );
+        assert_eq!(merge.deleted, vec![split.created[1].clone()]);
+        let merged = setup.client.get_object(&split.created[0]).unwrap().unwrap();
+        assert_eq!(merged.coin_balance(), Some(300));
+        assert_eq!(merged.version, 2);
+
+        let recipient = normalize_address("0xb0b").unwrap();
+        let pay = execute(
+            &setup,
+            TransactionKind::Pay {
+                coins: vec![split.created[0].clone()],
+                recipients: vec![recipient.clone()],
+                amounts: vec![250],
+            },
+        );
+        assert_eq!(pay.status, ExecutionStatus::Success);
+        let balances = setup.client.get_balance(&recipient, None).unwrap();
+        assert_eq!(balances[0].total_balance, 250);
+
+        let balance = setup.client.get_balance(&setup.sender, None).unwrap();
+        assert_eq!(balance[0].coin_type, crate::types::KARI_COIN_TYPE);
+        assert_eq!(
+            balance[0].total_balance,
+            1_000_000 - 250 - 3 * BASE_GAS_UNITS
+        );
+
+        let fetched = setup.client.get_transaction_block(&pay.digest).unwrap();
+        assert_eq!(fetched, Some(pay));
+    }
+
+    #[test]
+    fn test_failed_transaction_pays_gas() {
+        let setup = setup();
+        let gas = setup.client.get_gas_coins(&setup.sender).unwrap()[0]
+            .object_id
+            .clone();
+        let response = execute(
+            &setup,
+            TransactionKind::SplitCoin {
+                coin: gas.clone(),
+                amounts: vec![10_000_000],
+            },
+        );
+        assert!(matches!(response.status, ExecutionStatus::Failure { .. }));
+        assert!(response.created.is_empty());
+        let coin = setup.node.object(&gas).unwrap().unwrap();
+        assert_eq!(coin.coin_balance(), Some(1_000_000 - BASE_GAS_UNITS));
+    }
+
+    #[test]
+    fn test_rejects_bad_signature_and_replay() {
+        let setup = setup();
+        let mut other = Keystore::open(setup._dir.path().join("other.keystore")).unwrap();
+        other.generate();
+        let kind = TransactionKind::TransferObject {
+            object_id: "0x2".to_string(),
+            recipient: "0x3".to_string(),
+        };
+        let tx = tx(&setup.sender, kind);
+        let forged = other
+            .sign(&TransactionData {
+                sender: other.signer(None).unwrap(),
+                ..tx.clone()
+            })
+            .unwrap();
+        assert!(matches!(
+            setup.client.execute_transaction(&tx, &forged),
+            Err(ClientError::Rpc {
+                code: TRANSACTION_REJECTED,
+                ..
+            })
+        ));
+
+        let signature = setup.keystore.sign(&tx).unwrap();
+        let response = setup.client.execute_transaction(&tx, &signature).unwrap();
+        // The framework package is immutable
+        assert!(matches!(response.status, ExecutionStatus::Failure { .. }));
+        assert!(setup.client.execute_transaction(&tx, &signature).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_and_events() {
+        let setup = setup();
+        let call = TransactionKind::MoveCall {
+            package: "0x2".to_string(),
+            module: "metadata".to_string(),
+            function: "register".to_string(),
+            type_args: vec![],
+            args: vec!["42".to_string()],
+        };
+        let dry_run = setup
+            .client
+            .dry_run_transaction(&tx(&setup.sender, call.clone()))
+            .unwrap();
+        assert_eq!(dry_run.events.len(), 1);
+        assert!(setup
+            .client
+            .query_events(&EventFilter::default(), 10)
+            .unwrap()
+            .is_empty());
+
+        let response = execute(&setup, call);
+        let events = setup
+            .client
+            .query_events(
+                &EventFilter {
+                    sender: Some(setup.sender.clone()),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap();
+        assert_eq!(events, response.events);
+        assert_eq!(
+            events[0].type_,
+            format!("{}::metadata::register", normalize_address("0x2").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_publish_creates_package_and_upgrade_cap() {
+        let setup = setup();
+        let response = execute(
+            &setup,
+            TransactionKind::Publish {
+                modules: vec![vec![0xa1, 0x1c, 0xeb, 0x0b]],
+                dependencies: vec!["0x1".to_string()],
+            },
+        );
+        assert_eq!(response.status, ExecutionStatus::Success);
+        assert_eq!(response.gas_used, BASE_GAS_UNITS + 4 * PUBLISH_BYTE_UNITS);
+        let package = setup
+            .client
+            .get_object(&response.created[0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(package.owner, Owner::Immutable);
+        let cap = setup
+            .client
+            .get_object(&response.created[1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(cap.type_, UPGRADE_CAP_TYPE);
+        assert_eq!(cap.owner, Owner::AddressOwner(setup.sender.clone()));
+    }
+
+    #[test]
+    fn test_blocks_update_clock_and_random() {
+        let setup = setup();
+        setup.node.set_clock_ms(1_000);
+        setup.node.set_random_seed(7);
+        let gas = setup.client.get_gas_coins(&setup.sender).unwrap()[0]
+            .object_id
+            .clone();
+        let split = |amount| TransactionKind::SplitCoin {
+            coin: gas.clone(),
+            amounts: vec![amount],
+        };
+
+        setup
+            .client
+            .dry_run_transaction(&tx(&setup.sender, split(1)))
+            .unwrap();
+        assert!(setup.node.system_transactions().is_empty());
+
+        execute(&setup, split(1));
+        setup.node.set_clock_ms(500);
+        execute(&setup, split(2));
+
+        let clock = setup.client.get_object(CLOCK_OBJECT_ID).unwrap().unwrap();
+        assert_eq!(clock.owner, Owner::Shared);
+        assert_eq!(clock.fields["timestamp_ms"], 1_000);
+        assert_eq!(clock.version, 3);
+        let random = setup.client.get_object(RANDOM_OBJECT_ID).unwrap().unwrap();
+        assert_eq!(random.fields["randomness_round"], 1);
+        assert_eq!(
+            random.fields["random_bytes"],
+            hex::encode(DevnetBeacon::new(7).round(1))
+        );
+
+        let system_transactions = setup.node.system_transactions();
+        assert_eq!(system_transactions.len(), 4);
+        assert_eq!(
+            system_transactions[2],
+            SystemTransaction::ConsensusCommitPrologue {
+                epoch: 0,
+                round: 2,
+                commit_timestamp_ms: 1_000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_deny_list_rejects_denied_sender() {
+        let setup = setup();
+        let coin_type = "0x42::my_coin::MY_COIN";
+        let coin = setup.node.fund(&setup.sender, 0).unwrap();
+        {
+            let mut inner = setup.node.lock();
+            let coin = inner.objects.get_mut(&coin).unwrap();
+            coin.type_ = format!("0x2::coin::Coin<{}>", coin_type);
+            coin.fields = json!({ "balance": 500 });
+        }
+        let split = TransactionKind::SplitCoin {
+            coin: coin.clone(),
+            amounts: vec![100],
+        };
+
+        setup.node.deny_list_add(coin_type, &setup.sender).unwrap();
+        let deny_list = setup
+            .client
+            .get_object(DENY_LIST_OBJECT_ID)
+            .unwrap()
+            .unwrap();
+        assert_eq!(deny_list.version, 2);
+        let denied = tx(&setup.sender, split.clone());
+        let signature = setup.keystore.sign(&denied).unwrap();
+        assert!(matches!(
+            setup.client.execute_transaction(&denied, &signature),
+            Err(ClientError::Rpc {
+                code: TRANSACTION_REJECTED,
+                ..
+            })
+        ));
+        assert!(setup.node.system_transactions().is_empty());
+        let call = TransactionKind::MoveCall {
+            package: "0x2".to_string(),
+            module: "coin".to_string(),
+            function: "value".to_string(),
+            type_args: vec![coin_type.to_string()],
+            args: vec![],
+        };
+        assert!(setup
+            .client
+            .dry_run_transaction(&tx(&setup.sender, call))
+            .is_err());
+
+        setup
+            .node
+            .deny_list_remove(coin_type, &setup.sender)
+            .unwrap();
+        assert!(setup
+            .node
+            .deny_list_remove(coin_type, &setup.sender)
+            .is_err());
+        assert_eq!(execute(&setup, split).status, ExecutionStatus::Success);
+    }
+}
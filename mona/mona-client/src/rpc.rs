@@ -0,0 +1,172 @@
+//! The node's JSON-RPC API.
+//!
+//! Requests are JSON-RPC 2.0 with positional parameters. Transactions travel as
+//! hex encoded BCS bytes with a hex encoded signature, see [`crate::keystore`].
+//!
+//! | Method                         | Parameters                  | Result                        |
+//! |--------------------------------|-----------------------------|-------------------------------|
+//! | `kari_getObject`               | object ID                   | `ObjectInfo` or `null`        |
+//! | `kari_getOwnedObjects`         | address                     | `[ObjectInfo]`                |
+//! | `kari_getBalance`              | address, coin type or `null`| `[Balance]`                   |
+//! | `kari_getTransactionBlock`     | digest                      | `TransactionResponse` or `null` |
+//! | `kari_queryEvents`             | `EventFilter`, limit        | `[EventInfo]`                 |
+//! | `kari_executeTransactionBlock` | tx bytes, signature         | `TransactionResponse`         |
+//! | `kari_dryRunTransactionBlock`  | tx bytes                    | `TransactionResponse`         |
+
+use crate::types::{
+    normalize_address, Balance, ClientError, EventFilter, EventInfo, ObjectInfo, TransactionData,
+    TransactionResponse,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Sends JSON-RPC requests to a node.
+pub trait RpcTransport {
+    /// Call `method` with `params`, returning the `result` of the response.
+    fn request(&self, method: &str, params: Value) -> Result<Value, ClientError>;
+}
+
+/// Transport to a node listening on HTTP.
+pub struct HttpTransport {
+    url: String,
+    client: reqwest::blocking::Client,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        HttpTransport {
+            url: url.into(),
+            client: reqwest::blocking::Client::new(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl RpcTransport for HttpTransport {
+    fn request(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let mut response: Value = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        if let Some(error) = response.get("error") {
+            return Err(ClientError::Rpc {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+        Ok(response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or(Value::Null))
+    }
+}
+
+/// Typed access to the node's JSON-RPC API.
+pub struct RpcClient {
+    transport: Box<dyn RpcTransport>,
+}
+
+impl RpcClient {
+    pub fn new(transport: impl RpcTransport + 'static) -> Self {
+        RpcClient {
+            transport: Box::new(transport),
+        }
+    }
+
+    /// Client of the node at `url`.
+    pub fn http(url: &str) -> Self {
+        Self::new(HttpTransport::new(url))
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, ClientError> {
+        Ok(serde_json::from_value(
+            self.transport.request(method, params)?,
+        )?)
+    }
+
+    pub fn get_object(&self, object_id: &str) -> Result<Option<ObjectInfo>, ClientError> {
+        self.call("kari_getObject", json!([normalize_address(object_id)?]))
+    }
+
+    pub fn get_owned_objects(&self, owner: &str) -> Result<Vec<ObjectInfo>, ClientError> {
+        self.call("kari_getOwnedObjects", json!([normalize_address(owner)?]))
+    }
+
+    /// Gas coins of `owner`, largest first.
+    pub fn get_gas_coins(&self, owner: &str) -> Result<Vec<ObjectInfo>, ClientError> {
+        let mut coins: Vec<ObjectInfo> = self
+            .get_owned_objects(owner)?
+            .into_iter()
+            .filter(ObjectInfo::is_gas_coin)
+            .collect();
+        coins.sort_by_key(|coin| std::cmp::Reverse(coin.coin_balance()));
+        Ok(coins)
+    }
+
+    /// Balances of `owner`, of every coin type unless `coin_type` is given.
+    pub fn get_balance(
+        &self,
+        owner: &str,
+        coin_type: Option<&str>,
+    ) -> Result<Vec<Balance>, ClientError> {
+        self.call(
+            "kari_getBalance",
+            json!([normalize_address(owner)?, coin_type]),
+        )
+    }
+
+    pub fn get_transaction_block(
+        &self,
+        digest: &str,
+    ) -> Result<Option<TransactionResponse>, ClientError> {
+        self.call("kari_getTransactionBlock", json!([digest]))
+    }
+
+    pub fn query_events(
+        &self,
+        filter: &EventFilter,
+        limit: usize,
+    ) -> Result<Vec<EventInfo>, ClientError> {
+        self.call("kari_queryEvents", json!([filter, limit]))
+    }
+
+    /// Execute `tx`, signed with `signature`.
+    pub fn execute_transaction(
+        &self,
+        tx: &TransactionData,
+        signature: &str,
+    ) -> Result<TransactionResponse, ClientError> {
+        self.call(
+            "kari_executeTransactionBlock",
+            json!([hex::encode(tx.to_bytes()?), signature]),
+        )
+    }
+
+    /// Execute `tx` without committing its effects.
+    pub fn dry_run_transaction(
+        &self,
+        tx: &TransactionData,
+    ) -> Result<TransactionResponse, ClientError> {
+        self.call(
+            "kari_dryRunTransactionBlock",
+            json!([hex::encode(tx.to_bytes()?)]),
+        )
+    }
+}
@@ -0,0 +1,267 @@
+//! System transactions, executed by the node with sender `0x0` at the start of
+//! every block.
+//!
+//! - [`SystemTransaction::ConsensusCommitPrologue`] sets the timestamp of the
+//!   shared `0x2::clock::Clock` (`clock::consensus_commit_prologue`).
+//! - [`SystemTransaction::RandomnessStateUpdate`] records the next round of
+//!   the randomness beacon in the shared `0x2::random::Random`
+//!   (`random::update_randomness_state`).
+//!
+//! Devnets draw their randomness from a [`DevnetBeacon`].
+//!
+//! The shared `0x2::deny_list::DenyList` holds the addresses that may not use
+//! a regulated coin as a transaction input, mirrored by [`DenyList`].
+
+use crate::types::{normalize_address, normalize_type, ClientError};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// ID of the shared `Clock`, `kanari_framework::object::KARI_CLOCK_OBJECT_ID`.
+pub const CLOCK_OBJECT_ID: &str =
+    "0x0000000000000000000000000000000000000000000000000000000000000006";
+/// ID of the shared `Random`, `kanari_framework::object::KARI_RANDOM_ID`.
+pub const RANDOM_OBJECT_ID: &str =
+    "0x0000000000000000000000000000000000000000000000000000000000000008";
+
+/// ID of the shared `DenyList`, `kanari_framework::object::KARI_DENY_LIST_OBJECT_ID`.
+pub const DENY_LIST_OBJECT_ID: &str =
+    "0x0000000000000000000000000000000000000000000000000000000000000403";
+
+pub const CLOCK_TYPE: &str = "0x2::clock::Clock";
+pub const RANDOM_TYPE: &str = "0x2::random::Random";
+pub const DENY_LIST_TYPE: &str = "0x2::deny_list::DenyList";
+
+/// `random::CURRENT_VERSION`.
+const RANDOM_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum SystemTransaction {
+    ConsensusCommitPrologue {
+        epoch: u64,
+        round: u64,
+        commit_timestamp_ms: u64,
+    },
+    RandomnessStateUpdate {
+        epoch: u64,
+        randomness_round: u64,
+        random_bytes: Vec<u8>,
+    },
+}
+
+/// State of the `Random`, laid out like `random::RandomInner` so that its
+/// BCS encoding is the Move value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RandomState {
+    pub version: u64,
+    pub epoch: u64,
+    pub randomness_round: u64,
+    pub random_bytes: Vec<u8>,
+}
+
+impl Default for RandomState {
+    fn default() -> Self {
+        RandomState {
+            version: RANDOM_VERSION,
+            epoch: 0,
+            randomness_round: 0,
+            random_bytes: Vec::new(),
+        }
+    }
+}
+
+impl RandomState {
+    /// The round that follows the current one in `epoch`.
+    pub fn next_round(&self, epoch: u64) -> u64 {
+        if self.random_bytes.is_empty() || epoch > self.epoch {
+            0
+        } else {
+            self.randomness_round + 1
+        }
+    }
+
+    /// Record a new round, with the checks of `random::update_randomness_state`.
+    pub fn update(
+        &mut self,
+        epoch: u64,
+        randomness_round: u64,
+        random_bytes: Vec<u8>,
+    ) -> Result<(), String> {
+        let first = self.randomness_round == 0 && self.epoch == 0 && self.random_bytes.is_empty();
+        let valid = if first {
+            randomness_round == 0
+        } else {
+            (epoch > self.epoch && randomness_round == 0)
+                || randomness_round == self.randomness_round + 1
+        };
+        if !valid {
+            return Err(format!(
+                "Invalid randomness update to round {} of epoch {} after round {} of epoch {}",
+                randomness_round, epoch, self.randomness_round, self.epoch
+            ));
+        }
+        self.epoch = epoch;
+        self.randomness_round = randomness_round;
+        self.random_bytes = random_bytes;
+        Ok(())
+    }
+
+    /// Move to the next round of `beacon` in `epoch`, returning the system
+    /// transaction that does so.
+    pub fn advance(&mut self, beacon: &DevnetBeacon, epoch: u64) -> SystemTransaction {
+        let randomness_round = self.next_round(epoch);
+        let random_bytes = beacon.round(randomness_round);
+        self.update(epoch, randomness_round, random_bytes.clone())
+            .expect("the next round is a valid update");
+        SystemTransaction::RandomnessStateUpdate {
+            epoch,
+            randomness_round,
+            random_bytes,
+        }
+    }
+}
+
+/// Deterministic randomness beacon for devnets and reproducible runs: round
+/// `n` is the SHA3-256 hash of the seed and `n`. Anyone who knows the seed can
+/// predict it, so it must not back a public network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DevnetBeacon {
+    seed: [u8; 32],
+}
+
+impl DevnetBeacon {
+    pub fn new(seed: u64) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"kari::devnet_beacon::");
+        hasher.update(seed.to_le_bytes());
+        DevnetBeacon {
+            seed: hasher.finalize().into(),
+        }
+    }
+
+    /// Random bytes of round `round`.
+    pub fn round(&self, round: u64) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(self.seed);
+        hasher.update(round.to_le_bytes());
+        hasher.finalize().to_vec()
+    }
+}
+
+impl Default for DevnetBeacon {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// The coin list of the `DenyList`: addresses denied the use of each
+/// regulated coin type, as maintained by `coin::deny_list_add` and
+/// `coin::deny_list_remove`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DenyList {
+    /// Denied addresses by coin type `T`, with normalized addresses.
+    pub coin: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DenyList {
+    /// Deny `address` the use of coins of `coin_type`. Returns `false` if it
+    /// was already denied.
+    pub fn add(&mut self, coin_type: &str, address: &str) -> Result<bool, ClientError> {
+        let address = normalize_address(address)?;
+        Ok(self
+            .coin
+            .entry(normalize_type(coin_type)?)
+            .or_default()
+            .insert(address))
+    }
+
+    /// Allow `address` to use coins of `coin_type` again. Returns `false` if
+    /// it was not denied.
+    pub fn remove(&mut self, coin_type: &str, address: &str) -> Result<bool, ClientError> {
+        let address = normalize_address(address)?;
+        let coin_type = normalize_type(coin_type)?;
+        let Some(denied) = self.coin.get_mut(&coin_type) else {
+            return Ok(false);
+        };
+        let removed = denied.remove(&address);
+        if denied.is_empty() {
+            self.coin.remove(&coin_type);
+        }
+        Ok(removed)
+    }
+
+    pub fn contains(&self, coin_type: &str, address: &str) -> bool {
+        let (Ok(coin_type), Ok(address)) = (normalize_type(coin_type), normalize_address(address))
+        else {
+            return false;
+        };
+        self.coin
+            .get(&coin_type)
+            .is_some_and(|denied| denied.contains(&address))
+    }
+
+    /// The first of `coin_types` that `address` is denied, if any.
+    pub fn denied<'a>(
+        &self,
+        address: &str,
+        coin_types: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'a str> {
+        coin_types
+            .into_iter()
+            .find(|coin_type| self.contains(coin_type, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beacon_rounds() {
+        let beacon = DevnetBeacon::new(42);
+        assert_eq!(beacon.round(3), DevnetBeacon::new(42).round(3));
+        assert_ne!(beacon.round(3), beacon.round(4));
+        assert_ne!(beacon.round(3), DevnetBeacon::new(43).round(3));
+
+        let mut state = RandomState::default();
+        for expected in 0..3 {
+            let update = state.advance(&beacon, 0);
+            assert_eq!(
+                update,
+                SystemTransaction::RandomnessStateUpdate {
+                    epoch: 0,
+                    randomness_round: expected,
+                    random_bytes: beacon.round(expected),
+                }
+            );
+        }
+        assert_eq!(state.randomness_round, 2);
+        assert!(state.update(0, 7, vec![1]).is_err());
+        state.update(1, 0, vec![1]).unwrap();
+        assert_eq!(state.next_round(1), 1);
+    }
+
+    #[test]
+    fn test_deny_list() {
+        let mut deny_list = DenyList::default();
+        assert!(deny_list.add("0x42::my_coin::MY_COIN", "0xa").unwrap());
+        assert!(!deny_list.add("0x042::my_coin::MY_COIN", "0x0a").unwrap());
+        assert!(deny_list.contains(
+            "0x0000000000000000000000000000000000000000000000000000000000000042::my_coin::MY_COIN",
+            "0xA"
+        ));
+        assert!(!deny_list.contains("0x42::my_coin::MY_COIN", "0xb"));
+        assert_eq!(
+            deny_list.denied("0xa", ["0x2::kari::KARI", "0x42::my_coin::MY_COIN"]),
+            Some("0x42::my_coin::MY_COIN")
+        );
+
+        assert!(deny_list.remove("0x42::my_coin::MY_COIN", "0xa").unwrap());
+        assert!(!deny_list.remove("0x42::my_coin::MY_COIN", "0xa").unwrap());
+        assert_eq!(deny_list, DenyList::default());
+        assert!(deny_list
+            .add("0x42::my_coin::MY_COIN", "not an address")
+            .is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Types exchanged with a node over JSON-RPC.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+/// Coin type of the native token.
+pub const KARI_COIN_TYPE: &str = "0x2::kari::KARI";
+
+/// Type of the coins gas is paid with.
+pub const GAS_COIN_TYPE: &str = "0x2::coin::Coin<0x2::kari::KARI>";
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Serialization error: {0}")]
+    Bcs(#[from] bcs::Error),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("No key for address {0} in the keystore")]
+    KeyNotFound(String),
+
+    #[error("The keystore is empty")]
+    EmptyKeystore,
+}
+
+/// Normalize an address or object ID to `0x` followed by 64 lowercase hex digits.
+pub fn normalize_address(addr: &str) -> Result<String, ClientError> {
+    let hex = addr.strip_prefix("0x").unwrap_or(addr);
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ClientError::InvalidAddress(addr.to_string()));
+    }
+    Ok(format!("0x{:0>64}", hex.to_lowercase()))
+}
+
+/// Normalize the addresses of a type such as `0x2::coin::Coin<0x2::kari::KARI>`,
+/// so that spellings of the same type compare equal.
+pub fn normalize_type(type_: &str) -> Result<String, ClientError> {
+    let mut normalized = String::new();
+    let mut rest = type_;
+    loop {
+        let end = rest.find(['<', '>', ',']).unwrap_or(rest.len());
+        let part = rest[..end].trim();
+        match part.split_once("::") {
+            Some((address, path)) => {
+                normalized.push_str(&normalize_address(address)?);
+                normalized.push_str("::");
+                normalized.push_str(path);
+            }
+            None => normalized.push_str(part),
+        }
+        match rest[end..].chars().next() {
+            Some(',') => normalized.push_str(", "),
+            Some(delimiter) => normalized.push(delimiter),
+            None => return Ok(normalized),
+        }
+        rest = &rest[end + 1..];
+    }
+}
+
+/// Who may use an object in a transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Owner {
+    AddressOwner(String),
+    Shared,
+    Immutable,
+}
+
+/// An object as stored by the node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectInfo {
+    pub object_id: String,
+    pub version: u64,
+    pub digest: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub owner: Owner,
+    /// Fields of the Move value, e.g. `{"balance": 100}` for a coin.
+    pub fields: serde_json::Value,
+}
+
+impl ObjectInfo {
+    /// `T` of a `0x2::coin::Coin<T>` object.
+    pub fn coin_type(&self) -> Option<&str> {
+        self.type_
+            .strip_prefix("0x2::coin::Coin<")
+            .and_then(|rest| rest.strip_suffix('>'))
+    }
+
+    pub fn is_coin(&self) -> bool {
+        self.coin_type().is_some()
+    }
+
+    pub fn is_gas_coin(&self) -> bool {
+        self.type_ == GAS_COIN_TYPE
+    }
+
+    /// Balance of a coin object.
+    pub fn coin_balance(&self) -> Option<u64> {
+        if !self.is_coin() {
+            return None;
+        }
+        self.fields
+            .get("balance")
+            .and_then(|balance| balance.as_u64())
+    }
+}
+
+/// Total balance of one coin type held by an address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Balance {
+    pub coin_type: String,
+    pub coin_object_count: usize,
+    pub total_balance: u64,
+}
+
+/// What a transaction does.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TransactionKind {
+    /// Call a Move function. Arguments are object IDs or literals, resolved by
+    /// the node against the function's signature.
+    MoveCall {
+        package: String,
+        module: String,
+        function: String,
+        type_args: Vec<String>,
+        args: Vec<String>,
+    },
+    /// Publish compiled modules as a new package.
+    Publish {
+        modules: Vec<Vec<u8>>,
+        dependencies: Vec<String>,
+    },
+    TransferObject {
+        object_id: String,
+        recipient: String,
+    },
+    /// Split coins of the given amounts off `coin`.
+    SplitCoin { coin: String, amounts: Vec<u64> },
+    /// Merge `coins` into `primary`.
+    MergeCoins { primary: String, coins: Vec<String> },
+    /// Merge `coins` and pay `amounts[i]` to `recipients[i]`.
+    Pay {
+        coins: Vec<String>,
+        recipients: Vec<String>,
+        amounts: Vec<u64>,
+    },
+}
+
+/// A transaction before it is signed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransactionData {
+    pub sender: String,
+    pub kind: TransactionKind,
+    /// Coin gas is paid with; the node picks one of the sender's gas coins if unset.
+    pub gas_payment: Option<String>,
+    pub gas_budget: u64,
+    pub gas_price: u64,
+}
+
+impl TransactionData {
+    /// BCS bytes of the transaction, as signed and sent to the node.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ClientError> {
+        Ok(bcs::to_bytes(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ClientError> {
+        Ok(bcs::from_bytes(bytes)?)
+    }
+
+    /// Hex encoded SHA3-256 of the transaction bytes, which identifies the
+    /// executed transaction.
+    pub fn digest(&self) -> Result<String, ClientError> {
+        Ok(hex::encode(Sha3_256::digest(self.to_bytes()?)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    Success,
+    Failure { error: String },
+}
+
+/// An event emitted by a transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventInfo {
+    pub tx_digest: String,
+    /// Position of the event among those of its transaction.
+    pub sequence: u64,
+    pub sender: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub data: serde_json::Value,
+    pub timestamp_ms: u64,
+}
+
+/// Events to return from `kari_queryEvents`; unset fields match anything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct EventFilter {
+    pub sender: Option<String>,
+    pub tx_digest: Option<String>,
+    pub event_type: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &EventInfo) -> bool {
+        self.sender
+            .as_ref()
+            .is_none_or(|sender| *sender == event.sender)
+            && self
+                .tx_digest
+                .as_ref()
+                .is_none_or(|digest| *digest == event.tx_digest)
+            && self
+                .event_type
+                .as_ref()
+                .is_none_or(|type_| *type_ == event.type_)
+    }
+}
+
+/// Result of executing, or dry running, a transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransactionResponse {
+    pub digest: String,
+    pub status: ExecutionStatus,
+    pub gas_used: u64,
+    pub created: Vec<String>,
+    pub mutated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub events: Vec<EventInfo>,
+}
@@ -0,0 +1,365 @@
+//! Configuration shared by every `kari` command.
+//!
+//! The configuration lives in `kari.toml` inside the Kari home directory,
+//! which is `$KARI_HOME` if set and `~/.kari` otherwise. Commands may also be
+//! pointed at another file with `--config <path>`. A missing file is not an
+//! error: the defaults below are used until the configuration is saved.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Environment variable overriding the Kari home directory.
+pub const KARI_HOME_ENV: &str = "KARI_HOME";
+
+const KARI_DIR: &str = ".kari";
+const CONFIG_FILE: &str = "kari.toml";
+const STORAGE_DIR: &str = "storage";
+const KEYSTORE_FILE: &str = "kari.keystore";
+
+const DEFAULT_ENV: &str = "localnet";
+const DEFAULT_LOCALNET_RPC: &str = "http://127.0.0.1:9000";
+const DEVNET_ENV: &str = "devnet";
+const DEFAULT_DEVNET_RPC: &str = "https://fullnode.devnet.kanari.network:443";
+const DEFAULT_GAS_BUDGET: u64 = 10_000_000;
+const DEFAULT_GAS_PRICE: u64 = 1_000;
+const DEFAULT_RELEASE_INDEX: &str =
+    "https://github.com/kanari-network/kanari-sdk/releases/latest/download/release-index.json";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Could not find home directory, set {} to choose where Kari stores its data", KARI_HOME_ENV)]
+    NoHomeDir,
+
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid configuration file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to serialize configuration: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("Unknown environment '{0}'")]
+    UnknownEnv(String),
+
+    #[error("Unknown configuration key '{0}'")]
+    UnknownKey(String),
+
+    #[error("Invalid value '{value}' for '{key}'")]
+    InvalidValue { key: String, value: String },
+}
+
+/// Connection settings of a network environment.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EnvConfig {
+    pub rpc_url: String,
+}
+
+/// Default gas settings for transactions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GasConfig {
+    pub budget: u64,
+    pub price: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        GasConfig {
+            budget: DEFAULT_GAS_BUDGET,
+            price: DEFAULT_GAS_PRICE,
+        }
+    }
+}
+
+/// Defaults of `kari move`. Unset values keep the Move CLI's own defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MoveConfig {
+    /// Directory of the sandbox state, `storage` in the current directory by
+    /// default.
+    pub storage_dir: Option<PathBuf>,
+    /// Gas limit of unit tests and gas budget of sandbox scripts. By default
+    /// unit tests keep the runner's limit and scripts run unmetered.
+    pub gas_limit: Option<u64>,
+}
+
+/// Where `kari update` looks for releases and whose signatures it trusts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct UpdateConfig {
+    /// URL of the release index.
+    pub index_url: String,
+    /// Minisign public key, base64 encoded, that release archives must be
+    /// signed with instead of the release key built into `kari`.
+    pub public_key: Option<String>,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            index_url: DEFAULT_RELEASE_INDEX.to_string(),
+            public_key: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KariConfig {
+    /// Name of the environment commands talk to, a key of `envs`.
+    pub active_env: String,
+    /// Address used as sender and owner when none is given explicitly.
+    pub active_address: Option<String>,
+    /// Directory of the local file store, `<home>/storage` by default.
+    pub storage_path: Option<PathBuf>,
+    /// Keystore file, `<home>/kari.keystore` by default.
+    pub keystore_path: Option<PathBuf>,
+    pub gas: GasConfig,
+    pub r#move: MoveConfig,
+    pub update: UpdateConfig,
+    pub envs: BTreeMap<String, EnvConfig>,
+
+    /// Kari home directory the relative defaults are resolved against.
+    #[serde(skip)]
+    home: PathBuf,
+    /// File the configuration was loaded from and is saved to.
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Default for KariConfig {
+    fn default() -> Self {
+        let envs = [
+            (DEFAULT_ENV, DEFAULT_LOCALNET_RPC),
+            (DEVNET_ENV, DEFAULT_DEVNET_RPC),
+        ]
+        .into_iter()
+        .map(|(name, rpc_url)| {
+            let rpc_url = rpc_url.to_string();
+            (name.to_string(), EnvConfig { rpc_url })
+        })
+        .collect();
+        KariConfig {
+            active_env: DEFAULT_ENV.to_string(),
+            active_address: None,
+            storage_path: None,
+            keystore_path: None,
+            gas: GasConfig::default(),
+            r#move: MoveConfig::default(),
+            update: UpdateConfig::default(),
+            envs,
+            home: PathBuf::new(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+/// The Kari home directory: `$KARI_HOME`, or `~/.kari`.
+pub fn kari_home() -> Result<PathBuf, ConfigError> {
+    match std::env::var_os(KARI_HOME_ENV) {
+        Some(home) if !home.is_empty() => Ok(PathBuf::from(home)),
+        _ => dirs::home_dir()
+            .map(|home| home.join(KARI_DIR))
+            .ok_or(ConfigError::NoHomeDir),
+    }
+}
+
+// Expand a leading `~` so that paths in the file can be written portably
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl KariConfig {
+    /// Load the configuration from `path`, or from the Kari home directory
+    /// when `path` is `None`.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (home, path) = match path {
+            Some(path) => {
+                let home = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => PathBuf::from("."),
+                };
+                (home, path.to_path_buf())
+            }
+            None => {
+                let home = kari_home()?;
+                let path = home.join(CONFIG_FILE);
+                (home, path)
+            }
+        };
+
+        let mut config = if path.exists() {
+            let contents = fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+                path: path.clone(),
+                source,
+            })?
+        } else {
+            KariConfig::default()
+        };
+        config.home = home;
+        config.path = path;
+        Ok(config)
+    }
+
+    /// Write the configuration back to the file it was loaded from.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|source| ConfigError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(&self.path, toml::to_string_pretty(self)?).map_err(|source| ConfigError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// File the configuration is loaded from and saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    pub fn storage_path(&self) -> PathBuf {
+        match &self.storage_path {
+            Some(path) => expand_home(path),
+            None => self.home.join(STORAGE_DIR),
+        }
+    }
+
+    pub fn keystore_path(&self) -> PathBuf {
+        match &self.keystore_path {
+            Some(path) => expand_home(path),
+            None => self.home.join(KEYSTORE_FILE),
+        }
+    }
+
+    /// Directory of the Move sandbox state, if one is configured.
+    pub fn move_storage_dir(&self) -> Option<PathBuf> {
+        self.r#move.storage_dir.as_deref().map(expand_home)
+    }
+
+    /// Settings of the active environment.
+    pub fn active_env(&self) -> Result<&EnvConfig, ConfigError> {
+        self.envs
+            .get(&self.active_env)
+            .ok_or_else(|| ConfigError::UnknownEnv(self.active_env.clone()))
+    }
+
+    pub fn rpc_url(&self) -> Result<&str, ConfigError> {
+        Ok(&self.active_env()?.rpc_url)
+    }
+
+    /// Make `env` the active environment.
+    pub fn switch_env(&mut self, env: &str) -> Result<(), ConfigError> {
+        if !self.envs.contains_key(env) {
+            return Err(ConfigError::UnknownEnv(env.to_string()));
+        }
+        self.active_env = env.to_string();
+        Ok(())
+    }
+
+    /// Add or replace the environment `name`.
+    pub fn add_env(&mut self, name: &str, rpc_url: &str) {
+        self.envs.insert(
+            name.to_string(),
+            EnvConfig {
+                rpc_url: rpc_url.to_string(),
+            },
+        );
+    }
+
+    /// Set a single value by its key, as used by `kari config set`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        match key {
+            "active_env" => self.switch_env(value)?,
+            "active_address" => self.active_address = Some(value.to_string()),
+            "storage_path" => self.storage_path = Some(PathBuf::from(value)),
+            "keystore_path" => self.keystore_path = Some(PathBuf::from(value)),
+            "gas.budget" => self.gas.budget = value.parse().map_err(|_| invalid())?,
+            "gas.price" => self.gas.price = value.parse().map_err(|_| invalid())?,
+            "move.storage_dir" => self.r#move.storage_dir = Some(PathBuf::from(value)),
+            "move.gas_limit" => self.r#move.gas_limit = Some(value.parse().map_err(|_| invalid())?),
+            "update.index_url" => self.update.index_url = value.to_string(),
+            "update.public_key" => self.update.public_key = Some(value.to_string()),
+            _ => match key
+                .strip_prefix("envs.")
+                .and_then(|key| key.strip_suffix(".rpc_url"))
+            {
+                Some(env) if !env.is_empty() => self.add_env(env, value),
+                _ => return Err(ConfigError::UnknownKey(key.to_string())),
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_without_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = KariConfig::load(Some(&dir.path().join("kari.toml"))).unwrap();
+        assert_eq!(config.storage_path(), dir.path().join(STORAGE_DIR));
+        assert_eq!(config.keystore_path(), dir.path().join(KEYSTORE_FILE));
+        assert_eq!(config.rpc_url().unwrap(), DEFAULT_LOCALNET_RPC);
+        assert_eq!(config.envs[DEVNET_ENV].rpc_url, DEFAULT_DEVNET_RPC);
+        assert_eq!(config.move_storage_dir(), None);
+        assert_eq!(config.r#move.gas_limit, None);
+    }
+
+    #[test]
+    fn test_set_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kari.toml");
+        let mut config = KariConfig::load(Some(&path)).unwrap();
+        config
+            .set("envs.devnet.rpc_url", "http://devnet:9000")
+            .unwrap();
+        config.set("active_env", "devnet").unwrap();
+        config.set("gas.budget", "42").unwrap();
+        config.set("storage_path", "/data/kari").unwrap();
+        config.set("move.storage_dir", "/data/sandbox").unwrap();
+        config.set("move.gas_limit", "1000").unwrap();
+        assert!(config.set("gas.budget", "lots").is_err());
+        assert!(config.set("active_env", "mainnet").is_err());
+        assert!(config.set("colour", "blue").is_err());
+        config.save().unwrap();
+
+        let config = KariConfig::load(Some(&path)).unwrap();
+        assert_eq!(config.rpc_url().unwrap(), "http://devnet:9000");
+        assert_eq!(config.gas.budget, 42);
+        assert_eq!(config.storage_path(), PathBuf::from("/data/kari"));
+        assert_eq!(
+            config.move_storage_dir(),
+            Some(PathBuf::from("/data/sandbox"))
+        );
+        assert_eq!(config.r#move.gas_limit, Some(1000));
+    }
+}
@@ -0,0 +1,89 @@
+//! The TOML config genesis is built from.
+//!
+//! ```toml
+//! chain_id = "kari-devnet"
+//! timestamp_ms = 1735689600000
+//!
+//! [[validators]]
+//! name = "validator-0"
+//! address = "0xa1"
+//! protocol_key = "0x02..."
+//! network_address = "/dns/validator-0/tcp/9000/http"
+//! stake = 20000000000000000
+//!
+//! [[allocations]]
+//! recipient = "0xb0b"
+//! amount = 180000000000000000
+//! ```
+//!
+//! Amounts are in MIST. Validator stakes and allocations together must add up
+//! to the total supply of KARI, [`crate::TOTAL_SUPPLY_MIST`].
+
+use crate::GenesisError;
+use move_core_types::account_address::AccountAddress;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A config to start from, with one validator and one allocation.
+pub const TEMPLATE: &str = r#"# Genesis of a Kari network, see `kari genesis build`.
+# Amounts are in MIST (10^-9 KARI); stakes and allocations must add up to the
+# total supply of 200 million KARI.
+chain_id = "kari-devnet"
+# Start of the chain, in milliseconds since the Unix epoch.
+timestamp_ms = 0
+
+[[validators]]
+name = "validator-0"
+# Replace with the validator's account address and public protocol key.
+address = "0xa1"
+protocol_key = "0x00"
+network_address = "/dns/localhost/tcp/9000/http"
+stake = 20000000000000000
+
+[[allocations]]
+recipient = "0xb0b"
+amount = 180000000000000000
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GenesisConfig {
+    pub chain_id: String,
+    /// Initial time of the `Clock`, in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub timestamp_ms: u64,
+    pub validators: Vec<ValidatorConfig>,
+    #[serde(default)]
+    pub allocations: Vec<Allocation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorConfig {
+    pub name: String,
+    pub address: AccountAddress,
+    /// Hex encoded public key the validator signs with.
+    pub protocol_key: String,
+    pub network_address: String,
+    /// Stake of the validator, in MIST.
+    pub stake: u64,
+}
+
+/// KARI sent to an address at genesis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub recipient: AccountAddress,
+    /// Amount in MIST.
+    pub amount: u64,
+}
+
+impl GenesisConfig {
+    pub fn from_toml(config: &str) -> Result<Self, GenesisError> {
+        toml::from_str(config).map_err(|e| GenesisError::Config(e.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GenesisError> {
+        let config = fs::read_to_string(path)
+            .map_err(|e| GenesisError::Config(format!("{}: {}", path.display(), e)))?;
+        Self::from_toml(&config)
+    }
+}
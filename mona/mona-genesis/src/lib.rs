@@ -0,0 +1,686 @@
+//! Genesis of a Kari network.
+//!
+//! [`build`] turns a [`GenesisConfig`] into the initial state of the chain:
+//!
+//! - the system packages `0x1`, `0x2` and `0x3` embedded in `framework`,
+//! - the shared system objects `Clock`, `AuthenticatorState`, `Random` and
+//!   `DenyList` at the IDs reserved for them in `kanari_framework::object`,
+//!   with the dynamic fields their `create` functions add: the versioned
+//!   inner objects and the list of coin types of the `DenyList`,
+//! - the total supply of KARI (`kanari_framework::kari`), split between the
+//!   validator stakes and a `Coin<KARI>` per allocation,
+//! - the validator set.
+//!
+//! `kanari-system` does not define a system state object yet, so the
+//! validator set is kept next to the objects.
+//!
+//! The genesis blob is the BCS encoding of [`Genesis`] and its digest the
+//! SHA3-256 hash of the blob. Building is deterministic: the same config
+//! always gives the same blob, so every validator can check the digest.
+
+pub mod config;
+
+pub use config::{Allocation, GenesisConfig, ValidatorConfig};
+
+use framework::{BuiltInFramework, SystemPackage};
+use mona_types::{
+    addresses::is_vm_or_system_reserved_address,
+    dynamic_field::{field_id, field_type},
+};
+use move_core_types::{
+    account_address::AccountAddress, language_storage::TypeTag, parser::parse_struct_tag,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// `kanari_framework::kari::TOTAL_SUPPLY_MIST`.
+pub const TOTAL_SUPPLY_MIST: u64 = 200_000_000_000_000_000;
+
+pub const CLOCK_OBJECT_ID: AccountAddress = object_id(0x6);
+pub const AUTHENTICATOR_STATE_OBJECT_ID: AccountAddress = object_id(0x7);
+pub const RANDOM_OBJECT_ID: AccountAddress = object_id(0x8);
+pub const DENY_LIST_OBJECT_ID: AccountAddress = object_id(0x403);
+
+pub const KARI_COIN_TYPE: &str = "0x2::coin::Coin<0x2::kari::KARI>";
+
+const AUTHENTICATOR_STATE_INNER_TYPE: &str = "0x2::authenticator_state::AuthenticatorStateInner";
+const RANDOM_INNER_TYPE: &str = "0x2::random::RandomInner";
+const PER_TYPE_LIST_TYPE: &str = "0x2::deny_list::PerTypeList";
+
+// Version of `Random` and `AuthenticatorState` created at genesis
+const SYSTEM_OBJECT_VERSION: u64 = 1;
+// `deny_list::COIN_INDEX`, the key of the list of coin types in the `DenyList` bag
+const COIN_INDEX: u64 = 0;
+
+const fn object_id(id: u16) -> AccountAddress {
+    let mut bytes = [0; AccountAddress::LENGTH];
+    let [high, low] = id.to_be_bytes();
+    bytes[AccountAddress::LENGTH - 2] = high;
+    bytes[AccountAddress::LENGTH - 1] = low;
+    AccountAddress::new(bytes)
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GenesisError {
+    #[error("Invalid genesis config: {0}")]
+    Config(String),
+
+    #[error("Stakes and allocations add up to {actual} MIST instead of the total supply of {expected} MIST")]
+    Supply { expected: u64, actual: u128 },
+
+    #[error("Invalid genesis blob: {0}")]
+    Blob(String),
+
+    #[error("Genesis digest {actual} does not match the expected {expected}")]
+    Digest { expected: String, actual: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Address(AccountAddress),
+    Shared,
+    Immutable,
+    /// A dynamic field of the object with this ID.
+    Object(AccountAddress),
+}
+
+/// An object created at genesis, at version 1.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GenesisObject {
+    pub id: AccountAddress,
+    /// Move type, e.g. `0x2::clock::Clock`.
+    pub type_: String,
+    pub owner: Owner,
+    /// BCS encoding of the Move value.
+    pub contents: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GenesisValidator {
+    pub name: String,
+    pub address: AccountAddress,
+    pub protocol_key: Vec<u8>,
+    pub network_address: String,
+    pub stake: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Genesis {
+    pub chain_id: String,
+    pub timestamp_ms: u64,
+    /// The system packages, in dependency order.
+    pub packages: Vec<SystemPackage>,
+    pub objects: Vec<GenesisObject>,
+    pub validators: Vec<GenesisValidator>,
+}
+
+// Move values of the system objects, laid out like their structs
+#[derive(Serialize, Deserialize)]
+struct Clock {
+    id: AccountAddress,
+    timestamp_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthenticatorState {
+    id: AccountAddress,
+    version: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthenticatorStateInner {
+    version: u64,
+    active_jwks: Vec<ActiveJwk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ActiveJwk {
+    jwk_id: JwkId,
+    jwk: Jwk,
+    epoch: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JwkId {
+    iss: String,
+    kid: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Jwk {
+    kty: String,
+    e: String,
+    n: String,
+    alg: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Versioned {
+    id: AccountAddress,
+    version: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Random {
+    id: AccountAddress,
+    inner: Versioned,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RandomInner {
+    version: u64,
+    epoch: u64,
+    randomness_round: u64,
+    random_bytes: Vec<u8>,
+}
+
+// `Bag` and `Table`
+#[derive(Serialize, Deserialize)]
+struct Collection {
+    id: AccountAddress,
+    size: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DenyList {
+    id: AccountAddress,
+    lists: Collection,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PerTypeList {
+    id: AccountAddress,
+    denied_count: Collection,
+    denied_addresses: Collection,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Field<Name, Value> {
+    id: AccountAddress,
+    name: Name,
+    value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Coin {
+    id: AccountAddress,
+    balance: u64,
+}
+
+/// ID of the `index`th object created by the genesis of `chain_id`, for the
+/// objects that have no reserved ID.
+pub fn derive_object_id(chain_id: &str, index: u64) -> AccountAddress {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"kari::genesis::");
+    hasher.update(chain_id.as_bytes());
+    hasher.update(index.to_le_bytes());
+    AccountAddress::new(hasher.finalize().into())
+}
+
+fn system_object(id: AccountAddress, type_: &str, value: &impl Serialize) -> GenesisObject {
+    GenesisObject {
+        id,
+        type_: type_.to_string(),
+        owner: Owner::Shared,
+        contents: bcs::to_bytes(value).expect("system objects serialize"),
+    }
+}
+
+/// Type of the dynamic fields named by a `u64`, e.g. a version, holding a
+/// `value_type`.
+fn u64_field_type(value_type: &str) -> String {
+    let value_type = parse_struct_tag(value_type).expect("system types parse");
+    field_type(TypeTag::U64, TypeTag::Struct(Box::new(value_type))).to_string()
+}
+
+/// The dynamic field of `parent` named `name`, as `dynamic_field::add`
+/// creates it.
+fn dynamic_field(
+    parent: AccountAddress,
+    name: u64,
+    value_type: &str,
+    value: &impl Serialize,
+) -> GenesisObject {
+    let id = field_id(parent, &TypeTag::U64, &name.to_le_bytes());
+    GenesisObject {
+        id,
+        type_: u64_field_type(value_type),
+        owner: Owner::Object(parent),
+        contents: bcs::to_bytes(&Field { id, name, value }).expect("dynamic fields serialize"),
+    }
+}
+
+fn validate(config: &GenesisConfig) -> Result<Vec<GenesisValidator>, GenesisError> {
+    let invalid = |message: String| Err(GenesisError::Config(message));
+    if config.chain_id.trim().is_empty() {
+        return invalid("chain_id is empty".to_string());
+    }
+    if config.validators.is_empty() {
+        return invalid("at least one validator is required".to_string());
+    }
+
+    let mut addresses = BTreeSet::new();
+    let mut names = BTreeSet::new();
+    let mut validators = Vec::new();
+    for validator in &config.validators {
+        if !addresses.insert(validator.address) || !names.insert(&validator.name) {
+            return invalid(format!("validator {} is listed twice", validator.name));
+        }
+        if validator.stake == 0 {
+            return invalid(format!("validator {} has no stake", validator.name));
+        }
+        let protocol_key = hex::decode(validator.protocol_key.trim_start_matches("0x"))
+            .ok()
+            .filter(|key| !key.is_empty());
+        let Some(protocol_key) = protocol_key else {
+            return invalid(format!(
+                "protocol_key of validator {} is not a hex encoded key",
+                validator.name
+            ));
+        };
+        validators.push(GenesisValidator {
+            name: validator.name.clone(),
+            address: validator.address,
+            protocol_key,
+            network_address: validator.network_address.clone(),
+            stake: validator.stake,
+        });
+    }
+    for allocation in &config.allocations {
+        if is_vm_or_system_reserved_address(allocation.recipient) {
+            return invalid(format!(
+                "allocation to reserved address {}",
+                allocation.recipient.to_hex_literal()
+            ));
+        }
+        if allocation.amount == 0 {
+            return invalid(format!(
+                "empty allocation to {}",
+                allocation.recipient.to_hex_literal()
+            ));
+        }
+    }
+
+    let supply: u128 = config
+        .validators
+        .iter()
+        .map(|v| v.stake as u128)
+        .chain(config.allocations.iter().map(|a| a.amount as u128))
+        .sum();
+    if supply != TOTAL_SUPPLY_MIST as u128 {
+        return Err(GenesisError::Supply {
+            expected: TOTAL_SUPPLY_MIST,
+            actual: supply,
+        });
+    }
+    Ok(validators)
+}
+
+/// Build the genesis described by `config`.
+pub fn build(config: &GenesisConfig) -> Result<Genesis, GenesisError> {
+    let validators = validate(config)?;
+    let chain_id = config.chain_id.as_str();
+    let mut new_ids = (0..).map(|index| derive_object_id(chain_id, index));
+    let versioned_id = new_ids.next().unwrap();
+    let lists_id = new_ids.next().unwrap();
+    let per_type_list = PerTypeList {
+        id: new_ids.next().unwrap(),
+        denied_count: Collection {
+            id: new_ids.next().unwrap(),
+            size: 0,
+        },
+        denied_addresses: Collection {
+            id: new_ids.next().unwrap(),
+            size: 0,
+        },
+    };
+
+    let mut objects = vec![
+        system_object(
+            CLOCK_OBJECT_ID,
+            "0x2::clock::Clock",
+            &Clock {
+                id: CLOCK_OBJECT_ID,
+                timestamp_ms: config.timestamp_ms,
+            },
+        ),
+        system_object(
+            AUTHENTICATOR_STATE_OBJECT_ID,
+            "0x2::authenticator_state::AuthenticatorState",
+            &AuthenticatorState {
+                id: AUTHENTICATOR_STATE_OBJECT_ID,
+                version: SYSTEM_OBJECT_VERSION,
+            },
+        ),
+        dynamic_field(
+            AUTHENTICATOR_STATE_OBJECT_ID,
+            SYSTEM_OBJECT_VERSION,
+            AUTHENTICATOR_STATE_INNER_TYPE,
+            &AuthenticatorStateInner {
+                version: SYSTEM_OBJECT_VERSION,
+                active_jwks: vec![],
+            },
+        ),
+        system_object(
+            RANDOM_OBJECT_ID,
+            "0x2::random::Random",
+            &Random {
+                id: RANDOM_OBJECT_ID,
+                inner: Versioned {
+                    id: versioned_id,
+                    version: SYSTEM_OBJECT_VERSION,
+                },
+            },
+        ),
+        dynamic_field(
+            versioned_id,
+            SYSTEM_OBJECT_VERSION,
+            RANDOM_INNER_TYPE,
+            &RandomInner {
+                version: SYSTEM_OBJECT_VERSION,
+                epoch: 0,
+                randomness_round: 0,
+                random_bytes: vec![],
+            },
+        ),
+        system_object(
+            DENY_LIST_OBJECT_ID,
+            "0x2::deny_list::DenyList",
+            &DenyList {
+                id: DENY_LIST_OBJECT_ID,
+                // Holds the deny list of `Coin`
+                lists: Collection {
+                    id: lists_id,
+                    size: 1,
+                },
+            },
+        ),
+        dynamic_field(lists_id, COIN_INDEX, PER_TYPE_LIST_TYPE, &per_type_list),
+    ];
+
+    for allocation in &config.allocations {
+        let id = new_ids.next().unwrap();
+        objects.push(GenesisObject {
+            id,
+            type_: KARI_COIN_TYPE.to_string(),
+            owner: Owner::Address(allocation.recipient),
+            contents: bcs::to_bytes(&Coin {
+                id,
+                balance: allocation.amount,
+            })
+            .expect("coins serialize"),
+        });
+    }
+
+    Ok(Genesis {
+        chain_id: config.chain_id.clone(),
+        timestamp_ms: config.timestamp_ms,
+        packages: BuiltInFramework::iter_system_packages().cloned().collect(),
+        objects,
+        validators,
+    })
+}
+
+impl Genesis {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bcs::to_bytes(self).expect("genesis serializes")
+    }
+
+    pub fn from_bytes(blob: &[u8]) -> Result<Self, GenesisError> {
+        bcs::from_bytes(blob).map_err(|e| GenesisError::Blob(e.to_string()))
+    }
+
+    /// Hex encoded SHA3-256 digest of the genesis blob.
+    pub fn digest(&self) -> String {
+        hex::encode(Sha3_256::digest(self.to_bytes()))
+    }
+
+    /// KARI held by the validators and the genesis coins, in MIST.
+    pub fn total_supply(&self) -> Result<u128, GenesisError> {
+        let mut supply: u128 = self.validators.iter().map(|v| v.stake as u128).sum();
+        for object in &self.objects {
+            if object.type_ == KARI_COIN_TYPE {
+                let coin: Coin = bcs::from_bytes(&object.contents)
+                    .map_err(|e| GenesisError::Blob(format!("coin {}: {}", object.id, e)))?;
+                supply += coin.balance as u128;
+            }
+        }
+        Ok(supply)
+    }
+
+    /// Check that the genesis publishes the system packages of this build,
+    /// creates every system object with its dynamic fields and mints exactly
+    /// the total supply.
+    pub fn verify(&self) -> Result<(), GenesisError> {
+        let invalid = |message: String| Err(GenesisError::Blob(message));
+        let packages: Vec<&SystemPackage> = BuiltInFramework::iter_system_packages().collect();
+        if self.packages.iter().collect::<Vec<_>>() != packages {
+            return invalid("system packages differ from the embedded framework".to_string());
+        }
+        for id in [
+            CLOCK_OBJECT_ID,
+            AUTHENTICATOR_STATE_OBJECT_ID,
+            RANDOM_OBJECT_ID,
+            DENY_LIST_OBJECT_ID,
+        ] {
+            let found = self
+                .objects
+                .iter()
+                .any(|object| object.id == id && object.owner == Owner::Shared);
+            if !found {
+                return invalid(format!("system object {} is missing", id.to_hex_literal()));
+            }
+        }
+        let mut ids = BTreeSet::new();
+        if let Some(object) = self.objects.iter().find(|object| !ids.insert(object.id)) {
+            return invalid(format!("object {} is created twice", object.id));
+        }
+        self.verify_dynamic_fields()?;
+        if self.validators.is_empty() {
+            return invalid("the validator set is empty".to_string());
+        }
+        let supply = self.total_supply()?;
+        if supply != TOTAL_SUPPLY_MIST as u128 {
+            return Err(GenesisError::Supply {
+                expected: TOTAL_SUPPLY_MIST,
+                actual: supply,
+            });
+        }
+        Ok(())
+    }
+
+    // The inner objects of `AuthenticatorState` and `Random` must be the field
+    // named by their version, and the bag and tables of the `DenyList` must
+    // hold as many fields as their size
+    fn verify_dynamic_fields(&self) -> Result<(), GenesisError> {
+        let state: AuthenticatorState = self.decode(AUTHENTICATOR_STATE_OBJECT_ID)?;
+        let inner: AuthenticatorStateInner =
+            self.field(state.id, state.version, AUTHENTICATOR_STATE_INNER_TYPE)?;
+        self.check_version(state.id, state.version, inner.version)?;
+        self.check_size(state.id, 1)?;
+
+        let random: Random = self.decode(RANDOM_OBJECT_ID)?;
+        let inner: RandomInner =
+            self.field(random.inner.id, random.inner.version, RANDOM_INNER_TYPE)?;
+        self.check_version(random.id, random.inner.version, inner.version)?;
+        self.check_size(random.inner.id, 1)?;
+
+        let deny_list: DenyList = self.decode(DENY_LIST_OBJECT_ID)?;
+        let list: PerTypeList = self.field(deny_list.lists.id, COIN_INDEX, PER_TYPE_LIST_TYPE)?;
+        for collection in [&deny_list.lists, &list.denied_count, &list.denied_addresses] {
+            self.check_size(collection.id, collection.size)?;
+        }
+        Ok(())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, id: AccountAddress) -> Result<T, GenesisError> {
+        let object = self.objects.iter().find(|object| object.id == id);
+        let Some(object) = object else {
+            return Err(GenesisError::Blob(format!(
+                "object {} is missing",
+                id.to_hex_literal()
+            )));
+        };
+        bcs::from_bytes(&object.contents)
+            .map_err(|e| GenesisError::Blob(format!("object {}: {}", id.to_hex_literal(), e)))
+    }
+
+    // The value of the dynamic field of `parent` named `name`
+    fn field<T: DeserializeOwned>(
+        &self,
+        parent: AccountAddress,
+        name: u64,
+        value_type: &str,
+    ) -> Result<T, GenesisError> {
+        let id = field_id(parent, &TypeTag::U64, &name.to_le_bytes());
+        let field: Field<u64, T> = self.decode(id)?;
+        let object = self.objects.iter().find(|object| object.id == id).unwrap();
+        if object.type_ != u64_field_type(value_type)
+            || object.owner != Owner::Object(parent)
+            || field.id != id
+            || field.name != name
+        {
+            return Err(GenesisError::Blob(format!(
+                "field {} of {} is not a {} owned by it",
+                name,
+                parent.to_hex_literal(),
+                value_type
+            )));
+        }
+        Ok(field.value)
+    }
+
+    fn check_version(
+        &self,
+        id: AccountAddress,
+        version: u64,
+        inner_version: u64,
+    ) -> Result<(), GenesisError> {
+        if version != inner_version {
+            return Err(GenesisError::Blob(format!(
+                "object {} is at version {} but its inner object at version {}",
+                id.to_hex_literal(),
+                version,
+                inner_version
+            )));
+        }
+        Ok(())
+    }
+
+    // Check that `size` objects are dynamic fields of `parent`
+    fn check_size(&self, parent: AccountAddress, size: u64) -> Result<(), GenesisError> {
+        let fields = self
+            .objects
+            .iter()
+            .filter(|object| object.owner == Owner::Object(parent))
+            .count();
+        if fields as u64 != size {
+            return Err(GenesisError::Blob(format!(
+                "object {} has {} dynamic fields instead of {}",
+                parent.to_hex_literal(),
+                fields,
+                size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the genesis was built from `config`.
+    pub fn verify_config(&self, config: &GenesisConfig) -> Result<(), GenesisError> {
+        let expected = build(config)?.digest();
+        let actual = self.digest();
+        if expected != actual {
+            return Err(GenesisError::Digest { expected, actual });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> GenesisConfig {
+        GenesisConfig::from_toml(config::TEMPLATE).unwrap()
+    }
+
+    #[test]
+    fn test_build_is_deterministic() {
+        let genesis = build(&template()).unwrap();
+        genesis.verify().unwrap();
+        assert_eq!(genesis.digest(), build(&template()).unwrap().digest());
+        assert_eq!(genesis.packages.len(), 3);
+        assert_eq!(genesis.objects.len(), 8);
+
+        let decoded = Genesis::from_bytes(&genesis.to_bytes()).unwrap();
+        assert_eq!(decoded, genesis);
+        decoded.verify_config(&template()).unwrap();
+
+        let mut other = template();
+        other.chain_id = "kari-testnet".to_string();
+        assert!(matches!(
+            genesis.verify_config(&other),
+            Err(GenesisError::Digest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let mut config = template();
+        config.allocations[0].amount -= 1;
+        assert_eq!(
+            build(&config).unwrap_err(),
+            GenesisError::Supply {
+                expected: TOTAL_SUPPLY_MIST,
+                actual: TOTAL_SUPPLY_MIST as u128 - 1,
+            }
+        );
+
+        let mut config = template();
+        config.allocations[0].recipient = AccountAddress::TWO;
+        assert!(matches!(build(&config), Err(GenesisError::Config(_))));
+
+        let mut config = template();
+        config.validators.push(config.validators[0].clone());
+        assert!(matches!(build(&config), Err(GenesisError::Config(_))));
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let mut genesis = build(&template()).unwrap();
+        let coin = genesis.objects.last_mut().unwrap();
+        coin.contents = bcs::to_bytes(&Coin {
+            id: coin.id,
+            balance: u64::MAX,
+        })
+        .unwrap();
+        assert!(matches!(genesis.verify(), Err(GenesisError::Supply { .. })));
+
+        let mut genesis = build(&template()).unwrap();
+        genesis
+            .objects
+            .retain(|object| object.id != CLOCK_OBJECT_ID);
+        assert!(matches!(genesis.verify(), Err(GenesisError::Blob(_))));
+
+        // The inner object of `Random` must be created with it
+        let mut genesis = build(&template()).unwrap();
+        genesis
+            .objects
+            .retain(|object| !object.type_.ends_with("::random::RandomInner>"));
+        assert!(matches!(genesis.verify(), Err(GenesisError::Blob(_))));
+
+        // The `DenyList` bag must hold as many fields as its size
+        let mut genesis = build(&template()).unwrap();
+        let deny_list = genesis
+            .objects
+            .iter_mut()
+            .find(|object| object.id == DENY_LIST_OBJECT_ID)
+            .unwrap();
+        let mut value: DenyList = bcs::from_bytes(&deny_list.contents).unwrap();
+        value.lists.size = 2;
+        deny_list.contents = bcs::to_bytes(&value).unwrap();
+        assert!(matches!(genesis.verify(), Err(GenesisError::Blob(_))));
+    }
+}
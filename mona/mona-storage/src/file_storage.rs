@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::upload::{UploadSession, STAGING_DIR};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileStorage {
     pub id: Uuid,
@@ -39,6 +42,36 @@ pub enum StorageError {
 
     #[error("File not found: {0}")]
     FileNotFound(String),
+
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    #[error("Invalid content hash: '{0}'")]
+    InvalidHash(String),
+
+    #[error("Metadata record not found: {0}")]
+    RecordNotFound(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Index error: {0}")]
+    Index(#[from] rocksdb::Error),
+
+    #[error("Upload session not found: {0}")]
+    SessionNotFound(String),
+
+    #[error("Source file changed during upload: {0}")]
+    SourceChanged(String),
+
+    #[error("Invalid or unknown version: {0}")]
+    InvalidVersion(String),
+
+    #[error("Storage quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Transaction failed: {0}")]
+    Transaction(String),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -49,47 +82,41 @@ pub struct FileMetadata {
     pub uploaded_at: SystemTime,
 }
 
-const KARI_DIR: &str = ".kari";
-const STORAGE_DIR: &str = "storage";
-
-fn get_storage_path() -> PathBuf {
-    let home_dir = dirs::home_dir().expect("Could not find home directory");
-    let kari_path = home_dir.join(KARI_DIR);
-    let storage_path = kari_path.join(STORAGE_DIR);
-
-    // Create directories if they don't exist
-    if !kari_path.exists() {
-        std::fs::create_dir_all(&kari_path).expect("Failed to create .kari directory");
-    }
-    if !storage_path.exists() {
-        std::fs::create_dir_all(&storage_path).expect("Failed to create storage directory");
+/// Create the storage directory `root` if it doesn't exist.
+pub(crate) fn ensure_storage_dir(root: &Path) -> Result<PathBuf, StorageError> {
+    if !root.exists() {
+        fs::create_dir_all(root)?;
     }
+    Ok(root.to_path_buf())
+}
 
-    storage_path
+/// Write `data` to a temporary file next to `path`, then rename it over `path`.
+/// The temporary file has a unique name, so it never clobbers a stored file
+/// (e.g. the content of an upload with a `.tmp` extension).
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<(), StorageError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(data)?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
 }
 
 // Implement methods for FileStorage
 impl FileStorage {
-    pub fn init_storage() -> std::io::Result<()> {
-        let home = dirs::home_dir().expect("Could not find home directory");
-        let kari_dir = home.join(".kari");
-        let storage_dir = kari_dir.join("storage");
-
-        // Create .kari directory if it doesn't exist
-        if !kari_dir.exists() {
-            fs::create_dir_all(&kari_dir)?;
-        }
-
-        // Create storage directory if it doesn't exist
-        if !storage_dir.exists() {
-            fs::create_dir_all(&storage_dir)?;
-        }
-
+    pub fn init_storage(root: &Path) -> Result<(), StorageError> {
+        // Creates the storage directory if it doesn't exist
+        ensure_storage_dir(root)?;
         Ok(())
     }
 
-    pub fn new() -> Result<Self, StorageError> {
-        let path = get_storage_path();
+    /// A store in the directory `root`, e.g. the `storage_path` of the Kari
+    /// configuration.
+    pub fn new(root: &Path) -> Result<Self, StorageError> {
+        let path = ensure_storage_dir(root)?;
 
         Ok(FileStorage {
             id: Uuid::new_v4(),
@@ -105,14 +132,33 @@ impl FileStorage {
     }
 
     pub fn store(&self, filename: &str, data: &[u8]) -> Result<FileStorage, StorageError> {
+        self.store_reader(filename, data)
+    }
+
+    /// Stream `reader` into `filename`, staging the content so that the
+    /// destination is only replaced once it has been fully written.
+    pub fn store_reader(&self, filename: &str, mut reader: impl Read) -> Result<FileStorage, StorageError> {
         let file_path = self.path.join(filename);
-        fs::write(&file_path, data)?;
+        let staging_dir = self.path.join(STAGING_DIR);
+        fs::create_dir_all(&staging_dir)?;
+
+        let staged_path = staging_dir.join(format!("{}.part", Uuid::new_v4()));
+        let size = {
+            let mut staged = fs::File::create(&staged_path)?;
+            let size = std::io::copy(&mut reader, &mut staged)?;
+            staged.sync_all()?;
+            size
+        };
+        if let Err(e) = fs::rename(&staged_path, &file_path) {
+            let _ = fs::remove_file(&staged_path);
+            return Err(e.into());
+        }
 
         Ok(FileStorage {
             id: self.id,
             metadata: FileMetadata {
                 filename: filename.to_string(),
-                size: data.len() as u64,
+                size,
                 content_type: mime_guess::from_path(filename)
                     .first_or_octet_stream()
                     .to_string(),
@@ -156,69 +202,42 @@ impl FileStorage {
     }
 
     // Get complete storage path for a file
-    pub fn get_file_path(&self, filename: &str) -> PathBuf {
-        get_storage_path().join(filename)
+    pub fn get_file_path(&self, filename: &str) -> Result<PathBuf, StorageError> {
+        Ok(self.path.join(filename))
     }
 
     // file from storage
-    pub fn upload(source_path: impl AsRef<Path>, filename: String) -> Result<Self, StorageError> {
-        let source_path = source_path.as_ref();
-        
-        // Check source file
-        if !source_path.exists() {
-            return Err(StorageError::FileNotFound(
-                source_path.to_string_lossy().to_string(),
-            ));
-        }
-    
+    pub fn upload(
+        root: &Path,
+        source_path: impl AsRef<Path>,
+        filename: String,
+    ) -> Result<Self, StorageError> {
+        Self::upload_with_progress(root, source_path, filename, |_, _| {})
+    }
+
+    /// Upload `source_path` in chunks, reporting `(bytes_written, total_size)`
+    /// to `progress`. If the upload is interrupted it can be continued with
+    /// `UploadSession::resume`.
+    pub fn upload_with_progress(
+        root: &Path,
+        source_path: impl AsRef<Path>,
+        filename: String,
+        progress: impl FnMut(u64, u64),
+    ) -> Result<Self, StorageError> {
         // Initialize storage
-        FileStorage::init_storage()?;
-    
-        // Generate new UUID for file
-        let id = Uuid::new_v4();
-        let storage_path = get_storage_path();
-        
-        // Create paths
-        let file_ext = source_path.extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("");
-        let dest_filename = format!("{}.{}", id, file_ext);
-        let dest_path = storage_path.join(&dest_filename);
-        
-        // Create metadata
-        let metadata = FileMetadata {
-            filename,
-            size: fs::metadata(source_path)?.len(),
-            content_type: mime_guess::from_path(source_path)
-                .first_or_octet_stream()
-                .to_string(),
-            uploaded_at: SystemTime::now(),
-        };
-    
-        // Save file
-        fs::copy(source_path, &dest_path)?;
-        
-        // Save metadata
-        let metadata_path = storage_path.join(format!("{}.json", id));
-        let metadata_json = serde_json::to_string(&metadata)?;
-        fs::write(&metadata_path, metadata_json)?;
-    
-        Ok(FileStorage {
-            id,
-            metadata,
-            path: dest_path,
-            created_at: SystemTime::now(),
-        })
+        FileStorage::init_storage(root)?;
+
+        UploadSession::begin(root, source_path, filename, None)?.run(progress)
     }
 
 
-    pub fn get_by_id(id_str: &str) -> Result<Self, StorageError> {
+    pub fn get_by_id(root: &Path, id_str: &str) -> Result<Self, StorageError> {
         // Parse UUID
         let id = Uuid::parse_str(id_str)
             .map_err(|_| StorageError::InvalidId)?;
     
         // Get storage path
-        let storage_path = get_storage_path();
+        let storage_path = root;
         
         // Find file by looking for metadata first
         let metadata_path = storage_path.join(format!("{}.json", id_str));
@@ -1,7 +1,22 @@
 pub mod file_storage;
+pub mod metadata;
+pub mod quota;
+pub mod upload;
+pub mod versions;
 
 pub use file_storage::{
     FileStorage,
     StorageError,
     FileMetadata
-};
\ No newline at end of file
+};
+pub use metadata::{
+    LocalLedger,
+    MetadataCall,
+    MetadataEvent,
+    MetadataIndexer,
+    MetadataLedger,
+    MetadataRecord
+};
+pub use quota::QuotaManager;
+pub use upload::UploadSession;
+pub use versions::VersionStore;
\ No newline at end of file
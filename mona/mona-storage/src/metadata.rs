@@ -0,0 +1,635 @@
+//! Off-chain side of the `kanari_framework::metadata` registry.
+//!
+//! Registry calls are submitted through a [`MetadataLedger`], which returns the
+//! events emitted by the Move module. The [`MetadataIndexer`] consumes those
+//! events and keeps a RocksDB index so that records can be looked up by owner
+//! or by content hash.
+//!
+//! The [`LocalLedger`] keeps the event log the indexer reads. It executes the
+//! registry rules itself, or caches the events of calls executed on-chain
+//! with [`LocalLedger::append`].
+
+use crate::file_storage::StorageError;
+use rocksdb::{Direction, IteratorMode, DB};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Allows reading the content referenced by a record.
+pub const PERM_READ: u8 = 1;
+/// Allows publishing new versions of a record.
+pub const PERM_WRITE: u8 = 2;
+/// Allows changing the permissions of a record.
+pub const PERM_ADMIN: u8 = 4;
+/// Union of all known permission flags.
+pub const PERM_ALL: u8 = PERM_READ | PERM_WRITE | PERM_ADMIN;
+
+const METADATA_DIR: &str = "metadata";
+const EVENTS_FILE: &str = "events.jsonl";
+const INDEX_DIR: &str = "index";
+/// Length of a hex encoded SHA-256 hash.
+const HASH_HEX_LEN: usize = 64;
+
+/// Mirror of `kanari_framework::metadata::Permissions`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions {
+    pub public_read: bool,
+    pub grants: BTreeMap<String, u8>,
+}
+
+/// Mirror of `kanari_framework::metadata::MetadataRecord`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataRecord {
+    pub id: String,
+    /// Hex encoded SHA-256 of the file content.
+    pub hash: String,
+    pub owner: String,
+    pub version: u64,
+    pub timestamp: u64,
+    pub permissions: Permissions,
+}
+
+/// Events emitted by `kanari_framework::metadata`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MetadataEvent {
+    MetadataRegistered {
+        id: String,
+        owner: String,
+        hash: String,
+        timestamp: u64,
+    },
+    MetadataUpdated {
+        id: String,
+        updated_by: String,
+        previous_hash: String,
+        hash: String,
+        version: u64,
+        timestamp: u64,
+    },
+    MetadataTransferred {
+        id: String,
+        from: String,
+        to: String,
+    },
+    PermissionsChanged {
+        id: String,
+        changed_by: String,
+        grantee: String,
+        flags: u8,
+    },
+    PublicReadChanged {
+        id: String,
+        changed_by: String,
+        public_read: bool,
+    },
+}
+
+impl MetadataEvent {
+    /// ID of the record the event refers to.
+    pub fn record_id(&self) -> &str {
+        match self {
+            MetadataEvent::MetadataRegistered { id, .. }
+            | MetadataEvent::MetadataUpdated { id, .. }
+            | MetadataEvent::MetadataTransferred { id, .. }
+            | MetadataEvent::PermissionsChanged { id, .. }
+            | MetadataEvent::PublicReadChanged { id, .. } => id,
+        }
+    }
+}
+
+/// Entry functions of `kanari_framework::metadata`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataCall {
+    Register { hash: String },
+    UpdateVersion { id: String, hash: String },
+    Transfer { id: String, recipient: String },
+    SetPermissions { id: String, grantee: String, flags: u8 },
+    SetPublicRead { id: String, public_read: bool },
+}
+
+impl MetadataRecord {
+    /// Whether `addr` holds every flag of `flags`, following the rules of
+    /// `metadata::has_permission`.
+    pub fn has_permission(&self, addr: &str, flags: u8) -> bool {
+        if addr == self.owner {
+            return true;
+        }
+        if flags == PERM_READ && self.permissions.public_read {
+            return true;
+        }
+        self.permissions
+            .grants
+            .get(addr)
+            .is_some_and(|granted| granted & flags == flags)
+    }
+
+    /// Create a record from its registration event.
+    fn from_event(event: &MetadataEvent) -> Option<Self> {
+        match event {
+            MetadataEvent::MetadataRegistered {
+                id,
+                owner,
+                hash,
+                timestamp,
+            } => Some(MetadataRecord {
+                id: id.clone(),
+                hash: hash.clone(),
+                owner: owner.clone(),
+                version: 1,
+                timestamp: *timestamp,
+                permissions: Permissions::default(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Apply an event emitted for this record.
+    fn apply(&mut self, event: &MetadataEvent) {
+        match event {
+            MetadataEvent::MetadataRegistered { .. } => {}
+            MetadataEvent::MetadataUpdated {
+                hash,
+                version,
+                timestamp,
+                ..
+            } => {
+                self.hash = hash.clone();
+                self.version = *version;
+                self.timestamp = *timestamp;
+            }
+            MetadataEvent::MetadataTransferred { to, .. } => {
+                self.owner = to.clone();
+                self.permissions.grants.remove(to);
+            }
+            MetadataEvent::PermissionsChanged { grantee, flags, .. } => {
+                if *flags == 0 {
+                    self.permissions.grants.remove(grantee);
+                } else {
+                    self.permissions.grants.insert(grantee.clone(), *flags);
+                }
+            }
+            MetadataEvent::PublicReadChanged { public_read, .. } => {
+                self.permissions.public_read = *public_read;
+            }
+        }
+    }
+}
+
+/// Normalize an address to its `0x`-prefixed, 32 byte lowercase hex form.
+pub fn normalize_address(addr: &str) -> Result<String, StorageError> {
+    let hex = addr.strip_prefix("0x").unwrap_or(addr);
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(StorageError::InvalidAddress(addr.to_string()));
+    }
+    Ok(format!("0x{:0>64}", hex.to_lowercase()))
+}
+
+/// Compute the hex encoded SHA-256 of a file, without loading it in memory.
+pub fn hash_file(path: impl AsRef<Path>) -> Result<String, StorageError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Lowercase `hash`, which must be a hex encoded SHA-256.
+fn normalize_hash(hash: &str) -> Result<String, StorageError> {
+    if hash.len() != HASH_HEX_LEN || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(StorageError::InvalidHash(hash.to_string()));
+    }
+    Ok(hash.to_lowercase())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Something that executes metadata registry calls and exposes the emitted events.
+pub trait MetadataLedger {
+    /// Execute `call` on behalf of `sender`, returning the emitted events.
+    fn submit(&mut self, sender: &str, call: MetadataCall) -> Result<Vec<MetadataEvent>, StorageError>;
+
+    /// All events with a sequence number greater or equal to `from`, in order.
+    fn events_since(&self, from: u64) -> Result<Vec<(u64, MetadataEvent)>, StorageError>;
+}
+
+/// A ledger executing the registry rules locally, appending the emitted events
+/// to `<storage>/metadata/events.jsonl`.
+pub struct LocalLedger {
+    events_path: PathBuf,
+    records: HashMap<String, MetadataRecord>,
+    next_seq: u64,
+}
+
+impl LocalLedger {
+    /// Open the local ledger of the store `root`.
+    pub fn open_in(root: &Path) -> Result<Self, StorageError> {
+        Self::open(root.join(METADATA_DIR))
+    }
+
+    /// Open the ledger stored in `dir`, replaying its events.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        fs::create_dir_all(dir.as_ref())?;
+        let mut ledger = LocalLedger {
+            events_path: dir.as_ref().join(EVENTS_FILE),
+            records: HashMap::new(),
+            next_seq: 0,
+        };
+        for (seq, event) in ledger.events_since(0)? {
+            ledger.apply(&event);
+            ledger.next_seq = seq + 1;
+        }
+        Ok(ledger)
+    }
+
+    /// Append events emitted elsewhere, e.g. by the registry on-chain, to the
+    /// log.
+    pub fn append(&mut self, events: &[MetadataEvent]) -> Result<(), StorageError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.events_path)?;
+        for event in events {
+            writeln!(file, "{}", serde_json::to_string(event)?)?;
+            self.apply(event);
+            self.next_seq += 1;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, event: &MetadataEvent) {
+        if let Some(record) = MetadataRecord::from_event(event) {
+            self.records.insert(record.id.clone(), record);
+        } else if let Some(record) = self.records.get_mut(event.record_id()) {
+            record.apply(event);
+        }
+    }
+
+    fn record(&self, id: &str) -> Result<&MetadataRecord, StorageError> {
+        self.records
+            .get(id)
+            .ok_or_else(|| StorageError::RecordNotFound(id.to_string()))
+    }
+
+    fn require(&self, id: &str, sender: &str, flags: u8) -> Result<&MetadataRecord, StorageError> {
+        let record = self.record(id)?;
+        if !record.has_permission(sender, flags) {
+            return Err(StorageError::PermissionDenied(format!(
+                "{} cannot modify record {}",
+                sender, id
+            )));
+        }
+        Ok(record)
+    }
+
+    /// Derive the ID of a new record, mimicking a fresh object address.
+    fn derive_id(&self, sender: &str, hash: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(sender.as_bytes());
+        hasher.update(hash.as_bytes());
+        hasher.update(self.next_seq.to_le_bytes());
+        format!("0x{}", hex::encode(hasher.finalize()))
+    }
+
+    fn execute(&self, sender: &str, call: MetadataCall) -> Result<MetadataEvent, StorageError> {
+        let event = match call {
+            MetadataCall::Register { hash } => {
+                let hash = normalize_hash(&hash)?;
+                MetadataEvent::MetadataRegistered {
+                    id: self.derive_id(sender, &hash),
+                    owner: sender.to_string(),
+                    hash,
+                    timestamp: now_ms(),
+                }
+            }
+            MetadataCall::UpdateVersion { id, hash } => {
+                let hash = normalize_hash(&hash)?;
+                let record = self.require(&id, sender, PERM_WRITE)?;
+                MetadataEvent::MetadataUpdated {
+                    updated_by: sender.to_string(),
+                    previous_hash: record.hash.clone(),
+                    hash,
+                    version: record.version + 1,
+                    timestamp: now_ms(),
+                    id,
+                }
+            }
+            MetadataCall::Transfer { id, recipient } => {
+                let record = self.record(&id)?;
+                if record.owner != sender {
+                    return Err(StorageError::PermissionDenied(format!(
+                        "{} is not the owner of record {}",
+                        sender, id
+                    )));
+                }
+                let recipient = normalize_address(&recipient)?;
+                // `metadata::transfer` aborts with `ESameOwner`
+                if recipient == sender {
+                    return Err(StorageError::PermissionDenied(format!(
+                        "{} already owns record {}",
+                        sender, id
+                    )));
+                }
+                MetadataEvent::MetadataTransferred {
+                    id,
+                    from: sender.to_string(),
+                    to: recipient,
+                }
+            }
+            MetadataCall::SetPermissions { id, grantee, flags } => {
+                if flags & PERM_ALL != flags {
+                    return Err(StorageError::PermissionDenied(format!(
+                        "invalid permission flags {:#x}",
+                        flags
+                    )));
+                }
+                self.require(&id, sender, PERM_ADMIN)?;
+                MetadataEvent::PermissionsChanged {
+                    id,
+                    changed_by: sender.to_string(),
+                    grantee: normalize_address(&grantee)?,
+                    flags,
+                }
+            }
+            MetadataCall::SetPublicRead { id, public_read } => {
+                self.require(&id, sender, PERM_ADMIN)?;
+                MetadataEvent::PublicReadChanged {
+                    id,
+                    changed_by: sender.to_string(),
+                    public_read,
+                }
+            }
+        };
+        Ok(event)
+    }
+}
+
+impl MetadataLedger for LocalLedger {
+    fn submit(&mut self, sender: &str, call: MetadataCall) -> Result<Vec<MetadataEvent>, StorageError> {
+        let sender = normalize_address(sender)?;
+        let event = self.execute(&sender, call)?;
+        self.append(std::slice::from_ref(&event))?;
+        Ok(vec![event])
+    }
+
+    fn events_since(&self, from: u64) -> Result<Vec<(u64, MetadataEvent)>, StorageError> {
+        if !self.events_path.exists() {
+            return Ok(Vec::new());
+        }
+        let reader = BufReader::new(File::open(&self.events_path)?);
+        let mut events = Vec::new();
+        for (seq, line) in reader.lines().enumerate() {
+            let seq = seq as u64;
+            let line = line?;
+            if seq < from || line.trim().is_empty() {
+                continue;
+            }
+            events.push((seq, serde_json::from_str(&line)?));
+        }
+        Ok(events)
+    }
+}
+
+/// RocksDB backed index of `MetadataRecord`s built from registry events.
+///
+/// Keys:
+/// - `record/<id>` -> JSON encoded record
+/// - `owner/<owner>/<id>` and `hash/<hash>/<id>` -> empty
+/// - `cursor` -> sequence number of the next event to index
+pub struct MetadataIndexer {
+    db: DB,
+}
+
+const CURSOR_KEY: &[u8] = b"cursor";
+
+impl MetadataIndexer {
+    /// Open the index of the store `root`.
+    pub fn open_in(root: &Path) -> Result<Self, StorageError> {
+        Self::open(root.join(METADATA_DIR).join(INDEX_DIR))
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Ok(MetadataIndexer {
+            db: DB::open_default(path)?,
+        })
+    }
+
+    /// Index every event of `ledger` not indexed yet. Returns the number of
+    /// events processed.
+    pub fn sync(&self, ledger: &impl MetadataLedger) -> Result<usize, StorageError> {
+        let events = ledger.events_since(self.cursor()?)?;
+        for (seq, event) in &events {
+            self.index_event(event)?;
+            self.db.put(CURSOR_KEY, (seq + 1).to_le_bytes())?;
+        }
+        Ok(events.len())
+    }
+
+    fn cursor(&self) -> Result<u64, StorageError> {
+        Ok(match self.db.get(CURSOR_KEY)? {
+            Some(bytes) if bytes.len() == 8 => {
+                u64::from_le_bytes(bytes.as_slice().try_into().unwrap_or_default())
+            }
+            _ => 0,
+        })
+    }
+
+    /// Apply a single event to the index.
+    pub fn index_event(&self, event: &MetadataEvent) -> Result<(), StorageError> {
+        let previous = self.get(event.record_id())?;
+        let record = match (MetadataRecord::from_event(event), previous.clone()) {
+            (Some(record), _) => record,
+            (None, Some(mut record)) => {
+                record.apply(event);
+                record
+            }
+            // Events of records registered before the index was created.
+            (None, None) => return Ok(()),
+        };
+
+        if let Some(previous) = previous {
+            self.db.delete(owner_key(&previous.owner, &previous.id))?;
+            self.db.delete(hash_key(&previous.hash, &previous.id))?;
+        }
+        self.db.put(owner_key(&record.owner, &record.id), [])?;
+        self.db.put(hash_key(&record.hash, &record.id), [])?;
+        self.db
+            .put(record_key(&record.id), serde_json::to_vec(&record)?)?;
+        Ok(())
+    }
+
+    /// Look a record up by its ID.
+    pub fn get(&self, id: &str) -> Result<Option<MetadataRecord>, StorageError> {
+        match self.db.get(record_key(id))? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// All records currently owned by `owner`.
+    pub fn find_by_owner(&self, owner: &str) -> Result<Vec<MetadataRecord>, StorageError> {
+        let owner = normalize_address(owner)?;
+        self.find_by_prefix(format!("owner/{}/", owner))
+    }
+
+    /// All records whose current version has content hash `hash`.
+    pub fn find_by_hash(&self, hash: &str) -> Result<Vec<MetadataRecord>, StorageError> {
+        self.find_by_prefix(format!("hash/{}/", hash.to_lowercase()))
+    }
+
+    fn find_by_prefix(&self, prefix: String) -> Result<Vec<MetadataRecord>, StorageError> {
+        let mut records = Vec::new();
+        let iter = self
+            .db
+            .iterator(IteratorMode::From(prefix.as_bytes(), Direction::Forward));
+        for item in iter {
+            let (key, _) = item?;
+            let Some(id) = key.strip_prefix(prefix.as_bytes()) else {
+                break;
+            };
+            if let Some(record) = self.get(&String::from_utf8_lossy(id))? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn record_key(id: &str) -> Vec<u8> {
+    format!("record/{}", id).into_bytes()
+}
+
+fn owner_key(owner: &str, id: &str) -> Vec<u8> {
+    format!("owner/{}/{}", owner, id).into_bytes()
+}
+
+fn hash_key(hash: &str, id: &str) -> Vec<u8> {
+    format!("hash/{}/{}", hash, id).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(normalize_address("0x1").unwrap(), format!("0x{:0>64}", "1"));
+        assert_eq!(
+            normalize_address("ABC").unwrap(),
+            normalize_address("0xabc").unwrap()
+        );
+        assert!(normalize_address("0xzz").is_err());
+        assert!(normalize_address("").is_err());
+    }
+
+    #[test]
+    fn test_register_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = LocalLedger::open(dir.path()).unwrap();
+        let indexer = MetadataIndexer::open(dir.path().join(INDEX_DIR)).unwrap();
+
+        let hash = |digit: &str| digit.repeat(HASH_HEX_LEN);
+        let events = ledger
+            .submit("0xa", MetadataCall::Register { hash: hash("a") })
+            .unwrap();
+        let id = events[0].record_id().to_string();
+        ledger
+            .submit(
+                "0xa",
+                MetadataCall::UpdateVersion {
+                    id: id.clone(),
+                    hash: hash("b"),
+                },
+            )
+            .unwrap();
+        assert!(ledger
+            .submit(
+                "0xb",
+                MetadataCall::UpdateVersion {
+                    id: id.clone(),
+                    hash: hash("c"),
+                },
+            )
+            .is_err());
+
+        assert_eq!(indexer.sync(&ledger).unwrap(), 2);
+        assert!(indexer.find_by_hash(&hash("a")).unwrap().is_empty());
+        let by_hash = indexer.find_by_hash(&hash("b")).unwrap();
+        assert_eq!(by_hash.len(), 1);
+        assert_eq!(by_hash[0].version, 2);
+
+        ledger
+            .submit(
+                "0xa",
+                MetadataCall::Transfer {
+                    id: id.clone(),
+                    recipient: "0xb".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(indexer.sync(&ledger).unwrap(), 1);
+        assert!(indexer.find_by_owner("0xa").unwrap().is_empty());
+        assert_eq!(indexer.find_by_owner("0xb").unwrap()[0].id, id);
+
+        // Reopening the ledger replays its events.
+        let ledger = LocalLedger::open(dir.path()).unwrap();
+        assert_eq!(ledger.record(&id).unwrap().owner, normalize_address("0xb").unwrap());
+    }
+
+    #[test]
+    fn test_rejects_invalid_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = LocalLedger::open(dir.path()).unwrap();
+        let hash = "A".repeat(HASH_HEX_LEN);
+
+        // Hashes must be hex encoded SHA-256
+        let invalid = [
+            "aa".to_string(),
+            "g".repeat(HASH_HEX_LEN),
+            "a".repeat(HASH_HEX_LEN + 2),
+        ];
+        for hash in invalid {
+            assert!(matches!(
+                ledger.submit("0xa", MetadataCall::Register { hash }),
+                Err(StorageError::InvalidHash(_))
+            ));
+        }
+        let events = ledger
+            .submit("0xa", MetadataCall::Register { hash: hash.clone() })
+            .unwrap();
+        let id = events[0].record_id().to_string();
+        assert_eq!(ledger.record(&id).unwrap().hash, hash.to_lowercase());
+        assert!(matches!(
+            ledger.submit(
+                "0xa",
+                MetadataCall::UpdateVersion {
+                    id: id.clone(),
+                    hash: "bb".into(),
+                },
+            ),
+            Err(StorageError::InvalidHash(_))
+        ));
+
+        // Transferring a record to its owner aborts with `ESameOwner`
+        assert!(matches!(
+            ledger.submit(
+                "0xa",
+                MetadataCall::Transfer {
+                    id: id.clone(),
+                    recipient: "0x0a".into(),
+                },
+            ),
+            Err(StorageError::PermissionDenied(_))
+        ));
+        assert_eq!(ledger.record(&id).unwrap().version, 1);
+        assert_eq!(ledger.events_since(0).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,464 @@
+//! Disk usage accounting for the local store.
+//!
+//! Every stored file is tracked in `<storage>/usage.json` with its size, owner,
+//! pin state and last access time. Before an upload, [`QuotaManager::reserve`]
+//! checks the configured quotas and evicts the least recently used unpinned
+//! files until the new content fits. Files that are a version of a named file
+//! are never evicted, so that version chains don't point at deleted content.
+
+use crate::file_storage::{write_atomic, FileMetadata, FileStorage, StorageError};
+use crate::versions::VersionStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+const POLICY_FILE: &str = "policy.json";
+const USAGE_FILE: &str = "usage.json";
+
+/// Quotas applied to the local store. `None` means unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StoragePolicy {
+    pub max_total_bytes: Option<u64>,
+    pub max_bytes_per_owner: Option<u64>,
+}
+
+/// Usage record of a stored file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageEntry {
+    pub size: u64,
+    pub owner: Option<String>,
+    /// Pinned files are never evicted.
+    pub pinned: bool,
+    pub last_access: SystemTime,
+}
+
+/// Summary of the disk usage of the store.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct StorageStats {
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub pinned_count: usize,
+    pub pinned_bytes: u64,
+    pub bytes_per_owner: BTreeMap<String, u64>,
+    pub policy: StoragePolicy,
+}
+
+/// Tracks usage of the store and enforces its `StoragePolicy`.
+pub struct QuotaManager {
+    dir: PathBuf,
+    policy: StoragePolicy,
+    entries: BTreeMap<Uuid, UsageEntry>,
+}
+
+impl QuotaManager {
+    /// Open the usage index of the store in `dir`, picking up files stored
+    /// before they were tracked.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let dir = dir.as_ref().to_path_buf();
+        let policy = read_json(&dir.join(POLICY_FILE))?.unwrap_or_default();
+        let entries = read_json(&dir.join(USAGE_FILE))?.unwrap_or_default();
+        let mut manager = QuotaManager {
+            dir,
+            policy,
+            entries,
+        };
+        manager.reconcile()?;
+        Ok(manager)
+    }
+
+    pub fn policy(&self) -> &StoragePolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: StoragePolicy) -> Result<(), StorageError> {
+        self.policy = policy;
+        write_atomic(
+            &self.dir.join(POLICY_FILE),
+            serde_json::to_string_pretty(&self.policy)?.as_bytes(),
+        )
+    }
+
+    pub fn entry(&self, id: &Uuid) -> Option<&UsageEntry> {
+        self.entries.get(id)
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        write_atomic(
+            &self.dir.join(USAGE_FILE),
+            serde_json::to_string(&self.entries)?.as_bytes(),
+        )
+    }
+
+    // Track files present on disk but missing from the index, and forget
+    // entries whose files are gone.
+    fn reconcile(&mut self) -> Result<(), StorageError> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+        let mut on_disk = BTreeMap::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Uuid::parse_str(stem).ok())
+            else {
+                continue;
+            };
+            if let Some(metadata) = read_json::<FileMetadata>(&path)? {
+                on_disk.insert(id, metadata);
+            }
+        }
+
+        let before = self.entries.len();
+        self.entries.retain(|id, _| on_disk.contains_key(id));
+        let mut changed = before != self.entries.len();
+        for (id, metadata) in on_disk {
+            self.entries.entry(id).or_insert_with(|| {
+                changed = true;
+                UsageEntry {
+                    size: metadata.size,
+                    owner: None,
+                    pinned: false,
+                    last_access: metadata.uploaded_at,
+                }
+            });
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    fn owner_bytes(&self, owner: &str) -> u64 {
+        self.entries
+            .values()
+            .filter(|e| e.owner.as_deref() == Some(owner))
+            .map(|e| e.size)
+            .sum()
+    }
+
+    /// Unpinned files not in `versioned`, least recently used first, optionally
+    /// restricted to an owner.
+    fn eviction_candidates(
+        &self,
+        owner: Option<&str>,
+        versioned: &BTreeSet<Uuid>,
+    ) -> Vec<(Uuid, u64)> {
+        let mut candidates = self
+            .entries
+            .iter()
+            .filter(|(id, e)| !e.pinned && !versioned.contains(id))
+            .filter(|(_, e)| owner.is_none_or(|owner| e.owner.as_deref() == Some(owner)))
+            .map(|(id, e)| (*id, e.size, e.last_access))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, _, last_access)| *last_access);
+        candidates.into_iter().map(|(id, size, _)| (id, size)).collect()
+    }
+
+    /// Pick the files to evict so that `size` more bytes fit in the quotas,
+    /// keeping the files of `versioned`.
+    fn plan_eviction(
+        &self,
+        size: u64,
+        owner: Option<&str>,
+        versioned: &BTreeSet<Uuid>,
+    ) -> Result<Vec<Uuid>, StorageError> {
+        let mut evicted = Vec::new();
+
+        if let (Some(limit), Some(owner)) = (self.policy.max_bytes_per_owner, owner) {
+            let mut used = self.owner_bytes(owner);
+            let mut candidates = self.eviction_candidates(Some(owner), versioned).into_iter();
+            while used + size > limit {
+                let (id, freed) = candidates.next().ok_or_else(|| {
+                    StorageError::QuotaExceeded(format!(
+                        "owner {} would use {} of {} bytes",
+                        owner,
+                        used + size,
+                        limit
+                    ))
+                })?;
+                used -= freed;
+                evicted.push(id);
+            }
+        }
+
+        if let Some(limit) = self.policy.max_total_bytes {
+            let mut used = self.total_bytes()
+                - evicted
+                    .iter()
+                    .map(|id| self.entries[id].size)
+                    .sum::<u64>();
+            let mut candidates = self
+                .eviction_candidates(None, versioned)
+                .into_iter()
+                .filter(|(id, _)| !evicted.contains(id))
+                .collect::<Vec<_>>()
+                .into_iter();
+            while used + size > limit {
+                let (id, freed) = candidates.next().ok_or_else(|| {
+                    StorageError::QuotaExceeded(format!(
+                        "store would use {} of {} bytes",
+                        used + size,
+                        limit
+                    ))
+                })?;
+                used -= freed;
+                evicted.push(id);
+            }
+        }
+
+        Ok(evicted)
+    }
+
+    /// Make room for `size` bytes owned by `owner`, evicting least recently
+    /// used unpinned files that are no version of a named file as needed.
+    /// Returns the evicted file IDs, or `QuotaExceeded` without evicting
+    /// anything if the content cannot fit.
+    pub fn reserve(&mut self, size: u64, owner: Option<&str>) -> Result<Vec<Uuid>, StorageError> {
+        let versioned = VersionStore::open(&self.dir)?.file_ids()?;
+        let evicted = self.plan_eviction(size, owner, &versioned)?;
+        for id in &evicted {
+            match FileStorage::get_by_id(&self.dir, &id.to_string()) {
+                Ok(storage) => storage.delete()?,
+                Err(StorageError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+            self.entries.remove(id);
+        }
+        if !evicted.is_empty() {
+            self.save()?;
+        }
+        Ok(evicted)
+    }
+
+    /// Track a newly stored file.
+    pub fn record(&mut self, id: Uuid, size: u64, owner: Option<String>) -> Result<(), StorageError> {
+        self.entries.insert(
+            id,
+            UsageEntry {
+                size,
+                owner,
+                pinned: false,
+                last_access: SystemTime::now(),
+            },
+        );
+        self.save()
+    }
+
+    /// Mark a file as recently used.
+    pub fn touch(&mut self, id: &Uuid) -> Result<(), StorageError> {
+        if let Some(entry) = self.entries.get_mut(id) {
+            entry.last_access = SystemTime::now();
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Pin or unpin a file. Pinned files are never evicted.
+    pub fn set_pinned(&mut self, id: &Uuid, pinned: bool) -> Result<(), StorageError> {
+        let entry = self.entries.get_mut(id).ok_or(StorageError::NotFound)?;
+        entry.pinned = pinned;
+        self.save()
+    }
+
+    pub fn stats(&self) -> StorageStats {
+        let mut stats = StorageStats {
+            policy: self.policy.clone(),
+            ..Default::default()
+        };
+        for entry in self.entries.values() {
+            stats.file_count += 1;
+            stats.total_bytes += entry.size;
+            if entry.pinned {
+                stats.pinned_count += 1;
+                stats.pinned_bytes += entry.size;
+            }
+            if let Some(owner) = &entry.owner {
+                *stats.bytes_per_owner.entry(owner.clone()).or_default() += entry.size;
+            }
+        }
+        stats
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>, StorageError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Parse a byte size such as `512`, `10K`, `1.5G` (powers of 1024).
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn manager(policy: StoragePolicy) -> QuotaManager {
+        QuotaManager {
+            dir: PathBuf::new(),
+            policy,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    fn add(manager: &mut QuotaManager, size: u64, owner: &str, age_secs: u64, pinned: bool) -> Uuid {
+        let id = Uuid::new_v4();
+        manager.entries.insert(
+            id,
+            UsageEntry {
+                size,
+                owner: Some(owner.to_string()),
+                pinned,
+                last_access: SystemTime::now() - Duration::from_secs(age_secs),
+            },
+        );
+        id
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10K"), Some(10 * 1024));
+        assert_eq!(parse_size("1.5GB"), Some(3 << 29));
+        assert_eq!(parse_size("2MiB"), Some(2 << 20));
+        assert_eq!(parse_size("10X"), None);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_unpinned() {
+        let mut manager = manager(StoragePolicy {
+            max_total_bytes: Some(100),
+            max_bytes_per_owner: None,
+        });
+        let pinned = add(&mut manager, 40, "a", 300, true);
+        let oldest = add(&mut manager, 30, "a", 200, false);
+        let newest = add(&mut manager, 30, "b", 100, false);
+
+        let none = BTreeSet::new();
+        assert!(manager.plan_eviction(0, None, &none).unwrap().is_empty());
+        assert_eq!(
+            manager.plan_eviction(20, None, &none).unwrap(),
+            vec![oldest]
+        );
+        assert_eq!(
+            manager.plan_eviction(60, None, &none).unwrap(),
+            vec![oldest, newest]
+        );
+        assert!(matches!(
+            manager.plan_eviction(61, None, &none),
+            Err(StorageError::QuotaExceeded(_))
+        ));
+        assert!(!manager
+            .plan_eviction(60, None, &none)
+            .unwrap()
+            .contains(&pinned));
+        // Versioned files are kept like pinned ones
+        let versioned = BTreeSet::from([oldest]);
+        assert_eq!(
+            manager.plan_eviction(20, None, &versioned).unwrap(),
+            vec![newest]
+        );
+    }
+
+    #[test]
+    fn test_per_owner_quota() {
+        let mut manager = manager(StoragePolicy {
+            max_total_bytes: None,
+            max_bytes_per_owner: Some(50),
+        });
+        let own = add(&mut manager, 40, "a", 100, false);
+        add(&mut manager, 40, "b", 200, false);
+
+        let none = BTreeSet::new();
+        assert_eq!(
+            manager.plan_eviction(20, Some("a"), &none).unwrap(),
+            vec![own]
+        );
+        assert!(manager
+            .plan_eviction(20, Some("c"), &none)
+            .unwrap()
+            .is_empty());
+        assert!(manager.plan_eviction(51, Some("a"), &none).is_err());
+    }
+
+    #[test]
+    fn test_reserve() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("storage");
+        let store = |name: &str| {
+            let source = dir.path().join(name);
+            fs::write(&source, [0u8; 100]).unwrap();
+            FileStorage::upload(&root, &source, name.to_string()).unwrap()
+        };
+        // Uploaded in order, so least recently used first
+        let pinned = store("pinned.txt");
+        let versioned = store("versioned.txt");
+        let cached = store("cached.txt");
+
+        let mut quotas = QuotaManager::open(&root).unwrap();
+        quotas.set_pinned(&pinned.id, true).unwrap();
+        VersionStore::open(&root)
+            .unwrap()
+            .add_version(
+                "docs/a",
+                versioned.id,
+                &versioned.metadata,
+                "aa".to_string(),
+            )
+            .unwrap();
+        quotas
+            .set_policy(StoragePolicy {
+                max_total_bytes: Some(300),
+                max_bytes_per_owner: None,
+            })
+            .unwrap();
+
+        assert_eq!(quotas.reserve(50, None).unwrap(), vec![cached.id]);
+        assert!(!cached.path.exists());
+        assert!(matches!(
+            FileStorage::get_by_id(&root, &cached.id.to_string()),
+            Err(StorageError::NotFound)
+        ));
+        assert!(quotas.entry(&cached.id).is_none());
+
+        // Only pinned and versioned files are left
+        assert!(matches!(
+            quotas.reserve(150, None),
+            Err(StorageError::QuotaExceeded(_))
+        ));
+        assert!(pinned.path.exists());
+        assert!(versioned.path.exists());
+        let reopened = QuotaManager::open(&root).unwrap();
+        assert_eq!(reopened.stats().file_count, 2);
+    }
+}
@@ -0,0 +1,349 @@
+//! Chunked, resumable uploads into the local store.
+//!
+//! Content is streamed into `<storage>/staging/<upload_id>/` and only renamed to
+//! `<storage>/<uuid>.<ext>` once complete, so an interrupted upload never leaves
+//! a partial file among the stored ones. The session is persisted after every
+//! chunk so that [`UploadSession::resume`] can continue where it stopped.
+
+use crate::file_storage::{
+    ensure_storage_dir, write_atomic, FileMetadata, FileStorage, StorageError,
+};
+use crate::quota::QuotaManager;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Number of bytes copied between two session checkpoints.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+pub(crate) const STAGING_DIR: &str = "staging";
+const SESSION_FILE: &str = "session.json";
+const PART_FILE: &str = "data.part";
+
+/// State of an upload that has not completed yet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSession {
+    pub upload_id: Uuid,
+    /// ID the file will be stored under once the upload completes.
+    pub file_id: Uuid,
+    pub source: PathBuf,
+    pub filename: String,
+    pub extension: String,
+    pub content_type: String,
+    /// Owner the stored file is accounted to for quotas.
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub total_size: u64,
+    pub bytes_written: u64,
+    /// Modification time of the source when the upload started, used to
+    /// detect a source that changed before resuming.
+    pub source_modified: Option<SystemTime>,
+    pub started_at: SystemTime,
+    /// Store the upload goes into. Not persisted, so that a store that was
+    /// moved keeps its pending uploads.
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+impl UploadSession {
+    /// Start a new upload of `source` into the store `root`, stored under
+    /// `filename`. Room for the content is reserved up front, evicting cached
+    /// files if the store quotas require it.
+    pub fn begin(
+        root: &Path,
+        source: impl AsRef<Path>,
+        filename: String,
+        owner: Option<String>,
+    ) -> Result<Self, StorageError> {
+        let source = source.as_ref();
+        if !source.exists() {
+            return Err(StorageError::FileNotFound(
+                source.to_string_lossy().to_string(),
+            ));
+        }
+        let source_metadata = fs::metadata(source)?;
+        let root = ensure_storage_dir(root)?;
+        QuotaManager::open(&root)?.reserve(source_metadata.len(), owner.as_deref())?;
+
+        let session = UploadSession {
+            upload_id: Uuid::new_v4(),
+            file_id: Uuid::new_v4(),
+            source: source.canonicalize()?,
+            filename,
+            extension: source
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("")
+                .to_string(),
+            content_type: mime_guess::from_path(source)
+                .first_or_octet_stream()
+                .to_string(),
+            owner,
+            total_size: source_metadata.len(),
+            bytes_written: 0,
+            source_modified: source_metadata.modified().ok(),
+            started_at: SystemTime::now(),
+            root,
+        };
+
+        fs::create_dir_all(session.staging_dir())?;
+        File::create(session.part_path())?;
+        session.save()?;
+        Ok(session)
+    }
+
+    /// Load the session of an interrupted upload into the store `root`.
+    pub fn resume(root: &Path, upload_id: &str) -> Result<Self, StorageError> {
+        let id = Uuid::parse_str(upload_id).map_err(|_| StorageError::InvalidId)?;
+        let session_path = staging_root(root).join(id.to_string()).join(SESSION_FILE);
+        if !session_path.exists() {
+            return Err(StorageError::SessionNotFound(upload_id.to_string()));
+        }
+        let mut session: UploadSession = serde_json::from_str(&fs::read_to_string(session_path)?)?;
+        session.root = root.to_path_buf();
+
+        let source_metadata = fs::metadata(&session.source).map_err(|_| {
+            StorageError::FileNotFound(session.source.to_string_lossy().to_string())
+        })?;
+        if source_metadata.len() != session.total_size
+            || source_metadata.modified().ok() != session.source_modified
+        {
+            return Err(StorageError::SourceChanged(
+                session.source.to_string_lossy().to_string(),
+            ));
+        }
+        Ok(session)
+    }
+
+    /// All uploads into the store `root` that were started but not completed.
+    pub fn pending(root: &Path) -> Result<Vec<Self>, StorageError> {
+        let staging = staging_root(root);
+        if !staging.exists() {
+            return Ok(Vec::new());
+        }
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(staging)? {
+            let session_path = entry?.path().join(SESSION_FILE);
+            if let Ok(contents) = fs::read_to_string(&session_path) {
+                if let Ok(mut session) = serde_json::from_str::<UploadSession>(&contents) {
+                    session.root = root.to_path_buf();
+                    sessions.push(session);
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// Copy the remaining content, reporting `(bytes_written, total_size)` to
+    /// `progress` after every chunk, then move the file into the store.
+    pub fn run(mut self, mut progress: impl FnMut(u64, u64)) -> Result<FileStorage, StorageError> {
+        let mut source = File::open(&self.source)?;
+        source.seek(SeekFrom::Start(self.bytes_written))?;
+
+        // Anything past the last checkpoint may be incomplete; drop it.
+        let mut part = OpenOptions::new().write(true).open(self.part_path())?;
+        part.set_len(self.bytes_written)?;
+        part.seek(SeekFrom::End(0))?;
+
+        progress(self.bytes_written, self.total_size);
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            let read = read_chunk(&mut source, &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            part.write_all(&buffer[..read])?;
+            part.sync_data()?;
+
+            self.bytes_written += read as u64;
+            self.save()?;
+            progress(self.bytes_written, self.total_size);
+        }
+
+        if self.bytes_written != self.total_size {
+            return Err(StorageError::SourceChanged(
+                self.source.to_string_lossy().to_string(),
+            ));
+        }
+        drop(part);
+        self.finish()
+    }
+
+    /// Discard the upload and its staged content.
+    pub fn abort(self) -> Result<(), StorageError> {
+        fs::remove_dir_all(self.staging_dir())?;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<FileStorage, StorageError> {
+        let storage_path = &self.root;
+        let dest_path = storage_path.join(format!("{}.{}", self.file_id, self.extension));
+
+        let metadata = FileMetadata {
+            filename: self.filename.clone(),
+            size: self.total_size,
+            content_type: self.content_type.clone(),
+            uploaded_at: SystemTime::now(),
+        };
+
+        // The content is renamed before its metadata is written, so a file is
+        // only visible to `get_by_id` once both are in place.
+        fs::rename(self.part_path(), &dest_path)?;
+        let metadata_path = storage_path.join(format!("{}.json", self.file_id));
+        write_atomic(&metadata_path, serde_json::to_string(&metadata)?.as_bytes())?;
+        fs::remove_dir_all(self.staging_dir())?;
+        QuotaManager::open(&self.root)?.record(
+            self.file_id,
+            self.total_size,
+            self.owner.clone(),
+        )?;
+
+        Ok(FileStorage {
+            id: self.file_id,
+            metadata,
+            path: dest_path,
+            created_at: SystemTime::now(),
+        })
+    }
+
+    fn staging_dir(&self) -> PathBuf {
+        staging_root(&self.root).join(self.upload_id.to_string())
+    }
+
+    fn part_path(&self) -> PathBuf {
+        self.staging_dir().join(PART_FILE)
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        let session_path = self.staging_dir().join(SESSION_FILE);
+        write_atomic(&session_path, serde_json::to_string(self)?.as_bytes())
+    }
+}
+
+fn staging_root(root: &Path) -> PathBuf {
+    root.join(STAGING_DIR)
+}
+
+// Fill `buffer` as much as possible, returning fewer bytes only at end of input
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A source of `size` bytes that differ from chunk to chunk
+    fn source(dir: &Path, name: &str, size: usize) -> PathBuf {
+        let path = dir.join(name);
+        let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("storage");
+        let source = source(dir.path(), "data.bin", CHUNK_SIZE * 2 + 10);
+
+        let mut progress = Vec::new();
+        let session = UploadSession::begin(&root, &source, "data.bin".to_string(), None).unwrap();
+        let storage = session
+            .run(|written, total| progress.push((written, total)))
+            .unwrap();
+
+        let total = (CHUNK_SIZE * 2 + 10) as u64;
+        assert_eq!(progress.first(), Some(&(0, total)));
+        assert_eq!(progress.last(), Some(&(total, total)));
+        assert_eq!(fs::read(&storage.path).unwrap(), fs::read(&source).unwrap());
+        assert!(UploadSession::pending(&root).unwrap().is_empty());
+        let stored = FileStorage::get_by_id(&root, &storage.id.to_string()).unwrap();
+        assert_eq!(stored.metadata.size, total);
+    }
+
+    #[test]
+    fn test_tmp_extension() {
+        // The metadata of the upload is written next to `<id>.tmp`
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("storage");
+        let source = source(dir.path(), "notes.tmp", 100);
+
+        let storage = FileStorage::upload(&root, &source, "notes.tmp".to_string()).unwrap();
+        assert_eq!(fs::read(&storage.path).unwrap(), fs::read(&source).unwrap());
+        let stored = FileStorage::get_by_id(&root, &storage.id.to_string()).unwrap();
+        assert_eq!(stored.metadata.filename, "notes.tmp");
+    }
+
+    #[test]
+    fn test_resume_interrupted_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("storage");
+        let source = source(dir.path(), "data.bin", CHUNK_SIZE * 2 + 10);
+        let data = fs::read(&source).unwrap();
+
+        // Interrupted after the first checkpoint, with part of the next chunk
+        // written but not checkpointed
+        let mut session =
+            UploadSession::begin(&root, &source, "data.bin".to_string(), None).unwrap();
+        fs::write(session.part_path(), &data[..CHUNK_SIZE + 100]).unwrap();
+        session.bytes_written = CHUNK_SIZE as u64;
+        session.save().unwrap();
+
+        let pending = UploadSession::pending(&root).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].upload_id, session.upload_id);
+        assert_eq!(pending[0].bytes_written, CHUNK_SIZE as u64);
+
+        let resumed = UploadSession::resume(&root, &session.upload_id.to_string()).unwrap();
+        let mut progress = Vec::new();
+        let storage = resumed.run(|written, _| progress.push(written)).unwrap();
+        assert_eq!(progress.first(), Some(&(CHUNK_SIZE as u64)));
+        assert_eq!(fs::read(&storage.path).unwrap(), data);
+        assert!(UploadSession::pending(&root).unwrap().is_empty());
+        assert!(matches!(
+            UploadSession::resume(&root, &session.upload_id.to_string()),
+            Err(StorageError::SessionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resume_changed_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("storage");
+        let source = source(dir.path(), "data.bin", 1000);
+
+        let session = UploadSession::begin(&root, &source, "data.bin".to_string(), None).unwrap();
+        let upload_id = session.upload_id.to_string();
+        fs::write(&source, b"changed").unwrap();
+
+        assert!(matches!(
+            UploadSession::resume(&root, &upload_id),
+            Err(StorageError::SourceChanged(_))
+        ));
+        // A source that shrinks while uploading leaves the session resumable
+        assert!(matches!(
+            session.run(|_, _| {}),
+            Err(StorageError::SourceChanged(_))
+        ));
+        assert_eq!(UploadSession::pending(&root).unwrap().len(), 1);
+
+        fs::remove_file(&source).unwrap();
+        assert!(matches!(
+            UploadSession::resume(&root, &upload_id),
+            Err(StorageError::FileNotFound(_))
+        ));
+    }
+}
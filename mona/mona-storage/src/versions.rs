@@ -0,0 +1,356 @@
+//! Named files with version history.
+//!
+//! A name (e.g. `docs/report.pdf`) maps to a chain of stored files, one per
+//! upload. Each chain is kept in `<storage>/versions/<sha256(name)>.json`
+//! together with the retention policy used to prune its old versions.
+
+use crate::file_storage::{write_atomic, FileMetadata, FileStorage, StorageError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+const VERSIONS_DIR: &str = "versions";
+
+/// A single version of a named file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileVersion {
+    /// Version number, starting at 1.
+    pub version: u64,
+    /// ID of the stored file holding this version.
+    pub file_id: Uuid,
+    pub filename: String,
+    pub size: u64,
+    pub content_type: String,
+    /// Hex encoded SHA-256 of the content.
+    pub hash: String,
+    pub created_at: SystemTime,
+}
+
+/// Rules deciding which old versions of a name are pruned. The latest version
+/// is always kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Keep at most this many versions.
+    pub keep_last: Option<usize>,
+    /// Drop versions older than this many days.
+    pub max_age_days: Option<u64>,
+}
+
+/// The version history of a name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionChain {
+    pub name: String,
+    pub versions: Vec<FileVersion>,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+/// A metadata field that differs between two versions.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl VersionChain {
+    pub fn latest(&self) -> Option<&FileVersion> {
+        self.versions.last()
+    }
+
+    pub fn get(&self, version: u64) -> Option<&FileVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    /// Split off the versions that `retention` no longer allows, as of `now`.
+    fn apply_retention(&mut self, now: SystemTime) -> Vec<FileVersion> {
+        let Some(latest) = self.versions.last().map(|v| v.version) else {
+            return Vec::new();
+        };
+        let max_age = self
+            .retention
+            .max_age_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60));
+        let keep_from = self
+            .retention
+            .keep_last
+            .map(|keep| self.versions.len().saturating_sub(keep.max(1)))
+            .unwrap_or(0);
+
+        let (kept, pruned) = std::mem::take(&mut self.versions)
+            .into_iter()
+            .enumerate()
+            .partition::<Vec<_>, _>(|(index, v)| {
+                let too_old = max_age.is_some_and(|max_age| {
+                    now.duration_since(v.created_at).unwrap_or_default() > max_age
+                });
+                v.version == latest || (*index >= keep_from && !too_old)
+            });
+        self.versions = kept.into_iter().map(|(_, v)| v).collect();
+        pruned.into_iter().map(|(_, v)| v).collect()
+    }
+}
+
+/// Parse `name@version` into its parts. Without `@`, the version is `None`
+/// and refers to the latest version.
+pub fn parse_version_spec(spec: &str) -> Result<(&str, Option<u64>), StorageError> {
+    match spec.rsplit_once('@') {
+        Some((name, version)) => {
+            let version = version
+                .trim_start_matches('v')
+                .parse()
+                .map_err(|_| StorageError::InvalidVersion(spec.to_string()))?;
+            Ok((name, Some(version)))
+        }
+        None => Ok((spec, None)),
+    }
+}
+
+/// Compare the metadata of two versions.
+pub fn diff_versions(old: &FileVersion, new: &FileVersion) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field, old: String, new: String| {
+        if old != new {
+            changes.push(FieldChange { field, old, new });
+        }
+    };
+    compare("file_id", old.file_id.to_string(), new.file_id.to_string());
+    compare("filename", old.filename.clone(), new.filename.clone());
+    compare("size", old.size.to_string(), new.size.to_string());
+    compare("content_type", old.content_type.clone(), new.content_type.clone());
+    compare("hash", old.hash.clone(), new.hash.clone());
+    compare(
+        "created_at",
+        chrono::DateTime::<chrono::Utc>::from(old.created_at).to_rfc3339(),
+        chrono::DateTime::<chrono::Utc>::from(new.created_at).to_rfc3339(),
+    );
+    changes
+}
+
+/// Store of the version chains of all names.
+pub struct VersionStore {
+    /// Store holding the content of the versions.
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl VersionStore {
+    /// Open the version store of the store `root`.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let dir = root.as_ref().join(VERSIONS_DIR);
+        fs::create_dir_all(&dir)?;
+        Ok(VersionStore {
+            root: root.as_ref().to_path_buf(),
+            dir,
+        })
+    }
+
+    fn chain_path(&self, name: &str) -> PathBuf {
+        let digest = Sha256::digest(name.as_bytes());
+        self.dir.join(format!("{}.json", hex::encode(digest)))
+    }
+
+    fn save(&self, chain: &VersionChain) -> Result<(), StorageError> {
+        write_atomic(
+            &self.chain_path(&chain.name),
+            serde_json::to_string_pretty(chain)?.as_bytes(),
+        )
+    }
+
+    /// The history of `name`.
+    pub fn history(&self, name: &str) -> Result<VersionChain, StorageError> {
+        let path = self.chain_path(name);
+        if !path.exists() {
+            return Err(StorageError::FileNotFound(name.to_string()));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// All names with at least one version.
+    pub fn names(&self) -> Result<Vec<String>, StorageError> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let chain: VersionChain = serde_json::from_str(&fs::read_to_string(path)?)?;
+            names.push(chain.name);
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The stored files that are a version of some name.
+    pub fn file_ids(&self) -> Result<BTreeSet<Uuid>, StorageError> {
+        let mut file_ids = BTreeSet::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let chain: VersionChain = serde_json::from_str(&fs::read_to_string(path)?)?;
+            file_ids.extend(chain.versions.iter().map(|v| v.file_id));
+        }
+        Ok(file_ids)
+    }
+
+    /// Append the stored file `file_id` as the next version of `name`, then
+    /// prune the versions its retention policy no longer allows.
+    pub fn add_version(
+        &self,
+        name: &str,
+        file_id: Uuid,
+        metadata: &FileMetadata,
+        hash: String,
+    ) -> Result<FileVersion, StorageError> {
+        let mut chain = match self.history(name) {
+            Ok(chain) => chain,
+            Err(StorageError::FileNotFound(_)) => VersionChain {
+                name: name.to_string(),
+                versions: Vec::new(),
+                retention: RetentionPolicy::default(),
+            },
+            Err(e) => return Err(e),
+        };
+
+        let version = FileVersion {
+            version: chain.latest().map_or(1, |v| v.version + 1),
+            file_id,
+            filename: metadata.filename.clone(),
+            size: metadata.size,
+            content_type: metadata.content_type.clone(),
+            hash,
+            created_at: metadata.uploaded_at,
+        };
+        chain.versions.push(version.clone());
+
+        let pruned = chain.apply_retention(SystemTime::now());
+        self.save(&chain)?;
+        delete_contents(&self.root, &pruned)?;
+        Ok(version)
+    }
+
+    /// Resolve `name` or `name@version` to a version.
+    pub fn resolve(&self, spec: &str) -> Result<FileVersion, StorageError> {
+        let (name, version) = parse_version_spec(spec)?;
+        let chain = self.history(name)?;
+        let found = match version {
+            Some(version) => chain.get(version),
+            None => chain.latest(),
+        };
+        found
+            .cloned()
+            .ok_or_else(|| StorageError::InvalidVersion(spec.to_string()))
+    }
+
+    /// Compare the metadata of two versions of `name`.
+    pub fn diff(&self, name: &str, old: u64, new: u64) -> Result<Vec<FieldChange>, StorageError> {
+        let chain = self.history(name)?;
+        let lookup = |version| {
+            chain
+                .get(version)
+                .ok_or_else(|| StorageError::InvalidVersion(format!("{}@{}", name, version)))
+        };
+        Ok(diff_versions(lookup(old)?, lookup(new)?))
+    }
+
+    /// Set the retention policy of `name` and prune accordingly.
+    pub fn set_retention(
+        &self,
+        name: &str,
+        retention: RetentionPolicy,
+    ) -> Result<Vec<FileVersion>, StorageError> {
+        let mut chain = self.history(name)?;
+        chain.retention = retention;
+        let pruned = chain.apply_retention(SystemTime::now());
+        self.save(&chain)?;
+        delete_contents(&self.root, &pruned)?;
+        Ok(pruned)
+    }
+
+    /// Prune the old versions of `name` according to its retention policy.
+    pub fn prune(&self, name: &str) -> Result<Vec<FileVersion>, StorageError> {
+        let retention = self.history(name)?.retention;
+        self.set_retention(name, retention)
+    }
+}
+
+// Remove the stored files of pruned versions
+fn delete_contents(root: &Path, versions: &[FileVersion]) -> Result<(), StorageError> {
+    for version in versions {
+        match FileStorage::get_by_id(root, &version.file_id.to_string()) {
+            Ok(storage) => storage.delete()?,
+            Err(StorageError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: u64, age_days: u64) -> FileVersion {
+        FileVersion {
+            version,
+            file_id: Uuid::new_v4(),
+            filename: "report.pdf".to_string(),
+            size: version * 10,
+            content_type: "application/pdf".to_string(),
+            hash: format!("{:02x}", version),
+            created_at: SystemTime::now() - Duration::from_secs(age_days * 24 * 60 * 60),
+        }
+    }
+
+    #[test]
+    fn test_parse_version_spec() {
+        assert_eq!(parse_version_spec("docs/a.pdf").unwrap(), ("docs/a.pdf", None));
+        assert_eq!(parse_version_spec("docs/a.pdf@2").unwrap(), ("docs/a.pdf", Some(2)));
+        assert_eq!(parse_version_spec("a@v3").unwrap(), ("a", Some(3)));
+        assert!(parse_version_spec("a@latest").is_err());
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut chain = VersionChain {
+            name: "docs/report.pdf".to_string(),
+            versions: vec![version(1, 30), version(2, 20), version(3, 10), version(4, 0)],
+            retention: RetentionPolicy {
+                keep_last: Some(3),
+                max_age_days: None,
+            },
+        };
+        let pruned = chain.apply_retention(SystemTime::now());
+        assert_eq!(pruned.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1]);
+
+        chain.retention = RetentionPolicy {
+            keep_last: None,
+            max_age_days: Some(15),
+        };
+        let pruned = chain.apply_retention(SystemTime::now());
+        assert_eq!(pruned.iter().map(|v| v.version).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(chain.versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 4]);
+
+        // The latest version survives any policy.
+        chain.versions = vec![version(5, 100)];
+        assert!(chain.apply_retention(SystemTime::now()).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let (old, new) = (version(1, 1), version(2, 1));
+        let fields = diff_versions(&old, &new)
+            .into_iter()
+            .map(|change| change.field)
+            .collect::<Vec<_>>();
+        assert!(fields.contains(&"size"));
+        assert!(fields.contains(&"hash"));
+        assert!(!fields.contains(&"filename"));
+    }
+}
@@ -11,9 +11,18 @@ pub const KANARI_FRAMEWORK_ADDRESS_NAME: &str = "kanari_framework";
 pub const KANARI_FRAMEWORK_ADDRESS_LITERAL: &str = "0x2";
 pub const KANARI_FRAMEWORK_ADDRESS: AccountAddress = AccountAddress::TWO;
 
-pub static KANARI_FRAMEWORK_NAMED_ADDRESS_MAPPING: [(&str, &str); 2] = [
+pub const KANARI_SYSTEM_ADDRESS_NAME: &str = "kanari_system";
+pub const KANARI_SYSTEM_ADDRESS_LITERAL: &str = "0x3";
+pub const KANARI_SYSTEM_ADDRESS: AccountAddress = {
+    let mut addr = [0u8; AccountAddress::LENGTH];
+    addr[AccountAddress::LENGTH - 1] = 3;
+    AccountAddress::new(addr)
+};
+
+pub static KANARI_FRAMEWORK_NAMED_ADDRESS_MAPPING: [(&str, &str); 3] = [
     (MOVE_STD_ADDRESS_NAME, MOVE_STD_ADDRESS_LITERAL),
     (KANARI_FRAMEWORK_ADDRESS_NAME, KANARI_FRAMEWORK_ADDRESS_LITERAL),
+    (KANARI_SYSTEM_ADDRESS_NAME, KANARI_SYSTEM_ADDRESS_LITERAL),
 ];
 
 pub fn is_system_reserved_address(addr: AccountAddress) -> bool {
@@ -36,6 +45,7 @@ mod tests {
     fn test_is_system_reserved_address() {
         assert!(!is_system_reserved_address(AccountAddress::ZERO));
         assert!(is_system_reserved_address(AccountAddress::ONE));
+        assert!(is_system_reserved_address(KANARI_SYSTEM_ADDRESS));
         assert!(!is_system_reserved_address(new_address(11)));
         assert!(!is_system_reserved_address(AccountAddress::random()));
     }
@@ -0,0 +1,71 @@
+// Copyright (c) Kanari Network
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dynamic fields of `kanari_framework::dynamic_field`, as stored by the
+//! tooling that keeps objects outside of a node (the sandbox and genesis).
+//!
+//! A field is a `Field<Name, Value>` object whose ID is derived from the ID of
+//! its parent and its name, so it can be looked up without an index. This is
+//! the same for the entries of a `Bag` or a `Table`, which are dynamic fields
+//! of the bag or table.
+
+use crate::addresses::KANARI_FRAMEWORK_ADDRESS;
+use move_core_types::{
+    account_address::AccountAddress,
+    identifier::Identifier,
+    language_storage::{StructTag, TypeTag},
+};
+use sha3::{Digest, Sha3_256};
+
+/// Domain separator of field IDs, so that they never collide with the IDs of
+/// objects created by transactions.
+const FIELD_ID_DOMAIN: &[u8] = b"kanari::dynamic_field::";
+
+/// ID of the field of `parent` named `name`, the BCS encoding of a value of
+/// type `name_type` (`dynamic_field::hash_type_and_key`).
+pub fn field_id(parent: AccountAddress, name_type: &TypeTag, name: &[u8]) -> AccountAddress {
+    let mut hasher = Sha3_256::new();
+    hasher.update(FIELD_ID_DOMAIN);
+    hasher.update(parent.as_ref());
+    hasher.update((name.len() as u64).to_le_bytes());
+    hasher.update(name);
+    hasher.update(bcs::to_bytes(name_type).expect("type tags serialize"));
+    let hash = hasher.finalize();
+    AccountAddress::from_bytes(&hash[..AccountAddress::LENGTH]).expect("hash is long enough")
+}
+
+/// Type of the field object `0x2::dynamic_field::Field<Name, Value>`.
+pub fn field_type(name_type: TypeTag, value_type: TypeTag) -> StructTag {
+    StructTag {
+        address: KANARI_FRAMEWORK_ADDRESS,
+        module: Identifier::new("dynamic_field").unwrap(),
+        name: Identifier::new("Field").unwrap(),
+        type_params: vec![name_type, value_type],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_id() {
+        let parent = AccountAddress::from_hex_literal("0x403").unwrap();
+        let id = field_id(parent, &TypeTag::U64, &bcs_u64(0));
+        assert_eq!(id, field_id(parent, &TypeTag::U64, &bcs_u64(0)));
+        assert_ne!(id, field_id(parent, &TypeTag::U64, &bcs_u64(1)));
+        assert_ne!(
+            id,
+            field_id(AccountAddress::ONE, &TypeTag::U64, &bcs_u64(0))
+        );
+        // The same bytes named with another type are another field
+        assert_ne!(
+            id,
+            field_id(parent, &TypeTag::Vector(Box::new(TypeTag::U8)), &bcs_u64(0))
+        );
+    }
+
+    fn bcs_u64(value: u64) -> Vec<u8> {
+        value.to_le_bytes().to_vec()
+    }
+}
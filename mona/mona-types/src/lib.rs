@@ -1 +1,2 @@
-pub mod addresses;
\ No newline at end of file
+pub mod addresses;
+pub mod dynamic_field;
\ No newline at end of file